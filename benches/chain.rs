@@ -0,0 +1,67 @@
+#[macro_use]
+extern crate rmididings;
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use rmididings::proc::*;
+use rmididings::*;
+
+#[path = "../tests/testutil/mod.rs"]
+mod testutil;
+use testutil::*;
+
+const EVENTS: usize = 100_000;
+
+/// A chain of 10 filters, like a keyboard split to a synth.
+fn patch() -> FilterChain<'static> {
+    Chain!(
+        PortFilter(0),
+        Not!(ChannelFilter(9)),
+        KeyRangeFilter(21..=108),
+        VelocityRangeFilter(1..),
+        Not!(KeyFilter(60)),
+        Transpose(12),
+        VelocityLimit(20, 120),
+        CtrlMap(1, 11),
+        Channel(1),
+        Port(1)
+    )
+}
+
+/// Runs the patch on each event by itself, like the runner does.
+fn bench_filters(c: &mut Criterion) {
+    let patch = patch();
+    let events = mixed_corpus(EVENTS);
+    c.bench_function("chain/filters", |b| {
+        b.iter(|| {
+            for ev in events.iter() {
+                let mut evs = EventStream::from(ev);
+                patch.run(&mut evs);
+            }
+        })
+    });
+}
+
+/// Runs the patch in the runner, with events from the Null backend.
+fn bench_runner(c: &mut Criterion) {
+    let patch = patch();
+    let events = mixed_corpus(EVENTS);
+    let mut md = RMididings::builder().backend(BackendType::Null).in_port("input", "").out_port("output", "").build().unwrap();
+    let null = md.null_backend().unwrap();
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+    c.bench_function("chain/runner", |b| {
+        b.iter_batched(
+            || for ev in events.iter() { null.push_input(ev.clone()) },
+            |_| {
+                let _ = runner.step(Duration::ZERO).unwrap();
+                null.take_output()
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+criterion_group!(benches, bench_filters, bench_runner);
+criterion_main!(benches);
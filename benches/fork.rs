@@ -0,0 +1,50 @@
+#[macro_use]
+extern crate rmididings;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use rmididings::osc::OscType;
+use rmididings::proc::*;
+
+const BRANCHES: usize = 30;
+const EVENTS: usize = 64;
+
+/// A fork with a branch for each OSC address, like a control interface.
+fn routing_fork() -> FilterChain<'static> {
+    let branches: Vec<Box<dyn FilterTrait>> = (0..BRANCHES)
+        .map(|i| Box::new(Chain!(OscAddrFilter(format!("/strip/{}", i)), Port(i + 1))) as Box<dyn FilterTrait>)
+        .collect();
+    FilterChain::new(ConnectionType::Fork, branches)
+}
+
+/// A fork where each branch gets all events, like layering.
+fn layering_fork() -> FilterChain<'static> {
+    let branches: Vec<Box<dyn FilterTrait>> = (0..BRANCHES)
+        .map(|i| Box::new(Port(i + 1)) as Box<dyn FilterTrait>)
+        .collect();
+    FilterChain::new(ConnectionType::Fork, branches)
+}
+
+fn osc_events() -> Vec<Event<'static>> {
+    (0..EVENTS)
+        .map(|i| OscEvent(0, format!("/strip/{}", i % BRANCHES), vec![OscType::Float(0.5), OscType::String("volume".to_string())]))
+        .collect()
+}
+
+fn bench_fork(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fork");
+    for (name, fork) in [("routing", routing_fork()), ("layering", layering_fork())] {
+        let events = osc_events();
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || EventStream::from(events.clone()),
+                |mut evs| fork.run(&mut evs),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_fork);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+#[macro_use]
+extern crate rmididings;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+use rmididings::proc::*;
+
+#[path = "../tests/testutil/mod.rs"]
+mod testutil;
+use testutil::*;
+
+fn bench_categories(c: &mut Criterion) {
+    let mut group = c.benchmark_group("proc");
+    for category in categories(CORPUS_SIZE) {
+        group.bench_function(category.name, |b| {
+            b.iter_batched(
+                || EventStream::from(category.events.clone()),
+                |mut evs| category.filter.run(&mut evs),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_categories);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+extern crate rmididings;
+use rmididings::*;
+
+use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let mut md = RMididings::new()?;
+
+    md.config(ConfigArguments {
+        client_name: "RMididings Async Demo",
+        in_ports: &[
+            ["input", "Virtual Keyboard:Virtual Keyboard"],
+        ],
+        out_ports: &[
+            ["output", "midisnoop:MIDI Input"],
+        ],
+        ..ConfigArguments::default()
+    })?;
+
+    // Alternate between the scenes every 10 seconds, from another task.
+    let sender = md.event_sender();
+    let timer = async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(10));
+        interval.tick().await;
+        for scene in [2, 1].iter().cycle() {
+            interval.tick().await;
+            if sender.send(SceneSwitchEvent(*scene)).is_err() { break; }
+        }
+    };
+
+    // The engine is not Send, so it runs on the current thread, next to the timer.
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async {
+        let local = tokio::task::LocalSet::new();
+        local.spawn_local(timer);
+        local.run_until(md.run_async(RunArguments {
+            scenes: &[
+                &Scene { // 1
+                    name: "Run",
+                    patch: &Pass(),
+                    ..Scene::default()
+                },
+                &Scene { // 2
+                    name: "Transpose",
+                    patch: &Transpose(12),
+                    ..Scene::default()
+                }
+            ],
+            control: &TypeFilter!(SceneSwitch),
+            ..RunArguments::default()
+        })).await
+    })?;
+
+    Ok(())
+}
@@ -36,7 +36,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Chain!(
                 CarlaFilter(),
                 OscAddrFilter("/cb"),
-                ProcessOsc!(
+                ProcessOscOrDiscard!(
                     o::Int, o::Int, o::Int, o::Int, o::Int, o::Float, o::String,
                     |action: &i32, plugin_id: &i32, ival: &i32, _, _, fval: &f32, _| {
                         // Only react to value changed callback for the first plugin and the first parameter.
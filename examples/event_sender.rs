@@ -0,0 +1,35 @@
+extern crate rmididings;
+use rmididings::*;
+
+use std::{thread, time::Duration};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+
+    let mut md = RMididings::new()?;
+
+    md.config(ConfigArguments {
+        client_name: "RMididings Sender Demo",
+        out_ports: &[
+            ["output", "midisnoop:MIDI Input"],
+        ],
+        ..ConfigArguments::default()
+    })?;
+
+    // Play a note every second from another thread, e.g. a sensor or user interface.
+    let sender = md.event_sender();
+    thread::spawn(move || {
+        for note in (60..72).cycle() {
+            if sender.send(NoteOnEvent(1, 1, note, 100)).is_err() { break; }
+            thread::sleep(Duration::from_millis(500));
+            if sender.send(NoteOffEvent(1, 1, note)).is_err() { break; }
+            thread::sleep(Duration::from_millis(500));
+        }
+    });
+
+    md.run(RunArguments {
+        patch: &Pass(),
+        ..RunArguments::default()
+    })?;
+
+    Ok(())
+}
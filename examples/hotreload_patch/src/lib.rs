@@ -0,0 +1,15 @@
+//! Example patch library for use with `DylibPatch`.
+//!
+//! Build it with `cargo build` in this directory, and load the resulting library
+//! from your program with `DylibPatch::new("examples/hotreload_patch/target/debug/libhotreload_patch.so")`.
+//! Change the patch, rebuild, and the running program picks up the new patch.
+#[macro_use]
+extern crate rmididings;
+use rmididings::*;
+
+/// Number of semitones to transpose, can be set at build time for testing.
+fn transpose() -> i16 {
+    option_env!("HOTRELOAD_PATCH_TRANSPOSE").and_then(|s| s.parse().ok()).unwrap_or(12)
+}
+
+export_patch!(Transpose(transpose()));
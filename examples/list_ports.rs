@@ -0,0 +1,9 @@
+use rmididings::*;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    for port in RMididings::list_ports()? {
+        println!("{}", port);
+    }
+
+    Ok(())
+}
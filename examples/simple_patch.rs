@@ -4,27 +4,17 @@ use rmididings::*;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
 
-    let mut md = RMididings::new()?;
-
-    md.config(ConfigArguments {
-        client_name: "RMididings Demo",
-        in_ports: &[
-            ["input", "Virtual Keyboard:Virtual Keyboard"],
-        ],
-        out_ports: &[
-            ["output", "midisnoop:MIDI Input"],
-        ],
-        ..ConfigArguments::default()
-    })?;
+    let mut md = RMididings::builder()
+        .client_name("RMididings Demo")
+        .in_port("input", "Virtual Keyboard:Virtual Keyboard")
+        .out_port("output", "midisnoop:MIDI Input")
+        .build()?;
 
     println!("Started");
 
-    let patch = Pass();
-
-    md.run(RunArguments {
-        patch: &patch,
-        ..RunArguments::default()
-    })?;
+    md.run_builder()
+        .patch(Pass())
+        .run()?;
 
     Ok(())
 }
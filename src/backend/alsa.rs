@@ -9,13 +9,17 @@ use std::ffi::CString;
 
 use super::super::proc::event::*;
 use super::super::proc::EventStream;
-use super::backend::{Backend, PortNum};
+use super::backend::{Backend, PortDescriptor, PortNum};
 
 /// ALSA sequencer MIDI backend.
 pub struct AlsaBackend {
     alsaseq: alsa::Seq,
     in_ports: HashMap<PortNum, i32>,
     out_ports: HashMap<PortNum, i32>,
+    // Whether out ports have subscribers, kept up-to-date using the system announce port.
+    out_port_receivers: HashMap<PortNum, bool>,
+    out_port_receivers_changed: bool,
+    announce_port: Option<i32>,
 }
 
 impl AlsaBackend {
@@ -24,12 +28,15 @@ impl AlsaBackend {
             alsaseq: alsa::Seq::open(None, None, true)?,
             in_ports: HashMap::new(),
             out_ports: HashMap::new(),
+            out_port_receivers: HashMap::new(),
+            out_port_receivers_changed: false,
+            announce_port: None,
         })
     }
 
     fn _create_in_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
         let alsaseq_port = self.alsaseq.create_simple_port(
-            &CString::new(name).unwrap(),
+            &alsa_name(name)?,
             seq::PortCap::WRITE | seq::PortCap::SUBS_WRITE,
             seq::PortType::MIDI_GENERIC | seq::PortType::APPLICATION
         )?;
@@ -39,18 +46,64 @@ impl AlsaBackend {
 
     fn _create_out_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
         let alsaseq_port = self.alsaseq.create_simple_port(
-            &CString::new(name).unwrap(),
+            &alsa_name(name)?,
             seq::PortCap::READ | seq::PortCap::SUBS_READ,
             seq::PortType::MIDI_GENERIC | seq::PortType::APPLICATION
         )?;
         self.out_ports.insert(backend_port, alsaseq_port);
+        self.subscribe_announce()?;
+        self.update_out_port_receivers(backend_port);
         Ok(true)
     }
+
+    /// Listens to subscription changes, to know which out ports have receivers.
+    fn subscribe_announce(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.announce_port.is_some() { return Ok(()); }
+        let alsaseq_port = self.alsaseq.create_simple_port(
+            &alsa_name("announce")?,
+            seq::PortCap::WRITE | seq::PortCap::NO_EXPORT,
+            seq::PortType::APPLICATION
+        )?;
+        let subs = seq::PortSubscribe::empty()?;
+        subs.set_sender(seq::Addr::system_announce());
+        subs.set_dest(seq::Addr { client: self.alsaseq.client_id()?, port: alsaseq_port });
+        self.alsaseq.subscribe_port(&subs)?;
+        self.announce_port = Some(alsaseq_port);
+        Ok(())
+    }
+
+    fn update_out_port_receivers(&mut self, backend_port: PortNum) {
+        let (Some(alsaseq_port), Ok(client)) = (self.out_ports.get(&backend_port), self.alsaseq.client_id()) else { return };
+        let addr = seq::Addr { client, port: *alsaseq_port };
+        let has_receivers = seq::PortSubscribeIter::new(&self.alsaseq, addr, seq::QuerySubsType::READ).next().is_some();
+        if self.out_port_receivers.insert(backend_port, has_receivers) != Some(has_receivers) {
+            self.out_port_receivers_changed = true;
+        }
+    }
+
+    /// Updates the receivers of an out port when its subscriptions changed.
+    fn handle_subscription_change(&mut self, connect: seq::Connect) {
+        let Ok(client) = self.alsaseq.client_id() else { return };
+        if connect.sender.client != client { return; }
+        if let Some(backend_port) = self.out_ports.iter().find(|(_, p)| **p == connect.sender.port).map(|(b, _)| *b) {
+            self.update_out_port_receivers(backend_port);
+        }
+    }
+
+    /// Lists MIDI ports that can be connected to an input port, i.e. that can be read from.
+    pub fn list_in_ports(&self) -> Result<Vec<PortDescriptor>, Box<dyn Error>> {
+        self.list_alsaseq_ports(seq::PortCap::READ | seq::PortCap::SUBS_READ)
+    }
+
+    /// Lists MIDI ports that can be connected to an output port, i.e. that can be written to.
+    pub fn list_out_ports(&self) -> Result<Vec<PortDescriptor>, Box<dyn Error>> {
+        self.list_alsaseq_ports(seq::PortCap::WRITE | seq::PortCap::SUBS_WRITE)
+    }
 }
 
-impl Backend<'_> for AlsaBackend {
+impl Backend for AlsaBackend {
     fn set_client_name(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
-        Ok(self.alsaseq.set_client_name(&CString::new(name).unwrap())?)
+        Ok(self.alsaseq.set_client_name(&alsa_name(name)?)?)
     }
 
     fn create_in_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
@@ -101,6 +154,14 @@ impl Backend<'_> for AlsaBackend {
         return Ok(false);
     }
 
+    fn out_port_receivers(&self) -> Vec<(PortNum, bool)> {
+        self.out_port_receivers.iter().map(|(backend_port, has_receivers)| (*backend_port, *has_receivers)).collect()
+    }
+
+    fn out_port_receivers_changed(&mut self) -> bool {
+        std::mem::take(&mut self.out_port_receivers_changed)
+    }
+
     fn get_pollfds(&mut self) -> Result<Vec<libc::pollfd>, Box<dyn Error>> {
         Ok((&self.alsaseq, Some(alsa::Direction::Capture)).get()?)
     }
@@ -109,15 +170,25 @@ impl Backend<'_> for AlsaBackend {
         let mut alsaseq_input = self.alsaseq.input();
         match alsaseq_input.event_input_pending(true) {
             Ok(count) if count > 0 => {
-                Ok((EventStream::from(self.alsaseq_event_to_event(&alsaseq_input.event_input()?)?), false))
+                let alsaev = alsaseq_input.event_input()?;
+                if self.announce_port.is_some_and(|port| port == alsaev.get_dest().port) {
+                    // Only subscription changes matter, e.g. not clients coming and going.
+                    let connect = alsaev.get_data::<seq::Connect>();
+                    drop(alsaev);
+                    drop(alsaseq_input);
+                    if let Some(connect) = connect {
+                        self.handle_subscription_change(connect);
+                    }
+                    return Ok((EventStream::empty(), false));
+                }
+                Ok((EventStream::from(self.alsaseq_event_to_event(&alsaev)?), false))
             },
             Ok(_) => Ok((EventStream::empty(), false)),
             // Occasionally, this function may return -ENOSPC error. This means that the input FIFO of
             // sequencer overran, and some events are lost. Once this error is returned, the input FIFO
             // is cleared automatically.
-            // TODO emit a warning?
             Err(e) if e.nix_error() == alsa::nix::Error::Sys(alsa::nix::errno::Errno::ENOSPC) => {
-                println!("Buffer overrun");
+                warn!("Buffer overrun");
                 Ok((EventStream::empty(), false))
             },
             Err(e) => Err(Box::new(e)),
@@ -135,9 +206,9 @@ impl Backend<'_> for AlsaBackend {
                 Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
             },
             Event::NoteOff(ev) => {
+                // For note off events, ALSA carries the release velocity in the velocity field.
                 let mut alsaev = seq::Event::new(seq::EventType::Noteoff, &seq::EvNote {
-                    // TODO figure out what to do with duration and off_velocity
-                    channel: ev.channel, note: ev.note, velocity: 0, duration: 0, off_velocity: 0
+                    channel: ev.channel, note: ev.note, velocity: ev.velocity, duration: 0, off_velocity: 0
                 });
                 Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
             },
@@ -147,11 +218,58 @@ impl Backend<'_> for AlsaBackend {
                 });
                 Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
             },
+            Event::Program(ev) => {
+                let mut alsaev = seq::Event::new(seq::EventType::Pgmchange, &seq::EvCtrl {
+                    channel: ev.channel, param: 0, value: ev.program as i32
+                });
+                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+            },
+            Event::PitchBend(ev) => {
+                let mut alsaev = seq::Event::new(seq::EventType::Pitchbend, &seq::EvCtrl {
+                    channel: ev.channel, param: 0, value: ev.value as i32
+                });
+                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+            },
+            Event::Nrpn(ev) => {
+                // ALSA sends this as the sequence of controllers.
+                let mut alsaev = seq::Event::new(seq::EventType::Nonregparam, &seq::EvCtrl {
+                    channel: ev.channel, param: ev.param as u32, value: ev.value as i32
+                });
+                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+            },
+            Event::Clock(ev) => self.output_alsaseq_realtime_event(&ev.port, seq::EventType::Clock),
+            Event::Start(ev) => self.output_alsaseq_realtime_event(&ev.port, seq::EventType::Start),
+            Event::Stop(ev) => self.output_alsaseq_realtime_event(&ev.port, seq::EventType::Stop),
+            Event::Continue(ev) => self.output_alsaseq_realtime_event(&ev.port, seq::EventType::Continue),
+            Event::MtcQuarterFrame(ev) => {
+                // ALSA has the whole data byte as value.
+                let mut alsaev = seq::Event::new(seq::EventType::Qframe, &seq::EvCtrl {
+                    channel: 0, param: 0, value: ((ev.message_type & 0x07) << 4 | (ev.value & 0x0f)) as i32
+                });
+                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+            },
+            Event::SongPosition(ev) => {
+                let mut alsaev = seq::Event::new(seq::EventType::Songpos, &seq::EvCtrl {
+                    channel: 0, param: 0, value: ev.position as i32
+                });
+                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+            },
+            Event::SongSelect(ev) => {
+                let mut alsaev = seq::Event::new(seq::EventType::Songsel, &seq::EvCtrl {
+                    channel: 0, param: 0, value: ev.song as i32
+                });
+                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+            },
             Event::SysEx(ev) => {
                 let mut me = seq::MidiEvent::new(ev.data.len() as u32)?;
-                let (_, me_enc) = me.encode(ev.data)?;
-                let mut alsaev = me_enc.unwrap();
-                Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?)
+                match me.encode(ev.data)? {
+                    (_, Some(mut alsaev)) => Ok(self.output_alsaseq_event(&ev.port, &mut alsaev)?),
+                    // Incomplete data, like an empty SysEx, doesn't result in an event.
+                    (_, None) => {
+                        warn!("Could not output incomplete SysEx event on port {}", ev.port);
+                        Ok(0)
+                    },
+                }
             },
             _ => {
                 Ok(0)
@@ -160,19 +278,58 @@ impl Backend<'_> for AlsaBackend {
     }
 }
 
+/// Converts a client or port name for ALSA, which doesn't allow NUL bytes in them.
+fn alsa_name(name: &str) -> Result<CString, Box<dyn Error>> {
+    Ok(CString::new(name).map_err(|_| format!("Invalid name {:?}, it contains a NUL byte", name))?)
+}
+
 impl AlsaBackend {
     fn alsaseq_event_to_event<'a>(&self, alsaev: &seq::Event) -> Result<Option<Event<'a>>, Box<dyn Error>> {
         // map alsa port to our own port (index in self.in_ports), fallback to port 0
         let alsaseq_port = alsaev.get_dest().port;
         if let Some((port, _)) = self.in_ports.iter().find(|(_, as_p)| **as_p == alsaseq_port) {
             // convert alsaseq event to our own kind of event
+            match alsaev.get_type() {
+                seq::EventType::Clock => return Ok(Some(ClockEvent(*port))),
+                seq::EventType::Start => return Ok(Some(StartEvent(*port))),
+                seq::EventType::Stop => return Ok(Some(StopEvent(*port))),
+                seq::EventType::Continue => return Ok(Some(ContinueEvent(*port))),
+                seq::EventType::Songsel => {
+                    if let Some(e) = alsaev.get_data::<seq::EvCtrl>() {
+                        return Ok(Some(SongSelectEvent(*port, e.value as u8)));
+                    }
+                },
+                seq::EventType::Qframe => {
+                    if let Some(e) = alsaev.get_data::<seq::EvCtrl>() {
+                        return Ok(Some(MtcQuarterFrameEvent(*port, (e.value >> 4) as u8 & 0x07, e.value as u8 & 0x0f)));
+                    }
+                },
+                seq::EventType::Songpos => {
+                    if let Some(e) = alsaev.get_data::<seq::EvCtrl>() {
+                        return Ok(Some(SongPositionEvent(*port, e.value as u16)));
+                    }
+                },
+                _ => {},
+            }
+
             if let Some(e) = alsaev.get_data::<seq::EvNote>() {
                 if alsaev.get_type() == seq::EventType::Noteon {
                     return Ok(Some(NoteOnEvent(*port, e.channel, e.note, e.velocity)));
                 } else {
-                    return Ok(Some(NoteOffEvent(*port, e.channel, e.note)));
+                    // Release velocity is in velocity for note off events, off_velocity is only used for note events.
+                    let velocity = if alsaev.get_type() == seq::EventType::Noteoff { e.velocity } else { e.off_velocity };
+                    return Ok(Some(NoteOffEventWithVelocity(*port, e.channel, e.note, velocity)));
                 }
             } else if let Some(e) = alsaev.get_data::<seq::EvCtrl>() {
+                if alsaev.get_type() == seq::EventType::Pgmchange {
+                    return Ok(Some(ProgramEvent(*port, e.channel, e.value as u8)));
+                }
+                if alsaev.get_type() == seq::EventType::Pitchbend {
+                    return Ok(Some(PitchBendEvent(*port, e.channel, e.value as i16)));
+                }
+                if alsaev.get_type() == seq::EventType::Nonregparam {
+                    return Ok(Some(NrpnEvent(*port, e.channel, e.param as u16, e.value as u16)));
+                }
                 return Ok(Some(CtrlEvent(*port, e.channel, e.param, e.value)));
             }
         }
@@ -193,6 +350,30 @@ impl AlsaBackend {
         Ok(None)
     }
 
+    fn list_alsaseq_ports(&self, caps: seq::PortCap) -> Result<Vec<PortDescriptor>, Box<dyn Error>> {
+        let own_client_id = self.alsaseq.client_id()?;
+        let mut ports = vec![];
+        for client in seq::ClientIter::new(&self.alsaseq) {
+            if client.get_client() == own_client_id { continue; }
+            for port in seq::PortIter::new(&self.alsaseq, client.get_client()) {
+                if !port.get_type().contains(seq::PortType::MIDI_GENERIC) { continue; }
+                if !port.get_capability().contains(caps) { continue; }
+                ports.push(PortDescriptor {
+                    client_name: client.get_name()?.to_string(),
+                    port_name: port.get_name()?.to_string(),
+                    client_id: port.get_client(),
+                    port_id: port.get_port(),
+                });
+            }
+        }
+        Ok(ports)
+    }
+
+    fn output_alsaseq_realtime_event(&self, backend_port: &PortNum, event_type: seq::EventType) -> Result<u32, Box<dyn Error>> {
+        let mut alsaev = seq::Event::new(event_type, &seq::EvQueueControl { queue: 0, value: () });
+        self.output_alsaseq_event(backend_port, &mut alsaev)
+    }
+
     fn output_alsaseq_event(&self, backend_port: &PortNum, ev: &mut alsa::seq::Event) -> Result<u32, Box<dyn Error>> {
         if let Some(alsaseq_port) = self.out_ports.get(backend_port) {
             ev.set_source(*alsaseq_port);
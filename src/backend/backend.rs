@@ -4,21 +4,72 @@ use super::super::proc::{Event, EventStream};
 
 pub type PortNum = usize;
 
+/// A file descriptor to poll, as used by `poll(2)`.
+#[cfg(not(target_arch = "wasm32"))]
+pub use libc::pollfd;
+
+/// A file descriptor to poll, there are none in the browser.
+#[cfg(target_arch = "wasm32")]
+#[allow(non_camel_case_types, dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub struct pollfd {
+    pub fd: i32,
+    pub events: i16,
+    pub revents: i16,
+}
+
+/// A port of another client, that can be connected to.
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+pub struct PortDescriptor {
+    pub client_name: String,
+    pub port_name: String,
+    pub client_id: i32,
+    pub port_id: i32,
+}
+
+/// Formats the port like it is used for connecting, i.e. `client_name:port_name`.
+impl std::fmt::Display for PortDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.client_name, self.port_name)
+    }
+}
+
 /// MIDI Backend implementation.
-pub trait Backend<'a> {
+pub trait Backend {
     fn set_client_name(&mut self, name: &str) -> Result<(), Box<dyn Error>>;
 
-    fn create_in_port(&mut self, port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>>;
+    fn create_in_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>>;
 
-    fn create_out_port(&mut self, port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>>;
+    fn create_out_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>>;
 
-    fn connect_in_port(&mut self, port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>>;
+    fn connect_in_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>>;
 
-    fn connect_out_port(&mut self, port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>>;
+    fn connect_out_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>>;
 
-    fn get_pollfds(&mut self) -> Result<Vec<libc::pollfd>, Box<dyn Error>>;
+    /// File descriptors to wait on, `run` is called when one of them has something to read.
+    ///
+    /// Backends without any are run each time the runner wakes up.
+    fn get_pollfds(&mut self) -> Result<Vec<pollfd>, Box<dyn Error>>;
 
+    /// Returns received events, and whether its pollfds changed.
+    ///
+    /// When the set of file descriptors to poll changed, e.g. because a client connected or a
+    /// connection was closed, return `true` so that the runner gathers them again using
+    /// `get_pollfds`.
     fn run<'evs: 'run, 'run>(&'run mut self) -> Result<(EventStream<'evs>, bool), Box<dyn Error>>;
 
     fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>>;
+
+    /// Whether its output ports have something receiving events, for the ports it knows that of.
+    fn out_port_receivers(&self) -> Vec<(PortNum, bool)> {
+        vec![]
+    }
+
+    /// Whether `out_port_receivers` changed since this was last called.
+    ///
+    /// This is checked for each event, the receivers are only asked for again when it
+    /// returns `true`. So keep track of changes, instead of looking them up here.
+    fn out_port_receivers_changed(&mut self) -> bool {
+        false
+    }
 }
\ No newline at end of file
@@ -36,7 +36,7 @@ impl CtrlcBackend {
     }
 }
 
-impl Backend<'_> for CtrlcBackend {
+impl Backend for CtrlcBackend {
     fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
@@ -77,5 +77,7 @@ extern fn signal_handler(_: nix::libc::c_int) {
     // Signal handlers are special functions, only [async-signal-safe]
     // (http://man7.org/linux/man-pages/man7/signal-safety.7.html) functions
     // can be called in this context.
-    nix::unistd::write(unsafe { PIPE.1 }, &[0u8]).unwrap();
+    // When this fails, there's nothing sensible to do here; most likely the pipe is full
+    // because a quit is already pending.
+    let _ = nix::unistd::write(unsafe { PIPE.1 }, &[0u8]);
 }
\ No newline at end of file
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+pub extern crate dbus;
+
+use dbus::blocking::Connection;
+use dbus::message::{MatchRule, MessageType};
+use dbus::strings::{BusName, Interface, Member, Path};
+use dbus::Message;
+
+use super::super::proc::event::{DbusEvent, DbusEventImpl, Event};
+use super::super::proc::EventStream;
+use super::backend::{Backend, PortNum};
+
+/// Where a port receives signals from, or sends method calls to.
+///
+/// Parsed from `<bus_name>/<object_path>/<interface>`, where the object path and interface
+/// may be left out. The interface is recognised by containing a dot, which an object path
+/// element can't.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct DbusAddress {
+    service: String,
+    path: String,
+    interface: String,
+}
+
+impl DbusAddress {
+    fn parse(name: &str) -> Self {
+        let (service, rest) = match name.find('/') {
+            Some(i) => (&name[..i], &name[i..]),
+            None => (name, ""),
+        };
+        let (path, interface) = match rest.rsplit_once('/') {
+            Some((path, last)) if last.contains('.') => (path, last),
+            _ => (rest, ""),
+        };
+        Self { service: service.to_string(), path: path.to_string(), interface: interface.to_string() }
+    }
+
+    /// Rule matching signals from this address.
+    fn match_rule(&self) -> Result<MatchRule<'static>, Box<dyn Error>> {
+        let mut rule = MatchRule::new().with_type(MessageType::Signal);
+        if !self.service.is_empty() {
+            rule = rule.with_sender(BusName::new(self.service.clone())?);
+        }
+        if !self.path.is_empty() {
+            rule = rule.with_path(Path::new(self.path.clone())?);
+        }
+        if !self.interface.is_empty() {
+            rule = rule.with_interface(Interface::new(self.interface.clone())?);
+        }
+        Ok(rule)
+    }
+}
+
+/// D-Bus backend, for ports named like `dbus:<bus_name>/<object_path>/<interface>`.
+///
+/// In ports receive the signals matching their name as events, out ports send events as
+/// method calls, with their name filling in what the event leaves empty. It uses the session
+/// bus, which is connected to when the first port is created.
+pub struct DbusBackend {
+    connection: Option<Connection>,
+    // Rules without sender, as received signals have the unique name of the sender. A signal
+    // from another service on the same path and interface is also received by the port.
+    in_ports: HashMap<PortNum, MatchRule<'static>>,
+    out_ports: HashMap<PortNum, DbusAddress>,
+}
+
+impl DbusBackend {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            connection: None,
+            in_ports: HashMap::new(),
+            out_ports: HashMap::new(),
+        })
+    }
+
+    /// Connects to the session bus on first use, a failed connect is tried again next time.
+    fn connection(&mut self) -> Result<&Connection, Box<dyn Error>> {
+        let connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => Connection::new_session().map_err(|e| format!("Could not connect to the D-Bus session bus: {}", e))?,
+        };
+        Ok(self.connection.insert(connection))
+    }
+}
+
+impl Backend for DbusBackend {
+    fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn create_in_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        match name.split_once(':') {
+            Some(("dbus", port_name)) => {
+                let mut rule = DbusAddress::parse(port_name).match_rule()?;
+                self.connection()?.add_match_no_cb(&rule.match_str())?;
+                rule.sender = None;
+                self.in_ports.insert(backend_port, rule);
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    fn create_out_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        match name.split_once(':') {
+            Some(("dbus", port_name)) => {
+                self.connection()?;
+                self.out_ports.insert(backend_port, DbusAddress::parse(port_name));
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+
+    fn connect_in_port(&mut self, _backend_port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        // Not applicable, the port name says what to receive.
+        Ok(false)
+    }
+
+    fn connect_out_port(&mut self, _backend_port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        // Not applicable, the port name says where to send to.
+        Ok(false)
+    }
+
+    fn get_pollfds(&mut self) -> Result<Vec<libc::pollfd>, Box<dyn Error>> {
+        match &self.connection {
+            Some(connection) if !self.in_ports.is_empty() => {
+                Ok(vec![libc::pollfd { fd: connection.channel().watch().fd, events: libc::POLLIN, revents: 0 }])
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn run<'evs: 'run, 'run>(&'run mut self) -> Result<(EventStream<'evs>, bool), Box<dyn Error>> {
+        let mut evs = EventStream::empty();
+        let Some(connection) = &self.connection else { return Ok((evs, false)) };
+
+        let channel = connection.channel();
+        channel.read_write(Some(Duration::ZERO)).map_err(|_| "D-Bus connection lost")?;
+        while let Some(message) = channel.pop_message() {
+            for (backend_port, rule) in self.in_ports.iter() {
+                if rule.matches(&message) {
+                    evs.push(build_event(&message, *backend_port));
+                }
+            }
+        }
+
+        Ok((evs, false))
+    }
+
+    fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        let Event::Dbus(ev) = ev else { return Ok(0) };
+        let Some(address) = self.out_ports.get(&ev.port) else { return Ok(0) };
+
+        let message = match method_call(ev, address) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Not sending invalid D-Bus method call: {}", e);
+                return Ok(0);
+            },
+        };
+
+        // Replies are not waited for, this is like sending a MIDI event.
+        let Some(connection) = &self.connection else { return Ok(0) };
+        connection.channel().send(message).map_err(|_| "D-Bus message could not be sent")?;
+        connection.channel().flush();
+        Ok(1)
+    }
+}
+
+fn build_event<'a>(message: &Message, backend_port: PortNum) -> Event<'a> {
+    DbusEvent(
+        backend_port,
+        message.sender().map(|s| s.to_string()).unwrap_or_default(),
+        message.path().map(|s| s.to_string()).unwrap_or_default(),
+        message.interface().map(|s| s.to_string()).unwrap_or_default(),
+        message.member().map(|s| s.to_string()).unwrap_or_default(),
+        message.get_items(),
+    )
+}
+
+/// Method call for an event, with what it leaves empty taken from the port address.
+fn method_call(ev: &DbusEventImpl, address: &DbusAddress) -> Result<Message, String> {
+    let or = |a: &str, b: &str| if a.is_empty() { b.to_string() } else { a.to_string() };
+    let mut message = Message::new_method_call(
+        BusName::new(or(&ev.service, &address.service))?,
+        Path::new(or(&ev.path, &address.path))?,
+        Interface::new(or(&ev.interface, &address.interface))?,
+        Member::new(ev.method.clone())?,
+    )?;
+    message.append_items(&ev.args);
+    Ok(message)
+}
@@ -0,0 +1,219 @@
+use std::error::Error;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::Duration;
+use web_time::Instant;
+
+extern crate midly;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+extern crate nix;
+use nix::sys::time::TimeSpec;
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
+
+use crate::proc::{Event, EventStream};
+use crate::proc::{CtrlEvent, NoteOffEventWithVelocity, NoteOnEvent, PitchBendEvent, ProgramEvent};
+use crate::backend::{Backend, PortNum};
+
+/// Tempo when the file doesn't specify one, in microseconds per quarter note (120 bpm).
+const DEFAULT_TEMPO: u64 = 500_000;
+
+/// MIDI backend that plays back a standard MIDI file as input.
+///
+/// Notes, controllers, program changes and pitch bend of all tracks are played back following
+/// the file's tempo map, starting when the patch starts running. SysEx and aftertouch events
+/// are skipped. Events arrive on the in port named `midifile`
+/// (e.g. `midifile:player`), or on the first in port when there is none.
+///
+/// A timerfd wakes up the runner when the next event is due, so this is Linux-only.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::*;
+/// // A file with a single track, playing one note at the start.
+/// let path = std::env::temp_dir().join("rmididings-doctest-midifile.mid");
+/// std::fs::write(&path, [
+///     b"MThd".as_ref(), &[0, 0, 0, 6, 0, 0, 0, 1, 0, 96],
+///     b"MTrk".as_ref(), &[0, 0, 0, 8, 0x00, 0x90, 60, 100, 0x00, 0xff, 0x2f, 0x00],
+/// ].concat()).unwrap();
+///
+/// let patch = Chain!(KeyFilter(60), Quit());
+/// let mut md = RMididings::new().unwrap();
+/// md.config(ConfigArguments {
+///     backend: BackendType::Null,
+///     midi_file: Some(&path),
+///     ..ConfigArguments::default()
+/// }).unwrap();
+/// md.run(RunArguments {
+///     patch: &patch,
+///     ..RunArguments::default()
+/// }).unwrap();
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct MidiFileBackend {
+    events: Vec<(Duration, Event<'static>)>,
+    length: Duration,
+    looping: bool,
+    port: PortNum,
+    position: usize,
+    started: Option<Instant>,
+    timer: TimerFd,
+}
+
+impl MidiFileBackend {
+    /// Reads the MIDI file at `path`, which is played back from the start again when
+    /// `looping` and the end is reached.
+    pub fn new(path: &Path, looping: bool) -> Result<Self, Box<dyn Error>> {
+        let data = std::fs::read(path)?;
+        let smf = Smf::parse(&data)?;
+        let (events, length) = Self::schedule(&smf);
+
+        Ok(Self {
+            events,
+            length,
+            looping,
+            port: 0,
+            position: 0,
+            started: None,
+            timer: TimerFd::new(ClockId::CLOCK_MONOTONIC, TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC)?,
+        })
+    }
+
+    /// Returns all events with their time since the start, and the length of the file.
+    fn schedule(smf: &Smf) -> (Vec<(Duration, Event<'static>)>, Duration) {
+        // Merge all tracks on absolute tick, keeping the order of events within a tick.
+        let mut track_events = vec![];
+        for track in smf.tracks.iter() {
+            let mut tick: u64 = 0;
+            for ev in track.iter() {
+                tick += ev.delta.as_int() as u64;
+                track_events.push((tick, ev.kind));
+            }
+        }
+        track_events.sort_by_key(|(tick, _)| *tick);
+
+        let mut events = vec![];
+        let mut length = Duration::ZERO;
+        // Start of the current tempo segment, and its tempo.
+        let mut segment_tick: u64 = 0;
+        let mut segment_time = Duration::ZERO;
+        let mut tempo = DEFAULT_TEMPO;
+
+        for (tick, kind) in track_events {
+            let time = segment_time + match smf.header.timing {
+                Timing::Metrical(ppq) => {
+                    let ppq = (ppq.as_int() as u64).max(1);
+                    Duration::from_nanos((tick - segment_tick) * tempo * 1000 / ppq)
+                },
+                Timing::Timecode(fps, subframes) => {
+                    let fps = if fps.as_int() == 29 { 29.97 } else { fps.as_int() as f64 };
+                    Duration::from_secs_f64((tick - segment_tick) as f64 / fps / (subframes as f64).max(1.0))
+                },
+            };
+            length = time;
+
+            match kind {
+                TrackEventKind::Midi { channel, message } => {
+                    let channel = channel.as_int();
+                    let ev = match message {
+                        MidiMessage::NoteOn { key, vel } => Some(NoteOnEvent(0, channel, key.as_int(), vel.as_int())),
+                        MidiMessage::NoteOff { key, vel } => Some(NoteOffEventWithVelocity(0, channel, key.as_int(), vel.as_int())),
+                        MidiMessage::Controller { controller, value } => Some(CtrlEvent(0, channel, controller.as_int() as u32, value.as_int() as i32)),
+                        MidiMessage::ProgramChange { program } => Some(ProgramEvent(0, channel, program.as_int())),
+                        MidiMessage::PitchBend { bend } => Some(PitchBendEvent(0, channel, bend.as_int())),
+                        _ => None,
+                    };
+                    if let Some(ev) = ev { events.push((time, ev)); }
+                },
+                TrackEventKind::Meta(MetaMessage::Tempo(t)) => {
+                    segment_tick = tick;
+                    segment_time = time;
+                    tempo = t.as_int() as u64;
+                },
+                _ => {},
+            }
+        }
+
+        (events, length)
+    }
+
+    /// Sets the timer to expire when the next event is due, or disables it at the end.
+    fn schedule_timer(&self, now: Instant) -> Result<(), Box<dyn Error>> {
+        match (self.started, self.events.get(self.position)) {
+            (Some(started), Some((time, _))) => {
+                // A zero expiration would disable the timer, so wait at least a nanosecond.
+                let wait = (started + *time).saturating_duration_since(now).max(Duration::from_nanos(1));
+                self.timer.set(Expiration::OneShot(TimeSpec::from(wait)), TimerSetTimeFlags::empty())?;
+            },
+            _ => self.timer.unset()?,
+        }
+        Ok(())
+    }
+}
+
+impl Backend for MidiFileBackend {
+    fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn create_in_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        if name.split_once(':').map_or(name, |(backend_name, _)| backend_name) != "midifile" {
+            return Ok(false);
+        }
+        self.port = port;
+        Ok(true)
+    }
+
+    fn create_out_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn connect_in_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn connect_out_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn get_pollfds(&mut self) -> Result<Vec<libc::pollfd>, Box<dyn Error>> {
+        // Playback starts when the runner starts polling.
+        if self.started.is_none() {
+            let now = Instant::now();
+            self.started = Some(now);
+            self.schedule_timer(now)?;
+        }
+        Ok(vec![libc::pollfd { fd: self.timer.as_raw_fd(), events: libc::POLLIN, revents: 0 }])
+    }
+
+    fn run<'evs: 'run, 'run>(&'run mut self) -> Result<(EventStream<'evs>, bool), Box<dyn Error>> {
+        // Acknowledge the timer expiration; nothing to read is fine too.
+        let _ = nix::unistd::read(self.timer.as_raw_fd(), &mut [0u8; 8]);
+
+        let mut evs = EventStream::empty();
+        let now = Instant::now();
+        if let Some(mut started) = self.started {
+            loop {
+                while let Some((time, ev)) = self.events.get(self.position) {
+                    if started + *time > now { break; }
+                    let mut ev = ev.clone();
+                    ev.set_port(self.port);
+                    evs.push(ev);
+                    self.position += 1;
+                }
+                if self.position < self.events.len() || !self.looping || self.length.is_zero() { break; }
+                started += self.length;
+                self.position = 0;
+            }
+            self.started = Some(started);
+        }
+        self.schedule_timer(now)?;
+
+        Ok((evs, false))
+    }
+
+    fn output_event(&mut self, _ev: &Event) -> Result<u32, Box<dyn Error>> {
+        Ok(0)
+    }
+}
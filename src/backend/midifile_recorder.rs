@@ -0,0 +1,156 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use web_time::Instant;
+
+extern crate midly;
+use midly::num::{u4, u7, u15, u24, u28};
+use midly::{Format, Header, MetaMessage, MidiMessage, PitchBend, Smf, Timing, TrackEvent, TrackEventKind};
+
+use crate::proc::{Event, EventStream};
+use crate::backend::{Backend, PortNum};
+
+/// Ticks per quarter note in recorded files, at 120 bpm this is about a millisecond per tick.
+const TICKS_PER_BEAT: u16 = 480;
+/// Tempo of recorded files, in microseconds per quarter note (120 bpm).
+const TEMPO: u32 = 500_000;
+
+/// MIDI backend that records all output events to a standard MIDI file.
+///
+/// Notes, controllers, program changes and pitch bend are recorded with the time they were
+/// output, into a single track (format 0); events of all output ports end up in it. The file is
+/// written on [MidiFileRecorderBackend::flush] and when the backend is dropped.
+///
+/// It doesn't handle events itself, so they are still sent to the other backends as well.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::*;
+/// let path = std::env::temp_dir().join("rmididings-doctest-recorder.mid");
+///
+/// let mut md = RMididings::new().unwrap();
+/// md.config(ConfigArguments {
+///     backend: BackendType::Null,
+///     record_midi_file: Some(&path),
+///     ..ConfigArguments::default()
+/// }).unwrap();
+/// let mut runner = md.start(RunArguments {
+///     patch: &Transpose(12),
+///     ..RunArguments::default()
+/// }).unwrap();
+/// runner.inject_event(NoteOnEvent(1,1,60,100)).unwrap();
+/// drop(runner);
+/// drop(md);
+///
+/// // Header, then a track with the tempo, the note and its end.
+/// let data = std::fs::read(&path).unwrap();
+/// assert_eq!(&data[0..4], b"MThd");
+/// assert_eq!(&data[14..18], b"MTrk");
+/// assert!(data.windows(3).any(|w| w == [0x90, 72, 100]));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct MidiFileRecorderBackend {
+    path: PathBuf,
+    started: Instant,
+    events: Vec<(Instant, u8, MidiMessage)>,
+}
+
+impl MidiFileRecorderBackend {
+    /// Records to the MIDI file at `path`, replacing it when it exists.
+    pub fn new(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let recorder = Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            events: vec![],
+        };
+        // Make sure we can write there, rather than finding out at the end.
+        recorder.write()?;
+        Ok(recorder)
+    }
+
+    /// Writes all events recorded so far to the file.
+    pub fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.write()
+    }
+
+    fn write(&self) -> Result<(), Box<dyn Error>> {
+        let mut track = vec![TrackEvent {
+            delta: u28::new(0),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(TEMPO))),
+        }];
+        let ticks_per_us = TICKS_PER_BEAT as f64 / TEMPO as f64;
+        let mut last_tick: u64 = 0;
+        for (time, channel, message) in self.events.iter() {
+            let us = time.saturating_duration_since(self.started).as_micros() as f64;
+            let tick = (us * ticks_per_us).round() as u64;
+            track.push(TrackEvent {
+                delta: u28::new((tick - last_tick).min(u28::max_value().as_int() as u64) as u32),
+                kind: TrackEventKind::Midi { channel: u4::new(*channel), message: *message },
+            });
+            last_tick = tick;
+        }
+        track.push(TrackEvent { delta: u28::new(0), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) });
+
+        let smf = Smf {
+            header: Header::new(Format::SingleTrack, Timing::Metrical(u15::new(TICKS_PER_BEAT))),
+            tracks: vec![track],
+        };
+        Ok(smf.save(&self.path)?)
+    }
+}
+
+impl Drop for MidiFileRecorderBackend {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("Could not write MIDI file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+impl Backend for MidiFileRecorderBackend {
+    fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn create_in_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn create_out_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn connect_in_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn connect_out_port(&mut self, _port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(false)
+    }
+
+    fn get_pollfds(&mut self) -> Result<Vec<libc::pollfd>, Box<dyn Error>> {
+        Ok(vec![])
+    }
+
+    fn run<'evs: 'run, 'run>(&'run mut self) -> Result<(EventStream<'evs>, bool), Box<dyn Error>> {
+        Ok((EventStream::empty(), false))
+    }
+
+    fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        // Out of range values are clamped, rather than having their high bits dropped.
+        let data = |value: i64| u7::new(value.clamp(0, 127) as u8);
+        let recorded = match ev {
+            Event::NoteOn(ev) => Some((ev.channel, MidiMessage::NoteOn { key: data(ev.note as i64), vel: data(ev.velocity as i64) })),
+            Event::NoteOff(ev) => Some((ev.channel, MidiMessage::NoteOff { key: data(ev.note as i64), vel: data(ev.velocity as i64) })),
+            Event::Ctrl(ev) => Some((ev.channel, MidiMessage::Controller { controller: data(ev.ctrl as i64), value: data(ev.value as i64) })),
+            Event::Program(ev) => Some((ev.channel, MidiMessage::ProgramChange { program: data(ev.program as i64) })),
+            Event::PitchBend(ev) => Some((ev.channel, MidiMessage::PitchBend { bend: PitchBend::from_int(ev.value.clamp(-8192, 8191)) })),
+            _ => None,
+        };
+        if let Some((channel, message)) = recorded {
+            self.events.push((Instant::now(), channel.min(15), message));
+        }
+        // Let the other backends output the event too.
+        Ok(0)
+    }
+}
@@ -1,12 +1,14 @@
 extern crate libc;
 
 mod backend;
-pub use self::backend::{Backend, PortNum};
+pub use self::backend::{pollfd, Backend, PortDescriptor, PortNum};
 
 mod null;
 pub use self::null::NullBackend;
 
+#[cfg(not(target_arch = "wasm32"))]
 mod ctrlc;
+#[cfg(not(target_arch = "wasm32"))]
 pub use self::ctrlc::CtrlcBackend;
 
 #[cfg(feature = "alsa")]
@@ -17,4 +19,22 @@ pub use self::alsa::AlsaBackend;
 #[cfg(feature = "osc")]
 mod osc;
 #[cfg(feature = "osc")]
-pub use self::osc::OscBackend;
\ No newline at end of file
+pub use self::osc::{OscBackend, OscReconnect};
+
+#[cfg(feature = "dbus")]
+mod dbus;
+#[cfg(feature = "dbus")]
+pub use self::dbus::DbusBackend;
+
+#[cfg(feature = "midifile")]
+mod midifile;
+#[cfg(feature = "midifile")]
+pub use self::midifile::MidiFileBackend;
+#[cfg(feature = "midifile")]
+mod midifile_recorder;
+#[cfg(feature = "midifile")]
+pub use self::midifile_recorder::MidiFileRecorderBackend;
+#[cfg(feature = "webmidi")]
+mod webmidi;
+#[cfg(feature = "webmidi")]
+pub use self::webmidi::WebMidiBackend;
@@ -1,21 +1,79 @@
+use std::collections::{HashSet, VecDeque};
 use std::error::Error;
+use std::sync::{Arc, Mutex};
 
-use crate::proc::{Event, EventStream};
-use crate::backend::{Backend, PortNum};
+use crate::proc::{Event, EventStream, SysExEvent};
+use crate::backend::{pollfd, Backend, PortNum};
+
+#[derive(Default)]
+struct NullBackendState {
+    out_ports: HashSet<PortNum>,
+    input: VecDeque<Event<'static>>,
+    output: Option<Vec<Event<'static>>>,
+}
 
 /// Null MIDI backend.
 ///
-/// This backend receives no input and generates no output, it accepts all
-/// in/out ports. It isn't expected to be useful in practice, just for testing.
-pub struct NullBackend {}
+/// This backend accepts all in/out ports, and isn't connected to anything. It isn't expected
+/// to be useful in practice, just for testing: events given to [NullBackend::push_input] are
+/// received, and what is sent to its ports can be read with [NullBackend::take_output].
+///
+/// Clones share their events, get one of the backend that is used with
+/// [RMididings::null_backend](crate::RMididings::null_backend). Ports are numbered as by
+/// backends, starting at 0, so without the `port_offset` of patches.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::*;
+/// use std::time::Duration;
+///
+/// let mut md = RMididings::builder().backend(BackendType::Null).in_port("input", "").out_port("output", "").build().unwrap();
+/// let null = md.null_backend().unwrap();
+/// let mut runner = md.start(RunArguments { patch: &Transpose(12), ..RunArguments::default() }).unwrap();
+///
+/// null.push_input(NoteOnEvent(0,0,60,100));
+/// runner.step(Duration::ZERO).unwrap();
+/// assert_eq!(null.take_output(), vec![NoteOnEvent(0,0,72,100)]);
+/// ```
+#[derive(Clone, Default)]
+pub struct NullBackend {
+    state: Arc<Mutex<NullBackendState>>,
+}
 
 impl NullBackend {
     pub fn new() -> Result<Self, Box<dyn Error>> {
-        Ok(Self {})
+        Ok(Self::default())
+    }
+
+    /// Queues an event, which is received the next time the backend runs.
+    pub fn push_input(&self, ev: Event<'static>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.input.push_back(ev);
+        }
+    }
+
+    /// Starts keeping the events sent to its ports.
+    ///
+    /// This is done when it is gotten with
+    /// [RMididings::null_backend](crate::RMididings::null_backend), so that a long running
+    /// backend doesn't grow when nobody takes them.
+    pub(crate) fn keep_output(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.output.get_or_insert_with(Vec::new);
+        }
+    }
+
+    /// Returns the events sent to its ports since the last call.
+    pub fn take_output(&self) -> Vec<Event<'static>> {
+        match self.state.lock() {
+            Ok(mut state) => state.output.as_mut().map(std::mem::take).unwrap_or_default(),
+            Err(_) => vec![],
+        }
     }
 }
 
-impl Backend<'_> for NullBackend {
+impl Backend for NullBackend {
     fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
@@ -27,10 +85,13 @@ impl Backend<'_> for NullBackend {
         return Ok(true);
     }
 
-    fn create_out_port(&mut self, _port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+    fn create_out_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
         if let Some((backend_name, _port_name)) = name.split_once(':') {
             if backend_name != "null" { return Ok(false); }
         }
+        if let Ok(mut state) = self.state.lock() {
+            state.out_ports.insert(port);
+        }
         return Ok(true);
     }
 
@@ -42,15 +103,62 @@ impl Backend<'_> for NullBackend {
         Ok(false)
     }
 
-    fn get_pollfds(&mut self) -> Result<Vec<libc::pollfd>, Box<dyn Error>> {
+    fn get_pollfds(&mut self) -> Result<Vec<pollfd>, Box<dyn Error>> {
         Ok(vec![])
     }
 
     fn run<'evs: 'run, 'run>(&'run mut self) -> Result<(EventStream<'evs>, bool), Box<dyn Error>> {
-        Ok((EventStream::empty(), false))
+        let mut evs = EventStream::empty();
+        if let Ok(mut state) = self.state.lock() {
+            evs.extend(state.input.drain(..));
+        }
+        Ok((evs, false))
+    }
+
+    fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Ok(0),
+        };
+        // Leave events for ports of other backends to them.
+        if !ev.port().is_some_and(|port| state.out_ports.contains(&port)) { return Ok(0); }
+        if let Some(output) = &mut state.output {
+            output.push(owned_event(ev));
+        }
+        Ok(1)
     }
+}
 
-    fn output_event(&mut self, _ev: &Event) -> Result<u32, Box<dyn Error>> {
-        Ok(0)
+/// Copy of an event that doesn't borrow from it.
+fn owned_event(ev: &Event) -> Event<'static> {
+    match ev {
+        Event::None(ev) => Event::None(*ev),
+        Event::NoteOn(ev) => Event::NoteOn(*ev),
+        Event::NoteOff(ev) => Event::NoteOff(*ev),
+        Event::Ctrl(ev) => Event::Ctrl(*ev),
+        Event::Nrpn(ev) => Event::Nrpn(*ev),
+        Event::Program(ev) => Event::Program(*ev),
+        Event::PitchBend(ev) => Event::PitchBend(*ev),
+        // Only tests keep output, so keeping the data for the rest of the program is fine.
+        Event::SysEx(ev) => SysExEvent(ev.port, ev.data.to_vec().leak()),
+        Event::SceneSwitch(ev) => Event::SceneSwitch(*ev),
+        Event::SubSceneSwitch(ev) => Event::SubSceneSwitch(*ev),
+        Event::Quit(ev) => Event::Quit(*ev),
+        Event::Resync(ev) => Event::Resync(*ev),
+        Event::Chord(ev) => Event::Chord(ev.clone()),
+        Event::Clock(ev) => Event::Clock(*ev),
+        Event::Start(ev) => Event::Start(*ev),
+        Event::Stop(ev) => Event::Stop(*ev),
+        Event::Continue(ev) => Event::Continue(*ev),
+        Event::SongPosition(ev) => Event::SongPosition(*ev),
+        Event::SongSelect(ev) => Event::SongSelect(*ev),
+        Event::MtcQuarterFrame(ev) => Event::MtcQuarterFrame(*ev),
+        Event::MtcFullFrame(ev) => Event::MtcFullFrame(*ev),
+        #[cfg(feature = "osc")]
+        Event::Osc(ev) => Event::Osc(ev.clone()),
+        #[cfg(feature = "osc")]
+        Event::OscBundle(ev) => Event::OscBundle(ev.clone()),
+        #[cfg(feature = "dbus")]
+        Event::Dbus(ev) => Event::Dbus(ev.clone()),
     }
-}
\ No newline at end of file
+}
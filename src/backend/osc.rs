@@ -4,48 +4,102 @@ use std::io::{Read, Write};
 use std::os::unix::io::AsRawFd;
 use std::convert::TryInto;
 use std::collections::HashMap;
+use std::time::Duration;
+use web_time::Instant;
 
 pub extern crate rosc;
 
-use super::super::proc::event::{Event, OscEvent, OscEventImpl};
+use super::super::proc::event::{Event, OscBundleEventImpl, OscEventImpl};
 use super::super::proc::EventStream;
 use super::backend::{Backend, PortNum};
 
 /// Size of the network input buffer;
 const BUF_SIZE: usize = rosc::decoder::MTU;
 
+/// Time tag meaning a bundle is to be applied right away.
+const OSC_TIME_IMMEDIATELY: rosc::OscTime = rosc::OscTime { seconds: 0, fractional: 1 };
+
+/// A received OSC message, with the time tag of its bundle, if any.
+type TimedOscMessage = (rosc::OscMessage, Option<rosc::OscTime>);
+
 struct OscInPort {
     udp_listener: Option<UdpSocket>,
     tcp_listener: Option<TcpListener>,
     tcp_listen_streams: Vec<TcpStream>,
 }
 
-struct OscOutPort<'a> {
+struct OscOutPort {
     udp: bool,
     tcp: bool,
-    addr: Option<&'a str>,
+    addr: Option<String>,
     tcp_connect_stream: Option<TcpStream>,
+    // Delay before the next connection attempt, and when that may happen.
+    tcp_reconnect_delay: Duration,
+    tcp_reconnect_at: Option<Instant>,
+}
+
+impl OscOutPort {
+    // UDP can't tell whether anyone listens, TCP only reaches a connected peer.
+    fn has_receivers(&self) -> bool {
+        self.udp || self.tcp_connect_stream.is_some()
+    }
+}
+
+/// How often to retry connecting to an OSC TCP destination.
+///
+/// After a failed attempt, the next one is made after `initial_delay`, and each
+/// further failure multiplies the delay by `multiplier`, up to `max_delay`.
+/// Attempts are only made when there is an event to send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscReconnect {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f32,
+}
+
+impl Default for OscReconnect {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl OscReconnect {
+    fn next_delay(&self, delay: Duration) -> Duration {
+        delay.mul_f32(self.multiplier.max(1.0)).min(self.max_delay)
+    }
 }
 
 /// OSC Backend
-pub struct OscBackend<'a> {
+pub struct OscBackend {
     in_ports: HashMap<PortNum, OscInPort>,
-    out_ports: HashMap<PortNum, OscOutPort<'a>>,
+    out_ports: HashMap<PortNum, OscOutPort>,
     udp_sender: Option<UdpSocket>,
+    reconnect: OscReconnect,
+    out_port_receivers_changed: bool,
     buf: [u8; BUF_SIZE],
 }
 
-impl<'a> OscBackend<'a> {
+impl OscBackend {
     pub fn new() -> Result<Self, Box<dyn Error>> {
         Ok(Self {
             in_ports: HashMap::new(),
             out_ports: HashMap::new(),
             udp_sender: None,
+            reconnect: OscReconnect::default(),
+            out_port_receivers_changed: false,
             buf: [0; BUF_SIZE],
         })
     }
 
-    fn _create_in_port(&mut self, backend_port: PortNum, name: &'a str, udp: bool, tcp: bool) -> Result<bool, Box<dyn Error>> {
+    pub fn set_reconnect(&mut self, reconnect: OscReconnect) {
+        self.reconnect = reconnect;
+    }
+
+    fn _create_in_port(&mut self, backend_port: PortNum, name: &str, udp: bool, tcp: bool) -> Result<bool, Box<dyn Error>> {
         let mut udp_listener = None;
         let mut tcp_listener = None;
 
@@ -69,7 +123,7 @@ impl<'a> OscBackend<'a> {
         Ok(true)
     }
 
-    fn _create_out_port(&mut self, backend_port: PortNum, _name: &'a str, udp: bool, tcp: bool) -> Result<bool, Box<dyn Error>> {
+    fn _create_out_port(&mut self, backend_port: PortNum, _name: &str, udp: bool, tcp: bool) -> Result<bool, Box<dyn Error>> {
         if udp {
             if self.udp_sender.is_none() {
                 self.udp_sender = Some(UdpSocket::bind("0.0.0.0:0")?);
@@ -81,18 +135,21 @@ impl<'a> OscBackend<'a> {
             tcp,
             addr: None,
             tcp_connect_stream: None,
+            tcp_reconnect_delay: self.reconnect.initial_delay,
+            tcp_reconnect_at: None,
         });
+        self.out_port_receivers_changed = true;
 
         Ok(true)
     }
 }
 
-impl<'a> Backend<'a> for OscBackend<'a> {
+impl Backend for OscBackend {
     fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
 
-    fn create_in_port(&mut self, backend_port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>> {
+    fn create_in_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
         if let Some((backend_name, port_name)) = name.split_once(':') {
             let port_name = port_name.strip_prefix("//").unwrap_or(port_name); // allow use of: osc://localhost:1234
             match backend_name {
@@ -106,7 +163,7 @@ impl<'a> Backend<'a> for OscBackend<'a> {
         }
     }
 
-    fn create_out_port(&mut self, backend_port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>> {
+    fn create_out_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
         if let Some((backend_name, port_name)) = name.split_once(':') {
             let port_name = port_name.strip_prefix("//").unwrap_or(port_name); // allow use of: osc://localhost:1234
             match backend_name {
@@ -120,26 +177,23 @@ impl<'a> Backend<'a> for OscBackend<'a> {
         }
     }
 
-    fn connect_in_port(&mut self, _backend_port: PortNum, _name: &'a str) -> Result<bool, Box<dyn Error>> {
+    fn connect_in_port(&mut self, _backend_port: PortNum, _name: &str) -> Result<bool, Box<dyn Error>> {
         // Not applicable, others connect to our in ports.
         Ok(false)
     }
 
-    fn connect_out_port(&mut self, backend_port: PortNum, name: &'a str) -> Result<bool, Box<dyn Error>> {
+    fn connect_out_port(&mut self, backend_port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
         if let Some(port) = self.out_ports.get_mut(&backend_port) {
-            port.addr = Some(name);
+            port.addr = Some(name.to_string());
 
             // UDP needs no connection setup, we just send it.
 
             if port.tcp {
-                if let Ok(stream) = TcpStream::connect(name) {
-                    stream.set_nonblocking(true)?;
-                    port.tcp_connect_stream = Some(stream);
-                    println!("OSC connection to {} succeeded.", name);
+                if tcp_connect(port, &self.reconnect)? {
+                    self.out_port_receivers_changed = true;
+                    info!("OSC connection to {} succeeded.", name);
                 } else {
-                    // TODO better warning system
-                    // TODO allow connecting later (requires pollfds update during run)
-                    println!("OSC connection to {} failed.", name);
+                    warn!("OSC connection to {} failed, will retry later.", name);
                 }
             }
             Ok(true)
@@ -178,26 +232,26 @@ impl<'a> Backend<'a> for OscBackend<'a> {
             }
 
             if let Some(tcp_listener) = &port.tcp_listener {
-                // TODO move to function
-                for stream in tcp_listener.incoming() {
-                    match stream {
-                        Ok(stream) => {
-                            stream.set_nonblocking(true)?;
-                            // stream.set_nodelay(true)?;
-                            port.tcp_listen_streams.push(stream);
-                            new_connection = true;
-                        },
-                        Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => { break; },
-                        Err(e) => { return Err(Box::new(e)) },
-                    }
-                }
+                let streams = accept_tcp_streams(tcp_listener)?;
+                new_connection |= !streams.is_empty();
+                port.tcp_listen_streams.extend(streams);
             }
 
-            for tcp_stream in port.tcp_listen_streams.iter_mut() {
-                if let Some(data) = read_tcp_data(tcp_stream, &mut self.buf)? {
-                    evs.extend(decode_data_tcp(data).into_iter().map(|o| build_event(o, *backend_port)));
+            let mut closed = vec![];
+            for (i, tcp_stream) in port.tcp_listen_streams.iter_mut().enumerate() {
+                match read_tcp_data(tcp_stream, &mut self.buf) {
+                    // The client disconnected, it can connect again to the listener.
+                    Ok(Some([])) | Err(_) => closed.push(i),
+                    Ok(Some(data)) => {
+                        evs.extend(decode_data_tcp(data).into_iter().map(|o| build_event(o, *backend_port)));
+                    },
+                    Ok(None) => {},
                 }
             }
+            for i in closed.into_iter().rev() {
+                port.tcp_listen_streams.remove(i);
+                new_connection = true;
+            }
         }
 
         // As far as I've seen, OSC doesn't respond on connections opened by us.
@@ -213,20 +267,29 @@ impl<'a> Backend<'a> for OscBackend<'a> {
         Ok((evs, new_connection))
     }
 
+    fn out_port_receivers(&self) -> Vec<(PortNum, bool)> {
+        self.out_ports.iter().map(|(backend_port, port)| (*backend_port, port.has_receivers())).collect()
+    }
+
+    fn out_port_receivers_changed(&mut self) -> bool {
+        std::mem::take(&mut self.out_port_receivers_changed)
+    }
+
     fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
         match ev {
-            Event::Osc(ref ev) => { self._output_event(ev) },
+            Event::Osc(ref ev) => { self._output_event(ev.port, &osc_message_packet(ev)) },
+            Event::OscBundle(ref ev) => { self._output_event(ev.port, &osc_bundle_packet(ev)) },
             _ => Ok(0)
         }
     }
 }
 
-impl<'a> OscBackend<'a> {
-    fn _output_event(&mut self, ev: &OscEventImpl) -> Result <u32, Box<dyn Error>> {
+impl OscBackend {
+    fn _output_event(&mut self, port: PortNum, packet: &rosc::OscPacket) -> Result <u32, Box<dyn Error>> {
         let mut bytes = 0;
 
         // Use indicated port, but if there is only one OSC port, use that for ease of use.
-        let mut backend_port = ev.port;
+        let mut backend_port = port;
         if self.out_ports.len() == 1 {
             if let Some(p) = self.out_ports.keys().next() {
                 backend_port = *p;
@@ -234,28 +297,44 @@ impl<'a> OscBackend<'a> {
         }
 
         if let Some(port) = self.out_ports.get_mut(&backend_port) {
+            let had_receivers = port.has_receivers();
             if port.udp {
                 if let Some(addr) = &port.addr {
                     if let Some(socket) = &self.udp_sender {
-                        bytes += send_osc_udp(socket, addr, &ev.addr, &ev.args)?;
+                        bytes += send_osc_udp(socket, addr, packet)?;
                     }
                 }
             }
             if port.tcp {
-                if let Some(_) = &port.tcp_connect_stream {
-                    // We already have a stream, nothing to do.
-                } else if let Some(addr) = &port.addr {
-                    if let Ok(stream) = TcpStream::connect(addr) {
-                        stream.set_nonblocking(true)?;
-                        port.tcp_connect_stream = Some(stream);
-                        println!("OSC connection to {} succeeded, will retry later.", addr);
-                    }
+                // A restarted receiver closes the connection, which writes wouldn't notice.
+                if port.tcp_connect_stream.as_ref().is_some_and(tcp_peer_closed) {
+                    warn!("OSC connection to {} closed by the receiver, reconnecting.", port.addr.as_deref().unwrap_or_default());
+                    port.tcp_connect_stream = None;
+                    port.tcp_reconnect_delay = self.reconnect.initial_delay;
+                    port.tcp_reconnect_at = None;
+                }
+
+                let due = port.tcp_reconnect_at.is_none_or(|at| Instant::now() >= at);
+                if port.tcp_connect_stream.is_none() && due && tcp_connect(port, &self.reconnect)? {
+                    info!("OSC connection to {} succeeded.", port.addr.as_deref().unwrap_or_default());
                 }
 
                 if let Some(tcp_stream) = &mut port.tcp_connect_stream {
-                    bytes += send_osc_tcp(tcp_stream, &ev.addr, &ev.args)?;
+                    match send_osc_tcp(tcp_stream, packet) {
+                        Ok(b) => bytes += b,
+                        Err(e) => {
+                            // The connection dropped, connect again on a next event.
+                            warn!("OSC connection to {} lost: {}", port.addr.as_deref().unwrap_or_default(), e);
+                            port.tcp_connect_stream = None;
+                            port.tcp_reconnect_delay = self.reconnect.initial_delay;
+                            port.tcp_reconnect_at = None;
+                        },
+                    }
                 }
             }
+            if port.has_receivers() != had_receivers {
+                self.out_port_receivers_changed = true;
+            }
         }
 
         Ok(bytes as u32)
@@ -263,21 +342,80 @@ impl<'a> OscBackend<'a> {
 
 }
 
-fn send_osc_udp(socket: &UdpSocket, dest: &str, addr: &str, args: &Vec<rosc::OscType>) -> Result<usize, Box<dyn Error>> {
-    let message = rosc::OscMessage { addr: String::from(addr), args: args.clone() };
-    let data = rosc::encoder::encode(&rosc::OscPacket::Message(message))?;
+/// Connects the out port's TCP stream, scheduling the next attempt on failure.
+///
+/// Returns whether the connection succeeded.
+fn tcp_connect(port: &mut OscOutPort, reconnect: &OscReconnect) -> Result<bool, Box<dyn Error>> {
+    let addr = match &port.addr {
+        Some(addr) => addr,
+        None => return Ok(false),
+    };
+
+    if let Ok(stream) = TcpStream::connect(addr.as_str()) {
+        stream.set_nonblocking(true)?;
+        port.tcp_connect_stream = Some(stream);
+        port.tcp_reconnect_delay = reconnect.initial_delay;
+        port.tcp_reconnect_at = None;
+        Ok(true)
+    } else {
+        port.tcp_reconnect_at = Some(Instant::now() + port.tcp_reconnect_delay);
+        port.tcp_reconnect_delay = reconnect.next_delay(port.tcp_reconnect_delay);
+        Ok(false)
+    }
+}
+
+fn osc_message_packet(ev: &OscEventImpl) -> rosc::OscPacket {
+    rosc::OscPacket::Message(rosc::OscMessage { addr: ev.addr.clone(), args: ev.args.clone() })
+}
+
+fn osc_bundle_packet(ev: &OscBundleEventImpl) -> rosc::OscPacket {
+    rosc::OscPacket::Bundle(rosc::OscBundle {
+        timetag: ev.time.unwrap_or(OSC_TIME_IMMEDIATELY),
+        content: ev.messages.iter().map(osc_message_packet).collect(),
+    })
+}
+
+fn send_osc_udp(socket: &UdpSocket, dest: &str, packet: &rosc::OscPacket) -> Result<usize, Box<dyn Error>> {
+    let data = rosc::encoder::encode(packet)?;
     Ok(socket.send_to(&data, &dest)?)
 }
 
-fn send_osc_tcp(stream: &mut TcpStream, addr: &str, args: &Vec<rosc::OscType>) -> Result<usize, Box<dyn Error>> {
-    let message = rosc::OscMessage { addr: String::from(addr), args: args.clone() };
-    let data = rosc::encoder::encode(&rosc::OscPacket::Message(message))?;
+fn send_osc_tcp(stream: &mut TcpStream, packet: &rosc::OscPacket) -> Result<usize, Box<dyn Error>> {
+    let data = rosc::encoder::encode(packet)?;
     // https://github.com/klingtnet/rosc/issues/19
-    let mut bytes = 0;
-    bytes += stream.write(&(data.len() as i32).to_be_bytes())?;
-    bytes += stream.write(&data)?;
+    let length = (data.len() as i32).to_be_bytes();
+    // Partial writes would leave the receiver with a broken packet.
+    stream.write_all(&length)?;
+    stream.write_all(&data)?;
     stream.flush()?;
-    Ok(bytes)
+    Ok(length.len() + data.len())
+}
+
+/// Whether the other end closed the connection, which is only noticed when reading.
+fn tcp_peer_closed(stream: &TcpStream) -> bool {
+    let mut buf = [0u8; 1];
+    match stream.peek(&mut buf) {
+        Ok(0) => true,
+        Ok(_) => false,
+        Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => false,
+        Err(_) => true,
+    }
+}
+
+/// Accepts all clients waiting to connect, their streams need to be polled from now on.
+fn accept_tcp_streams(tcp_listener: &TcpListener) -> Result<Vec<TcpStream>, Box<dyn Error>> {
+    let mut streams = vec![];
+    for stream in tcp_listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                stream.set_nonblocking(true)?;
+                streams.push(stream);
+            },
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+    Ok(streams)
 }
 
 fn read_udp_data<'a>(socket: &UdpSocket, data: &'a mut [u8]) -> Result<Option<&'a [u8]>, Box<dyn Error>> {
@@ -288,9 +426,9 @@ fn read_udp_data<'a>(socket: &UdpSocket, data: &'a mut [u8]) -> Result<Option<&'
     }
 }
 
-fn decode_data(data: &[u8]) -> Vec::<rosc::OscMessage> {
+fn decode_data(data: &[u8]) -> Vec::<TimedOscMessage> {
     if let Ok(packet) = rosc::decoder::decode(data) {
-        get_messages_from_packet(packet)
+        get_messages_from_packet(packet, None)
     } else {
         // silently ignore malformed packets
         vec![]
@@ -305,9 +443,9 @@ fn read_tcp_data<'a>(stream: &mut TcpStream, data: &'a mut [u8]) -> Result<Optio
     }
 }
 
-fn decode_data_tcp(data: &[u8]) -> Vec::<rosc::OscMessage> {
+fn decode_data_tcp(data: &[u8]) -> Vec::<TimedOscMessage> {
     // https://github.com/klingtnet/rosc/issues/19
-    let mut messages = Vec::<rosc::OscMessage>::new();
+    let mut messages = Vec::<TimedOscMessage>::new();
 
     let mut i: usize = 0;
     while i < data.len() {
@@ -324,17 +462,19 @@ fn decode_data_tcp(data: &[u8]) -> Vec::<rosc::OscMessage> {
     messages
 }
 
-fn get_messages_from_packet(packet: rosc::OscPacket) -> Vec::<rosc::OscMessage> {
+/// Bundles are flattened, each message keeps the time tag of the bundle it was in.
+fn get_messages_from_packet(packet: rosc::OscPacket, time: Option<rosc::OscTime>) -> Vec::<TimedOscMessage> {
     match packet {
         rosc::OscPacket::Message(msg) => {
-            vec!(msg)
+            vec!((msg, time))
         },
         rosc::OscPacket::Bundle(bundle) => {
-            bundle.content.into_iter().map(|p| get_messages_from_packet(p)).flatten().collect()
+            let time = Some(bundle.timetag).filter(|t| *t != OSC_TIME_IMMEDIATELY);
+            bundle.content.into_iter().flat_map(|p| get_messages_from_packet(p, time)).collect()
         },
     }
 }
 
-fn build_event<'a>(message: rosc::OscMessage, port: PortNum) -> Event<'a> {
-    OscEvent(port, message.addr, message.args)
-}
\ No newline at end of file
+fn build_event<'a>((message, time): TimedOscMessage, port: PortNum) -> Event<'a> {
+    Event::Osc(OscEventImpl { port, addr: message.addr, args: message.args, time })
+}
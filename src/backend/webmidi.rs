@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::rc::Rc;
+
+extern crate js_sys;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_futures;
+extern crate web_sys;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{MidiAccess, MidiInput, MidiMessageEvent, MidiOutput, MidiPort};
+
+use crate::proc::event::*;
+use crate::proc::EventStream;
+use crate::backend::{pollfd, Backend, PortNum};
+
+type MidiMessageCallback = Closure<dyn FnMut(MidiMessageEvent)>;
+/// Raw MIDI messages received by the browser's callbacks, with our port.
+type ReceivedQueue = Rc<RefCell<VecDeque<(PortNum, Vec<u8>)>>>;
+
+/// MIDI backend using the Web MIDI API, for running in a browser (`wasm32-unknown-unknown`).
+///
+/// The browser asks the user for access to MIDI devices when the backend is created, until
+/// that is granted no events are received or sent. A web page can't create MIDI ports of its
+/// own, so each in and out port is bound to the device port it connects to, by name. Ports may
+/// be named like `webmidi:name` to make sure this backend handles them.
+///
+/// Received MIDI messages are queued by the browser's callbacks, and returned on the next `run`.
+/// There are no file descriptors to poll, so the runner is stepped from the browser's event
+/// loop instead, with [RMididings::spawn_local](crate::RMididings::spawn_local) or
+/// [Runner::run_web](crate::Runner::run_web).
+///
+/// SysEx messages are not received, and only sent when the browser allows it.
+pub struct WebMidiBackend {
+    access: Rc<RefCell<Option<MidiAccess>>>,
+    in_ports: HashMap<PortNum, WebMidiInPort>,
+    out_ports: HashMap<PortNum, WebMidiOutPort>,
+    received: ReceivedQueue,
+    bound: bool,
+    out_port_receivers_changed: bool,
+}
+
+struct WebMidiInPort {
+    connect: Option<String>,
+    input: Option<MidiInput>,
+    // Needs to live as long as it is set on the input.
+    callback: Option<MidiMessageCallback>,
+}
+
+struct WebMidiOutPort {
+    connect: Option<String>,
+    output: Option<MidiOutput>,
+}
+
+impl WebMidiBackend {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let navigator = web_sys::window().ok_or("Web MIDI is only available in a browser window")?.navigator();
+        let promise = navigator.request_midi_access().map_err(js_error)?;
+
+        let access = Rc::new(RefCell::new(None));
+        let granted = access.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match wasm_bindgen_futures::JsFuture::from(promise).await {
+                Ok(value) => { granted.replace(Some(value.unchecked_into::<MidiAccess>())); },
+                Err(e) => warn!("No access to MIDI devices: {:?}", e),
+            }
+        });
+
+        Ok(Self {
+            access,
+            in_ports: HashMap::new(),
+            out_ports: HashMap::new(),
+            received: Rc::new(RefCell::new(VecDeque::new())),
+            bound: false,
+            out_port_receivers_changed: false,
+        })
+    }
+
+    /// Binds our ports to the device ports they connect to, once access is granted.
+    fn bind_ports(&mut self, access: &MidiAccess) {
+        for (port, in_port) in self.in_ports.iter_mut() {
+            let Some(name) = &in_port.connect else { continue };
+            let Some(input) = find_port(access.inputs().values(), name).map(JsCast::unchecked_into::<MidiInput>) else {
+                warn!("MIDI input {} not found", name);
+                continue;
+            };
+
+            let received = self.received.clone();
+            let port = *port;
+            let callback: MidiMessageCallback = Closure::new(move |ev: MidiMessageEvent| {
+                if let Ok(data) = ev.data() {
+                    received.borrow_mut().push_back((port, data));
+                }
+            });
+            input.set_onmidimessage(Some(callback.as_ref().unchecked_ref()));
+            in_port.input = Some(input);
+            in_port.callback = Some(callback);
+        }
+
+        for out_port in self.out_ports.values_mut() {
+            let Some(name) = &out_port.connect else { continue };
+            out_port.output = find_port(access.outputs().values(), name).map(JsCast::unchecked_into::<MidiOutput>);
+            if out_port.output.is_none() {
+                warn!("MIDI output {} not found", name);
+            }
+        }
+    }
+}
+
+impl Drop for WebMidiBackend {
+    fn drop(&mut self) {
+        for in_port in self.in_ports.values() {
+            if let Some(input) = &in_port.input {
+                input.set_onmidimessage(None);
+            }
+        }
+    }
+}
+
+impl Backend for WebMidiBackend {
+    fn set_client_name(&mut self, _name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn create_in_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        if !is_webmidi_port(name) { return Ok(false); }
+        self.in_ports.insert(port, WebMidiInPort { connect: None, input: None, callback: None });
+        Ok(true)
+    }
+
+    fn create_out_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        if !is_webmidi_port(name) { return Ok(false); }
+        self.out_ports.insert(port, WebMidiOutPort { connect: None, output: None });
+        self.out_port_receivers_changed = true;
+        Ok(true)
+    }
+
+    fn connect_in_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        match self.in_ports.get_mut(&port) {
+            Some(in_port) if !name.is_empty() => { in_port.connect = Some(name.to_string()); Ok(true) },
+            _ => Ok(false),
+        }
+    }
+
+    fn connect_out_port(&mut self, port: PortNum, name: &str) -> Result<bool, Box<dyn Error>> {
+        match self.out_ports.get_mut(&port) {
+            Some(out_port) if !name.is_empty() => { out_port.connect = Some(name.to_string()); Ok(true) },
+            _ => Ok(false),
+        }
+    }
+
+    fn get_pollfds(&mut self) -> Result<Vec<pollfd>, Box<dyn Error>> {
+        // The browser calls us back instead.
+        Ok(vec![])
+    }
+
+    fn run<'evs: 'run, 'run>(&'run mut self) -> Result<(EventStream<'evs>, bool), Box<dyn Error>> {
+        if !self.bound {
+            let access = self.access.borrow().clone();
+            if let Some(access) = access {
+                self.bind_ports(&access);
+                self.bound = true;
+                self.out_port_receivers_changed = true;
+            }
+        }
+
+        let received: Vec<(PortNum, Vec<u8>)> = self.received.borrow_mut().drain(..).collect();
+        let evs = received.iter().filter_map(|(port, data)| midi_bytes_to_event(*port, data)).collect();
+        Ok((evs, false))
+    }
+
+    fn out_port_receivers(&self) -> Vec<(PortNum, bool)> {
+        self.out_ports.iter().map(|(port, out_port)| (*port, out_port.output.is_some())).collect()
+    }
+
+    fn out_port_receivers_changed(&mut self) -> bool {
+        std::mem::take(&mut self.out_port_receivers_changed)
+    }
+
+    fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        let Some((port, data)) = event_to_midi_bytes(ev) else { return Ok(0) };
+        let Some(output) = self.out_ports.get(&port).and_then(|out_port| out_port.output.as_ref()) else { return Ok(0) };
+        // E.g. SysEx is refused when the browser didn't allow it, that shouldn't stop other events.
+        if let Err(e) = output.send(&js_sys::Uint8Array::from(data.as_slice())) {
+            warn!("Could not send MIDI message on port {}: {:?}", port, e);
+            return Ok(0);
+        }
+        Ok(data.len() as u32)
+    }
+}
+
+fn is_webmidi_port(name: &str) -> bool {
+    name.split_once(':').is_none_or(|(backend_name, _)| backend_name == "webmidi")
+}
+
+/// Finds the device port with the name among the values of a MIDI input or output map.
+fn find_port(ports: js_sys::Iterator, name: &str) -> Option<JsValue> {
+    ports.into_iter()
+        .filter_map(Result::ok)
+        .find(|port| port.unchecked_ref::<MidiPort>().name().as_deref() == Some(name))
+}
+
+fn js_error(e: JsValue) -> Box<dyn Error> {
+    format!("{:?}", e).into()
+}
+
+/// Decodes a MIDI message as received by Web MIDI, which always has a status byte.
+fn midi_bytes_to_event<'a>(port: PortNum, data: &[u8]) -> Option<Event<'a>> {
+    let (&status, data) = data.split_first()?;
+    let byte = |i: usize| data.get(i).copied();
+    let channel = status & 0x0f;
+    match status {
+        0xf1 => Some(MtcQuarterFrameEvent(port, (byte(0)? >> 4) & 0x07, byte(0)? & 0x0f)),
+        0xf2 => Some(SongPositionEvent(port, byte(0)? as u16 | (byte(1)? as u16) << 7)),
+        0xf3 => Some(SongSelectEvent(port, byte(0)?)),
+        0xf8 => Some(ClockEvent(port)),
+        0xfa => Some(StartEvent(port)),
+        0xfb => Some(ContinueEvent(port)),
+        0xfc => Some(StopEvent(port)),
+        _ => match status & 0xf0 {
+            // A note on without velocity is a note off.
+            0x90 if byte(1)? > 0 => Some(NoteOnEvent(port, channel, byte(0)?, byte(1)?)),
+            0x90 => Some(NoteOffEvent(port, channel, byte(0)?)),
+            0x80 => Some(NoteOffEventWithVelocity(port, channel, byte(0)?, byte(1)?)),
+            0xb0 => Some(CtrlEvent(port, channel, byte(0)? as u32, byte(1)? as i32)),
+            0xc0 => Some(ProgramEvent(port, channel, byte(0)?)),
+            0xe0 => Some(PitchBendEvent(port, channel, (byte(0)? as i16 | (byte(1)? as i16) << 7) - 8192)),
+            _ => None,
+        },
+    }
+}
+
+/// Encodes an event as MIDI message(s) for Web MIDI, with the port to send it on.
+fn event_to_midi_bytes(ev: &Event) -> Option<(PortNum, Vec<u8>)> {
+    // Out of range values are clamped, rather than having their high bits dropped.
+    let data = |value: i64| value.clamp(0, 127) as u8;
+    let status = |kind: u8, channel: u8| kind | (channel & 0x0f);
+    match ev {
+        Event::NoteOn(ev) => Some((ev.port, vec![status(0x90, ev.channel), data(ev.note as i64), data(ev.velocity as i64)])),
+        Event::NoteOff(ev) => Some((ev.port, vec![status(0x80, ev.channel), data(ev.note as i64), data(ev.velocity as i64)])),
+        Event::Ctrl(ev) => Some((ev.port, vec![status(0xb0, ev.channel), data(ev.ctrl as i64), data(ev.value as i64)])),
+        Event::Program(ev) => Some((ev.port, vec![status(0xc0, ev.channel), data(ev.program as i64)])),
+        Event::PitchBend(ev) => {
+            let value = (ev.value.clamp(-8192, 8191) + 8192) as u16;
+            Some((ev.port, vec![status(0xe0, ev.channel), value as u8 & 0x7f, (value >> 7) as u8 & 0x7f]))
+        },
+        Event::Nrpn(ev) => {
+            // Parameter number and data entry controllers, like ALSA sends it.
+            let ctrl = status(0xb0, ev.channel);
+            Some((ev.port, vec![
+                ctrl, 99, (ev.param >> 7) as u8 & 0x7f,
+                ctrl, 98, ev.param as u8 & 0x7f,
+                ctrl, 6, (ev.value >> 7) as u8 & 0x7f,
+                ctrl, 38, ev.value as u8 & 0x7f,
+            ]))
+        },
+        Event::MtcQuarterFrame(ev) => Some((ev.port, vec![0xf1, (ev.message_type & 0x07) << 4 | (ev.value & 0x0f)])),
+        Event::SongPosition(ev) => Some((ev.port, vec![0xf2, ev.position as u8 & 0x7f, (ev.position >> 7) as u8 & 0x7f])),
+        Event::SongSelect(ev) => Some((ev.port, vec![0xf3, data(ev.song as i64)])),
+        Event::Clock(ev) => Some((ev.port, vec![0xf8])),
+        Event::Start(ev) => Some((ev.port, vec![0xfa])),
+        Event::Continue(ev) => Some((ev.port, vec![0xfb])),
+        Event::Stop(ev) => Some((ev.port, vec![0xfc])),
+        Event::SysEx(ev) if !ev.data.is_empty() => Some((ev.port, ev.data.to_vec())),
+        _ => None,
+    }
+}
@@ -0,0 +1,175 @@
+//! Port and scene configuration from a TOML file.
+//!
+//! This keeps the wiring out of the program, so connections can be changed without
+//! recompiling. The patches themselves are still written in Rust. A file looks like:
+//!
+//! ```toml
+//! client_name = "Gig"
+//! backend = "alsa"
+//! data_offset = 1
+//! channel_offset = 0
+//! initial_scene = 0
+//!
+//! [[in_ports]]
+//! name = "input"
+//! connect = "Virtual Keyboard:Virtual Keyboard"
+//!
+//! [[out_ports]]
+//! name = "output"
+//! connect = "midisnoop:MIDI Input"
+//!
+//! [[scenes]]
+//! name = "Run"
+//!
+//! [[scenes]]
+//! name = "Pause"
+//! ```
+//!
+//! All keys are optional, defaults are those of [ConfigArguments](crate::ConfigArguments).
+//! Use it with [RMididings::config_from](crate::RMididings::config_from):
+//!
+//! ```no_run
+//! # use rmididings::*;
+//! let cfg = rmididings::config::from_path("setup.toml")?;
+//! let mut md = RMididings::new()?;
+//! md.config_from(&cfg)?;
+//!
+//! md.run_builder()
+//!     .scene(&cfg.scenes[0].name, Pass())
+//!     .scene(&cfg.scenes[1].name, Discard())
+//!     .run()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::proc::SceneNum;
+use crate::{BackendType, ConfigArguments};
+
+/// Configuration read from a file, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub client_name: String,
+    /// Backend to use, or the default of [ConfigArguments] when not given.
+    pub backend: Option<BackendType>,
+    pub data_offset: u8,
+    pub port_offset: Option<u8>,
+    pub channel_offset: Option<u8>,
+    pub initial_scene: SceneNum,
+    pub in_ports: Vec<PortConfig>,
+    pub out_ports: Vec<PortConfig>,
+    pub scenes: Vec<SceneConfig>,
+}
+
+/// A port, like an entry of `in_ports` or `out_ports` in [ConfigArguments].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortConfig {
+    pub name: String,
+    /// Port to connect to, or empty to leave it unconnected.
+    #[serde(default)]
+    pub connect: String,
+}
+
+/// Metadata of a scene, its patch is given when running.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneConfig {
+    pub name: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let args = ConfigArguments::default();
+        Self {
+            client_name: args.client_name.to_string(),
+            backend: None,
+            data_offset: args.data_offset,
+            port_offset: args.port_offset,
+            channel_offset: args.channel_offset,
+            initial_scene: args.initial_scene,
+            in_ports: vec![],
+            out_ports: vec![],
+            scenes: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Returns an error naming the offending key when the configuration can't be used.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        validate_name("client_name", &self.client_name, false)?;
+        for (key, ports) in [("in_ports", &self.in_ports), ("out_ports", &self.out_ports)] {
+            for (i, port) in ports.iter().enumerate() {
+                validate_name(&format!("{}[{}].name", key, i), &port.name, false)?;
+                validate_name(&format!("{}[{}].connect", key, i), &port.connect, true)?;
+            }
+        }
+        for (i, scene) in self.scenes.iter().enumerate() {
+            validate_name(&format!("scenes[{}].name", i), &scene.name, false)?;
+        }
+        if !self.scenes.is_empty() && self.initial_scene as usize >= self.scenes.len() {
+            return Err(format!("initial_scene: there is no scene {}, there are {} scenes", self.initial_scene, self.scenes.len()).into());
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(key: &str, name: &str, allow_empty: bool) -> Result<(), Box<dyn Error>> {
+    if name.is_empty() && !allow_empty {
+        return Err(format!("{}: must not be empty", key).into());
+    }
+    if name.contains('\0') {
+        return Err(format!("{}: {:?} contains a NUL byte", key, name).into());
+    }
+    Ok(())
+}
+
+/// Reads and validates a configuration file.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Parses and validates a configuration.
+///
+/// # Examples
+///
+/// ```
+/// let cfg = rmididings::config::from_str(r#"
+///     client_name = "Gig"
+///
+///     [[in_ports]]
+///     name = "keys"
+///     connect = "Virtual Keyboard:Virtual Keyboard"
+/// "#).unwrap();
+/// assert_eq!(cfg.client_name, "Gig");
+/// assert_eq!(cfg.in_ports[0].connect, "Virtual Keyboard:Virtual Keyboard");
+/// assert_eq!(cfg.data_offset, 1);
+/// ```
+///
+/// ```
+/// let err = rmididings::config::from_str(r#"
+///     [[out_ports]]
+///     name = ""
+/// "#).unwrap_err();
+/// assert_eq!(err.to_string(), "out_ports[0].name: must not be empty");
+///
+/// let err = rmididings::config::from_str("clientname = \"Gig\"").unwrap_err();
+/// assert!(err.to_string().contains("unknown field `clientname`"));
+/// ```
+pub fn from_str(s: &str) -> Result<Config, Box<dyn Error>> {
+    let config: Config = toml::from_str(s)?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Formats a configuration as TOML, which [from_str] reads back.
+pub fn to_string(config: &Config) -> Result<String, Box<dyn Error>> {
+    Ok(toml::to_string(config)?)
+}
@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::proc::{Beats, Discard, FilterTrait, Pass, PatchState, SceneNum, TempoHandle};
+use crate::scene::Scene;
+#[cfg(feature = "osc")]
+use crate::backend::OscReconnect;
+use super::{BackendType, ConfigArguments, PortFlags, RMididings, RunArguments};
+
+/// Configures [RMididings] step by step, an alternative to [ConfigArguments].
+///
+/// Names are copied, so port lists can be built at runtime, e.g. from a configuration file.
+/// Get one with [RMididings::builder].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::*;
+/// let inputs = vec!["keys".to_string(), "pads".to_string()];
+///
+/// let mut builder = RMididings::builder()
+///     .backend(BackendType::Null)
+///     .client_name("Builder Demo")
+///     .out_port("output", "midisnoop:MIDI Input")
+///     .initial_scene(1);
+/// for name in inputs.iter() {
+///     builder = builder.in_port(name, "");
+/// }
+/// let mut md = builder.build().unwrap();
+///
+/// md.run_builder()
+///     .patch(Chain!(PortNameFilter("pads"), Transpose(12)))
+///     .post(Quit())
+///     .run_until_quit_after(NoteOnEvent(2,1,60,100))
+///     .unwrap();
+/// ```
+pub struct ConfigBuilder {
+    args: ConfigArguments<'static>,
+    client_name: String,
+    in_ports: Vec<[String; 2]>,
+    out_ports: Vec<[String; 2]>,
+    out_port_velocity_range: Vec<Option<(u8, u8)>>,
+    out_port_flags: Vec<PortFlags>,
+    #[cfg(feature = "midifile")]
+    midi_file: Option<std::path::PathBuf>,
+    #[cfg(feature = "midifile")]
+    record_midi_file: Option<std::path::PathBuf>,
+}
+
+impl ConfigBuilder {
+    pub(crate) fn new() -> Self {
+        let args = ConfigArguments::default();
+        Self {
+            client_name: args.client_name.to_string(),
+            args,
+            in_ports: vec![],
+            out_ports: vec![],
+            out_port_velocity_range: vec![],
+            out_port_flags: vec![],
+            #[cfg(feature = "midifile")]
+            midi_file: None,
+            #[cfg(feature = "midifile")]
+            record_midi_file: None,
+        }
+    }
+
+    pub fn backend(mut self, backend: BackendType) -> Self {
+        self.args.backend = backend;
+        self
+    }
+
+    pub fn client_name(mut self, name: &str) -> Self {
+        self.client_name = name.to_string();
+        self
+    }
+
+    /// Adds an input port, connected to `connect` when it isn't empty.
+    pub fn in_port(mut self, name: &str, connect: &str) -> Self {
+        self.in_ports.push([name.to_string(), connect.to_string()]);
+        self
+    }
+
+    /// Adds an output port, connected to `connect` when it isn't empty.
+    pub fn out_port(mut self, name: &str, connect: &str) -> Self {
+        self.out_ports.push([name.to_string(), connect.to_string()]);
+        self
+    }
+
+    /// See `out_port_velocity_range` of [ConfigArguments].
+    pub fn out_port_velocity_range(mut self, ranges: Vec<Option<(u8, u8)>>) -> Self {
+        self.out_port_velocity_range = ranges;
+        self
+    }
+
+    /// See `out_port_flags` of [ConfigArguments].
+    pub fn out_port_flags(mut self, flags: Vec<PortFlags>) -> Self {
+        self.out_port_flags = flags;
+        self
+    }
+
+    pub fn data_offset(mut self, data_offset: u8) -> Self {
+        self.args.data_offset = data_offset;
+        self
+    }
+
+    /// See `port_offset` of [ConfigArguments].
+    pub fn port_offset(mut self, port_offset: u8) -> Self {
+        self.args.port_offset = Some(port_offset);
+        self
+    }
+
+    /// See `channel_offset` of [ConfigArguments].
+    pub fn channel_offset(mut self, channel_offset: u8) -> Self {
+        self.args.channel_offset = Some(channel_offset);
+        self
+    }
+
+    /// See `octave_offset` of [ConfigArguments].
+    pub fn octave_offset(mut self, octave_offset: u8) -> Self {
+        self.args.octave_offset = octave_offset;
+        self
+    }
+
+    pub fn scene_offset(mut self, scene_offset: SceneNum) -> Self {
+        self.args.scene_offset = scene_offset;
+        self
+    }
+
+    pub fn initial_scene(mut self, initial_scene: SceneNum) -> Self {
+        self.args.initial_scene = initial_scene;
+        self
+    }
+
+    pub fn start_delay(mut self, seconds: f32) -> Self {
+        self.args.start_delay = seconds;
+        self
+    }
+
+    /// See `suppress_echo` of [ConfigArguments].
+    pub fn suppress_echo(mut self, seconds: f32) -> Self {
+        self.args.suppress_echo = seconds;
+        self
+    }
+
+    /// See `midi_clock_bpm` of [ConfigArguments].
+    pub fn midi_clock_bpm(mut self, bpm: f32) -> Self {
+        self.args.midi_clock_bpm = Some(bpm);
+        self
+    }
+
+    /// See `verbosity` of [ConfigArguments].
+    pub fn verbosity(mut self, verbosity: log::LevelFilter) -> Self {
+        self.args.verbosity = verbosity;
+        self
+    }
+
+    #[cfg(feature = "osc")]
+    pub fn osc_reconnect(mut self, reconnect: OscReconnect) -> Self {
+        self.args.osc_reconnect = reconnect;
+        self
+    }
+
+    /// See `midi_file` and `midi_file_loop` of [ConfigArguments].
+    #[cfg(feature = "midifile")]
+    pub fn midi_file(mut self, path: &std::path::Path, repeat: bool) -> Self {
+        self.midi_file = Some(path.to_path_buf());
+        self.args.midi_file_loop = repeat;
+        self
+    }
+
+    /// See `record_midi_file` of [ConfigArguments].
+    #[cfg(feature = "midifile")]
+    pub fn record_midi_file(mut self, path: &std::path::Path) -> Self {
+        self.record_midi_file = Some(path.to_path_buf());
+        self
+    }
+
+    /// Creates the ports and connects them, see [RMididings::config].
+    pub fn build(self) -> Result<RMididings<'static>, Box<dyn Error>> {
+        let in_ports: Vec<[&str; 2]> = self.in_ports.iter().map(|[name, connect]| [name.as_str(), connect.as_str()]).collect();
+        let out_ports: Vec<[&str; 2]> = self.out_ports.iter().map(|[name, connect]| [name.as_str(), connect.as_str()]).collect();
+
+        let mut md = RMididings::new()?;
+        md.config(ConfigArguments {
+            client_name: &self.client_name,
+            in_ports: &in_ports,
+            out_ports: &out_ports,
+            out_port_velocity_range: &self.out_port_velocity_range,
+            out_port_flags: &self.out_port_flags,
+            #[cfg(feature = "midifile")]
+            midi_file: self.midi_file.as_deref(),
+            #[cfg(feature = "midifile")]
+            record_midi_file: self.record_midi_file.as_deref(),
+            ..self.args
+        })?;
+        Ok(md)
+    }
+}
+
+/// Runs a patch or scenes step by step, an alternative to [RunArguments].
+///
+/// Filters are owned, so they can be created inline. Get one with [RMididings::run_builder].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rmididings::*;
+/// let mut md = RMididings::builder()
+///     .in_port("input", "Virtual Keyboard:Virtual Keyboard")
+///     .out_port("output", "midisnoop:MIDI Input")
+///     .build()?;
+///
+/// md.run_builder()
+///     .scene("Run", Pass())
+///     .scene("Pause", Discard())
+///     .control(Chain!(KeyFilter(60), SceneSwitch(2)))
+///     .run()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct RunBuilder<'r, 'a> {
+    md: &'r mut RMididings<'a>,
+    patch: Box<dyn FilterTrait + 'r>,
+    scenes: Vec<(String, Box<dyn FilterTrait + 'r>)>,
+    control: Box<dyn FilterTrait + 'r>,
+    pre: Box<dyn FilterTrait + 'r>,
+    post: Box<dyn FilterTrait + 'r>,
+    state: Option<Arc<PatchState>>,
+    tempo: Option<TempoHandle>,
+    scene_switch_quantize: Option<Beats>,
+}
+
+impl<'r, 'a> RunBuilder<'r, 'a> {
+    pub(crate) fn new(md: &'r mut RMididings<'a>) -> Self {
+        Self {
+            md,
+            patch: Box::new(Discard()),
+            scenes: vec![],
+            control: Box::new(Discard()),
+            pre: Box::new(Pass()),
+            post: Box::new(Pass()),
+            state: None,
+            tempo: None,
+            scene_switch_quantize: None,
+        }
+    }
+
+    /// The patch to run, when not using scenes.
+    pub fn patch<F: FilterTrait + 'r>(mut self, patch: F) -> Self {
+        self.patch = Box::new(patch);
+        self
+    }
+
+    /// Adds a scene, they are numbered in the order they are added.
+    pub fn scene<F: FilterTrait + 'r>(mut self, name: &str, patch: F) -> Self {
+        self.scenes.push((name.to_string(), Box::new(patch)));
+        self
+    }
+
+    pub fn control<F: FilterTrait + 'r>(mut self, control: F) -> Self {
+        self.control = Box::new(control);
+        self
+    }
+
+    pub fn pre<F: FilterTrait + 'r>(mut self, pre: F) -> Self {
+        self.pre = Box::new(pre);
+        self
+    }
+
+    pub fn post<F: FilterTrait + 'r>(mut self, post: F) -> Self {
+        self.post = Box::new(post);
+        self
+    }
+
+    /// See `state` of [RunArguments].
+    pub fn state(mut self, state: Arc<PatchState>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// See `tempo` of [RunArguments].
+    pub fn tempo(mut self, tempo: TempoHandle) -> Self {
+        self.tempo = Some(tempo);
+        self
+    }
+
+    /// See `scene_switch_quantize` of [RunArguments].
+    pub fn scene_switch_quantize(mut self, beats: Beats) -> Self {
+        self.scene_switch_quantize = Some(beats);
+        self
+    }
+
+    /// Runs the patch or scenes, until quit, see [RMididings::run].
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        self.run_with(|md, args| md.run(args))
+    }
+
+    /// Runs the patch or scenes for a single event, until quit.
+    ///
+    /// Useful for testing, like [Runner::inject_event](super::Runner::inject_event).
+    pub fn run_until_quit_after(self, ev: crate::proc::Event<'static>) -> Result<(), Box<dyn Error>> {
+        self.run_with(|md, args| md.start(args)?.inject_event(ev))
+    }
+
+    fn run_with<R>(self, f: impl FnOnce(&mut RMididings<'a>, RunArguments) -> Result<R, Box<dyn Error>>) -> Result<R, Box<dyn Error>> {
+        let scenes: Vec<Scene> = self.scenes.iter().map(|(name, patch)| Scene {
+            name,
+            patch: patch.as_ref(),
+            ..Scene::DEFAULT
+        }).collect();
+        let scene_refs: Vec<&Scene> = scenes.iter().collect();
+
+        f(self.md, RunArguments {
+            patch: self.patch.as_ref(),
+            scenes: &scene_refs,
+            control: self.control.as_ref(),
+            pre: self.pre.as_ref(),
+            post: self.post.as_ref(),
+            state: self.state,
+            tempo: self.tempo,
+            scene_switch_quantize: self.scene_switch_quantize,
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use web_time::Instant;
+
+use crate::proc::Event;
+
+/// Maximum number of outgoing events remembered for echo suppression.
+const ECHO_BUFFER_SIZE: usize = 64;
+
+/// Remembers recently sent events, so that their echo can be recognized.
+///
+/// Devices like controllers with motorized faders send back the value they
+/// just received, which can result in feedback loops. Events are compared
+/// in backend numbering, ignoring the port, but matching type, channel and data.
+pub struct EchoSuppressor {
+    window: Duration,
+    sent: VecDeque<(Instant, Event<'static>)>,
+}
+
+impl EchoSuppressor {
+    pub fn new(window: Duration) -> Self {
+        Self { window, sent: VecDeque::with_capacity(ECHO_BUFFER_SIZE) }
+    }
+
+    /// Remember an event that was sent to a backend.
+    pub fn sent(&mut self, ev: &Event, now: Instant) {
+        if let Some(key) = echo_key(ev) {
+            if self.sent.len() >= ECHO_BUFFER_SIZE {
+                self.sent.pop_front();
+            }
+            self.sent.push_back((now, key));
+        }
+    }
+
+    /// Returns whether an incoming event is the echo of a recently sent event.
+    ///
+    /// A matching sent event is forgotten, so that each sent event suppresses at most one echo.
+    pub fn is_echo(&mut self, ev: &Event, now: Instant) -> bool {
+        let window = self.window;
+        self.sent.retain(|(t, _)| now.saturating_duration_since(*t) <= window);
+
+        if let Some(key) = echo_key(ev) {
+            if let Some(i) = self.sent.iter().position(|(_, sent_ev)| *sent_ev == key) {
+                self.sent.remove(i);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Owned copy of an event without port, or `None` if it is not subject to echo suppression.
+fn echo_key(ev: &Event) -> Option<Event<'static>> {
+    let mut key = match ev {
+        Event::NoteOn(ev) => Event::NoteOn(*ev),
+        Event::NoteOff(ev) => Event::NoteOff(*ev),
+        Event::Ctrl(ev) => Event::Ctrl(*ev),
+        Event::Program(ev) => Event::Program(*ev),
+        Event::PitchBend(ev) => Event::PitchBend(*ev),
+        #[cfg(feature = "osc")]
+        Event::Osc(ev) => Event::Osc(ev.clone()),
+        _ => return None,
+    };
+    key.set_port(0);
+    Some(key)
+}
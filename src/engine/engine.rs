@@ -1,15 +1,60 @@
 use std::error::Error;
 use std::{thread, time};
 
-use crate::proc::SceneNum;
+use crate::proc::{ClockGenerator, PortRegistry, SceneNum};
 
 use crate::backend::*;
-use super::{RunArguments, Runner};
+use super::{ConfigBuilder, RunArguments, RunBuilder, Runner};
+use super::event_sender::{event_channel, EventReceiver, EventSender};
 
+/// Where MIDI comes from and goes to, see `backend` in [ConfigArguments].
+///
+/// In a [configuration file](crate::config) it is written in lowercase, like `"null"`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum BackendType {
     Null,
     #[cfg(feature = "alsa")]
     Alsa,
+    /// Web MIDI API, when running in a browser.
+    #[cfg(feature = "webmidi")]
+    WebMidi,
+}
+
+/// Attributes of an output port, see `out_port_flags` in [ConfigArguments].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::*;
+/// let flags = PortFlags::FEEDBACK;
+/// assert!(flags.contains(PortFlags::FEEDBACK));
+/// assert!(!PortFlags::default().contains(PortFlags::FEEDBACK));
+/// assert_eq!(PortFlags::NONE | PortFlags::FEEDBACK, PortFlags::FEEDBACK);
+/// ```
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+pub struct PortFlags(u8);
+
+impl PortFlags {
+    pub const NONE: PortFlags = PortFlags(0);
+    /// The port drives e.g. controller LEDs, its notes aren't musical and aren't tracked.
+    ///
+    /// The runner keeps track of the notes sounding on other ports, to end them after an all
+    /// notes off controller as sent by [Panic](crate::proc::Panic).
+    pub const FEEDBACK: PortFlags = PortFlags(1);
+
+    pub fn contains(self, other: PortFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for PortFlags {
+    type Output = PortFlags;
+
+    fn bitor(self, other: PortFlags) -> PortFlags {
+        PortFlags(self.0 | other.0)
+    }
 }
 
 pub struct ConfigArguments<'a> {
@@ -17,11 +62,54 @@ pub struct ConfigArguments<'a> {
     pub client_name: &'a str,
     pub in_ports: &'a [[&'a str; 2]],
     pub out_ports: &'a [[&'a str; 2]],
+    /// Range to clamp NoteOn velocities to for each of `out_ports`, `None` to leave them.
+    pub out_port_velocity_range: &'a [Option<(u8, u8)>],
+    /// Attributes of each of `out_ports`, see [PortFlags].
+    pub out_port_flags: &'a [PortFlags],
+    /// Number of the first port and channel in patches, also for generated events that don't
+    /// take them from an incoming event.
+    ///
+    /// Use `port_offset` or `channel_offset` to number one of them differently. Scenes are
+    /// numbered with `scene_offset`, which this doesn't change.
     pub data_offset: u8,
+    /// Number of the first port in patches, instead of `data_offset`.
+    pub port_offset: Option<u8>,
+    /// Number of the first channel in patches, instead of `data_offset`.
+    pub channel_offset: Option<u8>,
+    /// Number of the first scene and subscene, for scene switches and when showing them.
+    ///
+    /// Unlike in mididings, this is separate from `data_offset`.
     pub scene_offset: SceneNum,
-    //pub octave_offset: u8,
+    /// Number of octaves notes are shifted up by for patches, and down again on output.
+    ///
+    /// Like `data_offset` for ports and channels: with 1, note 60 from a backend is seen as 72
+    /// by patches, and 72 from a patch is output as 60. Notes that would be shifted out of the
+    /// MIDI note range are dropped, so that each note that passes is output as it came in. Note
+    /// names, as with [note!], are not shifted: `C4` is always note 60 in patches.
+    pub octave_offset: u8,
     pub initial_scene: SceneNum,
     pub start_delay: f32,
+    /// Drop incoming events identical to one sent within this many seconds (0 to disable).
+    pub suppress_echo: f32,
+    /// Send MIDI clock at this tempo to all output ports, see [ClockGenerator].
+    pub midi_clock_bpm: Option<f32>,
+    /// Most detailed messages to print when the application didn't set up a logger.
+    ///
+    /// This applies to the whole process. With a logger set up before configuring, use that to
+    /// select messages instead.
+    pub verbosity: log::LevelFilter,
+    /// How to retry connecting to OSC TCP destinations.
+    #[cfg(feature = "osc")]
+    pub osc_reconnect: OscReconnect,
+    /// Play back this MIDI file as input, see [MidiFileBackend].
+    #[cfg(feature = "midifile")]
+    pub midi_file: Option<&'a std::path::Path>,
+    /// Start `midi_file` again when it has ended.
+    #[cfg(feature = "midifile")]
+    pub midi_file_loop: bool,
+    /// Record all output to this MIDI file, see [MidiFileRecorderBackend].
+    #[cfg(feature = "midifile")]
+    pub record_midi_file: Option<&'a std::path::Path>,
 }
 
 impl ConfigArguments<'_> {
@@ -34,47 +122,131 @@ impl ConfigArguments<'_> {
             client_name: "RMididings",
             in_ports: &[],
             out_ports: &[],
+            out_port_velocity_range: &[],
+            out_port_flags: &[],
             data_offset: 1,
+            port_offset: None,
+            channel_offset: None,
             scene_offset: 1,
-            //octave_offset: 2,
+            octave_offset: 0,
             initial_scene: 0,
             start_delay: 0.0,
+            suppress_echo: 0.0,
+            midi_clock_bpm: None,
+            verbosity: log::LevelFilter::Info,
+            #[cfg(feature = "osc")]
+            osc_reconnect: OscReconnect::default(),
+            #[cfg(feature = "midifile")]
+            midi_file: None,
+            #[cfg(feature = "midifile")]
+            midi_file_loop: false,
+            #[cfg(feature = "midifile")]
+            record_midi_file: None,
         }
     }
 }
 
 pub struct RMididings<'a> {
-    backends: Vec<Box::<dyn Backend<'a> + 'a>>,
+    backends: Vec<Box::<dyn Backend + 'a>>,
     port_offset: u8,
     channel_offset: u8,
+    octave_offset: u8,
     scene_offset: u8,
     initial_scene_num: SceneNum,
+    suppress_echo: f32,
+    out_port_velocity_range: Vec<Option<(u8, u8)>>,
+    out_port_flags: Vec<PortFlags>,
+    midi_clock_bpm: Option<f32>,
+    out_port_count: usize,
+    ports: PortRegistry,
+    null_backend: Option<NullBackend>,
+    event_channel: (EventSender, EventReceiver),
 }
 
-impl<'a, 'cfgargs: 'a> RMididings<'a> {
+impl<'a> RMididings<'a> {
     pub fn new() -> Result<Self, Box<dyn Error>> {
         Ok(Self {
             backends: vec![],
             port_offset: 1,
             channel_offset: 1,
+            octave_offset: 0,
             scene_offset: 1,
             initial_scene_num: 0,
+            suppress_echo: 0.0,
+            out_port_velocity_range: vec![],
+            out_port_flags: vec![],
+            midi_clock_bpm: None,
+            out_port_count: 0,
+            ports: PortRegistry::default(),
+            null_backend: None,
+            event_channel: event_channel()?,
         })
     }
 
-    pub fn config(&mut self, args: ConfigArguments<'cfgargs>) -> Result<(), Box<dyn Error>> {
+    /// Returns a [ConfigBuilder], to configure step by step instead of with [ConfigArguments].
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    /// Lists the MIDI ports of other clients that can be connected to, for input or output.
+    ///
+    /// Ports are formatted like they are used in `in_ports` and `out_ports` of [ConfigArguments].
+    /// Without ALSA support, this returns an empty list.
+    pub fn list_ports() -> Result<Vec<PortDescriptor>, Box<dyn Error>> {
+        #[cfg(feature = "alsa")]
+        {
+            let backend = AlsaBackend::new()?;
+            let mut ports = backend.list_in_ports()?;
+            for port in backend.list_out_ports()? {
+                if !ports.contains(&port) { ports.push(port); }
+            }
+            Ok(ports)
+        }
+        #[cfg(not(feature = "alsa"))]
+        Ok(vec![])
+    }
+
+    pub fn config(&mut self, args: ConfigArguments<'_>) -> Result<(), Box<dyn Error>> {
+        validate_name("client name", args.client_name)?;
+        crate::logging::init(args.verbosity);
+        for [name, connect] in args.in_ports.iter().chain(args.out_ports.iter()) {
+            validate_name("port name", name)?;
+            validate_name("port to connect to", connect)?;
+        }
+
+        self.null_backend = None;
         self.backends = vec![match args.backend {
-                BackendType::Null => Box::new(NullBackend::new()?),
+                BackendType::Null => {
+                    let backend = NullBackend::new()?;
+                    self.null_backend = Some(backend.clone());
+                    Box::new(backend)
+                },
                 #[cfg(feature = "alsa")]
                 BackendType::Alsa => Box::new(AlsaBackend::new()?),
+                #[cfg(feature = "webmidi")]
+                BackendType::WebMidi => Box::new(WebMidiBackend::new()?),
             },
+            #[cfg(not(target_arch = "wasm32"))]
             Box::new(CtrlcBackend::new()?),
             // TODO include Osc backend only when osc ports are defined
             #[cfg(feature = "osc")]
-            Box::new(OscBackend::new()?),
-            // #[cfg(feature = "dbus")]
-            // Box::new(DbusBackend::new()?),
+            {
+                let mut backend = OscBackend::new()?;
+                backend.set_reconnect(args.osc_reconnect.clone());
+                Box::new(backend)
+            },
+            #[cfg(feature = "dbus")]
+            Box::new(DbusBackend::new()?),
         ];
+        #[cfg(feature = "midifile")]
+        if let Some(path) = args.midi_file {
+            self.backends.push(Box::new(MidiFileBackend::new(path, args.midi_file_loop)?));
+        }
+        // The recorder goes first, as it lets the other backends output events too.
+        #[cfg(feature = "midifile")]
+        if let Some(path) = args.record_midi_file {
+            self.backends.insert(0, Box::new(MidiFileRecorderBackend::new(path)?));
+        }
 
         for b in self.backends.iter_mut() { b.set_client_name(args.client_name)?; }
 
@@ -101,21 +273,179 @@ impl<'a, 'cfgargs: 'a> RMididings<'a> {
         }
 
         self.initial_scene_num = args.initial_scene;
-        self.port_offset = args.data_offset;
-        self.channel_offset = args.data_offset;
+        self.port_offset = args.port_offset.unwrap_or(args.data_offset);
+        self.channel_offset = args.channel_offset.unwrap_or(args.data_offset);
+        self.octave_offset = args.octave_offset;
         self.scene_offset = args.scene_offset;
+        self.suppress_echo = args.suppress_echo;
+        self.out_port_velocity_range = args.out_port_velocity_range.to_vec();
+        self.out_port_flags = args.out_port_flags.to_vec();
+        self.midi_clock_bpm = args.midi_clock_bpm;
+        self.out_port_count = args.out_ports.len();
+        let in_port_names: Vec<&str> = args.in_ports.iter().map(|[name, _]| *name).collect();
+        let out_port_names: Vec<&str> = args.out_ports.iter().map(|[name, _]| *name).collect();
+        self.ports = PortRegistry::new(&in_port_names, &out_port_names, self.port_offset as usize);
 
         Ok(())
     }
 
+    /// Configures from a configuration file, see [config](crate::config).
+    #[cfg(feature = "config-file")]
+    pub fn config_from(&mut self, cfg: &crate::config::Config) -> Result<(), Box<dyn Error>> {
+        cfg.validate()?;
+        let in_ports: Vec<[&str; 2]> = cfg.in_ports.iter().map(|port| [port.name.as_str(), port.connect.as_str()]).collect();
+        let out_ports: Vec<[&str; 2]> = cfg.out_ports.iter().map(|port| [port.name.as_str(), port.connect.as_str()]).collect();
+
+        self.config(ConfigArguments {
+            client_name: &cfg.client_name,
+            in_ports: &in_ports,
+            out_ports: &out_ports,
+            backend: cfg.backend.unwrap_or(ConfigArguments::default().backend),
+            data_offset: cfg.data_offset,
+            port_offset: cfg.port_offset,
+            channel_offset: cfg.channel_offset,
+            initial_scene: cfg.initial_scene,
+            ..ConfigArguments::default()
+        })
+    }
+
+    /// Runs the patch or scenes, until quit.
+    ///
+    /// Returns an error when both `patch` and `scenes` are given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// let mut md = RMididings::new().unwrap();
+    /// let result = md.run(RunArguments {
+    ///     patch: &Pass(),
+    ///     scenes: &[&Scene { name: "Run", patch: &Pass(), ..Scene::default() }],
+    ///     ..RunArguments::default()
+    /// });
+    /// assert!(result.is_err());
+    /// ```
     pub fn run(&mut self, args: RunArguments<'_>) -> Result<(), Box<dyn Error>> {
-        Runner::new(
+        self.start(args)?.run()
+    }
+
+    /// Runs the patch or scenes until quit, without blocking the thread, see [Runner::run_async].
+    ///
+    /// Events sent with an [EventSender] from [RMididings::event_sender] are processed too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// let patch = Quit();
+    /// let mut md = RMididings::new().unwrap();
+    /// let sender = md.event_sender();
+    /// sender.send(NoteOnEvent(1,1,60,20)).unwrap();
+    ///
+    /// let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    /// runtime.block_on(md.run_async(RunArguments {
+    ///     patch: &patch,
+    ///     ..RunArguments::default()
+    /// })).unwrap();
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(&mut self, args: RunArguments<'_>) -> Result<(), Box<dyn Error>> {
+        self.start(args)?.run_async().await
+    }
+
+    /// Runs the patch or scenes until quit in the browser, see [Runner::run_web].
+    ///
+    /// Returns right away, running continues from the browser's event loop in a task started
+    /// with `wasm_bindgen_futures::spawn_local`. The task owns everything, so the patch needs
+    /// to live forever, e.g. by leaking it with [Box::leak]. Errors are logged, there is
+    /// nobody left to return them to.
+    #[cfg(feature = "webmidi")]
+    pub fn spawn_local(mut self, args: RunArguments<'static>) where 'a: 'static {
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = match self.start(args) {
+                Ok(mut runner) => runner.run_web().await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("Stopped running: {}", e);
+            }
+        });
+    }
+
+    /// Returns the backend configured with [BackendType::Null], to give it input and read its
+    /// output, e.g. in tests.
+    pub fn null_backend(&self) -> Option<NullBackend> {
+        let backend = self.null_backend.as_ref()?;
+        backend.keep_output();
+        Some(backend.clone())
+    }
+
+    /// Returns a handle to send events into the running patch from other tasks or threads.
+    pub fn event_sender(&self) -> EventSender {
+        self.event_channel.0.clone()
+    }
+
+    /// Returns a [RunBuilder], to run step by step instead of with [RunArguments].
+    pub fn run_builder(&mut self) -> RunBuilder<'_, 'a> {
+        RunBuilder::new(self)
+    }
+
+    /// Returns a runner for the patch or scenes, to process events from another event loop.
+    ///
+    /// Nothing is run until the first [Runner::step] or [Runner::inject_event].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// use std::ops::ControlFlow;
+    /// use std::time::Duration;
+    ///
+    /// let patch = Chain!(KeyFilter(60), Quit());
+    /// let mut md = RMididings::new().unwrap();
+    /// let mut runner = md.start(RunArguments {
+    ///     patch: &patch,
+    ///     ..RunArguments::default()
+    /// }).unwrap();
+    ///
+    /// assert_eq!(runner.step(Duration::ZERO).unwrap(), ControlFlow::Continue(()));
+    /// runner.inject_event(NoteOnEvent(1,1,62,20)).unwrap();
+    /// assert!(!runner.is_finished());
+    ///
+    /// runner.inject_event(NoteOnEvent(1,1,60,20)).unwrap();
+    /// assert_eq!(runner.step(Duration::ZERO).unwrap(), ControlFlow::Break(()));
+    /// assert!(runner.is_finished());
+    /// ```
+    pub fn start<'r>(&'r mut self, args: RunArguments<'r>) -> Result<Runner<'r, 'a>, Box<dyn Error>> {
+        let clock_generator = self.midi_clock_bpm.map(|bpm| {
+            let first_port = self.port_offset as usize;
+            let ports: Vec<usize> = (first_port..first_port + self.out_port_count).collect();
+            ClockGenerator(bpm).with_ports(&ports).started()
+        });
+
+        let mut runner = Runner::new(
             args,
             &mut self.backends,
             self.port_offset,
             self.channel_offset,
             self.scene_offset,
             self.initial_scene_num,
-        ).run()
+            self.suppress_echo,
+        )?
+            .with_octave_offset(self.octave_offset)
+            .with_out_port_velocity_range(&self.out_port_velocity_range)
+            .with_out_port_flags(&self.out_port_flags)
+            .with_event_receiver(&self.event_channel.1)
+            .with_clock_generator(clock_generator);
+        runner.bind_ports(&self.ports)?;
+        Ok(runner)
     }
-}
\ No newline at end of file
+}
+
+/// Returns an error for names that backends can't handle, before anything is created.
+fn validate_name(kind: &str, name: &str) -> Result<(), Box<dyn Error>> {
+    if name.contains('\0') {
+        return Err(format!("Invalid {} {:?}, it contains a NUL byte", kind, name).into());
+    }
+    Ok(())
+}
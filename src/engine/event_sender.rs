@@ -0,0 +1,128 @@
+use std::error::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use std::os::unix::io::RawFd;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[cfg(not(target_arch = "wasm32"))]
+extern crate nix;
+#[cfg(not(target_arch = "wasm32"))]
+use nix::fcntl::OFlag;
+
+use crate::proc::Event;
+
+/// Sends events into the running patch from other threads.
+///
+/// Get one from [RMididings::event_sender](super::RMididings::event_sender). Events are
+/// processed like input from a backend, but they already use the same port and channel
+/// numbering as patches do. Sending wakes up the engine, so events are processed right away.
+/// In the browser there are no threads to wake up, events are picked up on the next step.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::*;
+/// use std::time::Duration;
+///
+/// let patch = Quit();
+/// let mut md = RMididings::new().unwrap();
+/// let sender = md.event_sender();
+/// std::thread::spawn(move || {
+///     sender.send(NoteOnEvent(1,1,60,100)).unwrap();
+/// });
+///
+/// let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+/// while !runner.is_finished() {
+///     runner.step(Duration::from_secs(1)).unwrap();
+/// }
+/// ```
+///
+/// The handle can be cloned, so several threads can send events:
+///
+/// ```
+/// # use rmididings::*;
+/// let patch = Chain!(KeyFilter(62), Quit());
+/// let mut md = RMididings::new().unwrap();
+///
+/// let threads: Vec<_> = [60, 62].iter().map(|note| {
+///     let sender = md.event_sender();
+///     let note = *note;
+///     std::thread::spawn(move || sender.send(NoteOnEvent(1,1,note,100)).unwrap())
+/// }).collect();
+///
+/// md.run(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+/// for thread in threads { thread.join().unwrap(); }
+/// ```
+#[derive(Clone)]
+pub struct EventSender {
+    sender: Sender<Event<'static>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    wake: Arc<Pipe>,
+}
+
+impl EventSender {
+    /// Queues an event and wakes up the engine, fails when the engine is gone.
+    pub fn send(&self, ev: Event<'static>) -> Result<(), Box<dyn Error>> {
+        self.sender.send(ev).map_err(|_| "engine is not running anymore")?;
+        // When the pipe is full, the engine is going to wake up anyway.
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = nix::unistd::write(self.wake.write_fd, &[0u8]);
+        Ok(())
+    }
+}
+
+/// Receiving end of [EventSender], polled by the runner.
+pub(crate) struct EventReceiver {
+    receiver: Receiver<Event<'static>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    wake: Arc<Pipe>,
+}
+
+impl EventReceiver {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn fd(&self) -> RawFd {
+        self.wake.read_fd
+    }
+
+    /// Returns all events sent so far.
+    pub(crate) fn drain(&self) -> Vec<Event<'static>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut buf = [0u8; 64];
+            while let Ok(n) = nix::unistd::read(self.wake.read_fd, &mut buf) {
+                if n < buf.len() { break; }
+            }
+        }
+        self.receiver.try_iter().collect()
+    }
+}
+
+/// Non-blocking pipe to wake up the runner's poll.
+#[cfg(not(target_arch = "wasm32"))]
+struct Pipe {
+    read_fd: RawFd,
+    write_fd: RawFd,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.read_fd);
+        let _ = nix::unistd::close(self.write_fd);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn event_channel() -> Result<(EventSender, EventReceiver), Box<dyn Error>> {
+    let (read_fd, write_fd) = nix::unistd::pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+    let wake = Arc::new(Pipe { read_fd, write_fd });
+    let (sender, receiver) = channel();
+    Ok((EventSender { sender, wake: wake.clone() }, EventReceiver { receiver, wake }))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn event_channel() -> Result<(EventSender, EventReceiver), Box<dyn Error>> {
+    let (sender, receiver) = channel();
+    Ok((EventSender { sender }, EventReceiver { receiver }))
+}
@@ -1,7 +1,16 @@
+mod echo;
+use echo::EchoSuppressor;
+
 mod runner;
-use runner::Runner;
+pub use runner::Runner;
 pub use runner::RunArguments;
 
+mod event_sender;
+pub use event_sender::EventSender;
+
 mod engine;
 pub use engine::RMididings;
-pub use engine::ConfigArguments;
\ No newline at end of file
+pub use engine::{BackendType, ConfigArguments, PortFlags};
+
+mod builder;
+pub use builder::{ConfigBuilder, RunBuilder};
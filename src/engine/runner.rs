@@ -1,16 +1,39 @@
 use std::error::Error;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::ops::ControlFlow;
+use std::os::raw::c_int;
+use std::time::Duration;
+use web_time::Instant;
 
 use crate::proc::*;
 use crate::scene::*;
-use crate::backend::Backend;
+use crate::backend::{pollfd, Backend};
+use super::{EchoSuppressor, PortFlags};
+use super::event_sender::EventReceiver;
+
+/// Maximum time to wait for events, in milliseconds.
+const MAX_POLL_TIMEOUT_MS: c_int = 1000;
+/// Maximum time between steps in the browser, to pick up Web MIDI input, in milliseconds.
+#[cfg(feature = "webmidi")]
+const MAX_WEB_STEP_INTERVAL_MS: c_int = 5;
 
 pub struct RunArguments<'a> {
     pub patch: &'a dyn FilterTrait,
     pub scenes: &'a [&'a Scene<'a>],
     pub control: &'a dyn FilterTrait,
+    /// Run once on each incoming event, before `control` and the patches. What it holds back
+    /// and outputs later, on a tick or at exit, continues through the patches.
     pub pre: &'a dyn FilterTrait,
+    /// Run on what each patch outputs. What it holds back and outputs later is sent directly.
     pub post: &'a dyn FilterTrait,
+    /// State shared by patches, created empty when not given.
+    pub state: Option<Arc<PatchState>>,
+    /// Tempo and bar position for `scene_switch_quantize`, 120 bpm when not given.
+    pub tempo: Option<TempoHandle>,
+    /// Wait with scene switches until the next bar of this many beats, `None` to switch directly.
+    pub scene_switch_quantize: Option<Beats>,
 }
 
 impl RunArguments<'_> {
@@ -21,17 +44,35 @@ impl RunArguments<'_> {
             control: &Discard(),
             pre: &Pass(),
             post: &Pass(),
+            state: None,
+            tempo: None,
+            scene_switch_quantize: None,
         }
     }
 }
 
+/// A scene switch waiting for the next bar.
+#[derive(Debug, Copy, Clone)]
+struct PendingSceneSwitch {
+    at: Instant,
+    scene: SceneNum,
+    subscene: Option<SceneNum>,
+}
+
+/// Runs patches on events from the backends, see [RMididings::start](super::RMididings::start).
+///
+/// Use [Runner::step] to process events from an application's own event loop, or
+/// [Runner::run] to keep processing until quit. The runner holds references to the
+/// patches and backends, which are not thread-safe, so it stays on the thread that
+/// started it. To get events from other threads, use an [EventSender](super::EventSender).
 pub struct Runner<'a, 'backend: 'a> {
-    backends: &'a mut Vec<Box::<dyn Backend<'backend> + 'backend>>,
+    backends: &'a mut Vec<Box::<dyn Backend + 'backend>>,
     port_offset: u8,
     channel_offset: u8,
+    octave_offset: u8,
     scene_offset: SceneNum,
     patch: &'a dyn FilterTrait,
-    scenes: &'a [&'a Scene<'a>],
+    scenes: Vec<RunnerScene<'a>>,
     control: &'a dyn FilterTrait,
     pre: &'a dyn FilterTrait,
     post: &'a dyn FilterTrait,
@@ -39,25 +80,54 @@ pub struct Runner<'a, 'backend: 'a> {
     current_scene_num: Option<SceneNum>,
     current_subscene_num: Option<SceneNum>,
     stored_subscene_nums: Vec<Option<SceneNum>>,
+    echo_suppressor: Option<EchoSuppressor>,
+    out_port_velocity_range: &'a [Option<(u8, u8)>],
+    out_port_flags: &'a [PortFlags],
+    // Notes sounding on output ports other than feedback ports: port, channel, note.
+    sounding_notes: HashSet<(usize, u8, u8)>,
+    event_receiver: Option<&'a EventReceiver>,
+    clock_generator: Option<ClockGenerator>,
+    state: Arc<PatchState>,
+    output_ports: Arc<OutputPorts>,
+    tempo: TempoHandle,
+    scene_switch_quantize: Option<Beats>,
+    pending_scene_switch: Option<PendingSceneSwitch>,
+    remembered_params: HashMap<SceneKey, Vec<(u32, i32)>>,
+    ports: PortRegistry,
+    started: bool,
     running: bool,
+    finished: bool,
+    pollfds: Vec<pollfd>,
+    pollfd_backend_idxs: HashMap<c_int, usize>,
+    unpolled_backend_idxs: Vec<usize>,
+    pollfds_need_update: bool,
 }
 
 impl<'a, 'backend: 'a> Runner<'a, 'backend> {
-    pub fn new(args: RunArguments<'a>, backends: &'a mut Vec<Box::<dyn Backend<'backend> + 'backend>>, port_offset: u8, channel_offset: u8, scene_offset: SceneNum, initial_scene_num: SceneNum) -> Self {
-        // TODO error when both patch and scenes are given?
+    pub(crate) fn new(args: RunArguments<'a>, backends: &'a mut Vec<Box::<dyn Backend + 'backend>>, port_offset: u8, channel_offset: u8, scene_offset: SceneNum, initial_scene_num: SceneNum, suppress_echo: f32) -> Result<Self, Box<dyn Error>> {
+        if !args.patch.is_discard() && !args.scenes.is_empty() {
+            return Err("Both `patch` and `scenes` are given in RunArguments, please use only one of them.".into());
+        }
 
         let stored_subscene_nums = args.scenes
             .iter()
             .map(|scene| { if scene.subscenes.is_empty() { None } else { Some(0) } })
             .collect();
 
-        Self {
+        let echo_suppressor = if suppress_echo > 0.0 {
+            Some(EchoSuppressor::new(Duration::from_secs_f32(suppress_echo)))
+        } else {
+            None
+        };
+
+        Ok(Self {
             backends,
             port_offset,
             channel_offset,
+            octave_offset: 0,
             scene_offset,
             patch: args.patch,
-            scenes: args.scenes,
+            scenes: args.scenes.iter().map(|scene| RunnerScene::Given(scene)).collect(),
             control: args.control,
             pre: args.pre,
             post: args.post,
@@ -65,72 +135,597 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
             current_scene_num: None,
             current_subscene_num: None,
             stored_subscene_nums,
+            echo_suppressor,
+            out_port_velocity_range: &[],
+            out_port_flags: &[],
+            sounding_notes: HashSet::new(),
+            event_receiver: None,
+            clock_generator: None,
+            state: args.state.unwrap_or_default(),
+            output_ports: Arc::new(OutputPorts::new()),
+            tempo: args.tempo.unwrap_or_else(|| TempoHandle::new(120.0)),
+            scene_switch_quantize: args.scene_switch_quantize,
+            pending_scene_switch: None,
+            remembered_params: HashMap::new(),
+            ports: PortRegistry::default(),
+            started: false,
             running: false,
+            finished: false,
+            pollfds: vec![],
+            pollfd_backend_idxs: HashMap::new(),
+            unpolled_backend_idxs: vec![],
+            pollfds_need_update: false,
+        })
+    }
+
+    /// Resolves ports referred to by name in all filters, fails when a name is unknown.
+    ///
+    /// The ports are kept for binding scenes added later, see [Runner::add_scene].
+    pub(crate) fn bind_ports(&mut self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        for filter in [self.patch, self.control, self.pre, self.post] {
+            filter.bind(ports)?;
         }
+        self.scenes.iter().try_for_each(|scene| scene.scene().bind(ports))?;
+        self.ports = ports.clone();
+        Ok(())
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
-       // Setup scene
-        if !self.scenes.is_empty() {
-            self.current_scene_num = Some(self.initial_scene_num);
+    /// Shifts notes up by this many octaves for patches, and down again on output.
+    pub(crate) fn with_octave_offset(mut self, octave_offset: u8) -> Self {
+        self.octave_offset = octave_offset;
+        self
+    }
 
-            self.current_subscene_num = *self.get_stored_subscene_num();
-            self.print_current_scene();
+    /// Clamps NoteOn velocities on output ports to the given ranges, indexed by backend port.
+    pub(crate) fn with_out_port_velocity_range(mut self, out_port_velocity_range: &'a [Option<(u8, u8)>]) -> Self {
+        self.out_port_velocity_range = out_port_velocity_range;
+        self
+    }
+
+    /// Attributes of output ports, indexed by backend port.
+    pub(crate) fn with_out_port_flags(mut self, out_port_flags: &'a [PortFlags]) -> Self {
+        self.out_port_flags = out_port_flags;
+        self
+    }
+
+    /// Processes events sent with an [EventSender](super::EventSender).
+    pub(crate) fn with_event_receiver(mut self, event_receiver: &'a EventReceiver) -> Self {
+        self.event_receiver = Some(event_receiver);
+        self
+    }
+
+    /// Sends MIDI clock independent of the patches, controlled by Start and Stop events they output.
+    pub(crate) fn with_clock_generator(mut self, clock_generator: Option<ClockGenerator>) -> Self {
+        self.clock_generator = clock_generator;
+        self
+    }
+
+    /// Runs the patch or scenes until quit, like [Runner::run], without blocking the thread.
+    ///
+    /// The backends' file descriptors are registered with the tokio reactor, so other tasks
+    /// keep running while waiting for events. Events sent with an
+    /// [EventSender](super::EventSender) wake it up too. Like the runner itself, the returned future is
+    /// not `Send`: use a current-thread runtime or a [tokio::task::LocalSet].
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(&mut self) -> Result<(), Box<dyn Error>> {
+        use std::future::Future;
+        use std::task::Poll;
+        use tokio::io::Interest;
+        use tokio::io::unix::AsyncFd;
+
+        if !self.started { self.start()?; }
+
+        let mut async_fds: Vec<AsyncFd<PollFd>> = vec![];
+
+        while !self.finished {
+            // Register the backends' fds, and the one woken by EventSender, again when they changed.
+            if async_fds.iter().map(|async_fd| async_fd.get_ref().0).ne(self.pollfds.iter().map(|pollfd| pollfd.fd)) {
+                async_fds.clear();
+                for pollfd in self.pollfds.iter() {
+                    async_fds.push(AsyncFd::with_interest(PollFd(pollfd.fd), Interest::READABLE)?);
+                }
+            }
+
+            // Readiness is only signalled for new data, so don't wait when events are pending.
+            if poll(&mut self.pollfds, 0) == 0 {
+                let sleep = tokio::time::sleep(Duration::from_millis(self.poll_timeout() as u64));
+                tokio::pin!(sleep);
+                std::future::poll_fn(|cx| {
+                    let mut ready = sleep.as_mut().poll(cx).is_ready();
+                    for async_fd in async_fds.iter() {
+                        if let Poll::Ready(guard) = async_fd.poll_read_ready(cx) {
+                            if let Ok(mut guard) = guard { guard.clear_ready(); }
+                            ready = true;
+                        }
+                    }
+                    if ready { Poll::Ready(()) } else { Poll::Pending }
+                }).await;
+            }
+
+            if self.step(Duration::ZERO)?.is_break() { break; }
         }
 
-        self.running = true;
+        Ok(())
+    }
 
-        self.run_current_scene_init()?;
-        self.run_current_subscene_init()?;
+    /// Runs the patch or scenes until quit, like [Runner::run], from the browser's event loop.
+    ///
+    /// A web page can't block while waiting for events, so the runner is stepped from timers
+    /// instead: when a filter needs to act on time passing, and often enough to pick up input
+    /// from Web MIDI. Start it with `wasm_bindgen_futures::spawn_local`, or use
+    /// [RMididings::spawn_local](super::RMididings::spawn_local).
+    #[cfg(feature = "webmidi")]
+    pub async fn run_web(&mut self) -> Result<(), Box<dyn Error>> {
+        while self.step(Duration::ZERO)?.is_continue() {
+            sleep_web(self.poll_timeout().min(MAX_WEB_STEP_INTERVAL_MS)).await?;
+        }
+        Ok(())
+    }
 
-        let (mut pollfds, mut pollfd_backend_idxs) = self.get_poll_fds()?;
-        let mut pollfds_need_update = false;
+    /// Runs the patch or scenes, until quit.
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        while self.step(Duration::from_millis(MAX_POLL_TIMEOUT_MS as u64))?.is_continue() {}
+        Ok(())
+    }
+
+    /// Waits for and processes incoming events once, for at most the given time.
+    ///
+    /// The first call runs the init patches. Returns [ControlFlow::Break] when a [Quit]
+    /// event was output, after running the exit patches; calling it again does nothing.
+    pub fn step(&mut self, timeout: Duration) -> Result<ControlFlow<()>, Box<dyn Error>> {
+        if self.finished { return Ok(ControlFlow::Break(())); }
+        if !self.started { self.start()?; }
 
-        // Then wait until we get new events
-        while self.running {
-            // Wait until there is a new event
-            poll(&mut pollfds, 1000);
+        if self.running {
+            // Wait until there is a new event, or a filter needs to act on time passing
+            let timeout_ms = timeout.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int;
+            let timeout_ms = timeout_ms.min(self.poll_timeout());
+            poll(&mut self.pollfds, timeout_ms);
 
-            // Allow the backends to run which have fds with events waiting
-            for pollfd in pollfds.iter() {
-                if pollfd.revents == 0 { continue; }
+            self.run_pending_scene_switch()?;
 
-                if let Some(backend_idx) = pollfd_backend_idxs.get(&pollfd.fd) {
-                    if let Some(backend) = self.backends.get_mut(*backend_idx) {
-                        let (evs, backend_pollfds_need_update) = backend.run()?;
-                        for mut ev in evs.into_iter() {
-                            self.backend_event_to_user(&mut ev);
-                            self.run_current_patches(&ev)?;
+            // Allow the backends to run which have fds with events waiting, and those without fds
+            let ready_backend_idxs: Vec<usize> = self.pollfds
+                .iter()
+                .filter(|pollfd| pollfd.revents != 0)
+                .filter_map(|pollfd| self.pollfd_backend_idxs.get(&pollfd.fd).copied())
+                .chain(self.unpolled_backend_idxs.iter().copied())
+                .collect();
+            for backend_idx in ready_backend_idxs {
+                if let Some(backend) = self.backends.get_mut(backend_idx) {
+                    let (evs, backend_pollfds_need_update) = backend.run()?;
+                    for mut ev in evs.into_iter() {
+                        if let Some(echo_suppressor) = &mut self.echo_suppressor {
+                            if echo_suppressor.is_echo(&ev, Instant::now()) { continue; }
                         }
-                        pollfds_need_update |= backend_pollfds_need_update;
+                        if !self.backend_event_to_user(&mut ev) { continue; }
+                        self.run_input_event(&ev)?;
                     }
+                    self.pollfds_need_update |= backend_pollfds_need_update;
+                }
+            }
+
+            // Events from other threads are already numbered like patches use them.
+            if let Some(event_receiver) = self.event_receiver {
+                for ev in event_receiver.drain() {
+                    self.run_input_event(&ev)?;
                 }
             }
 
             // Update pollfds when a backend requested it.
-            if pollfds_need_update {
-                let pollfd_result = self.get_poll_fds()?;
-                pollfds = pollfd_result.0;
-                pollfd_backend_idxs = pollfd_result.1;
-                pollfds_need_update = false;
+            if self.pollfds_need_update {
+                self.update_poll_fds()?;
+            }
+
+            // Give filters a chance to act on time passing.
+            if self.running {
+                self.run_current_ticks()?;
+            }
+        }
+
+        if self.running {
+            Ok(ControlFlow::Continue(()))
+        } else {
+            self.finish()?;
+            Ok(ControlFlow::Break(()))
+        }
+    }
+
+    /// Processes an event as if it was received, e.g. from a user interface.
+    ///
+    /// The event uses the same port and channel numbering as patches do. This runs on the
+    /// runner's own thread, from other threads send events with an [EventSender](super::EventSender)
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// use std::time::Duration;
+    ///
+    /// let patch = Chain!(KeyFilter(62), Quit());
+    /// let mut md = RMididings::new().unwrap();
+    /// let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+    ///
+    /// runner.inject_event(NoteOnEvent(1,1,60,100)).unwrap();
+    /// assert!(runner.step(Duration::ZERO).unwrap().is_continue());
+    /// runner.inject_event(NoteOnEvent(1,1,62,100)).unwrap();
+    /// assert!(runner.step(Duration::ZERO).unwrap().is_break());
+    /// ```
+    pub fn inject_event(&mut self, ev: Event) -> Result<(), Box<dyn Error>> {
+        if self.finished { return Ok(()); }
+        if !self.started { self.start()?; }
+        self.run_input_event(&ev)
+    }
+
+    fn run_input_event(&mut self, ev: &Event) -> Result<(), Box<dyn Error>> {
+        match ev {
+            Event::Start(_) => self.tempo.resync(),
+            Event::Ctrl(ev) => self.remember_param_edit(ev),
+            _ => {},
+        }
+        self.update_output_ports_if_changed();
+        self.run_current_patches(ev)
+    }
+
+    fn update_output_ports_if_changed(&mut self) {
+        let mut changed = false;
+        for backend in self.backends.iter_mut() {
+            changed |= backend.out_port_receivers_changed();
+        }
+        if changed {
+            self.update_output_ports();
+        }
+    }
+
+    /// Asks the backends which output ports have receivers, for [PortFallback].
+    fn update_output_ports(&mut self) {
+        let mut output_ports = OutputPorts::new();
+        for backend in self.backends.iter() {
+            for (backend_port, has_receivers) in backend.out_port_receivers() {
+                output_ports.set_has_receivers(backend_port + self.port_offset as usize, has_receivers);
             }
         }
+        self.output_ports = Arc::new(output_ports);
+    }
+
+    /// Whether quit was requested.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Attributes of an output port, numbered like patches do.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// let mut md = RMididings::new().unwrap();
+    /// md.config(ConfigArguments {
+    ///     backend: BackendType::Null,
+    ///     out_ports: &[["synth", ""], ["leds", ""]],
+    ///     out_port_flags: &[PortFlags::NONE, PortFlags::FEEDBACK],
+    ///     ..ConfigArguments::default()
+    /// }).unwrap();
+    /// let runner = md.start(RunArguments::default()).unwrap();
+    /// assert!(!runner.out_port_flags(1).contains(PortFlags::FEEDBACK));
+    /// assert!(runner.out_port_flags(2).contains(PortFlags::FEEDBACK));
+    /// assert_eq!(runner.out_port_flags(3), PortFlags::NONE);
+    /// ```
+    pub fn out_port_flags(&self, port: usize) -> PortFlags {
+        let port = port.saturating_sub(self.port_offset as usize);
+        self.out_port_flags.get(port).copied().unwrap_or_default()
+    }
+
+    /// The number of the current scene, if there are scenes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// use std::time::Duration;
+    ///
+    /// let (scene1, scene2) = (Scene { name: "One", ..Scene::default() }, Scene { name: "Two", ..Scene::default() });
+    /// let scenes = [&scene1, &scene2];
+    /// let mut md = RMididings::new().unwrap();
+    /// let mut runner = md.start(RunArguments { scenes: &scenes, control: &Pass(), ..RunArguments::default() }).unwrap();
+    ///
+    /// runner.step(Duration::ZERO).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(1));
+    ///
+    /// // Without quantizing, scenes are switched directly.
+    /// runner.inject_event(SceneSwitchEvent(2)).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(2));
+    /// assert_eq!(runner.pending_scene(), None);
+    /// ```
+    pub fn current_scene(&self) -> Option<SceneNum> {
+        self.current_scene_num.map(|s| s.saturating_add(self.scene_offset))
+    }
+
+    /// The number of the current subscene, if the current scene has subscenes.
+    ///
+    /// Each scene remembers its subscene, which is used again when switching back to it,
+    /// unless another one is given with [SceneSwitch::with_subscene](crate::proc::SceneSwitch::with_subscene).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// use std::time::Duration;
+    ///
+    /// let (sub1, sub2, sub3) = (
+    ///     Scene { name: "A", ..Scene::default() },
+    ///     Scene { name: "B", ..Scene::default() },
+    ///     Scene { name: "C", ..Scene::default() },
+    /// );
+    /// let subscenes = [&sub1, &sub2, &sub3];
+    /// let scene1 = Scene { name: "One", subscenes: &subscenes, ..Scene::default() };
+    /// let scene2 = Scene { name: "Two", subscenes: &subscenes, ..Scene::default() };
+    /// let scenes = [&scene1, &scene2];
+    /// let mut md = RMididings::new().unwrap();
+    /// let mut runner = md.start(RunArguments { scenes: &scenes, control: &Pass(), ..RunArguments::default() }).unwrap();
+    ///
+    /// runner.step(Duration::ZERO).unwrap();
+    /// assert_eq!(runner.current_subscene(), Some(1));
+    ///
+    /// // Switching back to a scene returns to the subscene it was in.
+    /// runner.inject_event(SubSceneSwitchEvent(2)).unwrap();
+    /// runner.inject_event(SceneSwitchEvent(2)).unwrap();
+    /// assert_eq!(runner.current_subscene(), Some(1));
+    /// runner.inject_event(SceneSwitchEvent(1)).unwrap();
+    /// assert_eq!(runner.current_subscene(), Some(2));
+    ///
+    /// // Unless a subscene is given, which is then remembered.
+    /// runner.inject_event(SceneSwitchSubSceneEvent(2, 3)).unwrap();
+    /// assert_eq!((runner.current_scene(), runner.current_subscene()), (Some(2), Some(3)));
+    /// runner.inject_event(SceneSwitchSubSceneEvent(2, 2)).unwrap();
+    /// assert_eq!((runner.current_scene(), runner.current_subscene()), (Some(2), Some(2)));
+    /// runner.inject_event(SceneSwitchEvent(1)).unwrap();
+    /// runner.inject_event(SceneSwitchEvent(2)).unwrap();
+    /// assert_eq!(runner.current_subscene(), Some(2));
+    ///
+    /// runner.inject_event(SubSceneResetEvent()).unwrap();
+    /// assert_eq!(runner.current_subscene(), Some(1));
+    /// ```
+    pub fn current_subscene(&self) -> Option<SceneNum> {
+        self.current_subscene_num.map(|s| s.saturating_add(self.scene_offset))
+    }
+
+    /// The number of the scene that will be switched to at the next bar, see
+    /// [RunArguments::scene_switch_quantize].
+    ///
+    /// The scene is pending as soon as the switch is requested, e.g. to let a user interface
+    /// show it. Switching again before the bar has ended only keeps the last switch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// use std::time::Duration;
+    ///
+    /// let (scene1, scene2, scene3) = (
+    ///     Scene { name: "One", ..Scene::default() },
+    ///     Scene { name: "Two", ..Scene::default() },
+    ///     Scene { name: "Three", ..Scene::default() },
+    /// );
+    /// let scenes = [&scene1, &scene2, &scene3];
+    /// let clock = FakeClock::new();
+    /// let mut md = RMididings::new().unwrap();
+    /// let mut runner = md.start(RunArguments {
+    ///     scenes: &scenes,
+    ///     control: &Pass(),
+    ///     // A bar of 4 beats at 120 bpm takes two seconds.
+    ///     tempo: Some(TempoHandle::new(120.0).with_clock(clock.clone())),
+    ///     scene_switch_quantize: Some(4.0),
+    ///     ..RunArguments::default()
+    /// }).unwrap();
+    /// runner.step(Duration::ZERO).unwrap();
+    ///
+    /// clock.advance(Duration::from_millis(500));
+    /// runner.inject_event(SceneSwitchEvent(2)).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(1));
+    /// assert_eq!(runner.pending_scene(), Some(2));
+    ///
+    /// clock.advance(Duration::from_millis(1000));
+    /// runner.step(Duration::ZERO).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(1));
+    ///
+    /// // Switches before the bar has ended coalesce, offsets are relative to the pending scene.
+    /// runner.inject_event(SceneSwitchOffsetEvent(1)).unwrap();
+    /// assert_eq!(runner.pending_scene(), Some(3));
+    ///
+    /// clock.advance(Duration::from_millis(500));
+    /// runner.step(Duration::ZERO).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(3));
+    /// assert_eq!(runner.pending_scene(), None);
+    /// ```
+    pub fn pending_scene(&self) -> Option<SceneNum> {
+        self.pending_scene_switch.map(|p| p.scene.saturating_add(self.scene_offset))
+    }
+
+    /// Adds a scene while running, and returns its number.
+    ///
+    /// The scene is added after the others. It can't have subscenes, init or exit patches. Its
+    /// patch is bound to the ports right away, so an unknown port name is an error. When it is
+    /// the first scene, the runner switches to it. Scenes can't be added when running a single
+    /// patch, as with `patch` in [RunArguments].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::*;
+    /// use std::time::Duration;
+    ///
+    /// let scene1 = Scene { name: "One", ..Scene::default() };
+    /// let scenes = [&scene1];
+    /// let mut md = RMididings::new().unwrap();
+    /// let mut runner = md.start(RunArguments { scenes: &scenes, control: &Pass(), ..RunArguments::default() }).unwrap();
+    /// runner.step(Duration::ZERO).unwrap();
+    ///
+    /// let num = runner.add_scene("Loaded", Box::new(Transpose(12))).unwrap();
+    /// assert_eq!(num, 2);
+    /// runner.inject_event(SceneSwitchEvent(num)).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(2));
+    ///
+    /// // The current scene can't be removed, others can.
+    /// assert!(runner.remove_scene(2).is_err());
+    /// runner.remove_scene(1).unwrap();
+    /// assert_eq!(runner.current_scene(), Some(1));
+    /// ```
+    pub fn add_scene(&mut self, name: &str, patch: Box<dyn FilterTrait + 'static>) -> Result<SceneNum, Box<dyn Error>> {
+        if !self.patch.is_discard() {
+            return Err("Can't add a scene when a `patch` is given in RunArguments.".into());
+        }
+        if self.scenes.len() > SceneNum::MAX as usize {
+            return Err(format!("Can't add scene {:?}, there are too many scenes.", name).into());
+        }
+        patch.bind(&self.ports)?;
 
+        let scene_num = self.scenes.len() as SceneNum;
+        self.scenes.push(RunnerScene::Added(Rc::new(AddedScene { name: name.to_string(), patch })));
+        self.stored_subscene_nums.push(None);
+        if self.started && self.current_scene_num.is_none() {
+            self.switch_scene_internal(scene_num, None)?;
+        }
+        Ok(scene_num.saturating_add(self.scene_offset))
+    }
+
+    /// Removes a scene while running, by its number.
+    ///
+    /// Scenes after it are numbered one lower. The current scene, or one that is pending to
+    /// be switched to, can't be removed. Before the first step, that is the initial scene.
+    /// See [Runner::add_scene].
+    pub fn remove_scene(&mut self, scene: SceneNum) -> Result<(), Box<dyn Error>> {
+        let scene_num = scene.checked_sub(self.scene_offset)
+            .filter(|scene_num| (*scene_num as usize) < self.scenes.len())
+            .ok_or_else(|| format!("Can't remove scene {}, there is no such scene.", scene))?;
+        let current_scene_num = if self.started { self.current_scene_num } else { Some(self.initial_scene_num) };
+        if current_scene_num == Some(scene_num) || self.pending_scene_switch.is_some_and(|p| p.scene == scene_num) {
+            return Err(format!("Can't remove scene {}, it is the current scene.", scene).into());
+        }
+
+        self.scenes.remove(scene_num as usize);
+        self.stored_subscene_nums.remove(scene_num as usize);
+        let renumber = |s: SceneNum| if s > scene_num { s - 1 } else { s };
+        self.remembered_params = self.remembered_params.drain()
+            .filter(|((s, _), _)| *s != scene_num)
+            .map(|((s, subscene), params)| ((renumber(s), subscene), params))
+            .collect();
+        self.current_scene_num = self.current_scene_num.map(renumber);
+        if let Some(pending) = &mut self.pending_scene_switch {
+            pending.scene = renumber(pending.scene);
+        }
+        if self.initial_scene_num > scene_num {
+            self.initial_scene_num -= 1;
+        }
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<(), Box<dyn Error>> {
+        self.started = true;
+        self.update_output_ports();
+
+        // Setup scene
+        if !self.scenes.is_empty() {
+            self.current_scene_num = Some(self.initial_scene_num);
+
+            self.current_subscene_num = *self.get_stored_subscene_num();
+            self.print_current_scene();
+        }
+
+        self.running = true;
+        if self.tempo.reference().is_none() { self.tempo.resync(); }
+
+        self.run_current_scene_init()?;
+        self.run_current_subscene_init()?;
+        if let Some(clock_generator) = &self.clock_generator {
+            clock_generator.run_init(&mut EventStream::none());
+        }
+
+        self.update_poll_fds()
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.finished = true;
+
+        if let Some(clock_generator) = &self.clock_generator {
+            clock_generator.run_exit(&mut EventStream::none());
+        }
+        self.run_pre_with(SceneRunType::Exit)?;
         self.run_current_subscene_exit()?;
         self.run_current_scene_exit()?;
+        self.run_post_with(SceneRunType::Exit)?;
 
         Ok(())
     }
 
+    fn update_poll_fds(&mut self) -> Result<(), Box<dyn Error>> {
+        let (pollfds, pollfd_backend_idxs) = self.get_poll_fds()?;
+        self.pollfds = pollfds;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(event_receiver) = self.event_receiver {
+            self.pollfds.push(pollfd { fd: event_receiver.fd(), events: libc::POLLIN, revents: 0 });
+        }
+        // Backends without fds, like Web MIDI, get a chance to run on each step.
+        self.unpolled_backend_idxs = (0..self.backends.len())
+            .filter(|i| !pollfd_backend_idxs.values().any(|idx| idx == i))
+            .collect();
+        self.pollfd_backend_idxs = pollfd_backend_idxs;
+        self.pollfds_need_update = false;
+        Ok(())
+    }
+
+    /// Switches scene, or at the next bar when scene switches are quantized.
+    ///
+    /// Without a subscene, the one last used in the scene is used.
+    fn switch_scene(&mut self, new_scene_num: SceneNum, new_subscene_num_opt: Option<SceneNum>) -> Result<(), Box<dyn Error>> {
+        match self.scene_switch_quantize {
+            Some(beats) if self.current_scene_num.is_some() => {
+                self.pending_scene_switch = Some(PendingSceneSwitch {
+                    at: self.tempo.next_boundary(beats),
+                    scene: new_scene_num,
+                    subscene: new_subscene_num_opt,
+                });
+                self.print_pending_scene();
+                Ok(())
+            },
+            _ => self.switch_scene_internal(new_scene_num, new_subscene_num_opt),
+        }
+    }
+
+    /// Switches subscene, or at the next bar when scene switches are quantized.
+    fn switch_subscene(&mut self, new_subscene_num: SceneNum) -> Result<(), Box<dyn Error>> {
+        match (self.scene_switch_quantize, self.pending_scene_switch.map(|p| p.scene).or(self.current_scene_num)) {
+            (Some(beats), Some(scene)) => {
+                self.pending_scene_switch = Some(PendingSceneSwitch {
+                    at: self.tempo.next_boundary(beats),
+                    scene,
+                    subscene: Some(new_subscene_num),
+                });
+                self.print_pending_scene();
+                Ok(())
+            },
+            _ => self.switch_subscene_internal(new_subscene_num),
+        }
+    }
+
+    /// Switches to the pending scene and subscene when the bar boundary has passed.
+    fn run_pending_scene_switch(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(pending) = self.pending_scene_switch {
+            if self.tempo.now() < pending.at { return Ok(()); }
+            self.pending_scene_switch = None;
+            self.switch_scene_internal(pending.scene, pending.subscene)?;
+        }
+        Ok(())
+    }
+
     fn switch_scene_internal(&mut self, new_scene_num: SceneNum, new_subscene_num_opt: Option<SceneNum>) -> Result<(), Box<dyn Error>> {
-        if let Some(current_scene_num) = self.current_scene_num {
-            if let Some(new_subscene_num) = new_subscene_num_opt {
-                // Only switch subscene if there is just a subscene change.
-                return self.switch_subscene_internal(new_subscene_num);
-            } else if current_scene_num == new_scene_num {
-                // Skip if we're already in the scene.
-                return Ok(());
-            }
+        if self.current_scene_num == Some(new_scene_num) {
+            // Only switch subscene if there is just a subscene change, skip if there is none.
+            return match new_subscene_num_opt {
+                Some(new_subscene_num) => self.switch_subscene_internal(new_subscene_num),
+                None => Ok(()),
+            };
         }
 
         // TODO scene bounds checking
@@ -139,10 +734,11 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
         self.run_current_scene_exit()?;
 
         self.current_scene_num = Some(new_scene_num);
-        self.current_subscene_num = new_subscene_num_opt.map_or(
-            *self.get_stored_subscene_num(),
-            |_| new_subscene_num_opt
-        );
+        // An explicit subscene is remembered, for scenes that have subscenes.
+        if let (Some(new_subscene_num), Some(stored @ Some(_))) = (new_subscene_num_opt, self.stored_subscene_nums.get_mut(new_scene_num as usize)) {
+            *stored = Some(new_subscene_num);
+        }
+        self.current_subscene_num = *self.get_stored_subscene_num();
         self.print_current_scene();
 
         self.run_current_scene_init()?;
@@ -175,6 +771,8 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
     fn run_current_scene_init(&mut self) -> Result<(), Box<dyn Error>> {
         self.run_patch(self.patch, SceneRunType::Init, None)?;
         if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+            let current_scene = current_scene.scene();
+            self.send_scene_params(&current_scene, None)?;
             self.run_patch(current_scene.init, SceneRunType::Patch, None)?;
             self.run_patch(current_scene.patch, SceneRunType::Init, None)?;
         }
@@ -183,7 +781,9 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
 
     fn run_current_subscene_init(&mut self) -> Result<(), Box<dyn Error>> {
         if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+            let current_scene = current_scene.scene();
             if let Some(current_subscene) = current_scene.get_subscene_opt(self.current_subscene_num) {
+                self.send_scene_params(current_subscene, self.current_subscene_num)?;
                 self.run_patch(current_subscene.init, SceneRunType::Patch, None)?;
                 self.run_patch(current_subscene.patch, SceneRunType::Init, None)?;
             }
@@ -191,26 +791,166 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
         Ok(())
     }
 
+    /// Sends the parameters of the current scene, or of its subscene, on the first port and channel.
+    fn send_scene_params(&mut self, scene: &Scene, subscene_num: Option<SceneNum>) -> Result<(), Box<dyn Error>> {
+        let mut params = scene.params;
+        if let (true, Some(scene_num)) = (scene.remember_edits, self.current_scene_num) {
+            if let Some(remembered) = self.remembered_params.get(&(scene_num, subscene_num)) {
+                params = remembered.as_slice();
+            }
+        }
+
+        let mut evs = EventStream::empty();
+        for (ctrl, value) in params {
+            evs.push(CtrlEvent(self.port_offset as usize, self.channel_offset, *ctrl, *value));
+        }
+        self.state.activate(|| self.post.run(&mut evs));
+        for ev in evs.iter() {
+            self.output_event(ev)?;
+        }
+        Ok(())
+    }
+
+    /// Remembers an incoming Ctrl event as edit of the current scene's and subscene's parameters.
+    fn remember_param_edit(&mut self, ev: &CtrlEventImpl) {
+        if let Some(scene_num) = self.current_scene_num {
+            if let Some(scene) = get_scene(&self.scenes, self.current_scene_num) {
+                let scene = scene.scene();
+                self.remember_scene_param_edit(&scene, (scene_num, None), ev);
+                if let Some(subscene) = scene.get_subscene_opt(self.current_subscene_num) {
+                    self.remember_scene_param_edit(subscene, (scene_num, self.current_subscene_num), ev);
+                }
+            }
+        }
+    }
+
+    fn remember_scene_param_edit(&mut self, scene: &Scene, key: SceneKey, ev: &CtrlEventImpl) {
+        if !scene.remember_edits || !scene.params.iter().any(|(ctrl, _)| *ctrl == ev.ctrl) {
+            return;
+        }
+        let params = self.remembered_params.entry(key).or_insert_with(|| scene.params.to_vec());
+        for param in params.iter_mut().filter(|(ctrl, _)| *ctrl == ev.ctrl) {
+            param.1 = ev.value;
+        }
+    }
+
     fn run_current_patches(&mut self, ev: &Event) -> Result<(), Box<dyn Error>> {
-        self.run_patch(self.control, SceneRunType::Patch, Some(ev))?;
+        // The pre patch runs once, each patch gets what it outputs.
+        let mut evs = EventStream::from(ev);
+        self.with_context(|| self.pre.run(&mut evs));
+        self.run_current_patches_on(&evs)
+    }
+
+    /// Runs the current patches on events that passed the pre patch.
+    fn run_current_patches_on(&mut self, evs: &EventStream) -> Result<(), Box<dyn Error>> {
+        self.run_patch_on(self.control, SceneRunType::Patch, evs.clone())?;
         // TODO don't run patch when scene was just switched in control
         //      maybe do scene switching at the end of the full patch?
         //      in that case we'll need current_scene and new_scene in EventStream
-        self.run_patch(self.patch, SceneRunType::Patch, Some(ev))?;
+        self.run_patch_on(self.patch, SceneRunType::Patch, evs.clone())?;
         if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
-            self.run_patch(current_scene.patch, SceneRunType::Patch, Some(ev))?;
+            let current_scene = current_scene.scene();
+            self.run_patch_on(current_scene.patch, SceneRunType::Patch, evs.clone())?;
             if let Some(current_subscene) = current_scene.get_subscene_opt(self.current_subscene_num) {
-                self.run_patch(current_subscene.patch, SceneRunType::Patch, Some(ev))?;
+                self.run_patch_on(current_subscene.patch, SceneRunType::Patch, evs.clone())?;
             }
         }
         Ok(())
     }
 
+    /// Runs what the pre patch outputs on tick or exit through the current patches.
+    ///
+    /// These events were held back by the pre patch, so they continue like the input they
+    /// came from, without passing the pre patch again.
+    fn run_pre_with(&mut self, run_type: SceneRunType) -> Result<(), Box<dyn Error>> {
+        let mut evs = EventStream::empty();
+        self.with_context(|| match run_type {
+            SceneRunType::Exit => self.pre.run_exit(&mut evs),
+            _ => self.pre.run_tick(&mut evs),
+        });
+        if evs.is_empty() { return Ok(()); }
+        self.run_current_patches_on(&evs)
+    }
+
+    /// Outputs what the post patch outputs on tick or exit, directly.
+    fn run_post_with(&mut self, run_type: SceneRunType) -> Result<(), Box<dyn Error>> {
+        let mut evs = EventStream::empty();
+        self.with_context(|| match run_type {
+            SceneRunType::Exit => self.post.run_exit(&mut evs),
+            _ => self.post.run_tick(&mut evs),
+        });
+        for ev in evs.iter() {
+            self.output_event(ev)?;
+        }
+        Ok(())
+    }
+
+    fn run_current_ticks(&mut self) -> Result<(), Box<dyn Error>> {
+        self.run_pre_with(SceneRunType::Tick)?;
+        self.run_patch(self.control, SceneRunType::Tick, None)?;
+        self.run_patch(self.patch, SceneRunType::Tick, None)?;
+        if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+            let current_scene = current_scene.scene();
+            for patch in [current_scene.init, current_scene.patch, current_scene.exit] {
+                self.run_patch(patch, SceneRunType::Tick, None)?;
+            }
+            if let Some(current_subscene) = current_scene.get_subscene_opt(self.current_subscene_num) {
+                for patch in [current_subscene.init, current_subscene.patch, current_subscene.exit] {
+                    self.run_patch(patch, SceneRunType::Tick, None)?;
+                }
+            }
+        }
+        self.run_post_with(SceneRunType::Tick)?;
+
+        // The clock is output directly, it doesn't pass the pre and post patches.
+        let mut evs = EventStream::empty();
+        if let Some(clock_generator) = &self.clock_generator {
+            clock_generator.run_tick(&mut evs);
+        }
+        for ev in evs.iter() {
+            self.output_event(ev)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the poll timeout in milliseconds, based on when filters need to be ticked next.
+    fn poll_timeout(&self) -> c_int {
+        let mut next_ticks = vec![
+            self.pre.next_tick(),
+            self.control.next_tick(),
+            self.patch.next_tick(),
+            self.post.next_tick(),
+            self.clock_generator.as_ref().and_then(|g| g.next_tick()),
+            self.pending_scene_switch.map(|p| p.at),
+        ];
+        if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+            let current_scene = current_scene.scene();
+            next_ticks.extend([current_scene.init, current_scene.patch, current_scene.exit].map(|p| p.next_tick()));
+            if let Some(current_subscene) = current_scene.get_subscene_opt(self.current_subscene_num) {
+                next_ticks.extend([current_subscene.init, current_subscene.patch, current_subscene.exit].map(|p| p.next_tick()));
+            }
+        }
+
+        match next_ticks.into_iter().flatten().min() {
+            Some(next_tick) => {
+                let wait = next_tick.saturating_duration_since(Instant::now());
+                // Round up, so that we don't wake up just before it's time.
+                let wait_ms = wait.as_micros().div_ceil(1000);
+                wait_ms.min(MAX_POLL_TIMEOUT_MS as u128) as c_int
+            },
+            None => MAX_POLL_TIMEOUT_MS,
+        }
+    }
+
     fn run_current_subscene_exit(&mut self) -> Result<(), Box<dyn Error>> {
         if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+            let current_scene = current_scene.scene();
             if let Some(current_subscene) = current_scene.get_subscene_opt(self.current_subscene_num) {
                 self.run_patch(current_subscene.patch, SceneRunType::Exit, None)?;
                 self.run_patch(current_subscene.exit, SceneRunType::Patch, None)?;
+                // They aren't ticked anymore, so what they still hold back is output now.
+                self.run_patch(current_subscene.init, SceneRunType::Exit, None)?;
+                self.run_patch(current_subscene.exit, SceneRunType::Exit, None)?;
             }
         }
         Ok(())
@@ -218,72 +958,155 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
 
     fn run_current_scene_exit(&mut self) -> Result<(), Box<dyn Error>> {
         if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+            let current_scene = current_scene.scene();
             self.run_patch(current_scene.patch, SceneRunType::Exit, None)?;
             self.run_patch(current_scene.exit, SceneRunType::Patch, None)?;
+            // They aren't ticked anymore, so what they still hold back is output now.
+            self.run_patch(current_scene.init, SceneRunType::Exit, None)?;
+            self.run_patch(current_scene.exit, SceneRunType::Exit, None)?;
         }
         self.run_patch(self.patch, SceneRunType::Exit, None)?;
         Ok(())
     }
 
     pub fn output_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        if let Event::Start(_) = ev { self.tempo.resync(); }
+        if let (Some(clock_generator), Event::Start(_) | Event::Stop(_) | Event::Continue(_)) = (&self.clock_generator, ev) {
+            clock_generator.run(&mut EventStream::from(ev));
+        }
+
         match ev {
+            // Placeholder events are for generators, they are never sent.
+            Event::None(_) => {},
             Event::Quit(_) => {
                 self.running = false;
             },
-            Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Fixed(f) }) => {
-                self.switch_scene_internal(f.saturating_sub(self.scene_offset), None)?;
+            Event::Resync(_) => {
+                self.tempo.resync();
             },
-            Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Offset(o) }) => {
-                if let Some(current_scene) = self.current_scene_num {
-                    let f = (current_scene as SceneOffset).saturating_add(*o) as SceneNum;
-                    self.switch_scene_internal(f, None)?;
+            Event::SceneSwitch(SceneSwitchEventImpl { scene, subscene }) => {
+                // Offsets are relative to a pending switch, so that they add up.
+                let base = self.pending_scene_switch.map(|p| p.scene).or(self.current_scene_num);
+                let new_scene_num = match scene {
+                    SceneSwitchValue::Fixed(f) => Some(f.saturating_sub(self.scene_offset)),
+                    SceneSwitchValue::Offset(o) => base.map(|s| (s as SceneOffset).saturating_add(*o) as SceneNum),
+                    SceneSwitchValue::First => Some(0),
+                };
+                if let Some(new_scene_num) = new_scene_num {
+                    self.switch_scene(new_scene_num, subscene.map(|s| s.saturating_sub(self.scene_offset)))?;
                 }
             },
-            Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene: SceneSwitchValue::Fixed(f) }) => {
-                self.switch_subscene_internal(f.saturating_sub(self.scene_offset))?;
-            },
-            Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene: SceneSwitchValue::Offset(o) }) => {
-                if let Some(current_subscene) = self.current_subscene_num {
-                    let f = (current_subscene as SceneOffset).saturating_add(*o) as SceneNum;
-                    self.switch_subscene_internal(f)?;
+            Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene }) => {
+                let base = self.pending_scene_switch.and_then(|p| p.subscene).or(self.current_subscene_num);
+                let new_subscene_num = match subscene {
+                    SceneSwitchValue::Fixed(f) => Some(f.saturating_sub(self.scene_offset)),
+                    SceneSwitchValue::Offset(o) => base.map(|s| (s as SceneOffset).saturating_add(*o) as SceneNum),
+                    SceneSwitchValue::First => Some(0),
+                };
+                if let Some(new_subscene_num) = new_subscene_num {
+                    self.switch_subscene(new_subscene_num)?;
                 }
             },
             _ => {
-                // If there is no channel and port offset, we can directly send the event.
-                if self.channel_offset == 0 && self.port_offset == 0 {
-                    // Try all backends until one handles it (i.e. sends more than 0 bytes).
-                    for backend in self.backends.iter_mut() {
-                        let r = backend.output_event(&ev)?;
-                        if r > 0 { return Ok(r); }
-                    }
-                // Otherwise we need to modify a copy of the event and send it.
-                } else {
-                    let mut ev = ev.clone();
-                    self.user_event_to_backend(&mut ev);
-                    // Try all backends until one handles it (i.e. sends more than 0 bytes).
-                    for backend in self.backends.iter_mut() {
-                        let r = backend.output_event(&ev)?;
-                        if r > 0 { return Ok(r); }
-                    }
+                let note_offs = self.track_output_note(ev);
+                let sent = self.output_user_event(ev)?;
+                for note_off in note_offs.iter() {
+                    self.output_user_event(note_off)?;
                 }
+                return Ok(sent);
             }
         }
         Ok(0)
     }
 
-    fn run_patch<'oev>(&mut self, filter: &dyn FilterTrait, run_type: SceneRunType, ev: Option<&Event<'oev>>) -> Result<(), Box<dyn Error>> {
-        let mut evs = if let Some(ev) = ev { EventStream::from(ev) } else { EventStream::none() };
+    fn output_user_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        // If there is no channel, port or octave offset or velocity range, we can directly send the event.
+        if self.channel_offset == 0 && self.port_offset == 0 && self.octave_offset == 0 && self.out_port_velocity_range.is_empty() {
+            self.output_backend_event(ev)
+        // Otherwise we need to modify a copy of the event and send it.
+        } else {
+            let mut ev = ev.clone();
+            if !self.user_event_to_backend(&mut ev) { return Ok(0); }
+            self.output_backend_event(&ev)
+        }
+    }
 
-        self.pre.run(&mut evs);
+    /// Keeps track of the notes sounding on output ports, except on feedback ports.
+    ///
+    /// Returns NoteOffs for the notes sounding on the port and channel of an all notes off
+    /// controller (123), as sent by [Panic], for devices that ignore it.
+    fn track_output_note(&mut self, ev: &Event) -> Vec<Event<'static>> {
+        let port = match ev {
+            Event::NoteOn(ev) => ev.port,
+            Event::NoteOff(ev) => ev.port,
+            Event::Ctrl(ev) if ev.ctrl == 123 => ev.port,
+            _ => return vec![],
+        };
+        if self.out_port_flags(port).contains(PortFlags::FEEDBACK) { return vec![]; }
+
+        match ev {
+            Event::NoteOn(ev) if ev.velocity > 0 => {
+                self.sounding_notes.insert((ev.port, ev.channel, ev.note));
+            },
+            Event::NoteOn(NoteOnEventImpl { port, channel, note, .. }) | Event::NoteOff(NoteOffEventImpl { port, channel, note, .. }) => {
+                self.sounding_notes.remove(&(*port, *channel, *note));
+            },
+            Event::Ctrl(ev) => {
+                let mut notes: Vec<(usize, u8, u8)> = self.sounding_notes.iter()
+                    .filter(|(port, channel, _)| *port == ev.port && *channel == ev.channel)
+                    .copied()
+                    .collect();
+                notes.sort_unstable();
+                for note in notes.iter() {
+                    self.sounding_notes.remove(note);
+                }
+                return notes.into_iter().map(|(port, channel, note)| NoteOffEvent(port, channel, note)).collect();
+            },
+            _ => {},
+        }
+        vec![]
+    }
 
-        // run patch
-        match run_type {
-            SceneRunType::Patch => filter.run(&mut evs),
-            SceneRunType::Init => filter.run_init(&mut evs),
-            SceneRunType::Exit => filter.run_exit(&mut evs),
+    fn output_backend_event(&mut self, ev: &Event) -> Result<u32, Box<dyn Error>> {
+        // Try all backends until one handles it (i.e. sends more than 0 bytes).
+        for backend in self.backends.iter_mut() {
+            let r = backend.output_event(ev)?;
+            if r > 0 {
+                if let Some(echo_suppressor) = &mut self.echo_suppressor {
+                    echo_suppressor.sent(ev, Instant::now());
+                }
+                return Ok(r);
+            }
         }
+        Ok(0)
+    }
 
-        self.post.run(&mut evs);
+    fn run_patch<'oev>(&mut self, filter: &dyn FilterTrait, run_type: SceneRunType, ev: Option<&Event<'oev>>) -> Result<(), Box<dyn Error>> {
+        // A few events fit in the EventStream itself, so this doesn't allocate for most events.
+        let mut evs = match (ev, &run_type) {
+            (Some(ev), _) => EventStream::from(ev),
+            // Init, exit and ticks only output what filters generate, they don't process a
+            // placeholder event. Init! and Exit! give their contents one of their own.
+            (None, SceneRunType::Init | SceneRunType::Exit | SceneRunType::Tick) => EventStream::empty(),
+            (None, SceneRunType::Patch) => EventStream::none(),
+        };
+        self.with_context(|| self.pre.run(&mut evs));
+        self.run_patch_on(filter, run_type, evs)
+    }
+
+    /// Runs a patch and the post patch on events that passed the pre patch, and outputs the result.
+    fn run_patch_on(&mut self, filter: &dyn FilterTrait, run_type: SceneRunType, mut evs: EventStream) -> Result<(), Box<dyn Error>> {
+        self.with_context(|| {
+            // run patch
+            match run_type {
+                SceneRunType::Patch => filter.run(&mut evs),
+                SceneRunType::Init => filter.run_init(&mut evs),
+                SceneRunType::Exit => filter.run_exit(&mut evs),
+                SceneRunType::Tick => filter.run_tick(&mut evs),
+            }
+
+            self.post.run(&mut evs);
+        });
 
         // handle resulting event stream
         for ev in evs.iter() {
@@ -293,13 +1116,29 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
         Ok(())
     }
 
+    /// Runs `f` with the state, output ports, offsets and scene that filters can ask for.
+    fn with_context<R>(&self, f: impl FnOnce() -> R) -> R {
+        let current_scene_num = self.current_scene_num.filter(|s| (*s as usize) < self.scenes.len());
+        self.state.activate(|| self.output_ports.activate(|| with_data_offset(self.port_offset as usize, self.channel_offset, || with_scene(self.scene_offset, current_scene_num, f))))
+    }
+
+    fn print_pending_scene(&self) {
+        if let Some(pending) = self.pending_scene_switch {
+            if let Some(scene) = get_scene(&self.scenes, Some(pending.scene)) {
+                let scene = scene.scene();
+                info!("Scene {} pending: {}", pending.scene.saturating_add(self.scene_offset), scene.name);
+            }
+        }
+    }
+
     fn print_current_scene(&self) {
         if let Some(current_scene_num) = self.current_scene_num {
-            if let Some(current_scene) = get_scene(self.scenes, self.current_scene_num) {
+            if let Some(current_scene) = get_scene(&self.scenes, self.current_scene_num) {
+                let current_scene = current_scene.scene();
                 if let Some(current_subscene_num) = self.current_subscene_num {
                     if let Some(current_subscene) = current_scene.get_subscene(current_subscene_num)
                     {
-                        println!(
+                        info!(
                             "Scene {}.{}: {} - {}",
                             current_scene_num.saturating_add(self.scene_offset),
                             current_subscene_num.saturating_add(self.scene_offset),
@@ -310,7 +1149,7 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
                     }
                 }
 
-                println!(
+                info!(
                     "Scene {}: {}",
                     current_scene_num.saturating_add(self.scene_offset),
                     current_scene.name
@@ -319,10 +1158,10 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
         }
     }
 
-    fn get_poll_fds(&mut self) -> Result<(Vec<libc::pollfd>, HashMap<libc::c_int, usize>), Box<dyn Error>> {
+    fn get_poll_fds(&mut self) -> Result<(Vec<pollfd>, HashMap<c_int, usize>), Box<dyn Error>> {
         // Gather polling file descriptors
-        let mut pollfds: Vec<libc::pollfd> = vec![];
-        let mut pollfd_backend_idxs: HashMap<libc::c_int, usize> = HashMap::new();
+        let mut pollfds: Vec<pollfd> = vec![];
+        let mut pollfd_backend_idxs: HashMap<c_int, usize> = HashMap::new();
 
         for (i, backend) in self.backends.iter_mut().enumerate() {
             let backend_pollfds = backend.get_pollfds()?;
@@ -346,54 +1185,169 @@ impl<'a, 'backend: 'a> Runner<'a, 'backend> {
         &None
     }
 
-    fn backend_event_to_user(&self, ev: &mut Event) {
+    /// Shifts a note up by the octave offset, `None` when that is out of the MIDI note range.
+    fn octave_shift_up(&self, note: u8) -> Option<u8> {
+        let note = note as u16 + self.octave_offset as u16 * 12;
+        (note <= 127).then_some(note as u8)
+    }
+
+    /// Shifts a note down by the octave offset, `None` when that is below the lowest note.
+    fn octave_shift_down(&self, note: u8) -> Option<u8> {
+        (note as u16).checked_sub(self.octave_offset as u16 * 12).map(|note| note as u8)
+    }
+
+    /// Numbers an event from a backend like patches do, returns false when it can't be.
+    fn backend_event_to_user(&self, ev: &mut Event) -> bool {
         match ev {
             Event::NoteOn(ev) => {
                 ev.port = ev.port.saturating_add(self.port_offset as usize);
                 ev.channel = ev.channel.saturating_add(self.channel_offset);
+                let Some(note) = self.octave_shift_up(ev.note) else { return false };
+                ev.note = note;
             },
             Event::NoteOff(ev) => {
                 ev.port = ev.port.saturating_add(self.port_offset as usize);
                 ev.channel = ev.channel.saturating_add(self.channel_offset);
+                let Some(note) = self.octave_shift_up(ev.note) else { return false };
+                ev.note = note;
             },
             Event::Ctrl(ev) => {
                 ev.port = ev.port.saturating_add(self.port_offset as usize);
                 ev.channel = ev.channel.saturating_add(self.channel_offset);
             },
+            Event::Nrpn(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+                ev.channel = ev.channel.saturating_add(self.channel_offset);
+            },
+            Event::Program(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+                ev.channel = ev.channel.saturating_add(self.channel_offset);
+            },
+            Event::PitchBend(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+                ev.channel = ev.channel.saturating_add(self.channel_offset);
+            },
             Event::SysEx(ev) => {
                 ev.port = ev.port.saturating_add(self.port_offset as usize);
             },
+            Event::Clock(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::Start(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::Stop(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::Continue(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::SongPosition(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::SongSelect(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::MtcQuarterFrame(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            Event::MtcFullFrame(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
             #[cfg(feature = "osc")]
             Event::Osc(ev) => {
                 ev.port = ev.port.saturating_add(self.port_offset as usize);
             },
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ev) => {
+                ev.port = ev.port.saturating_add(self.port_offset as usize);
+            },
             _ => {}
         }
+        true
     }
 
-    fn user_event_to_backend(&self, ev: &mut Event) {
+    /// Numbers an event from a patch like backends do, returns false when it can't be.
+    fn user_event_to_backend(&self, ev: &mut Event) -> bool {
         match ev {
             Event::NoteOn(ev) => {
                 ev.port = ev.port.saturating_sub(self.port_offset as usize);
                 ev.channel = ev.channel.saturating_sub(self.channel_offset);
+                let Some(note) = self.octave_shift_down(ev.note) else { return false };
+                ev.note = note;
+                // Velocity 0 means NoteOff, so leave that alone.
+                if let Some(Some((min, max))) = self.out_port_velocity_range.get(ev.port) {
+                    if ev.velocity > 0 { ev.velocity = ev.velocity.max(*min).min(*max); }
+                }
             },
             Event::NoteOff(ev) => {
                 ev.port = ev.port.saturating_sub(self.port_offset as usize);
                 ev.channel = ev.channel.saturating_sub(self.channel_offset);
+                let Some(note) = self.octave_shift_down(ev.note) else { return false };
+                ev.note = note;
             },
             Event::Ctrl(ev) => {
                 ev.port = ev.port.saturating_sub(self.port_offset as usize);
                 ev.channel = ev.channel.saturating_sub(self.channel_offset);
             },
+            Event::Nrpn(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+                ev.channel = ev.channel.saturating_sub(self.channel_offset);
+            },
+            Event::Program(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+                ev.channel = ev.channel.saturating_sub(self.channel_offset);
+            },
+            Event::PitchBend(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+                ev.channel = ev.channel.saturating_sub(self.channel_offset);
+            },
             Event::SysEx(ev) => {
                 ev.port = ev.port.saturating_sub(self.port_offset as usize);
             },
+            Event::Clock(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::Start(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::Stop(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::Continue(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::SongPosition(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::SongSelect(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::MtcQuarterFrame(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            Event::MtcFullFrame(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
             #[cfg(feature = "osc")]
             Event::Osc(ev) => {
                 ev.port = ev.port.saturating_sub(self.port_offset as usize);
             },
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ev) => {
+                ev.port = ev.port.saturating_sub(self.port_offset as usize);
+            },
             _ => {}
         }
+        true
     }
 }
 
@@ -402,20 +1356,73 @@ enum SceneRunType {
     Patch,
     Init,
     Exit,
+    Tick,
 }
 
-fn get_scene<'a>(scenes: &'a [&Scene<'a>], scene_num_opt: Option<SceneNum>) -> Option<&'a Scene<'a>> {
-    if let Some(scene_num) = scene_num_opt {
-        if scenes.len() > scene_num as usize {
-            return Some(&scenes[scene_num as usize]);
+/// A scene, and optionally one of its subscenes.
+type SceneKey = (SceneNum, Option<SceneNum>);
+
+/// A scene given in [RunArguments], or one added while running.
+#[derive(Clone)]
+enum RunnerScene<'a> {
+    Given(&'a Scene<'a>),
+    Added(Rc<AddedScene>),
+}
+
+/// A scene added with [Runner::add_scene], which owns its patch.
+struct AddedScene {
+    name: String,
+    patch: Box<dyn FilterTrait>,
+}
+
+impl RunnerScene<'_> {
+    fn scene(&self) -> Scene<'_> {
+        match self {
+            RunnerScene::Given(scene) => **scene,
+            RunnerScene::Added(scene) => Scene { name: &scene.name, patch: scene.patch.as_ref(), ..Scene::DEFAULT },
         }
     }
-    None
+}
+
+// Returns a copy, so that it can be used while the runner is changed.
+fn get_scene<'a>(scenes: &[RunnerScene<'a>], scene_num_opt: Option<SceneNum>) -> Option<RunnerScene<'a>> {
+    scenes.get(scene_num_opt? as usize).cloned()
 }
 
 // https://www.reddit.com/r/rust/comments/65kflg/does_rust_have_native_epoll_support/dgcnbtd?utm_source=share&utm_medium=web2x&context=3
-fn poll(fds: &mut [libc::pollfd], timeout: libc::c_int) -> libc::c_int {
+/// A backend's file descriptor, for registering with tokio without taking ownership.
+#[cfg(feature = "tokio")]
+struct PollFd(std::os::unix::io::RawFd);
+
+#[cfg(feature = "tokio")]
+impl std::os::unix::io::AsRawFd for PollFd {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.0
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn poll(fds: &mut [pollfd], timeout: c_int) -> c_int {
     unsafe {
-        libc::poll(&mut fds[0] as *mut libc::pollfd, fds.len() as libc::nfds_t, timeout)
+        libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout)
     }
 }
+
+/// There is nothing to block on in the browser, the caller waits between steps instead.
+#[cfg(target_arch = "wasm32")]
+fn poll(_fds: &mut [pollfd], _timeout: c_int) -> c_int {
+    0
+}
+
+/// Waits for the given number of milliseconds on a browser timer.
+#[cfg(feature = "webmidi")]
+async fn sleep_web(timeout: c_int) -> Result<(), Box<dyn Error>> {
+    let window = web_sys::window().ok_or("Running requires a browser window")?;
+    let mut result = Ok(0);
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        result = window.set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, timeout);
+    });
+    result.map_err(|e| format!("{:?}", e))?;
+    wasm_bindgen_futures::JsFuture::from(promise).await.map_err(|e| format!("{:?}", e))?;
+    Ok(())
+}
@@ -8,6 +8,9 @@
 //! [mididings]: http://das.nasophon.de/mididings/
 #![macro_use]
 
+#[macro_use]
+mod logging;
+
 pub mod proc;
 pub use proc::*;
 
@@ -15,6 +18,14 @@ mod scene;
 pub use scene::*;
 
 mod backend;
+pub use backend::{NullBackend, PortDescriptor};
+#[cfg(feature = "osc")]
+pub use backend::OscReconnect;
+#[cfg(feature = "midifile")]
+pub use backend::{MidiFileBackend, MidiFileRecorderBackend};
 
 mod engine;
-pub use engine::*;
\ No newline at end of file
+pub use engine::*;
+
+#[cfg(feature = "config-file")]
+pub mod config;
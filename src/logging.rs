@@ -0,0 +1,89 @@
+//! Logging of runtime messages.
+//!
+//! Messages go through the [log] crate, so that applications can control verbosity and
+//! destination, e.g. with `env_logger`. When the application didn't set up a logger before
+//! configuring, a fallback logger prints messages up to the `verbosity` of
+//! [ConfigArguments](crate::ConfigArguments): warnings and errors to standard error, others to
+//! standard output.
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Highest level printed by the fallback, as a `log::LevelFilter`.
+static FALLBACK_LEVEL: AtomicUsize = AtomicUsize::new(log::LevelFilter::Info as usize);
+
+/// Whether messages go through the log crate, set on configuring. Before that, they are printed.
+static LOGGER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Prints this crate's messages when the application didn't set up a logger.
+struct FallbackLogger;
+
+impl log::Log for FallbackLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("rmididings") && fallback_enabled(metadata.level())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            print(record.level(), *record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static FALLBACK_LOGGER: FallbackLogger = FallbackLogger;
+
+/// Installs the fallback logger printing up to `level`, unless the application set up a logger.
+pub(crate) fn init(level: log::LevelFilter) {
+    FALLBACK_LEVEL.store(level as usize, Ordering::Relaxed);
+    // When configured again, the fallback logger may already be installed.
+    if log::set_logger(&FALLBACK_LOGGER).is_ok() || std::ptr::addr_eq(log::logger(), &FALLBACK_LOGGER) {
+        log::set_max_level(level);
+    }
+    LOGGER_INSTALLED.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn logger_installed() -> bool {
+    LOGGER_INSTALLED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn fallback_enabled(level: log::Level) -> bool {
+    level as usize <= FALLBACK_LEVEL.load(Ordering::Relaxed)
+}
+
+pub(crate) fn print(level: log::Level, args: fmt::Arguments) {
+    match level {
+        log::Level::Error | log::Level::Warn => eprintln!("{}", args),
+        _ => println!("{}", args),
+    }
+}
+
+macro_rules! log_message {
+    ($level:expr, $($arg:tt)+) => {
+        if crate::logging::logger_installed() {
+            log::log!($level, $($arg)+);
+        } else if crate::logging::fallback_enabled($level) {
+            crate::logging::print($level, format_args!($($arg)+));
+        }
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! error {
+    ($($arg:tt)+) => { log_message!(log::Level::Error, $($arg)+) };
+}
+
+#[allow(unused_macros)]
+macro_rules! warn {
+    ($($arg:tt)+) => { log_message!(log::Level::Warn, $($arg)+) };
+}
+
+#[allow(unused_macros)]
+macro_rules! info {
+    ($($arg:tt)+) => { log_message!(log::Level::Info, $($arg)+) };
+}
+
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)+) => { log_message!(log::Level::Debug, $($arg)+) };
+}
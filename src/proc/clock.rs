@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Source of the current time for filters that act on time passing.
+///
+/// Filters use the [SystemClock] by default, a [FakeClock] can be used to test
+/// timing behaviour without waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Returns a shared handle to the system clock.
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// A clock that only moves when told to, for testing.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(3));
+/// assert_eq!(clock.now() - start, Duration::from_secs(3));
+/// ```
+#[derive(Debug)]
+pub struct FakeClock {
+    now: Mutex<Instant>,
+}
+
+impl FakeClock {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { now: Mutex::new(Instant::now()) })
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, duration: Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += duration;
+        }
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        match self.now.lock() {
+            Ok(now) => *now,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+}
@@ -1,24 +1,62 @@
 #![allow(non_snake_case)]
-use std::hash::{Hash, Hasher};
+use std::fmt;
+use std::hash::Hash;
+#[cfg(feature = "osc")]
+use std::hash::Hasher;
 
 #[cfg(feature = "osc")]
 extern crate rosc;
 #[cfg(feature = "dbus")]
 extern crate dbus;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+/// An event, like a MIDI message or a scene switch.
+///
+/// With the `serde` feature, events can be serialized, e.g. to log them as JSON.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// # #[cfg(feature = "serde")]
+/// # {
+/// let evs = EventStream::from(vec![NoteOnEvent(0,1,60,20), CtrlEvent(1,0,7,100)]);
+/// let json = serde_json::to_string(&evs).unwrap();
+/// assert_eq!(serde_json::from_str::<EventStream>(&json).unwrap(), evs);
+/// # }
+/// ```
 #[derive(Debug, Clone, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Event<'a> {
     None(NoneEventImpl),
     NoteOn(NoteOnEventImpl),
     NoteOff(NoteOffEventImpl),
     Ctrl(CtrlEventImpl),
+    Nrpn(NrpnEventImpl),
+    Program(ProgramEventImpl),
+    PitchBend(PitchBendEventImpl),
+    #[cfg_attr(feature = "serde", serde(borrow))]
     SysEx(SysExEventImpl<'a>),
     SceneSwitch(SceneSwitchEventImpl),
     SubSceneSwitch(SubSceneSwitchEventImpl),
     Quit(QuitEventImpl),
+    Resync(ResyncEventImpl),
+    Chord(ChordEventImpl),
+    Clock(ClockEventImpl),
+    Start(StartEventImpl),
+    Stop(StopEventImpl),
+    Continue(ContinueEventImpl),
+    SongPosition(SongPositionEventImpl),
+    SongSelect(SongSelectEventImpl),
+    MtcQuarterFrame(MtcQuarterFrameEventImpl),
+    MtcFullFrame(MtcFullFrameEventImpl),
     #[cfg(feature = "osc")]
     Osc(OscEventImpl),
+    #[cfg(feature = "osc")]
+    OscBundle(OscBundleEventImpl),
     #[cfg(feature = "dbus")]
+    #[cfg_attr(feature = "serde", serde(skip))]
     Dbus(DbusEventImpl),
 }
 impl Event<'_> {
@@ -27,9 +65,24 @@ impl Event<'_> {
             Event::NoteOn(ref ev) => Some(ev.port),
             Event::NoteOff(ref ev) => Some(ev.port),
             Event::Ctrl(ref ev) => Some(ev.port),
+            Event::Nrpn(ref ev) => Some(ev.port),
+            Event::Program(ref ev) => Some(ev.port),
+            Event::PitchBend(ref ev) => Some(ev.port),
             Event::SysEx(ref ev) => Some(ev.port),
+            Event::Clock(ref ev) => Some(ev.port),
+            Event::Start(ref ev) => Some(ev.port),
+            Event::Stop(ref ev) => Some(ev.port),
+            Event::Continue(ref ev) => Some(ev.port),
+            Event::SongPosition(ref ev) => Some(ev.port),
+            Event::SongSelect(ref ev) => Some(ev.port),
+            Event::MtcQuarterFrame(ref ev) => Some(ev.port),
+            Event::MtcFullFrame(ref ev) => Some(ev.port),
             #[cfg(feature = "osc")]
             Event::Osc(ref ev) => Some(ev.port),
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ref ev) => Some(ev.port),
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ref ev) => Some(ev.port),
             _ => None,
         }
     }
@@ -39,9 +92,24 @@ impl Event<'_> {
             Event::NoteOn(ref mut ev) => { ev.port = port; true },
             Event::NoteOff(ref mut ev) => { ev.port = port; true },
             Event::Ctrl(ref mut ev) => { ev.port = port; true },
+            Event::Nrpn(ref mut ev) => { ev.port = port; true },
+            Event::Program(ref mut ev) => { ev.port = port; true },
+            Event::PitchBend(ref mut ev) => { ev.port = port; true },
             Event::SysEx(ref mut ev) => { ev.port = port; true },
+            Event::Clock(ref mut ev) => { ev.port = port; true },
+            Event::Start(ref mut ev) => { ev.port = port; true },
+            Event::Stop(ref mut ev) => { ev.port = port; true },
+            Event::Continue(ref mut ev) => { ev.port = port; true },
+            Event::SongPosition(ref mut ev) => { ev.port = port; true },
+            Event::SongSelect(ref mut ev) => { ev.port = port; true },
+            Event::MtcQuarterFrame(ref mut ev) => { ev.port = port; true },
+            Event::MtcFullFrame(ref mut ev) => { ev.port = port; true },
             #[cfg(feature = "osc")]
             Event::Osc(ref mut ev) => { ev.port = port; true },
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ref mut ev) => { ev.port = port; true },
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ref mut ev) => { ev.port = port; true },
             _ => false,
         }
     }
@@ -51,6 +119,17 @@ impl Event<'_> {
             Event::NoteOn(ev) => Some(ev.channel),
             Event::NoteOff(ev) => Some(ev.channel),
             Event::Ctrl(ev) => Some(ev.channel),
+            Event::Nrpn(ev) => Some(ev.channel),
+            Event::Program(ev) => Some(ev.channel),
+            Event::PitchBend(ev) => Some(ev.channel),
+            _ => None,
+        }
+    }
+
+    pub fn note(&self) -> Option<u8> {
+        match self {
+            Event::NoteOn(ev) => Some(ev.note),
+            Event::NoteOff(ev) => Some(ev.note),
             _ => None,
         }
     }
@@ -60,6 +139,9 @@ impl Event<'_> {
             Event::NoteOn(ref mut ev) => { ev.channel = channel; true },
             Event::NoteOff(ref mut ev) => { ev.channel = channel; true },
             Event::Ctrl(ref mut ev) => { ev.channel = channel; true },
+            Event::Nrpn(ref mut ev) => { ev.channel = channel; true },
+            Event::Program(ref mut ev) => { ev.channel = channel; true },
+            Event::PitchBend(ref mut ev) => { ev.channel = channel; true },
             _ => false,
         }
     }
@@ -69,14 +151,103 @@ impl Default for Event<'_> {
         Event::None(NoneEventImpl::default())
     }
 }
+/// Human-readable representation of an event.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// assert_eq!(NoteOnEvent(0,0,60,20).to_string(), "NoteOn port=0 channel=0 note=60 velocity=20");
+/// assert_eq!(CtrlEvent(1,2,7,100).to_string(), "Ctrl port=1 channel=2 ctrl=7 value=100");
+/// assert_eq!(ProgramEvent(0,1,5).to_string(), "Program port=0 channel=1 program=5");
+/// assert_eq!(PitchBendEvent(0,1,-200).to_string(), "PitchBend port=0 channel=1 value=-200");
+/// assert_eq!(NrpnEvent(0,0,1000,200).to_string(), "Nrpn port=0 channel=0 param=1000 value=200");
+/// assert_eq!(SysExEvent(0,&[0xf0, 0x7e, 0xf7]).to_string(), "SysEx port=0 data=f0 7e f7");
+/// assert_eq!(ClockEvent(0).to_string(), "Clock port=0");
+/// assert_eq!(SongPositionEvent(0,16).to_string(), "SongPosition port=0 position=16");
+/// assert_eq!(SongSelectEvent(0,3).to_string(), "SongSelect port=0 song=3");
+/// assert_eq!(MtcQuarterFrameEvent(0,1,2).to_string(), "MtcQuarterFrame port=0 type=1 value=2");
+/// assert_eq!(MtcFullFrameEvent(0,1,2,3,4,MtcFrameRate::Fps25).to_string(), "MtcFullFrame port=0 time=01:02:03:04 rate=Fps25");
+/// assert_eq!(SceneSwitchEvent(2).to_string(), "SceneSwitch scene=2");
+/// assert_eq!(SceneSwitchSubSceneEvent(2, 1).to_string(), "SceneSwitch scene=2 subscene=1");
+/// assert_eq!(ResyncEvent().to_string(), "Resync");
+/// assert_eq!(ChordEvent(vec![60, 64, 67]).to_string(), "Chord notes=[60, 64, 67]");
+/// assert_eq!(SubSceneSwitchOffsetEvent(-1).to_string(), "SubSceneSwitch offset=-1");
+/// assert_eq!(SubSceneResetEvent().to_string(), "SubSceneSwitch first");
+/// # #[cfg(feature = "osc")]
+/// assert_eq!(OscEvent(0, "/foo".to_string(), vec![rmididings::osc::OscType::Int(1)]).to_string(), "Osc port=0 addr=/foo args=[Int(1)]");
+/// # #[cfg(feature = "osc")]
+/// assert_eq!(OscBundleEvent(0, vec![], None).to_string(), "OscBundle port=0 messages=0");
+/// ```
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::None(_) => write!(f, "None"),
+            Event::NoteOn(ev) => write!(f, "NoteOn port={} channel={} note={} velocity={}", ev.port, ev.channel, ev.note, ev.velocity),
+            Event::NoteOff(ev) => write!(f, "NoteOff port={} channel={} note={} velocity={}", ev.port, ev.channel, ev.note, ev.velocity),
+            Event::Ctrl(ev) => write!(f, "Ctrl port={} channel={} ctrl={} value={}", ev.port, ev.channel, ev.ctrl, ev.value),
+            Event::Program(ev) => write!(f, "Program port={} channel={} program={}", ev.port, ev.channel, ev.program),
+            Event::PitchBend(ev) => write!(f, "PitchBend port={} channel={} value={}", ev.port, ev.channel, ev.value),
+            Event::Nrpn(ev) => write!(f, "Nrpn port={} channel={} param={} value={}", ev.port, ev.channel, ev.param, ev.value),
+            Event::SysEx(ev) => {
+                write!(f, "SysEx port={} data=", ev.port)?;
+                for (i, byte) in ev.data.iter().enumerate() {
+                    if i > 0 { write!(f, " ")?; }
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            },
+            Event::SceneSwitch(ev) => {
+                write!(f, "SceneSwitch {}", SceneSwitchValueDisplay(&ev.scene))?;
+                if let Some(subscene) = ev.subscene { write!(f, " subscene={}", subscene)?; }
+                Ok(())
+            },
+            Event::SubSceneSwitch(ev) => write!(f, "SubSceneSwitch {}", SceneSwitchValueDisplay(&ev.subscene)),
+            Event::Quit(_) => write!(f, "Quit"),
+            Event::Resync(_) => write!(f, "Resync"),
+            Event::Chord(ev) => write!(f, "Chord notes={:?}", ev.notes),
+            Event::Clock(ev) => write!(f, "Clock port={}", ev.port),
+            Event::Start(ev) => write!(f, "Start port={}", ev.port),
+            Event::Stop(ev) => write!(f, "Stop port={}", ev.port),
+            Event::Continue(ev) => write!(f, "Continue port={}", ev.port),
+            Event::SongPosition(ev) => write!(f, "SongPosition port={} position={}", ev.port, ev.position),
+            Event::SongSelect(ev) => write!(f, "SongSelect port={} song={}", ev.port, ev.song),
+            Event::MtcQuarterFrame(ev) => write!(f, "MtcQuarterFrame port={} type={} value={}", ev.port, ev.message_type, ev.value),
+            Event::MtcFullFrame(ev) => write!(f, "MtcFullFrame port={} time={:02}:{:02}:{:02}:{:02} rate={:?}", ev.port, ev.hours, ev.minutes, ev.seconds, ev.frames, ev.frame_rate),
+            #[cfg(feature = "osc")]
+            Event::Osc(ev) => write!(f, "Osc port={} addr={} args={:?}", ev.port, ev.addr, ev.args),
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ev) => {
+                write!(f, "OscBundle port={} messages={}", ev.port, ev.messages.len())?;
+                if let Some(time) = ev.time { write!(f, " time={}.{:08x}", time.seconds, time.fractional)?; }
+                Ok(())
+            },
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ev) => write!(f, "Dbus port={} service={} path={} interface={} method={} args={:?}", ev.port, ev.service, ev.path, ev.interface, ev.method, ev.args),
+        }
+    }
+}
+
+struct SceneSwitchValueDisplay<'a>(&'a SceneSwitchValue);
+impl fmt::Display for SceneSwitchValueDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            SceneSwitchValue::Fixed(scene) => write!(f, "scene={}", scene),
+            SceneSwitchValue::Offset(offset) => write!(f, "offset={}", offset),
+            SceneSwitchValue::First => write!(f, "first"),
+        }
+    }
+}
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NoneEventImpl {}
 pub fn NoneEvent<'a>() -> Event<'a> {
     Event::None(NoneEventImpl { })
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NoteOnEventImpl {
     pub port: usize,
     pub channel: u8,
@@ -88,16 +259,23 @@ pub fn NoteOnEvent<'a>(port: usize, channel: u8, note: u8, velocity: u8) -> Even
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NoteOffEventImpl {
     pub port: usize,
     pub channel: u8,
     pub note: u8,
+    /// Release velocity, used by some instruments for the release time.
+    pub velocity: u8,
 }
 pub fn NoteOffEvent<'a>(port: usize, channel: u8, note: u8) -> Event<'a> {
-    Event::NoteOff(NoteOffEventImpl { port, channel, note })
+    Event::NoteOff(NoteOffEventImpl { port, channel, note, velocity: 0 })
+}
+pub fn NoteOffEventWithVelocity<'a>(port: usize, channel: u8, note: u8, velocity: u8) -> Event<'a> {
+    Event::NoteOff(NoteOffEventImpl { port, channel, note, velocity })
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CtrlEventImpl {
     pub port: usize,
     pub channel: u8,
@@ -109,6 +287,46 @@ pub fn CtrlEvent<'a>(port: usize, channel: u8, ctrl: u32, value: i32) -> Event<'
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProgramEventImpl {
+    pub port: usize,
+    pub channel: u8,
+    pub program: u8,
+}
+pub fn ProgramEvent<'a>(port: usize, channel: u8, program: u8) -> Event<'a> {
+    Event::Program(ProgramEventImpl { port, channel, program })
+}
+
+/// Pitch bend, with a value from -8192 to 8191 where 0 is the center.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PitchBendEventImpl {
+    pub port: usize,
+    pub channel: u8,
+    pub value: i16,
+}
+pub fn PitchBendEvent<'a>(port: usize, channel: u8, value: i16) -> Event<'a> {
+    Event::PitchBend(PitchBendEventImpl { port, channel, value })
+}
+
+/// Non-registered parameter number (NRPN) with its 14-bit value.
+///
+/// On MIDI this is sent as a sequence of controllers, see [Nrpn](super::Nrpn) and
+/// [NrpnDecode](super::NrpnDecode).
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NrpnEventImpl {
+    pub port: usize,
+    pub channel: u8,
+    pub param: u16,
+    pub value: u16,
+}
+pub fn NrpnEvent<'a>(port: usize, channel: u8, param: u16, value: u16) -> Event<'a> {
+    Event::Nrpn(NrpnEventImpl { port, channel, param, value })
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SysExEventImpl<'a> {
     pub port: usize,
     pub data: &'a [u8],
@@ -118,18 +336,168 @@ pub fn SysExEvent<'a>(port: usize, data: &'a [u8]) -> Event<'a> {
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct QuitEventImpl {}
 pub fn QuitEvent<'a>() -> Event<'a> {
     Event::Quit(QuitEventImpl { })
 }
 
+/// Starts counting bars from now, see [TempoHandle](super::TempoHandle).
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResyncEventImpl {}
+pub fn ResyncEvent<'a>() -> Event<'a> {
+    Event::Resync(ResyncEventImpl { })
+}
+
+/// A chord was pressed, see [ChordDetector](super::ChordDetector).
+#[derive(Debug, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChordEventImpl {
+    pub notes: Vec<u8>,
+}
+pub fn ChordEvent<'a>(notes: Vec<u8>) -> Event<'a> {
+    Event::Chord(ChordEventImpl { notes })
+}
+
+/// MIDI timing clock, sent 24 times per quarter note.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClockEventImpl {
+    pub port: usize,
+}
+pub fn ClockEvent<'a>(port: usize) -> Event<'a> {
+    Event::Clock(ClockEventImpl { port })
+}
+
+/// Start playing from the beginning of the song.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StartEventImpl {
+    pub port: usize,
+}
+pub fn StartEvent<'a>(port: usize) -> Event<'a> {
+    Event::Start(StartEventImpl { port })
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StopEventImpl {
+    pub port: usize,
+}
+pub fn StopEvent<'a>(port: usize) -> Event<'a> {
+    Event::Stop(StopEventImpl { port })
+}
+
+/// Continue playing from where it was stopped.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ContinueEventImpl {
+    pub port: usize,
+}
+pub fn ContinueEvent<'a>(port: usize) -> Event<'a> {
+    Event::Continue(ContinueEventImpl { port })
+}
+
+/// Song position pointer, in sixteenth notes (6 clocks) since the start of the song.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SongPositionEventImpl {
+    pub port: usize,
+    pub position: u16,
+}
+pub fn SongPositionEvent<'a>(port: usize, position: u16) -> Event<'a> {
+    Event::SongPosition(SongPositionEventImpl { port, position })
+}
+
+/// Song select, the number of the song or sequence to play.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SongSelectEventImpl {
+    pub port: usize,
+    pub song: u8,
+}
+pub fn SongSelectEvent<'a>(port: usize, song: u8) -> Event<'a> {
+    Event::SongSelect(SongSelectEventImpl { port, song })
+}
+
+/// MIDI Timecode quarter frame, one of the eight pieces of a timecode.
+///
+/// The _message_type_ (0-7) says which piece it is, the _value_ (0-15) is its nibble, see
+/// [MtcAssembler](super::MtcAssembler) to get the timecode from them.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MtcQuarterFrameEventImpl {
+    pub port: usize,
+    pub message_type: u8,
+    pub value: u8,
+}
+pub fn MtcQuarterFrameEvent<'a>(port: usize, message_type: u8, value: u8) -> Event<'a> {
+    Event::MtcQuarterFrame(MtcQuarterFrameEventImpl { port, message_type, value })
+}
+
+/// Frame rate of a MIDI Timecode.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MtcFrameRate {
+    #[default]
+    Fps24,
+    Fps25,
+    /// 29.97 frames per second, drop frame.
+    Fps30Drop,
+    Fps30,
+}
+
+impl MtcFrameRate {
+    /// Frame rate from its number in the timecode (0-3).
+    pub fn from_code(code: u8) -> Self {
+        match code & 0x03 {
+            0 => MtcFrameRate::Fps24,
+            1 => MtcFrameRate::Fps25,
+            2 => MtcFrameRate::Fps30Drop,
+            _ => MtcFrameRate::Fps30,
+        }
+    }
+
+    /// Number of the frame rate in the timecode (0-3).
+    pub fn code(&self) -> u8 {
+        match self {
+            MtcFrameRate::Fps24 => 0,
+            MtcFrameRate::Fps25 => 1,
+            MtcFrameRate::Fps30Drop => 2,
+            MtcFrameRate::Fps30 => 3,
+        }
+    }
+}
+
+/// MIDI Timecode, as SMPTE time.
+///
+/// This is assembled from quarter frames by [MtcAssembler](super::MtcAssembler), backends
+/// don't send or receive it.
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MtcFullFrameEventImpl {
+    pub port: usize,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub frame_rate: MtcFrameRate,
+}
+pub fn MtcFullFrameEvent<'a>(port: usize, hours: u8, minutes: u8, seconds: u8, frames: u8, frame_rate: MtcFrameRate) -> Event<'a> {
+    Event::MtcFullFrame(MtcFullFrameEventImpl { port, hours, minutes, seconds, frames, frame_rate })
+}
+
 pub type SceneNum = u8;
 pub type SceneOffset = i16; // large enough to do computation too
 
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SceneSwitchValue {
     Fixed(SceneNum),
     Offset(SceneOffset),
+    /// The first (sub)scene, whatever the scene offset is.
+    First,
 }
 impl Default for SceneSwitchValue {
     fn default() -> Self {
@@ -138,17 +506,24 @@ impl Default for SceneSwitchValue {
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SceneSwitchEventImpl {
     pub scene: SceneSwitchValue,
+    /// Subscene to switch to, instead of the one last used in the scene.
+    pub subscene: Option<SceneNum>,
 }
 pub fn SceneSwitchEvent<'a>(scene: SceneNum) -> Event<'a> {
-    Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Fixed(scene) })
+    Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Fixed(scene), subscene: None })
+}
+pub fn SceneSwitchSubSceneEvent<'a>(scene: SceneNum, subscene: SceneNum) -> Event<'a> {
+    Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Fixed(scene), subscene: Some(subscene) })
 }
 pub fn SceneSwitchOffsetEvent<'a>(offset: SceneOffset) -> Event<'a> {
-    Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Offset(offset) })
+    Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Offset(offset), subscene: None })
 }
 
 #[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SubSceneSwitchEventImpl {
     pub subscene: SceneSwitchValue,
 }
@@ -158,13 +533,22 @@ pub fn SubSceneSwitchEvent<'a>(subscene: SceneNum) -> Event<'a> {
 pub fn SubSceneSwitchOffsetEvent<'a>(offset: SceneOffset) -> Event<'a> {
     Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene: SceneSwitchValue::Offset(offset) })
 }
+pub fn SubSceneResetEvent<'a>() -> Event<'a> {
+    Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene: SceneSwitchValue::First })
+}
 
 #[cfg(feature = "osc")]
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct OscEventImpl {
     pub port: usize,
     pub addr: String,
+    // rosc doesn't support serde
+    #[cfg_attr(feature = "serde", serde(with = "osc_args_serde"))]
     pub args: Vec<rosc::OscType>,
+    /// Time tag of the bundle the message arrived in, if it was to be applied at a specific time.
+    #[cfg_attr(feature = "serde", serde(default, with = "osc_args_serde::time"))]
+    pub time: Option<rosc::OscTime>,
 }
 #[cfg(feature = "osc")]
 impl OscEventImpl {
@@ -203,7 +587,7 @@ impl Eq for OscEventImpl {}
 
 #[cfg(feature = "osc")]
 pub fn OscEvent<'a>(port: usize, addr: String, args: Vec<rosc::OscType>) -> Event<'a> {
-    Event::Osc(OscEventImpl { port, addr, args })
+    Event::Osc(OscEventImpl { port, addr, args, time: None })
 }
 
 #[cfg(feature = "osc")]
@@ -213,16 +597,144 @@ impl From<rosc::OscMessage> for Event<'_> {
     }
 }
 
+/// Several OSC messages that are sent together, optionally to be applied at a specific time.
+#[cfg(feature = "osc")]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OscBundleEventImpl {
+    pub port: usize,
+    pub messages: Vec<OscEventImpl>,
+    /// When the receiver should apply the messages, `None` for right away.
+    #[cfg_attr(feature = "serde", serde(default, with = "osc_args_serde::time"))]
+    pub time: Option<rosc::OscTime>,
+}
+
+#[cfg(feature = "osc")]
+pub fn OscBundleEvent<'a>(port: usize, messages: Vec<OscEventImpl>, time: Option<rosc::OscTime>) -> Event<'a> {
+    Event::OscBundle(OscBundleEventImpl { port, messages, time })
+}
+
+/// A D-Bus signal that was received, or a method call to make.
+///
+/// Empty fields of a method call are taken from the name of the output port.
 #[cfg(feature = "dbus")]
-#[derive(Debug, Clone, Default, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct DbusEventImpl {
+    pub port: usize,
+    /// Bus name of the sender of a signal, or the destination of a method call.
     pub service: String,
     pub path: String,
     pub interface: String,
+    /// Member, i.e. the name of the signal or method.
     pub method: String,
-    pub args: Vec<dbus::arg::ArgType>
+    pub args: Vec<dbus::arg::messageitem::MessageItem>,
 }
+
+// Arguments can be floating point, only the names are hashed.
 #[cfg(feature = "dbus")]
-pub fn DbusEvent<'a>(service: String, path: String, interface: String, method: String, args: Vec<dbus::arg::ArgType>) -> Event<'a> {
-    Event::Dbus(DbusEventImpl { service, path, interface, method, args })
-}
\ No newline at end of file
+impl Hash for DbusEventImpl {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.port.hash(state);
+        self.service.hash(state);
+        self.path.hash(state);
+        self.interface.hash(state);
+        self.method.hash(state);
+        self.args.len().hash(state);
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl Eq for DbusEventImpl {}
+
+#[cfg(feature = "dbus")]
+pub fn DbusEvent<'a>(port: usize, service: String, path: String, interface: String, method: String, args: Vec<dbus::arg::messageitem::MessageItem>) -> Event<'a> {
+    Event::Dbus(DbusEventImpl { port, service, path, interface, method, args })
+}
+
+#[cfg(all(feature = "osc", feature = "serde"))]
+mod osc_args_serde {
+    use rosc::{OscArray, OscColor, OscMidiMessage, OscTime, OscType};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    // Mirror of OscType that can be serialized.
+    #[derive(Serialize, Deserialize)]
+    enum OscArg {
+        Int(i32),
+        Float(f32),
+        String(String),
+        Blob(Vec<u8>),
+        Time(u32, u32),
+        Long(i64),
+        Double(f64),
+        Char(char),
+        Color(u8, u8, u8, u8),
+        Midi(u8, u8, u8, u8),
+        Bool(bool),
+        Array(Vec<OscArg>),
+        Nil,
+        Inf,
+    }
+
+    impl From<&OscType> for OscArg {
+        fn from(arg: &OscType) -> Self {
+            match arg {
+                OscType::Int(x) => OscArg::Int(*x),
+                OscType::Float(x) => OscArg::Float(*x),
+                OscType::String(x) => OscArg::String(x.clone()),
+                OscType::Blob(x) => OscArg::Blob(x.clone()),
+                OscType::Time(x) => OscArg::Time(x.seconds, x.fractional),
+                OscType::Long(x) => OscArg::Long(*x),
+                OscType::Double(x) => OscArg::Double(*x),
+                OscType::Char(x) => OscArg::Char(*x),
+                OscType::Color(x) => OscArg::Color(x.red, x.green, x.blue, x.alpha),
+                OscType::Midi(x) => OscArg::Midi(x.port, x.status, x.data1, x.data2),
+                OscType::Bool(x) => OscArg::Bool(*x),
+                OscType::Array(x) => OscArg::Array(x.content.iter().map(OscArg::from).collect()),
+                OscType::Nil => OscArg::Nil,
+                OscType::Inf => OscArg::Inf,
+            }
+        }
+    }
+
+    impl From<OscArg> for OscType {
+        fn from(arg: OscArg) -> Self {
+            match arg {
+                OscArg::Int(x) => OscType::Int(x),
+                OscArg::Float(x) => OscType::Float(x),
+                OscArg::String(x) => OscType::String(x),
+                OscArg::Blob(x) => OscType::Blob(x),
+                OscArg::Time(seconds, fractional) => OscType::Time(OscTime { seconds, fractional }),
+                OscArg::Long(x) => OscType::Long(x),
+                OscArg::Double(x) => OscType::Double(x),
+                OscArg::Char(x) => OscType::Char(x),
+                OscArg::Color(red, green, blue, alpha) => OscType::Color(OscColor { red, green, blue, alpha }),
+                OscArg::Midi(port, status, data1, data2) => OscType::Midi(OscMidiMessage { port, status, data1, data2 }),
+                OscArg::Bool(x) => OscType::Bool(x),
+                OscArg::Array(x) => OscType::Array(OscArray { content: x.into_iter().map(OscType::from).collect() }),
+                OscArg::Nil => OscType::Nil,
+                OscArg::Inf => OscType::Inf,
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(args: &[OscType], serializer: S) -> Result<S::Ok, S::Error> {
+        args.iter().map(OscArg::from).collect::<Vec<_>>().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<OscType>, D::Error> {
+        Ok(Vec::<OscArg>::deserialize(deserializer)?.into_iter().map(OscType::from).collect())
+    }
+
+    pub mod time {
+        use rosc::OscTime;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<S: Serializer>(time: &Option<OscTime>, serializer: S) -> Result<S::Ok, S::Error> {
+            time.map(|t| (t.seconds, t.fractional)).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<OscTime>, D::Error> {
+            Ok(Option::<(u32, u32)>::deserialize(deserializer)?.map(|(seconds, fractional)| OscTime { seconds, fractional }))
+        }
+    }
+}
@@ -1,16 +1,57 @@
 use std::iter::FromIterator;
 use std::collections::HashSet;
 
-use super::event::*;
+use smallvec::SmallVec;
 
-#[derive(Debug, Clone, Eq, Default, Hash, PartialEq)]
+use super::event::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Number of events an EventStream holds without allocating, most only have one.
+const INLINE_EVENTS: usize = 4;
+
+/// The events a filter is run on.
+///
+/// Filters change the stream in place: they remove events, change them, or add new ones.
+/// A filter of your own implements [FilterTrait](super::FilterTrait) using the methods here,
+/// and can then be used in patches like any other filter.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// /// Doubles the velocity of notes, and drops program changes.
+/// struct Louder();
+///
+/// impl FilterTrait for Louder {
+///     fn run(&self, evs: &mut EventStream) {
+///         evs.retain(|ev| !matches!(ev, Event::Program(_)));
+///         evs.map(|ev| match ev {
+///             Event::NoteOn(ev) => NoteOnEvent(ev.port, ev.channel, ev.note, ev.velocity.saturating_mul(2).min(127)),
+///             ev => ev,
+///         });
+///     }
+/// }
+///
+/// # fn main() {
+/// let patch = Chain!(ChannelFilter(0), Louder());
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), ProgramEvent(0,0,1), CtrlEvent(0,0,7,100)]);
+/// patch.run(&mut evs);
+/// assert_eq!(evs.events(), &[NoteOnEvent(0,0,60,40), CtrlEvent(0,0,7,100)]);
+/// # }
+/// ```
+#[derive(Debug, Clone, Eq, Default, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct EventStream<'a> {
-    events: Vec<Event<'a>>,
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    events: SmallVec<[Event<'a>; INLINE_EVENTS]>,
 }
 
 impl<'a> EventStream<'a> {
     pub fn append(&mut self, other: &mut Vec<Event<'a>>) {
-        self.events.append(other);
+        self.events.extend(other.drain(..));
     }
 
     pub fn clear(&mut self) {
@@ -21,6 +62,11 @@ impl<'a> EventStream<'a> {
         self.events.is_empty()
     }
 
+    /// The events, in order.
+    pub fn events(&self) -> &[Event<'a>] {
+        &self.events
+    }
+
     pub fn iter(&self) -> std::slice::Iter<'_, Event<'a>> {
         self.events.iter()
     }
@@ -45,17 +91,134 @@ impl<'a> EventStream<'a> {
         self.events.remove(index)
     }
 
-    pub fn retain<F>(&mut self, f: F) where F: FnMut(&Event) -> bool {
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&Event) -> bool {
+        self.events.retain(|ev| f(ev))
+    }
+
+    /// Keeps only the events for which the function returns true, which may change them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,127,20)]);
+    /// evs.retain_mut(|ev| match ev {
+    ///     Event::NoteOn(ev) if ev.note < 120 => { ev.note += 12; true },
+    ///     _ => false,
+    /// });
+    /// assert_eq!(evs, NoteOnEvent(0,0,72,20));
+    /// ```
+    pub fn retain_mut<F>(&mut self, f: F) where F: FnMut(&mut Event<'a>) -> bool {
         self.events.retain(f)
     }
 
+    /// Replaces each event by what the function returns for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOffEvent(0,0,60)]);
+    /// evs.map(|ev| match ev {
+    ///     Event::NoteOn(ev) => CtrlEvent(ev.port, ev.channel, 20, ev.note as i32),
+    ///     ev => ev,
+    /// });
+    /// assert_eq!(evs, vec![CtrlEvent(0,0,20,60), NoteOffEvent(0,0,60)]);
+    /// ```
+    pub fn map<F>(&mut self, f: F) where F: FnMut(Event<'a>) -> Event<'a> {
+        self.events = std::mem::take(&mut self.events).into_iter().map(f).collect();
+    }
+
     pub fn replace(&mut self, other: EventStream<'a>) {
         self.events = other.events;
     }
 
-    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> std::vec::Splice<'_, <I as IntoIterator>::IntoIter>
-            where R: std::ops::RangeBounds<usize>, I: IntoIterator<Item = Event<'a>>{
-        self.events.splice(range, replace_with)
+    pub fn insert(&mut self, index: usize, ev: Event<'a>) {
+        self.events.insert(index, ev);
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.events.truncate(len);
+    }
+
+    /// Removes the events in the range, and returns them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+    /// let removed: Vec<Event> = evs.drain(..2).collect();
+    /// assert_eq!(removed, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20)]);
+    /// assert_eq!(evs, NoteOnEvent(0,0,64,20));
+    /// ```
+    pub fn drain<R: std::ops::RangeBounds<usize>>(&mut self, range: R) -> smallvec::Drain<'_, [Event<'a>; INLINE_EVENTS]> {
+        self.events.drain(range)
+    }
+
+    /// Replaces the events in the range by the events of another stream.
+    ///
+    /// The replacement may have a different length, so indices of events after
+    /// the range change. When replacing multiple ranges, start at the end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20)]);
+    /// evs.splice(0..1, EventStream::from(vec![NoteOnEvent(1,0,60,20), NoteOnEvent(2,0,60,20)]));
+    /// assert_eq!(evs, vec![NoteOnEvent(1,0,60,20), NoteOnEvent(2,0,60,20), NoteOnEvent(0,0,62,20)]);
+    /// ```
+    ///
+    /// An event in the middle can be replaced by none, one, or more events.
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+    ///
+    /// let mut evs_none = evs.clone();
+    /// evs_none.splice(1..2, EventStream::empty());
+    /// assert_eq!(evs_none, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,64,20)]);
+    ///
+    /// let mut evs_one = evs.clone();
+    /// evs_one.splice(1..2, EventStream::from(CtrlEvent(0,0,7,100)));
+    /// assert_eq!(evs_one, vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,100), NoteOnEvent(0,0,64,20)]);
+    ///
+    /// let mut evs_many = evs.clone();
+    /// evs_many.splice(1..2, EventStream::from(vec![CtrlEvent(0,0,7,100), CtrlEvent(0,0,10,64), CtrlEvent(0,0,11,127)]));
+    /// assert_eq!(evs_many, vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,100), CtrlEvent(0,0,10,64), CtrlEvent(0,0,11,127), NoteOnEvent(0,0,64,20)]);
+    /// ```
+    pub fn splice(&mut self, range: std::ops::Range<usize>, replacement: EventStream<'a>) {
+        self.events.drain(range.clone());
+        self.events.insert_many(range.start, replacement);
+    }
+
+    /// Replaces each event for which the function returns a stream by the events of it.
+    ///
+    /// Events for which it returns `None` are kept as they are. This takes care of the
+    /// indices that [EventStream::splice] needs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,100), NoteOnEvent(0,0,64,20)]);
+    /// evs.map_replace(|ev| match ev {
+    ///     Event::NoteOn(n) if n.note == 60 => Some(EventStream::empty()),
+    ///     Event::NoteOn(n) => Some(EventStream::from(vec![ev.clone(), NoteOnEvent(n.port, n.channel, n.note + 7, n.velocity)])),
+    ///     _ => None,
+    /// });
+    /// assert_eq!(evs, vec![CtrlEvent(0,0,7,100), NoteOnEvent(0,0,64,20), NoteOnEvent(0,0,71,20)]);
+    /// ```
+    pub fn map_replace<F>(&mut self, mut f: F) where F: FnMut(&Event<'a>) -> Option<EventStream<'a>> {
+        let mut events = SmallVec::with_capacity(self.events.len());
+        for ev in self.events.drain(..) {
+            match f(&ev) {
+                Some(replacement) => events.extend(replacement),
+                None => events.push(ev),
+            }
+        }
+        self.events = events;
     }
 
     /// EventStream with a single None event.
@@ -63,7 +226,7 @@ impl<'a> EventStream<'a> {
     /// This is used mainly for init and exit patches, so that e.g. a {SceneSwitch}
     /// will work there, as it only works when there is at least one event.
     pub fn none() -> Self {
-        Self { events: vec![Event::default()] }
+        Self { events: smallvec::smallvec![Event::default()] }
     }
 
     /// EventStream without any events.
@@ -73,17 +236,60 @@ impl<'a> EventStream<'a> {
         Self::default()
     }
 
+    /// Sorts events by a key, keeping the order of events with the same key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,2,60,20), NoteOnEvent(0,1,64,20), NoteOnEvent(0,2,62,20)]);
+    /// evs.sort_by_key(|ev| ev.channel());
+    /// assert_eq!(evs, vec![NoteOnEvent(0,1,64,20), NoteOnEvent(0,2,60,20), NoteOnEvent(0,2,62,20)]);
+    /// ```
+    pub fn sort_by_key<K: Ord, F: FnMut(&Event<'a>) -> K>(&mut self, f: F) {
+        self.events.sort_by_key(f);
+    }
+
+    /// Sorts events by note, then channel, then port.
+    ///
+    /// Events without a note, like controller changes, come before notes, and events without
+    /// a channel before those with one. Otherwise the order is kept, so a NoteOn stays before
+    /// its NoteOff.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,1,64,20), NoteOnEvent(0,1,60,20), CtrlEvent(0,1,7,100), NoteOnEvent(0,0,64,20)]);
+    /// evs.sort();
+    /// assert_eq!(evs, vec![CtrlEvent(0,1,7,100), NoteOnEvent(0,1,60,20), NoteOnEvent(0,0,64,20), NoteOnEvent(0,1,64,20)]);
+    /// ```
+    pub fn sort(&mut self) {
+        self.sort_by_key(|ev| (ev.note(), ev.channel(), ev.port()));
+    }
+
     /// Dedups events.
     pub fn dedup(&mut self) {
-        // https://stackoverflow.com/a/47648303
-        let mut uniques = HashSet::new();
-        self.events.retain(|e| uniques.insert(e.clone()));
+        // Find the duplicates first, so that events don't need to be copied to compare them.
+        let mut uniques = HashSet::with_capacity(self.events.len());
+        let keep: Vec<bool> = self.events.iter().map(|e| uniques.insert(e)).collect();
+        let mut keep = keep.into_iter();
+        self.events.retain(|_| keep.next().unwrap_or(true));
+    }
+}
+
+// Not derived, so that streams with different lifetimes can be compared. These don't become
+// the same type, as the SmallVec makes EventStream invariant over its lifetime.
+impl<'a, 'b> PartialEq<EventStream<'b>> for EventStream<'a> {
+    fn eq(&self, other: &EventStream<'b>) -> bool {
+        fn eq_event<'e>(a: &Event<'e>, b: &Event<'e>) -> bool { a == b }
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| eq_event(a, b))
     }
 }
 
 impl<'a> PartialEq<Vec<Event<'a>>> for EventStream<'a> {
     fn eq(&self, other: &Vec<Event<'a>>) -> bool {
-        self.events == *other
+        self.events.as_slice() == other.as_slice()
     }
 }
 
@@ -101,13 +307,13 @@ impl<'a> Extend<Event<'a>> for EventStream<'a> {
 
 impl<'a> From<Event<'a>> for EventStream<'a> {
     fn from(ev: Event<'a>) -> Self {
-        Self { events: vec![ev] }
+        Self { events: smallvec::smallvec![ev] }
     }
 }
 
 impl<'a> From<&Event<'a>> for EventStream<'a> {
     fn from(ev: &Event<'a>) -> Self {
-        Self { events: vec![ev.clone()] }
+        Self { events: smallvec::smallvec![ev.clone()] }
     }
 }
 
@@ -133,13 +339,13 @@ impl<'a> From<Option<&Event<'a>>> for EventStream<'a> {
 
 impl<'a> From<Vec<Event<'a>>> for EventStream<'a> {
     fn from(events: Vec<Event<'a>>) -> Self {
-        Self { events }
+        Self { events: SmallVec::from_vec(events) }
     }
 }
 
 impl<'a> From<&Vec<Event<'a>>> for EventStream<'a> {
     fn from(events: &Vec<Event<'a>>) -> Self {
-        Self { events: events.clone() }
+        Self { events: events.iter().cloned().collect() }
     }
 }
 
@@ -167,7 +373,7 @@ impl<'a> FromIterator<EventStream<'a>> for EventStream<'a> {
 
 impl<'a> IntoIterator for EventStream<'a> {
     type Item = Event<'a>;
-    type IntoIter = std::vec::IntoIter<Event<'a>>;
+    type IntoIter = smallvec::IntoIter<[Event<'a>; INLINE_EVENTS]>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.events.into_iter()
@@ -190,4 +396,67 @@ impl<'a> IntoIterator for &'a mut EventStream<'a> {
     fn into_iter(self) -> Self::IntoIter {
         self.events.iter_mut()
     }
-}
\ No newline at end of file
+}
+/// Lists events that differ from the expected ones, or returns `None` when they are the same.
+///
+/// Used by [assert_events!], each event is shown on a line, prefixed with `-` when only
+/// expected and with `+` when only present.
+#[doc(hidden)]
+pub fn events_diff<'e, 'a: 'e>(events: impl IntoIterator<Item = &'e Event<'a>>, expected: &[Event]) -> Option<String> {
+    let events: Vec<&Event> = events.into_iter().collect();
+    if events.len() == expected.len() && events.iter().zip(expected).all(|(ev, exp)| *ev == exp) {
+        return None;
+    }
+    let mut diff = String::new();
+    for i in 0..events.len().max(expected.len()) {
+        match (expected.get(i), events.get(i)) {
+            (Some(exp), Some(ev)) if exp == *ev => diff += &format!("  {}: {}\n", i, ev),
+            (exp, ev) => {
+                if let Some(exp) = exp { diff += &format!("- {}: {}\n", i, exp); }
+                if let Some(ev) = ev { diff += &format!("+ {}: {}\n", i, ev); }
+            },
+        }
+    }
+    Some(diff)
+}
+
+/// Asserts that events are the expected ones, showing which differ when they are not.
+///
+/// The events can be an [EventStream], or anything else that iterates over events with `iter()`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,100)]);
+/// Transpose(12).run(&mut evs);
+/// assert_events!(evs, [NoteOnEvent(0,0,72,20), CtrlEvent(0,0,7,100)]);
+///
+/// Discard().run(&mut evs);
+/// assert_events!(evs, []);
+/// # }
+/// ```
+///
+/// ```should_panic
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,100)]);
+/// // Panics with:
+/// //     events differ (- expected, + actual):
+/// //       0: NoteOn port=0 channel=0 note=60 velocity=20
+/// //     - 1: Ctrl port=0 channel=0 ctrl=7 value=90
+/// //     + 1: Ctrl port=0 channel=0 ctrl=7 value=100
+/// assert_events!(evs, [NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,90)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_events {
+    ( $evs:expr, [ $($ev:expr),* $(,)? ] ) => {
+        if let Some(diff) = $crate::proc::events_diff($evs.iter(), &[$($ev),*]) {
+            panic!("events differ (- expected, + actual):\n{}", diff);
+        }
+    };
+}
@@ -1,20 +1,52 @@
 #![macro_use]
+use std::error::Error;
+use web_time::Instant;
+
 use super::event::*;
 use super::event_stream::*;
 use super::filter_trait::*;
+use super::port_registry::PortRegistry;
 
 /// Collections of filters that are run either in sequence or in parallel.
 ///
 /// See the [Chain!] and [Fork!] macros.
+///
+/// Inverting (see [Not!]) follows De Morgan's laws, so that it works like a logical _not_
+/// on the filters:
+/// - a chain passes events that pass all filters, its inverse passes events that are
+///   rejected by _any_ of the filters: `not(A and B) = not(A) or not(B)`. So the inverted
+///   filters are run as a fork.
+/// - a fork passes events that pass any filter, its inverse passes events that are
+///   rejected by _all_ of the filters: `not(A or B) = not(A) and not(B)`. So the inverted
+///   filters are run as a chain.
+///
+/// This only makes sense for filters; modifiers and generators are not inverted.
 pub struct FilterChain<'a> {
     // lifetime: https://www.reddit.com/r/rust/comments/30ehed/why_must_this_reference_have_a_static_lifetime/
     filters: Vec<Box<dyn FilterTrait + 'a>>,
     connection: ConnectionType,
+    dedup: bool,
 }
 
 impl<'a> FilterChain<'a> {
     pub fn new(connection: ConnectionType, filters: Vec<Box<dyn FilterTrait + 'a>>) -> Self {
-        FilterChain { filters, connection, }
+        FilterChain { filters, connection, dedup: true }
+    }
+
+    /// Makes a fork output what its filters give for each event in turn, see [Fork!].
+    ///
+    /// A chain is left as it is.
+    pub fn ordered(mut self) -> Self {
+        if self.connection == ConnectionType::Fork {
+            self.connection = ConnectionType::ForkOrdered;
+        }
+        self
+    }
+
+    /// Makes a fork output identical events from its filters more than once, see [Fork!].
+    pub fn keep_duplicates(mut self) -> Self {
+        self.dedup = false;
+        self
     }
 
     fn run_chain(&self, evs: &mut EventStream, method: &dyn Fn(&Box<dyn FilterTrait + 'a>, &mut EventStream)) {
@@ -25,21 +57,55 @@ impl<'a> FilterChain<'a> {
         }
     }
 
-    fn run_fork(&self, evs: &mut EventStream, method: &dyn Fn(&Box<dyn FilterTrait + 'a>, &mut EventStream)) {
+    fn run_fork(&self, evs: &mut EventStream, method: &dyn Fn(&Box<dyn FilterTrait + 'a>, &mut EventStream), quick_reject: bool) {
         // Run each filter over the original evs and gather all events
         // into a single EventStream.
-        // TODO allocate full size of events_out
-        // TODO don't clone for first/last filter (can do when running last) ...
-        // TODO ... or repeat evs filters.size times, and run on each slice.
+        let Some((last, others)) = self.filters.split_last() else {
+            evs.clear();
+            return;
+        };
         let mut events_out = Vec::<Event>::new();
-        for f in self.filters.iter() {
-            let mut evs_this = evs.clone();
-            method(&f, &mut evs_this);
-            events_out.extend(evs_this);
+        // Reused for each filter, so that its allocation is kept.
+        let mut evs_this = EventStream::empty();
+        for f in others.iter() {
+            // Only copy the events the filter may output something for. This doesn't
+            // hold for inverted filters, so that passes quick_reject false.
+            evs_this.clear();
+            if quick_reject {
+                evs_this.extend(evs.iter().filter(|ev| !f.quick_reject(ev)).cloned());
+                if evs_this.is_empty() { continue; }
+            } else {
+                evs_this.extend(evs.iter().cloned());
+            }
+            method(f, &mut evs_this);
+            events_out.extend(evs_this.drain(..));
+        }
+        // The original events aren't needed after this, so the last filter gets them
+        // without copying.
+        if quick_reject {
+            evs.retain(|ev| !last.quick_reject(ev));
+        }
+        if !(quick_reject && evs.is_empty()) {
+            method(last, evs);
+        }
+        evs.splice(0..0, EventStream::from(events_out));
+        if self.dedup { evs.dedup(); }
+    }
+
+    fn run_fork_ordered(&self, evs: &mut EventStream, method: &dyn Fn(&Box<dyn FilterTrait + 'a>, &mut EventStream)) {
+        // Run each filter over each event, keeping the output for an event together.
+        let mut events_out = Vec::<Event>::new();
+        for ev in evs.iter() {
+            for f in self.filters.iter() {
+                if f.quick_reject(ev) { continue; }
+                let mut evs_this = EventStream::from(ev);
+                method(f, &mut evs_this);
+                events_out.extend(evs_this);
+            }
         }
         evs.clear();
         evs.extend(events_out);
-        evs.dedup();
+        if self.dedup { evs.dedup(); }
     }
 }
 
@@ -54,14 +120,15 @@ impl<'a> FilterTrait for FilterChain<'a> {
     fn run(&self, evs: &mut EventStream) {
         match self.connection {
             ConnectionType::Chain => self.run_chain(evs, &run_single),
-            ConnectionType::Fork => self.run_fork(evs, &run_single),
+            ConnectionType::Fork => self.run_fork(evs, &run_single, true),
+            ConnectionType::ForkOrdered => self.run_fork_ordered(evs, &run_single),
         }
     }
 
     fn run_inverse(&self, evs: &mut EventStream) {
         match self.connection {
-            ConnectionType::Chain => self.run_fork(evs, &run_inverse_single),
-            ConnectionType::Fork => self.run_chain(evs, &run_inverse_single),
+            ConnectionType::Chain => self.run_fork(evs, &run_inverse_single, false),
+            ConnectionType::Fork | ConnectionType::ForkOrdered => self.run_chain(evs, &run_inverse_single),
         }
     }
 
@@ -76,12 +143,36 @@ impl<'a> FilterTrait for FilterChain<'a> {
             f.run_exit(evs);
         }
     }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        for f in self.filters.iter() {
+            f.run_tick(evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        self.filters.iter().filter_map(|f| f.next_tick()).min()
+    }
+
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.filters.iter().try_for_each(|f| f.bind(ports))
+    }
+
+    fn quick_reject(&self, ev: &Event) -> bool {
+        match self.connection {
+            // What the first filter rejects doesn't reach the others.
+            ConnectionType::Chain => self.filters.first().is_some_and(|f| f.quick_reject(ev)),
+            ConnectionType::Fork | ConnectionType::ForkOrdered => self.filters.iter().all(|f| f.quick_reject(ev)),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ConnectionType {
     Chain,
     Fork,
+    /// A fork that outputs the events for each incoming event together, see [FilterChain::ordered].
+    ForkOrdered,
 }
 
 // Connecting filters
@@ -111,7 +202,37 @@ pub enum ConnectionType {
 /// # }
 /// ```
 ///
-/// TODO test inverse
+/// Inverting a chain passes events that don't pass all of its filters, i.e. that are rejected
+/// by any of them (see [FilterChain]).
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let chain = Not!(Chain!(ChannelFilter(1), KeyFilter(60)));
+///
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = NoteOnEvent(0,0,61,20);
+/// let ev3 = NoteOnEvent(0,1,60,20);
+/// let ev4 = NoteOnEvent(0,1,61,20);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// chain.run(&mut evs);
+/// assert_eq!(evs, vec![ev1.clone(), ev2.clone(), ev4.clone()]);
+///
+/// // This is the same as a fork of inverted filters.
+/// let fork = Fork!(Not!(ChannelFilter(1)), Not!(KeyFilter(60)));
+/// let mut evs_fork = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// fork.run(&mut evs_fork);
+/// assert_eq!(evs, evs_fork);
+///
+/// // And inverting twice gives the chain again.
+/// let chain = Not!(Not!(Chain!(ChannelFilter(1), KeyFilter(60))));
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// chain.run(&mut evs);
+/// assert_eq!(evs, ev3);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! Chain {
     ( $($f:expr),+ ) => (
@@ -127,6 +248,14 @@ macro_rules! Chain {
 /// Each event is passed to each of the filters, they are run in parallel.
 /// At the end of the filter chain, duplicate events are filtered out.
 ///
+/// The output of the first filter for all events comes first, then that of the second filter,
+/// and so on. Duplicates are removed over all of it, keeping the first. So events from
+/// different filters don't keep the order of the incoming events, and identical events lose
+/// that they were there more than once. When that matters, use [FilterChain::ordered] to get
+/// what all filters output for an event before the output for the next event, and
+/// [FilterChain::keep_duplicates] to keep identical events. To get the events in an order that
+/// doesn't depend on the filters, follow the fork by [Sort](super::Sort).
+///
 /// # Examples
 ///
 /// ```
@@ -147,7 +276,87 @@ macro_rules! Chain {
 /// # }
 /// ```
 ///
-/// TODO test inverse
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = NoteOnEvent(0,0,64,20);
+///
+/// let fork = Fork!(Pass(), Transpose(12));
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// fork.run(&mut evs);
+/// assert_eq!(evs, vec![ev1.clone(), ev2.clone(), NoteOnEvent(0,0,72,20), NoteOnEvent(0,0,76,20)]);
+///
+/// let fork = Fork!(Pass(), Transpose(12)).ordered();
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// fork.run(&mut evs);
+/// assert_eq!(evs, vec![ev1.clone(), NoteOnEvent(0,0,72,20), ev2.clone(), NoteOnEvent(0,0,76,20)]);
+///
+/// // Layering the same note on two ports, which happen to be the same here.
+/// let fork = Fork!(Port(1), Port(1)).keep_duplicates();
+/// let mut evs = EventStream::from(&ev1);
+/// fork.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(1,0,60,20), NoteOnEvent(1,0,60,20)]);
+/// # }
+/// ```
+///
+/// With `ordered`, filters are run on one event at a time. Filters that look at all events
+/// together, like [Tail](super::Tail), see a single event then.
+///
+/// Branches only get the events their first filter may pass, which saves copying events
+/// that would be discarded anyway. When a branch starts with a modifier, it gets all events.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let fork = Fork!(
+///     Chain!(ChannelFilter(0), Port(1)),
+///     Chain!(Channel(2), KeyFilter(60)),
+///     Chain!(Fork!(KeyFilter(61), KeyFilter(62)), Transpose(12))
+/// );
+///
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = NoteOnEvent(0,1,60,20);
+/// let ev3 = NoteOnEvent(0,1,61,20);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// fork.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(1,0,60,20), NoteOnEvent(0,2,60,20), NoteOnEvent(0,1,73,20)]);
+///
+/// // Without events for any branch, the result is empty.
+/// let mut evs = EventStream::from(NoteOnEvent(0,3,50,20));
+/// Fork!(ChannelFilter(0), Chain!(KeyFilter(60), Port(1))).run(&mut evs);
+/// assert!(evs.is_empty());
+/// # }
+/// ```
+///
+/// Inverting a fork passes events that pass none of its filters, i.e. that are rejected by
+/// all of them (see [FilterChain]).
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let fork = Not!(Fork!(ChannelFilter(1), KeyFilter(60)));
+///
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = NoteOnEvent(0,0,61,20);
+/// let ev3 = NoteOnEvent(0,1,60,20);
+/// let ev4 = NoteOnEvent(0,1,61,20);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// fork.run(&mut evs);
+/// assert_eq!(evs, ev2.clone());
+///
+/// // This is the same as a chain of inverted filters.
+/// let chain = Chain!(Not!(ChannelFilter(1)), Not!(KeyFilter(60)));
+/// let mut evs_chain = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// chain.run(&mut evs_chain);
+/// assert_eq!(evs, evs_chain);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! Fork {
     ( $($f:expr),+ ) => (
@@ -176,6 +385,86 @@ macro_rules! define_filter {
             fn run_inverse(&self, evs: &mut EventStream) {
                 evs.retain(|ev| !self.filter_single(&ev));
             }
+
+            fn quick_reject(&self, ev: &Event) -> bool {
+                !self.filter_single(ev)
+            }
+        }
+    }
+}
+
+/// Like define_filter!, but on a list of values, which are in `self.values`.
+///
+/// The filter is created with anything that converts into a `Vec`, like an array or a `Vec`.
+#[macro_export]
+macro_rules! define_list_filter {
+    ($(#[$meta:meta])* $name:ident ( $t:ty ) $item:item) => {
+        $(#[$meta])*
+        pub struct $name {
+            pub values: Vec<$t>,
+        }
+
+        pub fn $name<V: Into<Vec<$t>>>(values: V) -> $name {
+            $name { values: values.into() }
+        }
+
+        impl $name {
+            $item
+        }
+
+        impl FilterTrait for $name {
+            fn run(&self, evs: &mut EventStream) {
+                evs.retain(|ev| self.filter_single(&ev));
+            }
+
+            fn run_inverse(&self, evs: &mut EventStream) {
+                evs.retain(|ev| !self.filter_single(&ev));
+            }
+
+            fn quick_reject(&self, ev: &Event) -> bool {
+                !self.filter_single(ev)
+            }
+        }
+    }
+}
+
+/// Like define_filter!, but on a range of values, which is in `self.range`.
+///
+/// The filter is created with any range, like `60..=72` or `60..`, or with `between` for
+/// an inclusive range.
+#[macro_export]
+macro_rules! define_range_filter {
+    ($(#[$meta:meta])* $name:ident ( $t:ty ) $item:item) => {
+        $(#[$meta])*
+        pub struct $name {
+            pub range: (std::ops::Bound<$t>, std::ops::Bound<$t>),
+        }
+
+        pub fn $name<R: std::ops::RangeBounds<$t>>(range: R) -> $name {
+            $name { range: (std::ops::RangeBounds::start_bound(&range).cloned(), std::ops::RangeBounds::end_bound(&range).cloned()) }
+        }
+
+        impl $name {
+            /// Filter on values from _low_ up to and including _high_.
+            pub const fn between(low: $t, high: $t) -> $name {
+                $name { range: (std::ops::Bound::Included(low), std::ops::Bound::Included(high)) }
+            }
+
+            $item
+        }
+
+        impl FilterTrait for $name {
+            fn run(&self, evs: &mut EventStream) {
+                evs.retain(|ev| self.filter_single(&ev));
+            }
+
+            fn run_inverse(&self, evs: &mut EventStream) {
+                evs.retain(|ev| !self.filter_single(&ev));
+            }
+
+            fn quick_reject(&self, ev: &Event) -> bool {
+                !self.filter_single(ev)
+            }
         }
     }
 }
@@ -214,8 +503,10 @@ macro_rules! define_generator {
             fn run(&self, evs: &mut EventStream) {
                 if evs.is_empty() { return; }
 
-                // Generate new event
-                let new_ev = self.generate_single();
+                // Generate new event, on the first port and channel unless the incoming event has them.
+                let mut new_ev = self.generate_single();
+                new_ev.set_port($crate::proc::first_port());
+                new_ev.set_channel($crate::proc::first_channel());
                 // Replace each event, keeping port and channel.
                 for ev in evs.iter_mut() {
                     let mut this_new_ev = new_ev.clone();
@@ -231,4 +522,66 @@ macro_rules! define_generator {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Like define_generator!, but generates multiple events in order using `generate_multi`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// define_multi_generator!(
+///     /// Select a bank using MSB (CC#0) and LSB (CC#32).
+///     BankSelect(i32, i32)
+///     fn generate_multi(&self) -> Vec<Event<'static>> {
+///         vec![CtrlEvent(0, 0, 0, self.0), CtrlEvent(0, 0, 32, self.1)]
+///     }
+/// );
+///
+/// # fn main() {
+/// let mut evs = EventStream::from(NoteOnEvent(1,2,60,20));
+/// BankSelect(3, 5).run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(1,2,0,3), CtrlEvent(1,2,32,5)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! define_multi_generator {
+    ($(#[$meta:meta])* $name:ident ( $($args:ty),* ) $item:item) => {
+        $(#[$meta])*
+        pub struct $name($(pub $args),*);
+
+        impl $name {
+            $item
+        }
+
+        impl FilterTrait for $name {
+            fn run(&self, evs: &mut EventStream) {
+                if evs.is_empty() { return; }
+
+                // Generate new events, on the first port and channel unless the incoming event has them.
+                let mut new_evs = self.generate_multi();
+                for new_ev in new_evs.iter_mut() {
+                    new_ev.set_port($crate::proc::first_port());
+                    new_ev.set_channel($crate::proc::first_channel());
+                }
+                // Replace each event by all new events, keeping port and channel.
+                let mut result = EventStream::empty();
+                for ev in evs.iter() {
+                    for new_ev in new_evs.iter() {
+                        let mut this_new_ev = new_ev.clone();
+                        if let Some(port) = ev.port() {
+                            this_new_ev.set_port(port);
+                        }
+                        if let Some(channel) = ev.channel() {
+                            this_new_ev.set_channel(channel);
+                        }
+                        result.push(this_new_ev);
+                    }
+                }
+                evs.replace(result);
+                evs.dedup();
+            }
+        }
+    }
+}
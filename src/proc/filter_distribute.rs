@@ -0,0 +1,201 @@
+#![macro_use]
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Mutex;
+use web_time::Instant;
+
+use super::event::*;
+use super::event_stream::*;
+use super::filter_trait::*;
+use super::port_registry::PortRegistry;
+use super::rng::Rng;
+
+/// Collection of filters, where each note is sent to one of them.
+///
+/// NoteOff events, and NoteOn events with velocity 0, go to the same filter as their NoteOn,
+/// other events go to all filters. See the [RoundRobin!] and [Random!] macros.
+pub struct FilterDistribute<'a> {
+    filters: Vec<Box<dyn FilterTrait + 'a>>,
+    distribution: DistributionType,
+    // Round robin position.
+    counter: Mutex<u64>,
+    rng: Mutex<Rng>,
+    // Filter index for each held note: port, channel, note.
+    held: Mutex<HashMap<(usize, u8, u8), usize>>,
+}
+
+impl<'a> FilterDistribute<'a> {
+    pub fn new(distribution: DistributionType, filters: Vec<Box<dyn FilterTrait + 'a>>) -> Self {
+        FilterDistribute {
+            filters,
+            distribution,
+            counter: Mutex::new(0),
+            rng: Mutex::new(Rng::from_time()),
+            held: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn next_index(&self) -> usize {
+        let value = match self.distribution {
+            DistributionType::RoundRobin => {
+                let Ok(mut counter) = self.counter.lock() else { return 0 };
+                let value = *counter;
+                *counter = counter.wrapping_add(1);
+                value
+            },
+            DistributionType::Random => {
+                let Ok(mut rng) = self.rng.lock() else { return 0 };
+                rng.next_random()
+            },
+        };
+        (value % self.filters.len() as u64) as usize
+    }
+}
+
+impl<'a> FilterTrait for FilterDistribute<'a> {
+    fn run(&self, evs: &mut EventStream) {
+        if self.filters.is_empty() { return; }
+
+        let mut events_out = EventStream::empty();
+        for ev in evs.iter() {
+            let index = match ev {
+                // A NoteOn with velocity 0 ends a note, like a NoteOff.
+                Event::NoteOn(ev) if ev.velocity == 0 => self.held.lock().ok().and_then(|mut held| held.remove(&(ev.port, ev.channel, ev.note))),
+                Event::NoteOn(ev) => {
+                    let i = self.next_index();
+                    if let Ok(mut held) = self.held.lock() {
+                        held.insert((ev.port, ev.channel, ev.note), i);
+                    }
+                    Some(i)
+                },
+                Event::NoteOff(ev) => self.held.lock().ok().and_then(|mut held| held.remove(&(ev.port, ev.channel, ev.note))),
+                _ => None,
+            };
+
+            match index {
+                Some(i) => {
+                    let mut evs_this = EventStream::from(ev);
+                    self.filters[i].run(&mut evs_this);
+                    events_out.extend(evs_this);
+                },
+                None => {
+                    for f in self.filters.iter() {
+                        let mut evs_this = EventStream::from(ev);
+                        f.run(&mut evs_this);
+                        events_out.extend(evs_this);
+                    }
+                },
+            }
+        }
+        evs.replace(events_out);
+        evs.dedup();
+    }
+
+    fn run_init(&self, evs: &mut EventStream) {
+        for f in self.filters.iter() {
+            f.run_init(evs);
+        }
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        // Release held notes, so they don't hang.
+        if let Ok(mut held) = self.held.lock() {
+            for ((port, channel, note), i) in held.drain() {
+                let mut evs_this = EventStream::from(NoteOffEvent(port, channel, note));
+                self.filters[i].run(&mut evs_this);
+                evs.extend(evs_this);
+            }
+        }
+        for f in self.filters.iter() {
+            f.run_exit(evs);
+        }
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        for f in self.filters.iter() {
+            f.run_tick(evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        self.filters.iter().filter_map(|f| f.next_tick()).min()
+    }
+
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.filters.iter().try_for_each(|f| f.bind(ports))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DistributionType {
+    RoundRobin,
+    Random,
+}
+
+/// Sends each note to the next filter in turn.
+///
+/// The NoteOff, or NoteOn with velocity 0, goes to the same filter as its NoteOn, other
+/// events go to all filters.
+/// When exiting, held notes are released.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = RoundRobin!(Port(0), Port(1), Port(2));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(5,0,60,20), NoteOnEvent(5,0,62,20), NoteOnEvent(5,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(1,0,62,20), NoteOnEvent(2,0,64,20)]);
+///
+/// let mut evs = EventStream::from(vec![NoteOffEvent(5,0,60), NoteOffEvent(5,0,62), NoteOffEvent(5,0,64)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOffEvent(0,0,60), NoteOffEvent(1,0,62), NoteOffEvent(2,0,64)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! RoundRobin {
+    ( $($f:expr),+ ) => (
+        FilterDistribute::new(
+            DistributionType::RoundRobin,
+            vec!( $(Box::new($f)),+ )
+        )
+    )
+}
+
+/// Sends each note to a random filter.
+///
+/// The NoteOff, or NoteOn with velocity 0, goes to the same filter as its NoteOn, other
+/// events go to all filters.
+/// When exiting, held notes are released.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Random!(Port(0), Port(1), Port(2));
+///
+/// for note in 60..70 {
+///     let mut evs = EventStream::from(NoteOnEvent(5,0,note,20));
+///     filter.run(&mut evs);
+///     let port = evs.iter().next().unwrap().port();
+///
+///     let mut evs = EventStream::from(NoteOffEvent(5,0,note));
+///     filter.run(&mut evs);
+///     assert_eq!(evs.iter().next().unwrap().port(), port);
+/// }
+/// # }
+/// ```
+#[macro_export]
+macro_rules! Random {
+    ( $($f:expr),+ ) => (
+        FilterDistribute::new(
+            DistributionType::Random,
+            vec!( $(Box::new($f)),+ )
+        )
+    )
+}
@@ -1,4 +1,9 @@
+use std::error::Error;
+use web_time::Instant;
+
+use super::event::Event;
 use super::event_stream::EventStream;
+use super::port_registry::PortRegistry;
 
 // All filters implement this trait.
 pub trait FilterTrait {
@@ -15,4 +20,22 @@ pub trait FilterTrait {
     fn run_init(&self, _evs: &mut EventStream) {}
     // Only used for Exit filter
     fn run_exit(&self, _evs: &mut EventStream) {}
+
+    // Only used for filters that act on time passing, run each time the runner wakes up.
+    // Like init and exit, resulting events are output directly.
+    fn run_tick(&self, _evs: &mut EventStream) {}
+    // When run_tick needs to be called next, the runner wakes up at that time.
+    fn next_tick(&self) -> Option<Instant> { None }
+
+    // Whether run is sure to output nothing for this event, so a Fork can skip it.
+    // Returning false is always safe, define_filter! implements it using filter_single.
+    fn quick_reject(&self, _ev: &Event) -> bool { false }
+
+    // Resolves ports referred to by name, run by the runner when it is started.
+    // Filters containing other filters need to pass this on.
+    fn bind(&self, _ports: &PortRegistry) -> Result<(), Box<dyn Error>> { Ok(()) }
+
+    // Only used for Discard, so the runner can tell whether a patch was given.
+    #[doc(hidden)]
+    fn is_discard(&self) -> bool { false }
 }
@@ -0,0 +1,427 @@
+//! Patches loaded from a dynamic library, reloaded when the library changes.
+//!
+//! Rust has no stable ABI, so the patch library and the host communicate through a small
+//! C-compatible shim: the library exports a `create_patch` function returning a [PatchVTable],
+//! and events are exchanged as [RawEvent]s through the accessor functions in [HostApi].
+//! Both sides use the definitions from this module, the [export_patch!] macro takes care of
+//! the library side.
+//!
+//! Only `None`, `NoteOn`, `NoteOff`, `Ctrl`, scene switch and `Quit` events are passed to the
+//! library. Other events bypass the library patch and keep their position in the stream, the
+//! events before and after them are run through the library patch separately.
+extern crate libloading;
+
+use std::error::Error;
+use std::ffi::c_void;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use web_time::Instant;
+
+use super::event::*;
+use super::event_stream::*;
+use super::filter_trait::*;
+
+/// Version of the shim, patch libraries built against another version are refused.
+pub const ABI_VERSION: u32 = 1;
+
+/// How often [DylibPatch] checks whether the library changed.
+pub const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Name of the constructor function a patch library exports.
+pub const CREATE_PATCH_SYMBOL: &[u8] = b"create_patch";
+
+pub const RAW_NONE: u32 = 0;
+pub const RAW_NOTE_ON: u32 = 1;
+pub const RAW_NOTE_OFF: u32 = 2;
+pub const RAW_CTRL: u32 = 3;
+pub const RAW_SCENE_SWITCH: u32 = 4;
+pub const RAW_SCENE_SWITCH_OFFSET: u32 = 5;
+pub const RAW_SUBSCENE_SWITCH: u32 = 6;
+pub const RAW_SUBSCENE_SWITCH_OFFSET: u32 = 7;
+pub const RAW_QUIT: u32 = 8;
+
+/// C-compatible representation of an event.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RawEvent {
+    pub kind: u32,
+    pub port: usize,
+    pub channel: u8,
+    pub data1: i32,
+    pub data2: i32,
+}
+
+impl RawEvent {
+    /// Converts an event, returns `None` when it cannot be represented.
+    pub fn from_event(ev: &Event) -> Option<Self> {
+        let raw = |kind, port, channel, data1, data2| Some(RawEvent { kind, port, channel, data1, data2 });
+        match ev {
+            Event::None(_) => raw(RAW_NONE, 0, 0, 0, 0),
+            Event::NoteOn(ev) => raw(RAW_NOTE_ON, ev.port, ev.channel, ev.note as i32, ev.velocity as i32),
+            Event::NoteOff(ev) => raw(RAW_NOTE_OFF, ev.port, ev.channel, ev.note as i32, ev.velocity as i32),
+            Event::Ctrl(ev) => raw(RAW_CTRL, ev.port, ev.channel, ev.ctrl as i32, ev.value),
+            Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Fixed(f), subscene: None }) =>
+                raw(RAW_SCENE_SWITCH, 0, 0, *f as i32, 0),
+            Event::SceneSwitch(SceneSwitchEventImpl { scene: SceneSwitchValue::Offset(o), subscene: None }) =>
+                raw(RAW_SCENE_SWITCH_OFFSET, 0, 0, *o as i32, 0),
+            Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene: SceneSwitchValue::Fixed(f) }) =>
+                raw(RAW_SUBSCENE_SWITCH, 0, 0, *f as i32, 0),
+            Event::SubSceneSwitch(SubSceneSwitchEventImpl { subscene: SceneSwitchValue::Offset(o) }) =>
+                raw(RAW_SUBSCENE_SWITCH_OFFSET, 0, 0, *o as i32, 0),
+            Event::Quit(_) => raw(RAW_QUIT, 0, 0, 0, 0),
+            _ => None,
+        }
+    }
+
+    /// Converts back to an event, returns `None` for an unknown kind.
+    pub fn to_event(&self) -> Option<Event<'static>> {
+        match self.kind {
+            RAW_NONE => Some(NoneEvent()),
+            RAW_NOTE_ON => Some(NoteOnEvent(self.port, self.channel, self.data1 as u8, self.data2 as u8)),
+            RAW_NOTE_OFF => Some(NoteOffEventWithVelocity(self.port, self.channel, self.data1 as u8, self.data2 as u8)),
+            RAW_CTRL => Some(CtrlEvent(self.port, self.channel, self.data1 as u32, self.data2)),
+            RAW_SCENE_SWITCH => Some(SceneSwitchEvent(self.data1 as SceneNum)),
+            RAW_SCENE_SWITCH_OFFSET => Some(SceneSwitchOffsetEvent(self.data1 as SceneOffset)),
+            RAW_SUBSCENE_SWITCH => Some(SubSceneSwitchEvent(self.data1 as SceneNum)),
+            RAW_SUBSCENE_SWITCH_OFFSET => Some(SubSceneSwitchOffsetEvent(self.data1 as SceneOffset)),
+            RAW_QUIT => Some(QuitEvent()),
+            _ => None,
+        }
+    }
+}
+
+/// Opaque handle to a list of [RawEvent]s owned by the host.
+#[repr(C)]
+pub struct RawEventStream {
+    _private: [u8; 0],
+}
+
+/// Functions provided by the host to access a [RawEventStream].
+#[repr(C)]
+pub struct HostApi {
+    pub len: extern "C" fn(evs: *const RawEventStream) -> usize,
+    pub get: extern "C" fn(evs: *const RawEventStream, index: usize, ev: *mut RawEvent) -> bool,
+    pub clear: extern "C" fn(evs: *mut RawEventStream),
+    pub push: extern "C" fn(evs: *mut RawEventStream, ev: *const RawEvent),
+}
+
+type RunFn = extern "C" fn(patch: *mut c_void, evs: *mut RawEventStream, api: *const HostApi);
+
+/// Function table of a patch, as returned by the library's `create_patch` function.
+#[repr(C)]
+pub struct PatchVTable {
+    pub abi_version: u32,
+    pub patch: *mut c_void,
+    pub run: RunFn,
+    pub run_init: RunFn,
+    pub run_exit: RunFn,
+    pub drop: extern "C" fn(patch: *mut c_void),
+}
+
+impl PatchVTable {
+    /// Wraps a patch, to be returned from a library's `create_patch` function.
+    ///
+    /// Use the [export_patch!] macro instead of calling this directly.
+    pub fn new(patch: Box<dyn FilterTrait>) -> Self {
+        PatchVTable {
+            abi_version: ABI_VERSION,
+            patch: Box::into_raw(Box::new(patch)) as *mut c_void,
+            run: library_run,
+            run_init: library_run_init,
+            run_exit: library_run_exit,
+            drop: library_drop,
+        }
+    }
+}
+
+fn library_run_with(patch: *mut c_void, evs: *mut RawEventStream, api: *const HostApi, method: &dyn Fn(&dyn FilterTrait, &mut EventStream)) {
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        let patch = unsafe { &*(patch as *const Box<dyn FilterTrait>) };
+        let api = unsafe { &*api };
+
+        let mut proc_evs = EventStream::empty();
+        let mut raw_ev = RawEvent::default();
+        for i in 0..(api.len)(evs) {
+            if !(api.get)(evs, i, &mut raw_ev) { continue; }
+            if let Some(ev) = raw_ev.to_event() {
+                proc_evs.push(ev);
+            }
+        }
+
+        method(patch.as_ref(), &mut proc_evs);
+
+        (api.clear)(evs);
+        for raw_ev in proc_evs.iter().filter_map(RawEvent::from_event) {
+            (api.push)(evs, &raw_ev);
+        }
+    }));
+}
+
+extern "C" fn library_run(patch: *mut c_void, evs: *mut RawEventStream, api: *const HostApi) {
+    library_run_with(patch, evs, api, &|f, evs| f.run(evs));
+}
+
+extern "C" fn library_run_init(patch: *mut c_void, evs: *mut RawEventStream, api: *const HostApi) {
+    library_run_with(patch, evs, api, &|f, evs| f.run_init(evs));
+}
+
+extern "C" fn library_run_exit(patch: *mut c_void, evs: *mut RawEventStream, api: *const HostApi) {
+    library_run_with(patch, evs, api, &|f, evs| f.run_exit(evs));
+}
+
+extern "C" fn library_drop(patch: *mut c_void) {
+    let _ = catch_unwind(|| {
+        drop(unsafe { Box::from_raw(patch as *mut Box<dyn FilterTrait>) });
+    });
+}
+
+/// Exports a patch from a `cdylib` crate, for use with [DylibPatch].
+///
+/// The argument is an expression evaluating to the patch. It is evaluated each time the
+/// library is loaded.
+///
+/// ```ignore
+/// #[macro_use]
+/// extern crate rmididings;
+/// use rmididings::*;
+///
+/// export_patch!(Chain!(ChannelFilter(1), Transpose(12)));
+/// ```
+#[macro_export]
+macro_rules! export_patch {
+    ( $f:expr ) => {
+        #[no_mangle]
+        pub extern "C" fn create_patch() -> $crate::proc::hotreload::PatchVTable {
+            $crate::proc::hotreload::PatchVTable::new(Box::new($f))
+        }
+    };
+}
+
+extern "C" fn host_len(evs: *const RawEventStream) -> usize {
+    unsafe { &*(evs as *const Vec<RawEvent>) }.len()
+}
+
+extern "C" fn host_get(evs: *const RawEventStream, index: usize, ev: *mut RawEvent) -> bool {
+    if let Some(raw_ev) = unsafe { &*(evs as *const Vec<RawEvent>) }.get(index) {
+        unsafe { *ev = *raw_ev; }
+        true
+    } else {
+        false
+    }
+}
+
+extern "C" fn host_clear(evs: *mut RawEventStream) {
+    unsafe { &mut *(evs as *mut Vec<RawEvent>) }.clear();
+}
+
+extern "C" fn host_push(evs: *mut RawEventStream, ev: *const RawEvent) {
+    unsafe { &mut *(evs as *mut Vec<RawEvent>) }.push(unsafe { *ev });
+}
+
+static HOST_API: HostApi = HostApi { len: host_len, get: host_get, clear: host_clear, push: host_push };
+
+/// Counter to give each loaded copy of a library a unique filename.
+static LOAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A patch from a library that is currently loaded.
+struct LoadedPatch {
+    vtable: PatchVTable,
+    copy_path: PathBuf,
+    // Needs to be dropped last, as the vtable points into the library.
+    _library: libloading::Library,
+}
+
+impl LoadedPatch {
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        // Load a copy, so that the library can be replaced while loaded, and the
+        // dynamic loader doesn't return the previously loaded library for the same path.
+        let file_name = path.file_name().ok_or("Patch library path has no file name")?;
+        let mut copy_path = std::env::temp_dir();
+        copy_path.push(format!(
+            "rmididings-{}-{}-{}",
+            std::process::id(),
+            LOAD_COUNT.fetch_add(1, Ordering::Relaxed),
+            file_name.to_string_lossy()
+        ));
+        fs::copy(path, &copy_path)?;
+
+        let library = match unsafe { libloading::Library::new(&copy_path) } {
+            Ok(library) => library,
+            Err(e) => {
+                let _ = fs::remove_file(&copy_path);
+                return Err(Box::new(e));
+            }
+        };
+        let vtable = unsafe {
+            let create_patch = library.get::<extern "C" fn() -> PatchVTable>(CREATE_PATCH_SYMBOL)?;
+            create_patch()
+        };
+        if vtable.abi_version != ABI_VERSION {
+            (vtable.drop)(vtable.patch);
+            let _ = fs::remove_file(&copy_path);
+            return Err(format!("Patch library {} has ABI version {}, expected {}", path.display(), vtable.abi_version, ABI_VERSION).into());
+        }
+
+        Ok(Self { vtable, copy_path, _library: library })
+    }
+
+    fn run_with(&self, evs: &mut EventStream, method: RunFn) {
+        let raw_evs: Option<Vec<RawEvent>> = evs.iter().map(RawEvent::from_event).collect();
+        if let Some(mut raw_evs) = raw_evs {
+            // All events can be passed to the library at once, the common case.
+            self.run_raw(&mut raw_evs, method);
+            evs.clear();
+            push_raw_events(evs, &raw_evs);
+            return;
+        }
+
+        // Run the events between those the library can't represent separately,
+        // so that the latter keep their position.
+        let mut out_evs = EventStream::empty();
+        let mut raw_evs: Vec<RawEvent> = vec![];
+        for ev in evs.iter() {
+            if let Some(raw_ev) = RawEvent::from_event(ev) {
+                raw_evs.push(raw_ev);
+                continue;
+            }
+            if !raw_evs.is_empty() {
+                self.run_raw(&mut raw_evs, method);
+                push_raw_events(&mut out_evs, &raw_evs);
+                raw_evs.clear();
+            }
+            out_evs.push(ev.clone());
+        }
+        if !raw_evs.is_empty() {
+            self.run_raw(&mut raw_evs, method);
+            push_raw_events(&mut out_evs, &raw_evs);
+        }
+        evs.replace(out_evs);
+    }
+
+    fn run_raw(&self, raw_evs: &mut Vec<RawEvent>, method: RunFn) {
+        method(self.vtable.patch, raw_evs as *mut Vec<RawEvent> as *mut RawEventStream, &HOST_API);
+    }
+}
+
+fn push_raw_events(evs: &mut EventStream, raw_evs: &[RawEvent]) {
+    for raw_ev in raw_evs.iter() {
+        if let Some(ev) = raw_ev.to_event() {
+            evs.push(ev);
+        }
+    }
+}
+
+impl Drop for LoadedPatch {
+    fn drop(&mut self) {
+        (self.vtable.drop)(self.vtable.patch);
+        let _ = fs::remove_file(&self.copy_path);
+    }
+}
+
+/// Runs a patch from a dynamic library, and reloads it when the library file changes.
+///
+/// The library is a `cdylib` crate that exports its patch using [export_patch!].
+/// Every [RELOAD_CHECK_INTERVAL], the modification time of the library is checked, so this
+/// doesn't happen for each event. When it changed, the new library is loaded, the exit patch
+/// of the old library and the init patch of the new library are run, and their events are
+/// output. When loading fails, an error is printed and the old patch is kept.
+///
+/// See the module documentation for which events the patch receives.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rmididings::proc::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let patch = DylibPatch::new("mypatch/target/debug/libmypatch.so")?;
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// patch.run(&mut evs);
+/// # Ok(())
+/// # }
+/// ```
+pub struct DylibPatch {
+    path: PathBuf,
+    state: Mutex<DylibState>,
+}
+
+struct DylibState {
+    loaded: LoadedPatch,
+    modified: Option<SystemTime>,
+    next_check: Instant,
+}
+
+impl DylibPatch {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref().to_path_buf();
+        let modified = fs::metadata(&path)?.modified().ok();
+        let loaded = LoadedPatch::load(&path)?;
+        let next_check = Instant::now() + RELOAD_CHECK_INTERVAL;
+        Ok(Self { path, state: Mutex::new(DylibState { loaded, modified, next_check }) })
+    }
+
+    fn reload_if_changed(&self, state: &mut DylibState, evs: &mut EventStream) {
+        let modified = match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => Some(modified),
+            // The library may be replaced right now, try again next time.
+            Err(_) => return,
+        };
+        if modified == state.modified { return; }
+        state.modified = modified;
+
+        match LoadedPatch::load(&self.path) {
+            Ok(loaded) => {
+                let mut exit_evs = EventStream::none();
+                state.loaded.run_with(&mut exit_evs, state.loaded.vtable.run_exit);
+                state.loaded = loaded;
+                let mut init_evs = EventStream::none();
+                state.loaded.run_with(&mut init_evs, state.loaded.vtable.run_init);
+                info!("Reloaded patch library {}", self.path.display());
+
+                // Only keep events generated by the exit and init patches.
+                evs.extend(exit_evs.into_iter().filter(|ev| !matches!(ev, Event::None(_))));
+                evs.extend(init_evs.into_iter().filter(|ev| !matches!(ev, Event::None(_))));
+            },
+            Err(e) => {
+                warn!("Reloading patch library {} failed: {}", self.path.display(), e);
+            },
+        }
+    }
+}
+
+impl FilterTrait for DylibPatch {
+    fn run(&self, evs: &mut EventStream) {
+        if let Ok(state) = self.state.lock() {
+            state.loaded.run_with(evs, state.loaded.vtable.run);
+        }
+    }
+
+    fn run_init(&self, evs: &mut EventStream) {
+        if let Ok(state) = self.state.lock() {
+            state.loaded.run_with(evs, state.loaded.vtable.run_init);
+        }
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(state) = self.state.lock() {
+            state.loaded.run_with(evs, state.loaded.vtable.run_exit);
+        }
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            let now = Instant::now();
+            if now < state.next_check { return; }
+            state.next_check = now + RELOAD_CHECK_INTERVAL;
+            self.reload_if_changed(&mut state, evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        Some(self.state.lock().ok()?.next_check)
+    }
+}
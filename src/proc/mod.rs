@@ -1,15 +1,45 @@
 #![allow(non_snake_case)]
 #![macro_use]
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
 
+pub mod clock;
 pub mod event;
 pub mod event_stream;
 pub mod filter_chain;
+pub mod filter_distribute;
 pub mod filter_trait;
+pub mod notes;
+pub mod output_ports;
+pub mod phrase;
+pub mod port_registry;
+pub mod program_sync;
+mod rng;
+pub mod scheduler;
+pub mod state;
+pub mod sysex;
+pub mod tempo;
+pub use self::clock::*;
 pub use self::event::*;
 pub use self::event_stream::*;
 pub use self::filter_chain::*;
+pub use self::filter_distribute::*;
 pub use self::filter_trait::*;
+pub use self::notes::*;
+pub use self::output_ports::*;
+pub use self::phrase::*;
+pub use self::port_registry::*;
+pub use self::program_sync::*;
+pub use self::scheduler::*;
+pub use self::state::*;
+pub use self::sysex::*;
+pub use self::tempo::*;
+use self::rng::Rng;
 
 // Filters
 
@@ -21,7 +51,17 @@ define_filter!(
             Event::NoteOn(_) => true,
             Event::NoteOff(_) => true,
             Event::Ctrl(_) => true,
+            Event::Nrpn(_) => true,
+            Event::Program(_) => true,
+            Event::PitchBend(_) => true,
             Event::SysEx(_) => true,
+            Event::Clock(_) => true,
+            Event::Start(_) => true,
+            Event::Stop(_) => true,
+            Event::Continue(_) => true,
+            Event::SongPosition(_) => true,
+            Event::SongSelect(_) => true,
+            Event::MtcQuarterFrame(_) => true,
             _ => false,
         }
     }
@@ -58,6 +98,20 @@ define_filter!(
         if let Event::Ctrl(_) = ev { true } else { false }
     }
 );
+define_filter!(
+    #[doc(hidden)]
+    _TypeProgramFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Program(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypePitchBendFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::PitchBend(_))
+    }
+);
 define_filter!(
     #[doc(hidden)]
     _TypeSysExFilter()
@@ -65,6 +119,62 @@ define_filter!(
         if let Event::SysEx(_) = ev { true } else { false }
     }
 );
+define_filter!(
+    #[doc(hidden)]
+    _TypeClockFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Clock(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeStartFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Start(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeStopFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Stop(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeContinueFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Continue(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeSongPositionFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::SongPosition(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeSongSelectFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::SongSelect(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeMtcQuarterFrameFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::MtcQuarterFrame(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeMtcFullFrameFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::MtcFullFrame(_))
+    }
+);
 define_filter!(
     #[doc(hidden)]
     _TypeNoneFilter()
@@ -79,6 +189,20 @@ define_filter!(
         if let Event::Quit(_) = ev { true } else { false }
     }
 );
+define_filter!(
+    #[doc(hidden)]
+    _TypeResyncFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Resync(_))
+    }
+);
+define_filter!(
+    #[doc(hidden)]
+    _TypeChordFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::Chord(_))
+    }
+);
 define_filter!(
     #[doc(hidden)]
     _TypeSceneSwitchFilter()
@@ -107,6 +231,14 @@ define_filter!(
         if let Event::Osc(_) = ev { true } else { false }
     }
 );
+#[cfg(feature = "osc")]
+define_filter!(
+    #[doc(hidden)]
+    _TypeOscBundleFilter()
+    fn filter_single(&self, ev: &Event) -> bool {
+        matches!(ev, Event::OscBundle(_))
+    }
+);
 #[cfg(feature = "dbus")]
 define_filter!(
     #[doc(hidden)]
@@ -179,6 +311,20 @@ define_filter!(
 /// assert_eq!(evs, CtrlEvent(0,0,7,20));
 /// # }
 /// ```
+///
+/// Realtime events have no channel, but they do have a port.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Chain!(TypeFilter!(Clock), PortFilter(1));
+///
+/// let mut evs = EventStream::from(vec![ClockEvent(0), ClockEvent(1), StartEvent(1)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, ClockEvent(1));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! TypeFilter {
     (Midi) => { _TypeMidiFilter() };
@@ -186,10 +332,23 @@ macro_rules! TypeFilter {
     (NoteOn) => { _TypeNoteOnFilter() };
     (NoteOff) => { _TypeNoteOffFilter() };
     (Ctrl) => { _TypeCtrlFilter() };
+    (Program) => { _TypeProgramFilter() };
+    (PitchBend) => { _TypePitchBendFilter() };
     (SysEx) => { _TypeSysExFilter() };
+    (Clock) => { _TypeClockFilter() };
+    (Start) => { _TypeStartFilter() };
+    (Stop) => { _TypeStopFilter() };
+    (Continue) => { _TypeContinueFilter() };
+    (SongPosition) => { _TypeSongPositionFilter() };
+    (SongSelect) => { _TypeSongSelectFilter() };
+    (MtcQuarterFrame) => { _TypeMtcQuarterFrameFilter() };
+    (MtcFullFrame) => { _TypeMtcFullFrameFilter() };
     (Quit) => { _TypeQuitFilter() };
+    (Resync) => { _TypeResyncFilter() };
+    (Chord) => { _TypeChordFilter() };
     (SceneSwitch) => { _TypeSceneSwitchFilter() };
     (Osc) => { _TypeOscFilter() };
+    (OscBundle) => { _TypeOscBundleFilter() };
     (Dbus) => { _TypeDbusFilter() };
 }
 
@@ -246,14 +405,29 @@ define_filter!(
             Event::NoteOff(ev) => ev.port == self.0,
             Event::Ctrl(ev) => ev.port == self.0,
             Event::SysEx(ev) => ev.port == self.0,
+            Event::Nrpn(ev) => ev.port == self.0,
+            Event::Program(ev) => ev.port == self.0,
+            Event::PitchBend(ev) => ev.port == self.0,
+            Event::Clock(ev) => ev.port == self.0,
+            Event::Start(ev) => ev.port == self.0,
+            Event::Stop(ev) => ev.port == self.0,
+            Event::Continue(ev) => ev.port == self.0,
+            Event::SongPosition(ev) => ev.port == self.0,
+            Event::SongSelect(ev) => ev.port == self.0,
+            Event::MtcQuarterFrame(ev) => ev.port == self.0,
+            Event::MtcFullFrame(ev) => ev.port == self.0,
             #[cfg(feature = "osc")]
             Event::Osc(ev) => ev.port == self.0,
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ev) => ev.port == self.0,
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ev) => ev.port == self.0,
             _ => true,
         }
     }
 );
 
-define_filter!(
+define_list_filter!(
     /// Filter on multiple port numbers
     ///
     /// When calling [`RMididings.config()`] the `in_ports` and `out_ports`
@@ -275,21 +449,131 @@ define_filter!(
     /// filter.run(&mut evs);
     /// assert_eq!(evs, vec![ev2, ev3]);
     /// ```
-    PortsFilter(&'static [usize])
+    PortsFilter(usize)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::NoteOn(ev) => self.0.contains(&ev.port),
-            Event::NoteOff(ev) => self.0.contains(&ev.port),
-            Event::Ctrl(ev) => self.0.contains(&ev.port),
-            Event::SysEx(ev) => self.0.contains(&ev.port),
+            Event::NoteOn(ev) => self.values.contains(&ev.port),
+            Event::NoteOff(ev) => self.values.contains(&ev.port),
+            Event::Ctrl(ev) => self.values.contains(&ev.port),
+            Event::SysEx(ev) => self.values.contains(&ev.port),
+            Event::Nrpn(ev) => self.values.contains(&ev.port),
+            Event::Program(ev) => self.values.contains(&ev.port),
+            Event::PitchBend(ev) => self.values.contains(&ev.port),
+            Event::Clock(ev) => self.values.contains(&ev.port),
+            Event::Start(ev) => self.values.contains(&ev.port),
+            Event::Stop(ev) => self.values.contains(&ev.port),
+            Event::Continue(ev) => self.values.contains(&ev.port),
+            Event::SongPosition(ev) => self.values.contains(&ev.port),
+            Event::SongSelect(ev) => self.values.contains(&ev.port),
+            Event::MtcQuarterFrame(ev) => self.values.contains(&ev.port),
+            Event::MtcFullFrame(ev) => self.values.contains(&ev.port),
             #[cfg(feature = "osc")]
-            Event::Osc(ev) => self.0.contains(&ev.port),
+            Event::Osc(ev) => self.values.contains(&ev.port),
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ev) => self.values.contains(&ev.port),
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ev) => self.values.contains(&ev.port),
             _ => true,
         }
     }
 
 );
 
+/// Filter on multiple port numbers, given at runtime
+///
+/// Same as [PortsFilter], which takes a `Vec` as well.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let active_ports = vec![1, 2];
+/// let filter = DynPortsFilter(active_ports.clone());
+///
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = NoteOnEvent(1,0,60,20);
+/// let ev3 = NoteOnEvent(2,0,60,20);
+/// let ev4 = SysExEvent(4, &[0xf0, 0xf7]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev2, ev3]);
+/// ```
+pub fn DynPortsFilter(values: Vec<usize>) -> PortsFilter {
+    PortsFilter(values)
+}
+
+/// Filter on an input port, by its name.
+///
+/// The argument is: _name_, as given in `in_ports` to [`RMididings.config()`]. Unlike with
+/// [PortFilter], changing the order of ports doesn't change what it filters on. The port is
+/// looked up when the runner starts, which fails when there is no port with the name.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = PortNameFilter("pads");
+/// filter.bind(&PortRegistry::new(&["keys", "pads"], &[], 1)).unwrap();
+///
+/// let ev1 = NoteOnEvent(1,0,60,20);
+/// let ev2 = NoteOnEvent(2,0,60,20);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, ev2);
+/// ```
+///
+/// ```
+/// # use rmididings::*;
+/// let patch = PortNameFilter("drums");
+/// let mut md = RMididings::new().unwrap();
+/// md.config(ConfigArguments {
+///     backend: BackendType::Null,
+///     in_ports: &[["keys", ""]],
+///     ..ConfigArguments::default()
+/// }).unwrap();
+///
+/// let result = md.start(RunArguments { patch: &patch, ..RunArguments::default() });
+/// assert_eq!(result.err().unwrap().to_string(), "Unknown input port \"drums\", available are: \"keys\"");
+/// ```
+pub struct PortNameFilter {
+    name: &'static str,
+    port: AtomicUsize,
+}
+
+pub fn PortNameFilter(name: &'static str) -> PortNameFilter {
+    PortNameFilter { name, port: AtomicUsize::new(UNBOUND_PORT) }
+}
+
+/// Port of filters referring to a port by name, before the runner looked it up.
+const UNBOUND_PORT: usize = usize::MAX;
+
+impl PortNameFilter {
+    fn filter_single(&self, ev: &Event) -> bool {
+        PortFilter(self.port.load(Ordering::Relaxed)).filter_single(ev)
+    }
+}
+
+impl FilterTrait for PortNameFilter {
+    fn run(&self, evs: &mut EventStream) {
+        evs.retain(|ev| self.filter_single(ev));
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.retain(|ev| !self.filter_single(ev));
+    }
+
+    fn quick_reject(&self, ev: &Event) -> bool {
+        !self.filter_single(ev)
+    }
+
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.port.store(ports.in_port(self.name)?, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
 define_filter!(
     /// Filter on channel
     ///
@@ -312,12 +596,15 @@ define_filter!(
             Event::NoteOn(ev) => ev.channel == self.0,
             Event::NoteOff(ev) => ev.channel == self.0,
             Event::Ctrl(ev) => ev.channel == self.0,
+            Event::Nrpn(ev) => ev.channel == self.0,
+            Event::Program(ev) => ev.channel == self.0,
+            Event::PitchBend(ev) => ev.channel == self.0,
             _ => true,
         }
     }
 );
 
-define_filter!(
+define_list_filter!(
     /// Filter on multiple channels
     ///
     /// # Examples
@@ -335,17 +622,43 @@ define_filter!(
     /// filter.run(&mut evs);
     /// assert_eq!(evs, vec![ev3, ev4]);
     /// ```
-    ChannelsFilter(&'static [u8])
+    ChannelsFilter(u8)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::NoteOn(ev) => self.0.contains(&ev.channel),
-            Event::NoteOff(ev) => self.0.contains(&ev.channel),
-            Event::Ctrl(ev) => self.0.contains(&ev.channel),
+            Event::NoteOn(ev) => self.values.contains(&ev.channel),
+            Event::NoteOff(ev) => self.values.contains(&ev.channel),
+            Event::Ctrl(ev) => self.values.contains(&ev.channel),
+            Event::Nrpn(ev) => self.values.contains(&ev.channel),
+            Event::Program(ev) => self.values.contains(&ev.channel),
+            Event::PitchBend(ev) => self.values.contains(&ev.channel),
             _ => true,
         }
     }
 );
 
+/// Filter on multiple channels, given at runtime
+///
+/// Same as [ChannelsFilter], which takes a `Vec` as well.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DynChannelsFilter(vec![2,3]);
+///
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = CtrlEvent(0,1,7,20);
+/// let ev3 = NoteOnEvent(0,2,60,20);
+/// let ev4 = ProgramEvent(0,3,5);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev3, ev4]);
+/// ```
+pub fn DynChannelsFilter(values: Vec<u8>) -> ChannelsFilter {
+    ChannelsFilter(values)
+}
+
 define_filter!(
     /// Filter on key (note)
     ///
@@ -372,9 +685,11 @@ define_filter!(
     }
 );
 
-define_filter!(
+define_list_filter!(
     /// Filter on multiple keys (notes)
     ///
+    /// The keys can be given as an array, or as a `Vec` built at runtime.
+    ///
     /// # Examples
     ///
     /// ```
@@ -390,25 +705,61 @@ define_filter!(
     /// filter.run(&mut evs);
     /// assert_eq!(evs, vec![ev1, ev4]);
     /// ```
-    KeysFilter(&'static [u8])
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let keys: Vec<u8> = (60..=72).step_by(12).collect();
+    /// let filter = KeysFilter(keys);
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20), NoteOnEvent(0,0,72,20)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,72,20)]);
+    /// ```
+    KeysFilter(u8)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::NoteOn(ev) => self.0.contains(&ev.note),
-            Event::NoteOff(ev) => self.0.contains(&ev.note),
+            Event::NoteOn(ev) => self.values.contains(&ev.note),
+            Event::NoteOff(ev) => self.values.contains(&ev.note),
             _ => true,
         }
 
     }
 );
 
-define_filter!(
+/// Filter on multiple keys (notes), given at runtime
+///
+/// Same as [KeysFilter], which takes a `Vec` as well.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DynKeysFilter(vec![60, 63]);
+///
+/// let ev1 = NoteOnEvent(0,0,60,20);
+/// let ev2 = NoteOnEvent(0,0,61,20);
+/// let ev3 = NoteOffEvent(0,0,63);
+/// let ev4 = CtrlEvent(0,0,7,40);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev1, ev3, ev4]);
+/// ```
+pub fn DynKeysFilter(values: Vec<u8>) -> KeysFilter {
+    KeysFilter(values)
+}
+
+define_range_filter!(
     /// Filter on a range of keys (notes)
     ///
+    /// The range can be open-ended, and [KeyRangeFilter::between] takes both ends of an
+    /// inclusive range.
+    ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let filter = KeyRangeFilter(60, 62);
+    /// let filter = KeyRangeFilter(60..=62);
     ///
     /// let ev1 = NoteOnEvent(0,0,60,20);
     /// let ev2 = NoteOnEvent(0,0,61,20);
@@ -417,18 +768,137 @@ define_filter!(
     ///
     /// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
     /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![ev1.clone(), ev2.clone(), ev3.clone()]);
+    ///
+    /// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+    /// KeyRangeFilter::between(60, 62).run(&mut evs);
     /// assert_eq!(evs, vec![ev1, ev2, ev3]);
     /// ```
-    KeyRangeFilter(u8, u8)
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rmididings;
+    /// # use rmididings::proc::*;
+    /// # fn main() {
+    /// let upper = KeyRangeFilter(60..);
+    /// let lower = Not!(KeyRangeFilter(60..));
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,59,20), NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,127,20)]);
+    /// upper.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,127,20)]);
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,0,20), NoteOnEvent(0,0,59,20), NoteOnEvent(0,0,60,20)]);
+    /// lower.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,0,20), NoteOnEvent(0,0,59,20)]);
+    ///
+    /// // An exclusive end.
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,47,20), NoteOnEvent(0,0,48,20)]);
+    /// KeyRangeFilter(..48).run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,47,20));
+    /// # }
+    /// ```
+    KeyRangeFilter(u8)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::NoteOn(ev) => ev.note >= self.0 && ev.note <= self.1,
-            Event::NoteOff(ev) => ev.note >= self.0 && ev.note <= self.1,
+            Event::NoteOn(ev) => self.range.contains(&ev.note),
+            Event::NoteOff(ev) => self.range.contains(&ev.note),
             _ => true,
         }
     }
 );
 
+/// Split notes into key ranges, each processed by its own patch.
+///
+/// The split points before `=>` are the exclusive upper bounds of each key range,
+/// except the last range which goes up to the highest note. So there is one patch
+/// more than split points. Other events than notes are passed to all patches.
+///
+/// Split points are increasing constants above 0, the lowest notes always go to the first
+/// patch. This is checked at compile time.
+///
+/// This is a shorthand for a [Fork!] of [Chain!]s with a [KeyRangeFilter].
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Split!(60 => Port(0), Port(1));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(2,0,59,20), NoteOnEvent(2,0,60,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,59,20), NoteOnEvent(1,0,60,20)]);
+/// # }
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Split!(48, 72 => Channel(0), Channel(1), Channel(2));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,5,40,20), NoteOnEvent(0,5,60,20), NoteOnEvent(0,5,72,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,40,20), NoteOnEvent(0,1,60,20), NoteOnEvent(0,2,72,20)]);
+/// # }
+/// ```
+///
+/// A controller goes to each patch, and is only passed once when the patches leave it the same.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Split!(60 => Port(0), Port(1));
+///
+/// let mut evs = EventStream::from(CtrlEvent(2,0,7,40));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,40), CtrlEvent(1,0,7,40)]);
+///
+/// let filter = Split!(60 => Transpose(-12), Transpose(12));
+///
+/// let mut evs = EventStream::from(CtrlEvent(2,0,7,40));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(2,0,7,40));
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Split!(0 => Port(0), Port(1));
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Split!(60, 48 => Port(0), Port(1), Port(2));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! Split {
+    ( $($split:expr),+ => $($patch:expr),+ ) => {{
+        $( const _: () = assert!($split > 0, "Split! points must be above 0"); )+
+        _Split!(@ranges [] 0; $($split),+ ; $($patch),+)
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _Split {
+    // Last range, up to the highest note.
+    ( @ranges [$($out:expr),*] $lo:expr; ; $patch:expr ) => {
+        Fork!($($out,)* Chain!(KeyRangeFilter($lo..), $patch))
+    };
+    ( @ranges [$($out:expr),*] $lo:expr; $split:expr $(, $splits:expr)* ; $patch:expr, $($patches:expr),+ ) => {{
+        const _: () = assert!($split > $lo, "Split! points must increase");
+        _Split!(@ranges [$($out,)* Chain!(KeyRangeFilter($lo..$split), $patch)] $split; $($splits),* ; $($patches),+)
+    }};
+}
+
 define_filter!(
     /// Filter on controller (CC)
     ///
@@ -454,7 +924,7 @@ define_filter!(
     }
 );
 
-define_filter!(
+define_list_filter!(
     /// Filter multiple controllers (CC)
     ///
     /// # Examples
@@ -471,15 +941,37 @@ define_filter!(
     /// filter.run(&mut evs);
     /// assert_eq!(evs, vec![ev1, ev2]);
     /// ```
-    CtrlsFilter(&'static [u32])
+    CtrlsFilter(u32)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::Ctrl(ev) => self.0.contains(&ev.ctrl),
+            Event::Ctrl(ev) => self.values.contains(&ev.ctrl),
             _ => true,
         }
     }
 );
 
+/// Filter multiple controllers (CC), given at runtime
+///
+/// Same as [CtrlsFilter], which takes a `Vec` as well.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DynCtrlsFilter(vec![7, 8]);
+///
+/// let ev1 = CtrlEvent(0,0,7,40);
+/// let ev2 = CtrlEvent(0,0,8,40);
+/// let ev3 = CtrlEvent(0,0,9,40);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev1, ev2]);
+/// ```
+pub fn DynCtrlsFilter(values: Vec<u32>) -> CtrlsFilter {
+    CtrlsFilter(values)
+}
+
 define_filter!(
     /// Filter on a controller (CC) value
     ///
@@ -505,7 +997,7 @@ define_filter!(
     }
 );
 
-define_filter!(
+define_list_filter!(
     /// Filter on multiple controller (CC) values.
     ///
     /// # Examples
@@ -522,25 +1014,49 @@ define_filter!(
     /// filter.run(&mut evs);
     /// assert_eq!(evs, vec![ev1, ev2]);
     /// ```
-    CtrlValuesFilter(&'static[i32])
+    CtrlValuesFilter(i32)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::Ctrl(ev) => self.0.contains(&ev.value),
+            Event::Ctrl(ev) => self.values.contains(&ev.value),
             _ => true,
         }
     }
 );
 
-define_filter!(
-    /// Filter on a range of controller (CC) values.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use rmididings::proc::*;
-    /// let filter = CtrlValueRangeFilter(0, 20);
-    ///
-    /// let ev1 = CtrlEvent(0,0,7,0);
+/// Filter on multiple controller (CC) values, given at runtime.
+///
+/// Same as [CtrlValuesFilter], which takes a `Vec` as well.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DynCtrlValuesFilter(vec![0,1]);
+///
+/// let ev1 = CtrlEvent(0,0,7,0);
+/// let ev2 = CtrlEvent(0,0,7,1);
+/// let ev3 = CtrlEvent(0,0,7,2);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev1, ev2]);
+/// ```
+pub fn DynCtrlValuesFilter(values: Vec<i32>) -> CtrlValuesFilter {
+    CtrlValuesFilter(values)
+}
+
+define_range_filter!(
+    /// Filter on a range of controller (CC) values.
+    ///
+    /// The range can be open-ended, see [KeyRangeFilter].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let filter = CtrlValueRangeFilter(0..=20);
+    ///
+    /// let ev1 = CtrlEvent(0,0,7,0);
     /// let ev2 = CtrlEvent(0,0,7,10);
     /// let ev3 = CtrlEvent(0,0,7,50);
     ///
@@ -548,10 +1064,71 @@ define_filter!(
     /// filter.run(&mut evs);
     /// assert_eq!(evs, vec![ev1, ev2]);
     /// ```
-    CtrlValueRangeFilter(i32, i32)
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rmididings;
+    /// # use rmididings::proc::*;
+    /// # fn main() {
+    /// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,20), CtrlEvent(0,0,7,21)]);
+    /// CtrlValueRangeFilter(..=20).run(&mut evs);
+    /// assert_eq!(evs, CtrlEvent(0,0,7,20));
+    ///
+    /// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,20), CtrlEvent(0,0,7,21)]);
+    /// Not!(CtrlValueRangeFilter(..=20)).run(&mut evs);
+    /// assert_eq!(evs, CtrlEvent(0,0,7,21));
+    ///
+    /// // Everything.
+    /// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,-1), CtrlEvent(0,0,7,127)]);
+    /// CtrlValueRangeFilter(..).run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,0,7,-1), CtrlEvent(0,0,7,127)]);
+    /// # }
+    /// ```
+    CtrlValueRangeFilter(i32)
+    fn filter_single(&self, ev: &Event) -> bool {
+        match ev {
+            Event::Ctrl(ev) => self.range.contains(&ev.value),
+            _ => true,
+        }
+    }
+);
+
+define_range_filter!(
+    /// Filter on a range of note velocities.
+    ///
+    /// Only NoteOn events are filtered, so that NoteOff events still end the notes that passed.
+    /// The range can be open-ended, see [KeyRangeFilter].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let filter = VelocityRangeFilter(100..);
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,99), NoteOnEvent(0,0,61,100), NoteOffEvent(0,0,60)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,61,100), NoteOffEvent(0,0,60)]);
+    /// ```
+    ///
+    /// Layering a soft and a loud sound on different channels:
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rmididings;
+    /// # use rmididings::proc::*;
+    /// # fn main() {
+    /// let filter = Fork!(
+    ///     Chain!(VelocityRangeFilter(..100), Channel(0)),
+    ///     Chain!(Not!(VelocityRangeFilter(..100)), Channel(1))
+    /// );
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,5,60,99), NoteOnEvent(0,5,61,100)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,99), NoteOnEvent(0,1,61,100)]);
+    /// # }
+    /// ```
+    VelocityRangeFilter(u8)
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::Ctrl(ev) => ev.value >= self.0 && ev.value <= self.1,
+            Event::NoteOn(ev) => self.range.contains(&ev.velocity),
             _ => true,
         }
     }
@@ -564,8 +1141,9 @@ define_generator!(
     ///
     /// The arguments are: _note_, _velocity_.
     ///
-    /// Port and channel are set to `0`, you can use the modifiers
-    /// [Port] and [Channel] so change them.
+    /// Port and channel are taken from the incoming event, or are the first port and channel
+    /// (see [with_data_offset]) when it has none. You can use the modifiers [Port] and
+    /// [Channel] to change them.
     ///
     /// # Examples
     ///
@@ -588,8 +1166,9 @@ define_generator!(
     ///
     /// The argument is: _note_.
     ///
-    /// Port and channel are set to `0`, you can use the modifiers
-    /// [Port] and [Channel] so change them.
+    /// Port and channel are taken from the incoming event, or are the first port and channel
+    /// (see [with_data_offset]) when it has none. You can use the modifiers [Port] and
+    /// [Channel] to change them.
     ///
     /// # Examples
     ///
@@ -612,8 +1191,9 @@ define_generator!(
     ///
     /// The argument is: _ctrl_, _value_.
     ///
-    /// Port and channel are set to `0`, you can use the modifiers
-    /// [Port] and [Channel] so change them.
+    /// Port and channel are taken from the incoming event, or are the first port and channel
+    /// (see [with_data_offset]) when it has none. You can use the modifiers [Port] and
+    /// [Channel] to change them.
     ///
     /// # Examples
     ///
@@ -631,13 +1211,39 @@ define_generator!(
     }
 );
 
+define_generator!(
+    /// Generate a program change event.
+    ///
+    /// The argument is: _program_.
+    ///
+    /// Port and channel are taken from the incoming event, or are the first port and channel
+    /// (see [with_data_offset]) when it has none. You can use the modifiers [Port] and
+    /// [Channel] to change them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = Program(5);
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, ProgramEvent(0, 0, 5));
+    /// ```
+    Program(u8)
+    fn generate_single(&self) -> Event<'static> {
+        ProgramEvent(0, 0, self.0)
+    }
+);
+
 define_generator!(
     /// Generate a system exclusive event.
     ///
     /// The argument is: _sysex message_.
     ///
-    /// Port and channel are set to `0`, you can use the modifiers
-    /// [Port] and [Channel] so change them.
+    /// Port and channel are taken from the incoming event, or are the first port and channel
+    /// (see [with_data_offset]) when it has none. You can use the modifiers [Port] and
+    /// [Channel] to change them.
     ///
     /// # Examples
     ///
@@ -655,6 +1261,284 @@ define_generator!(
     }
 );
 
+// // 14-bit controllers
+
+/// Offset from the MSB to the LSB controller number, for controllers 0 to 31.
+const CTRL_LSB_OFFSET: u32 = 32;
+
+define_multi_generator!(
+    /// Generate a 14-bit controller value as MSB and LSB controller events.
+    ///
+    /// The arguments are: _ctrl_ (the MSB controller, 0 to 31), _value_ (0 to 16383).
+    ///
+    /// The MSB is sent on _ctrl_, followed by the LSB on _ctrl_ + 32. Port and channel
+    /// are taken from the incoming event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = Ctrl14(7, 1000);
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,1,60,20));
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,1,7,7), CtrlEvent(0,1,39,104)]);
+    /// ```
+    Ctrl14(u32, i32)
+    fn generate_multi(&self) -> Vec<Event<'static>> {
+        vec![
+            CtrlEvent(0, 0, self.0, (self.1 >> 7) & 0x7f),
+            CtrlEvent(0, 0, self.0 + CTRL_LSB_OFFSET, self.1 & 0x7f),
+        ]
+    }
+);
+
+/// Combines 14-bit controller pairs into a single controller event with a 14-bit value.
+///
+/// Controllers 0 to 31 are the MSB, and controllers 32 to 63 are the LSB of the
+/// controller 32 lower. The MSB is consumed and remembered, and each following LSB results
+/// in a controller event on the MSB controller number, with a value of 0 to 16383.
+/// So the MSB needs to be sent before the LSB, and an LSB can follow by itself for fine changes.
+///
+/// An LSB without a preceding MSB is passed unchanged, as are other events.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Ctrl14Decode();
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,7), CtrlEvent(0,0,39,104)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,7,1000));
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,39,105), CtrlEvent(0,0,40,1), CtrlEvent(0,0,64,127)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,1001), CtrlEvent(0,0,40,1), CtrlEvent(0,0,64,127)]);
+/// ```
+pub struct Ctrl14Decode {
+    // Last MSB value for each port, channel and MSB controller.
+    msbs: Mutex<HashMap<(usize, u8, u32), i32>>,
+}
+
+pub fn Ctrl14Decode() -> Ctrl14Decode {
+    Ctrl14Decode { msbs: Mutex::new(HashMap::new()) }
+}
+
+impl FilterTrait for Ctrl14Decode {
+    fn run(&self, evs: &mut EventStream) {
+        let mut msbs = match self.msbs.lock() {
+            Ok(msbs) => msbs,
+            Err(_) => return,
+        };
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            match ev {
+                Event::Ctrl(ev) if ev.ctrl < CTRL_LSB_OFFSET => {
+                    msbs.insert((ev.port, ev.channel, ev.ctrl), ev.value & 0x7f);
+                },
+                Event::Ctrl(ev) if ev.ctrl < 2 * CTRL_LSB_OFFSET => {
+                    let ctrl = ev.ctrl - CTRL_LSB_OFFSET;
+                    match msbs.get(&(ev.port, ev.channel, ctrl)) {
+                        Some(msb) => out.push(CtrlEvent(ev.port, ev.channel, ctrl, (msb << 7) | (ev.value & 0x7f))),
+                        None => out.push(Event::Ctrl(*ev)),
+                    }
+                },
+                _ => out.push(ev.clone()),
+            }
+        }
+        evs.replace(out);
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut msbs) = self.msbs.lock() {
+            msbs.clear();
+        }
+    }
+}
+
+// // Parameter numbers
+
+/// Controllers used to select a registered or non-registered parameter, and enter its data.
+const CTRL_DATA_ENTRY_MSB: u32 = 6;
+const CTRL_DATA_ENTRY_LSB: u32 = 38;
+const CTRL_NRPN_LSB: u32 = 98;
+const CTRL_NRPN_MSB: u32 = 99;
+const CTRL_RPN_LSB: u32 = 100;
+const CTRL_RPN_MSB: u32 = 101;
+
+/// Controller events that set a 14-bit parameter to a 14-bit value.
+fn parameter_ctrls(ctrl_msb: u32, ctrl_lsb: u32, param: u16, value: u16) -> Vec<Event<'static>> {
+    vec![
+        CtrlEvent(0, 0, ctrl_msb, ((param >> 7) & 0x7f) as i32),
+        CtrlEvent(0, 0, ctrl_lsb, (param & 0x7f) as i32),
+        CtrlEvent(0, 0, CTRL_DATA_ENTRY_MSB, ((value >> 7) & 0x7f) as i32),
+        CtrlEvent(0, 0, CTRL_DATA_ENTRY_LSB, (value & 0x7f) as i32),
+    ]
+}
+
+define_multi_generator!(
+    /// Generate the controller events to set a registered parameter (RPN).
+    ///
+    /// The arguments are: _param_, _value_, both 14-bit.
+    ///
+    /// Each is split in a most significant (MSB) and least significant (LSB) 7-bit part.
+    /// This results in CC#101 (param MSB), CC#100 (param LSB), CC#6 (value MSB) and
+    /// CC#38 (value LSB), in that order. Port and channel are taken from the incoming event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// // Pitch bend sensitivity of 2 semitones.
+    /// let generator = Rpn(0, 2 << 7);
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,3,60,20));
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,3,101,0), CtrlEvent(0,3,100,0), CtrlEvent(0,3,6,2), CtrlEvent(0,3,38,0)]);
+    /// ```
+    Rpn(u16, u16)
+    fn generate_multi(&self) -> Vec<Event<'static>> {
+        parameter_ctrls(CTRL_RPN_MSB, CTRL_RPN_LSB, self.0, self.1)
+    }
+);
+
+define_multi_generator!(
+    /// Generate the controller events to set a non-registered parameter (NRPN).
+    ///
+    /// The arguments are: _param_, _value_, both 14-bit.
+    ///
+    /// Each is split in a most significant (MSB) and least significant (LSB) 7-bit part.
+    /// This results in CC#99 (param MSB), CC#98 (param LSB), CC#6 (value MSB) and
+    /// CC#38 (value LSB), in that order. Port and channel are taken from the incoming event.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = Nrpn(1000, 200);
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,0,99,7), CtrlEvent(0,0,98,104), CtrlEvent(0,0,6,1), CtrlEvent(0,0,38,72)]);
+    /// ```
+    Nrpn(u16, u16)
+    fn generate_multi(&self) -> Vec<Event<'static>> {
+        parameter_ctrls(CTRL_NRPN_MSB, CTRL_NRPN_LSB, self.0, self.1)
+    }
+);
+
+/// Collects NRPN controller sequences into [NrpnEvent]s.
+///
+/// The parameter is selected with CC#99 (MSB) followed by CC#98 (LSB), after which each
+/// CC#6 (value MSB) followed by CC#38 (value LSB) results in an NRPN event. The parameter
+/// stays selected, so further values can be sent with just CC#6 and CC#38. Selecting a
+/// registered parameter (CC#101 and CC#100) deselects it.
+///
+/// These controllers are consumed while an NRPN is being received, other events pass.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = NrpnDecode();
+///
+/// let mut evs = EventStream::from(vec![
+///     CtrlEvent(0,0,99,7), CtrlEvent(0,0,98,104), CtrlEvent(0,0,6,1), CtrlEvent(0,0,38,72),
+///     NoteOnEvent(0,0,60,20),
+/// ]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NrpnEvent(0,0,1000,200), NoteOnEvent(0,0,60,20)]);
+///
+/// // The sequence may be spread over multiple runs.
+/// let mut evs = EventStream::from(CtrlEvent(0,0,6,0));
+/// filter.run(&mut evs);
+/// assert!(evs.is_empty());
+/// let mut evs = EventStream::from(CtrlEvent(0,0,38,5));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NrpnEvent(0,0,1000,5));
+/// ```
+pub struct NrpnDecode {
+    states: Mutex<HashMap<(usize, u8), NrpnDecodeState>>,
+}
+
+pub fn NrpnDecode() -> NrpnDecode {
+    NrpnDecode { states: Mutex::new(HashMap::new()) }
+}
+
+#[derive(Debug, Default)]
+struct NrpnDecodeState {
+    param_msb: Option<u8>,
+    param: Option<u16>,
+    value_msb: Option<u8>,
+}
+
+impl NrpnDecodeState {
+    /// Handles a controller, returns whether it was consumed, with an event when complete.
+    fn ctrl(&mut self, ev: &CtrlEventImpl) -> (bool, Option<Event<'static>>) {
+        let value = (ev.value & 0x7f) as u8;
+        match ev.ctrl {
+            CTRL_NRPN_MSB => {
+                *self = NrpnDecodeState { param_msb: Some(value), ..Default::default() };
+                (true, None)
+            },
+            CTRL_NRPN_LSB => match self.param_msb {
+                Some(msb) => {
+                    self.param = Some(((msb as u16) << 7) | value as u16);
+                    self.value_msb = None;
+                    (true, None)
+                },
+                None => (false, None),
+            },
+            CTRL_RPN_MSB | CTRL_RPN_LSB => {
+                *self = NrpnDecodeState::default();
+                (false, None)
+            },
+            CTRL_DATA_ENTRY_MSB if self.param.is_some() => {
+                self.value_msb = Some(value);
+                (true, None)
+            },
+            CTRL_DATA_ENTRY_LSB => match (self.param, self.value_msb.take()) {
+                (Some(param), Some(msb)) => {
+                    (true, Some(NrpnEvent(ev.port, ev.channel, param, ((msb as u16) << 7) | value as u16)))
+                },
+                _ => (false, None),
+            },
+            _ => (false, None),
+        }
+    }
+}
+
+impl FilterTrait for NrpnDecode {
+    fn run(&self, evs: &mut EventStream) {
+        let mut states = match self.states.lock() {
+            Ok(states) => states,
+            Err(_) => return,
+        };
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            if let Event::Ctrl(ctrl) = ev {
+                match states.entry((ctrl.port, ctrl.channel)).or_default().ctrl(ctrl) {
+                    (_, Some(nrpn)) => out.push(nrpn),
+                    (true, None) => {},
+                    (false, None) => out.push(ev.clone()),
+                }
+            } else {
+                out.push(ev.clone());
+            }
+        }
+        evs.replace(out);
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut states) = self.states.lock() {
+            states.clear();
+        }
+    }
+}
+
 // // Modifiers
 
 define_modifier!(
@@ -683,13 +1567,158 @@ define_modifier!(
             Event::NoteOff(ev) => ev.port = self.0,
             Event::Ctrl(ev) => ev.port = self.0,
             Event::SysEx(ev) => ev.port = self.0,
+            Event::Nrpn(ev) => ev.port = self.0,
+            Event::Program(ev) => ev.port = self.0,
+            Event::PitchBend(ev) => ev.port = self.0,
+            Event::Clock(ev) => ev.port = self.0,
+            Event::Start(ev) => ev.port = self.0,
+            Event::Stop(ev) => ev.port = self.0,
+            Event::Continue(ev) => ev.port = self.0,
+            Event::SongPosition(ev) => ev.port = self.0,
+            Event::SongSelect(ev) => ev.port = self.0,
+            Event::MtcQuarterFrame(ev) => ev.port = self.0,
+            Event::MtcFullFrame(ev) => ev.port = self.0,
             #[cfg(feature = "osc")]
             Event::Osc(ev) => ev.port = self.0,
+            #[cfg(feature = "osc")]
+            Event::OscBundle(ev) => ev.port = self.0,
+            #[cfg(feature = "dbus")]
+            Event::Dbus(ev) => ev.port = self.0,
             _ => {},
         }
     }
 );
 
+/// Modify the port to an output port, by its name.
+///
+/// The argument is: _name_, as given in `out_ports` to [`RMididings.config()`]. The port is
+/// looked up when the runner starts, which fails when there is no port with the name.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = PortName("synth");
+/// modifier.bind(&PortRegistry::new(&["keys"], &["lights", "synth"], 1)).unwrap();
+///
+/// let mut evs = EventStream::from(NoteOnEvent(1,0,60,20));
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(2,0,60,20));
+/// ```
+pub struct PortName {
+    name: &'static str,
+    port: AtomicUsize,
+}
+
+pub fn PortName(name: &'static str) -> PortName {
+    PortName { name, port: AtomicUsize::new(UNBOUND_PORT) }
+}
+
+impl FilterTrait for PortName {
+    fn run(&self, evs: &mut EventStream) {
+        let port = Port(self.port.load(Ordering::Relaxed));
+        for ev in evs.iter_mut() {
+            port.modify_single(ev);
+        }
+    }
+
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.port.store(ports.out_port(self.name)?, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Sends events to the first of the ports that has something receiving them.
+///
+/// The argument is: _ports_.
+///
+/// Backends are asked which ports have receivers, e.g. ALSA ports need a subscriber. When
+/// that isn't known, the port is assumed to have receivers. When none of the ports have them,
+/// the first port is used. Events without a port are passed unchanged.
+///
+/// A NoteOff, or NoteOn with velocity 0, goes to the port its NoteOn went to, also when
+/// receivers came or went in between.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::sync::Arc;
+///
+/// let filter = PortFallback(&[1, 2]);
+///
+/// let mut ports = OutputPorts::new();
+/// ports.set_has_receivers(1, false);
+/// ports.set_has_receivers(2, true);
+/// let ports = Arc::new(ports);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// ports.activate(|| filter.run(&mut evs));
+/// assert_eq!(evs, NoteOnEvent(2,0,60,20));
+///
+/// // Outside of the runner, the first port is used.
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(1,0,60,20));
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::sync::Arc;
+///
+/// let filter = PortFallback(&[1, 2]);
+///
+/// let mut ports = OutputPorts::new();
+/// ports.set_has_receivers(1, false);
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// Arc::new(ports).activate(|| filter.run(&mut evs));
+/// assert_eq!(evs, NoteOnEvent(2,0,60,20));
+///
+/// // Port 1 got a receiver, but the note is still sounding on port 2.
+/// let mut ports = OutputPorts::new();
+/// ports.set_has_receivers(1, true);
+/// let mut evs = EventStream::from(vec![NoteOffEvent(0,0,60), NoteOnEvent(0,0,62,20)]);
+/// Arc::new(ports).activate(|| filter.run(&mut evs));
+/// assert_eq!(evs, vec![NoteOffEvent(2,0,60), NoteOnEvent(1,0,62,20)]);
+/// ```
+pub struct PortFallback {
+    ports: &'static [usize],
+    // Port each sounding note was sent to, by its port, channel and note.
+    sounding: Mutex<BTreeMap<(usize, u8, u8), usize>>,
+}
+
+pub const fn PortFallback(ports: &'static [usize]) -> PortFallback {
+    PortFallback { ports, sounding: Mutex::new(BTreeMap::new()) }
+}
+
+impl FilterTrait for PortFallback {
+    fn run(&self, evs: &mut EventStream) {
+        let first = match self.ports.first() {
+            Some(first) => *first,
+            None => return,
+        };
+        let port = match OutputPorts::active() {
+            Some(ports) => self.ports.iter().copied().find(|port| ports.has_receivers(*port)).unwrap_or(first),
+            None => first,
+        };
+        let Ok(mut sounding) = self.sounding.lock() else { return };
+        for ev in evs.iter_mut() {
+            let port = match ev {
+                Event::NoteOn(ev) if ev.velocity > 0 => {
+                    sounding.insert((ev.port, ev.channel, ev.note), port);
+                    port
+                },
+                Event::NoteOn(NoteOnEventImpl { port: from, channel, note, .. }) |
+                Event::NoteOff(NoteOffEventImpl { port: from, channel, note, .. }) => {
+                    sounding.remove(&(*from, *channel, *note)).unwrap_or(port)
+                },
+                _ => port,
+            };
+            if ev.port().is_some() { ev.set_port(port); }
+        }
+    }
+}
+
 define_modifier!(
     /// Modify the channel to a set value.
     ///
@@ -711,6 +1740,9 @@ define_modifier!(
             Event::NoteOn(ev) => ev.channel = self.0,
             Event::NoteOff(ev) => ev.channel = self.0,
             Event::Ctrl(ev) => ev.channel = self.0,
+            Event::Nrpn(ev) => ev.channel = self.0,
+            Event::Program(ev) => ev.channel = self.0,
+            Event::PitchBend(ev) => ev.channel = self.0,
             _ => {},
         }
     }
@@ -740,44 +1772,103 @@ define_modifier!(
     /// modifier.run(&mut evs);
     /// assert_eq!(evs, NoteOnEvent(0,0,56,20));
     /// ```
+    ///
+    /// Notes are clamped to the valid range 0..=127, see [TransposeDrop] to drop them instead.
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,127,20));
+    /// Transpose(5).run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,127,20));
+    ///
+    /// let mut evs = EventStream::from(NoteOffEvent(0,0,0));
+    /// Transpose(-5).run(&mut evs);
+    /// assert_eq!(evs, NoteOffEvent(0,0,0));
+    /// ```
     Transpose(i16)
     fn modify_single(&self, ev: &mut Event) {
         match ev {
-            Event::NoteOn(ev) => ev.note = (ev.note as i16).saturating_add(self.0) as u8,
-            Event::NoteOff(ev) => ev.note = (ev.note as i16).saturating_add(self.0) as u8,
+            Event::NoteOn(ev) => ev.note = transpose_note(ev.note, self.0).clamp(0, 127) as u8,
+            Event::NoteOff(ev) => ev.note = transpose_note(ev.note, self.0).clamp(0, 127) as u8,
             _ => {},
         }
     }
 );
 
-/// Modify the key (note) by an number of octaves.
+fn transpose_note(note: u8, semitones: i16) -> i16 {
+    (note as i16).saturating_add(semitones)
+}
+
+/// Modify the key (note) by a number of semitones, dropping notes outside of the valid range.
 ///
-/// The argument is: _octaves_.
+/// The argument is: _semitones_.
+///
+/// Unlike [Transpose], which clamps them, notes ending up below 0 or above 127 are removed.
 ///
 /// # Examples
 ///
 /// ```
 /// # use rmididings::proc::*;
-/// let modifier = TransposeOctave(1);
+/// let filter = TransposeDrop(5);
 ///
 /// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
-/// modifier.run(&mut evs);
-/// assert_eq!(evs, NoteOnEvent(0,0,72,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,65,20));
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,127,20));
+/// filter.run(&mut evs);
+/// assert!(evs.is_empty());
 /// ```
 ///
 /// ```
 /// # use rmididings::proc::*;
-/// let modifier = TransposeOctave(-1);
+/// let filter = TransposeDrop(-5);
 ///
-/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
-/// modifier.run(&mut evs);
-/// assert_eq!(evs, NoteOnEvent(0,0,48,20));
+/// let mut evs = EventStream::from(vec![NoteOffEvent(0,0,0), CtrlEvent(0,0,7,100)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,7,100));
 /// ```
-pub fn TransposeOctave(octaves: i16) -> Transpose {
-    Transpose(octaves * 12)
+pub struct TransposeDrop(pub i16);
+
+impl FilterTrait for TransposeDrop {
+    fn run(&self, evs: &mut EventStream) {
+        evs.retain(|ev| match ev {
+            Event::NoteOn(ev) => (0..=127).contains(&transpose_note(ev.note, self.0)),
+            Event::NoteOff(ev) => (0..=127).contains(&transpose_note(ev.note, self.0)),
+            _ => true,
+        });
+        Transpose(self.0).run(evs);
+    }
 }
 
-define_modifier!(
+/// Modify the key (note) by an number of octaves.
+///
+/// The argument is: _octaves_.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = TransposeOctave(1);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,72,20));
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = TransposeOctave(-1);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,48,20));
+/// ```
+pub fn TransposeOctave(octaves: i16) -> Transpose {
+    Transpose(octaves * 12)
+}
+
+define_modifier!(
     /// Modify the key (note) to a set value.
     ///
     /// The argument is: _key_.
@@ -803,38 +1894,115 @@ define_modifier!(
 );
 
 define_modifier!(
-    /// Modify the note velocity by an amount.
+    /// Snap the key (note) to the nearest multiple of a step size.
     ///
-    /// The argument is: _offset_.
+    /// The argument is: _step_.
+    ///
+    /// A step of 0 is taken as 1, leaving notes alone. Notes are clamped to the valid range 0..=127.
     ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let modifier = Velocity(10);
+    /// let modifier = KeyQuantize(4);
     ///
-    /// let mut evs = EventStream::from(NoteOnEvent(0,0,60,40));
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,37,20), NoteOnEvent(0,0,38,20), NoteOffEvent(0,0,39), NoteOnEvent(0,0,127,20)]);
     /// modifier.run(&mut evs);
-    /// assert_eq!(evs, NoteOnEvent(0,0,60,50));
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,36,20), NoteOnEvent(0,0,40,20), NoteOffEvent(0,0,40), NoteOnEvent(0,0,127,20)]);
     /// ```
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let modifier = Velocity(-10);
-    ///
-    /// let mut evs = EventStream::from(NoteOnEvent(0,0,60,40));
-    /// modifier.run(&mut evs);
-    /// assert_eq!(evs, NoteOnEvent(0,0,60,30));
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,37,20));
+    /// KeyQuantize(0).run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,37,20));
     /// ```
-    Velocity(i16)
+    KeyQuantize(u8)
     fn modify_single(&self, ev: &mut Event) {
         match ev {
-            Event::NoteOn(ev) => ev.velocity = (ev.velocity as i16).saturating_add(self.0) as u8,
+            Event::NoteOn(ev) => ev.note = quantize(ev.note, self.0),
+            Event::NoteOff(ev) => ev.note = quantize(ev.note, self.0),
             _ => {},
         }
     }
 );
 
+/// Rounds to the nearest multiple of step, clamped to 0..=127.
+fn quantize(value: u8, step: u8) -> u8 {
+    let step = step.max(1) as u16;
+    ((value as u16 + step / 2) / step * step).min(127) as u8
+}
+
+/// Modify the note velocity by an amount.
+///
+/// The argument is: _offset_.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = Velocity(10);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,40));
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,50));
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = Velocity(-10);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,40));
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,30));
+/// ```
+///
+/// This leaves NoteOff events alone, unless [Velocity::with_note_off] is used to modify their
+/// release velocity too. Use [NoteOffVelocityOffset] to modify only the release velocity.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = Velocity(10).with_note_off();
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,40), NoteOffEventWithVelocity(0,0,60,64), NoteOffEventWithVelocity(0,0,61,120)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,50), NoteOffEventWithVelocity(0,0,60,74), NoteOffEventWithVelocity(0,0,61,127)]);
+/// ```
+pub struct Velocity {
+    offset: i16,
+    note_off: bool,
+}
+
+pub fn Velocity(offset: i16) -> Velocity {
+    Velocity { offset, note_off: false }
+}
+
+impl Velocity {
+    /// Modifies the release velocity of NoteOff events too, kept within 0..=127.
+    pub fn with_note_off(mut self) -> Self {
+        self.note_off = true;
+        self
+    }
+
+    fn modify_single(&self, ev: &mut Event) {
+        match ev {
+            Event::NoteOn(ev) => ev.velocity = (ev.velocity as i16).saturating_add(self.offset) as u8,
+            Event::NoteOff(ev) if self.note_off => {
+                ev.velocity = (ev.velocity as i16).saturating_add(self.offset).clamp(0, 127) as u8;
+            },
+            _ => {},
+        }
+    }
+}
+
+impl FilterTrait for Velocity {
+    fn run(&self, evs: &mut EventStream) {
+        for ev in evs.iter_mut() {
+            self.modify_single(ev);
+        }
+    }
+}
+
 define_modifier!(
     /// Modify the note velocity by a multiplication factor.
     ///
@@ -859,217 +2027,3294 @@ define_modifier!(
     }
 );
 
+/// Modify the note velocity to a set value.
+///
+/// The argument is: _velocity_.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = VelocityFixed(100);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,40), NoteOffEvent(0,0,60)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,100), NoteOffEvent(0,0,60)]);
+/// ```
+///
+/// This leaves NoteOff events alone, unless [VelocityFixed::with_note_off] is used to set their
+/// release velocity too. Use [NoteOffVelocity] to set only the release velocity.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = VelocityFixed(100).with_note_off();
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,40), NoteOffEvent(0,0,60)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,100), NoteOffEventWithVelocity(0,0,60,100)]);
+/// ```
+pub struct VelocityFixed {
+    velocity: u8,
+    note_off: bool,
+}
+
+pub fn VelocityFixed(velocity: u8) -> VelocityFixed {
+    VelocityFixed { velocity, note_off: false }
+}
+
+impl VelocityFixed {
+    /// Sets the release velocity of NoteOff events too.
+    pub fn with_note_off(mut self) -> Self {
+        self.note_off = true;
+        self
+    }
+
+    fn modify_single(&self, ev: &mut Event) {
+        match ev {
+            Event::NoteOn(ev) => ev.velocity = self.velocity,
+            Event::NoteOff(ev) if self.note_off => ev.velocity = self.velocity,
+            _ => {},
+        }
+    }
+}
+
+impl FilterTrait for VelocityFixed {
+    fn run(&self, evs: &mut EventStream) {
+        for ev in evs.iter_mut() {
+            self.modify_single(ev);
+        }
+    }
+}
+
 define_modifier!(
-    /// Modify the note velocity to a set value.
+    /// Limit the note velocity to a range.
     ///
-    /// The argument is: _velocity_.
+    /// The arguments are: _min_, _max_.
+    ///
+    /// A velocity of 0 is left alone, as that means NoteOff.
     ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let modifier = Velocity(10);
+    /// let modifier = VelocityLimit(20, 100);
     ///
-    /// let mut evs = EventStream::from(NoteOnEvent(0,0,60,40));
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,5), NoteOnEvent(0,0,61,20), NoteOnEvent(0,0,62,127)]);
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20), NoteOnEvent(0,0,62,100)]);
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,60,0));
     /// modifier.run(&mut evs);
-    /// assert_eq!(evs, NoteOnEvent(0,0,60,50));
+    /// assert_eq!(evs, NoteOnEvent(0,0,60,0));
     /// ```
-    VelocityFixed(u8)
+    VelocityLimit(u8, u8)
     fn modify_single(&self, ev: &mut Event) {
-        match ev {
-            Event::NoteOn(ev) => ev.velocity = self.0,
-            _ => {},
+        if let Event::NoteOn(ev) = ev {
+            if ev.velocity > 0 {
+                ev.velocity = ev.velocity.max(self.0).min(self.1);
+            }
         }
     }
 );
 
 define_modifier!(
-    /// Modifies the controller number (CC), changing one for another.
+    /// Compress the note velocity above a threshold.
     ///
-    /// The arguments are: _from_ctrl_ and _to_ctrl_.
+    /// The arguments are: _threshold_, _ratio_.
+    ///
+    /// The part of the velocity above the threshold is divided by the ratio, so a ratio
+    /// of 2 halves it. Velocities up to the threshold are left alone.
     ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let modifier = CtrlMap(7, 8);
+    /// let modifier = VelocityCompress(80, 2.0);
     ///
-    /// let mut evs = EventStream::from(CtrlEvent(0,0,7,50));
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,40), NoteOnEvent(0,0,61,80), NoteOnEvent(0,0,62,120)]);
     /// modifier.run(&mut evs);
-    /// assert_eq!(evs, CtrlEvent(0,0,8,50));
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,40), NoteOnEvent(0,0,61,80), NoteOnEvent(0,0,62,100)]);
     /// ```
-    CtrlMap(u32, u32)
+    VelocityCompress(u8, f32)
     fn modify_single(&self, ev: &mut Event) {
-        match ev {
-            Event::Ctrl(ev) if ev.ctrl == self.0 => ev.ctrl = self.1,
-            _ => {}
+        if let Event::NoteOn(ev) = ev {
+            if ev.velocity > self.0 && self.1 > 0.0 {
+                let above = (ev.velocity - self.0) as f32 / self.1;
+                ev.velocity = (self.0 as f32 + above).round().min(127.0) as u8;
+            }
         }
     }
 );
 
-// // Scene switching
-
-/// Switches to a specific scene.
-///
-/// The argument is: _scene_number_.
-///
-/// This event consumes all other events, so after this filter
-/// only the curent scene switch remains.
-///
-/// Note that the scene is only switched when there are events, so
-/// that when an event filter discards all events, the scene switch
-/// is not done. It also means that you need to generate an event
-/// when putting this in a pre, init, exit or post patch.
-///
-/// # Examples
-///
-/// ```
-/// # use rmididings::proc::*;
-/// let generator = SceneSwitch(5);
-///
-/// let mut evs = EventStream::none();
-/// generator.run(&mut evs);
-/// assert_eq!(evs, SceneSwitchEvent(5));
-/// ```
-pub struct SceneSwitch(pub SceneNum);
-impl FilterTrait for SceneSwitch {
-    fn run(&self, evs: &mut EventStream) {
-        if evs.is_empty() { return; }
-        TypeFilter!(SceneSwitch).run(evs);
-        evs.push(SceneSwitchEvent(self.0));
-    }
-}
-
-define_generator!(
-    /// Change the current scene by the specified amount.
-    ///
-    /// The argument is: _scene_delta_.
+define_modifier!(
+    /// Snap the note velocity to the nearest multiple of a step size.
     ///
-    /// To go to the next scene, use `SceneSwitchOffset(1)`,
-    /// to go to the previous scene, use `SceneSwitchOffset(-1)`.
+    /// The argument is: _step_.
     ///
-    /// Note that the scene is only switched when there are events, so
-    /// that when an event filter discards all events, the scene switch
-    /// is not done. It also means that you need to generate an event
-    /// when putting this in a pre, init, exit or post patch.
+    /// A step of 0 is taken as 1, leaving velocities alone. Velocities are clamped to 127, and a
+    /// velocity of 0 is left alone, as that means NoteOff. Other velocities don't become 0, so
+    /// they go to the first step instead.
     ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let generator = SceneSwitchOffset(1);
+    /// let modifier = VelocityQuantize(8);
     ///
-    /// let mut evs = EventStream::none();
-    /// generator.run(&mut evs);
-    /// assert_eq!(evs, SceneSwitchOffsetEvent(1));
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,3), NoteOnEvent(0,0,61,43), NoteOnEvent(0,0,62,44), NoteOnEvent(0,0,63,127)]);
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,8), NoteOnEvent(0,0,61,40), NoteOnEvent(0,0,62,48), NoteOnEvent(0,0,63,127)]);
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,0), NoteOffEventWithVelocity(0,0,60,43)]);
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,0), NoteOffEventWithVelocity(0,0,60,43)]);
     /// ```
-    SceneSwitchOffset(SceneOffset)
-    fn generate_single(&self) -> Event<'static> {
-        SceneSwitchOffsetEvent(self.0)
+    VelocityQuantize(u8)
+    fn modify_single(&self, ev: &mut Event) {
+        if let Event::NoteOn(ev) = ev {
+            if ev.velocity > 0 {
+                ev.velocity = quantize(ev.velocity, self.0).max(self.0.clamp(1, 127));
+            }
+        }
     }
 );
 
-define_generator!(
-    /// Switches to a specific subscene.
+define_modifier!(
+    /// Modify the release velocity of NoteOff events to a set value.
     ///
-    /// The argument is: _subscene_number_.
+    /// The argument is: _velocity_.
     ///
-    /// Note that the subscene is only switched when there are events, so
-    /// that when an event filter discards all events, the subscene switch
-    /// is not done. It also means that you need to generate an event
-    /// when putting this in a pre, init, exit or post patch.
+    /// Some instruments use the release velocity for the release time.
     ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let modifier = SubSceneSwitch(5);
+    /// let modifier = NoteOffVelocity(64);
     ///
-    /// let mut evs = EventStream::none();
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,40), NoteOffEvent(0,0,60)]);
     /// modifier.run(&mut evs);
-    /// assert_eq!(evs, SubSceneSwitchEvent(5));
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,40), NoteOffEventWithVelocity(0,0,60,64)]);
     /// ```
-    SubSceneSwitch(SceneNum)
-    fn generate_single(&self) -> Event<'static> {
-        SubSceneSwitchEvent(self.0)
+    NoteOffVelocity(u8)
+    fn modify_single(&self, ev: &mut Event) {
+        if let Event::NoteOff(ev) = ev {
+            ev.velocity = self.0;
+        }
     }
 );
 
-define_generator!(
-    /// Change the current subscene by the specified amount.
-    ///
-    /// The argument is: _subscene_delta_.
+define_modifier!(
+    /// Modify the release velocity of NoteOff events by an amount.
     ///
-    /// To go to the next scene, use `SubSceneSwitchOffset(1)`,
-    /// to go to the previous scene, use `SubSceneSwitchOffset(-1)`.
+    /// The argument is: _offset_.
     ///
-    /// Note that the subscene is only switched when there are events, so
-    /// that when an event filter discards all events, the subscene switch
-    /// is not done. It also means that you need to generate an event
-    /// when putting this in a pre, init, exit or post patch.
+    /// The result is kept within the valid range 0..=127.
     ///
     /// # Examples
     ///
     /// ```
     /// # use rmididings::proc::*;
-    /// let generator = SubSceneSwitchOffset(1);
+    /// let modifier = NoteOffVelocityOffset(-10);
     ///
-    /// let mut evs = EventStream::none();
-    /// generator.run(&mut evs);
-    /// assert_eq!(evs, SubSceneSwitchOffsetEvent(1));
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,40), NoteOffEventWithVelocity(0,0,60,64), NoteOffEvent(0,0,61)]);
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,40), NoteOffEventWithVelocity(0,0,60,54), NoteOffEvent(0,0,61)]);
     /// ```
-    SubSceneSwitchOffset(SceneOffset)
-    fn generate_single(&self) -> Event<'static> {
-        SubSceneSwitchOffsetEvent(self.0)
+    NoteOffVelocityOffset(i16)
+    fn modify_single(&self, ev: &mut Event) {
+        if let Event::NoteOff(ev) = ev {
+            ev.velocity = (ev.velocity as i16).saturating_add(self.0).clamp(0, 127) as u8;
+        }
     }
 );
 
-#[doc(hidden)]
-pub struct _Init<'a>(pub Box<dyn FilterTrait + 'a>);
-#[doc(hidden)]
-impl FilterTrait for _Init<'_> {
-    fn run(&self, _evs: &mut EventStream) {}
-    fn run_init(&self, evs: &mut EventStream) {
-        self.0.run(evs);
+define_modifier!(
+    /// Modifies the controller number (CC), changing one for another.
+    ///
+    /// The arguments are: _from_ctrl_ and _to_ctrl_.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let modifier = CtrlMap(7, 8);
+    ///
+    /// let mut evs = EventStream::from(CtrlEvent(0,0,7,50));
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, CtrlEvent(0,0,8,50));
+    /// ```
+    CtrlMap(u32, u32)
+    fn modify_single(&self, ev: &mut Event) {
+        match ev {
+            Event::Ctrl(ev) if ev.ctrl == self.0 => ev.ctrl = self.1,
+            _ => {}
+        }
+    }
+);
+
+/// Turns absolute controller values into relative ones, for synths expecting increments.
+///
+/// The argument is: _ctrl_.
+///
+/// The value of each Ctrl event for _ctrl_ is replaced by how much it changed since the
+/// previous one on the same port and channel. The first one only sets the baseline, and
+/// results in 0. Use [CtrlValueDelta::with_max_delta] to limit the jumps a knob makes when it
+/// is moved to a quite different position. Other events are passed unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = CtrlValueDelta(7);
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,64), CtrlEvent(0,0,7,66), CtrlEvent(0,0,7,61), CtrlEvent(0,0,8,20)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,0), CtrlEvent(0,0,7,2), CtrlEvent(0,0,7,-5), CtrlEvent(0,0,8,20)]);
+///
+/// // Each channel has its own baseline.
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,1,7,10), CtrlEvent(0,0,7,62)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,1,7,0), CtrlEvent(0,0,7,1)]);
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = CtrlValueDelta(7).with_max_delta(4);
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,0), CtrlEvent(0,0,7,127), CtrlEvent(0,0,7,125)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,0), CtrlEvent(0,0,7,4), CtrlEvent(0,0,7,-2)]);
+/// ```
+pub struct CtrlValueDelta {
+    ctrl: u32,
+    max_delta: Option<i32>,
+    // Previous value for each port and channel.
+    previous: Mutex<HashMap<(usize, u8), i32>>,
+}
+
+pub fn CtrlValueDelta(ctrl: u32) -> CtrlValueDelta {
+    CtrlValueDelta { ctrl, max_delta: None, previous: Mutex::new(HashMap::new()) }
+}
+
+impl CtrlValueDelta {
+    /// Limits the deltas to this much up or down.
+    pub fn with_max_delta(mut self, max_delta: i32) -> Self {
+        self.max_delta = Some(max_delta.abs());
+        self
+    }
+}
+
+impl FilterTrait for CtrlValueDelta {
+    fn run(&self, evs: &mut EventStream) {
+        let mut previous = match self.previous.lock() {
+            Ok(previous) => previous,
+            Err(_) => return,
+        };
+
+        for ev in evs.iter_mut() {
+            if let Event::Ctrl(ev) = ev {
+                if ev.ctrl != self.ctrl { continue; }
+                let baseline = previous.insert((ev.port, ev.channel), ev.value).unwrap_or(ev.value);
+                let delta = ev.value.saturating_sub(baseline);
+                ev.value = match self.max_delta {
+                    Some(max_delta) => delta.clamp(-max_delta, max_delta),
+                    None => delta,
+                };
+            }
+        }
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut previous) = self.previous.lock() {
+            previous.clear();
+        }
+    }
+}
+
+/// Turns relative controller values into absolute ones, the opposite of [CtrlValueDelta].
+///
+/// The arguments are: _ctrl_, _initial_.
+///
+/// The value of each Ctrl event for _ctrl_ is added to a running value, which is kept for each
+/// port and channel and starts at _initial_. The result is clamped to 0..127 and replaces the
+/// value. Other events are passed unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = CtrlDeltaToAbsolute(7, 64);
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,2), CtrlEvent(0,0,7,-5), CtrlEvent(0,1,7,1), CtrlEvent(0,0,8,20)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,66), CtrlEvent(0,0,7,61), CtrlEvent(0,1,7,65), CtrlEvent(0,0,8,20)]);
+///
+/// // It doesn't go beyond the valid range, and turns back right away.
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,100), CtrlEvent(0,0,7,-1)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,127), CtrlEvent(0,0,7,126)]);
+/// ```
+pub struct CtrlDeltaToAbsolute {
+    ctrl: u32,
+    initial: i32,
+    // Current value for each port and channel.
+    values: Mutex<HashMap<(usize, u8), i32>>,
+}
+
+pub fn CtrlDeltaToAbsolute(ctrl: u32, initial: i32) -> CtrlDeltaToAbsolute {
+    CtrlDeltaToAbsolute { ctrl, initial: initial.clamp(0, 127), values: Mutex::new(HashMap::new()) }
+}
+
+impl FilterTrait for CtrlDeltaToAbsolute {
+    fn run(&self, evs: &mut EventStream) {
+        let mut values = match self.values.lock() {
+            Ok(values) => values,
+            Err(_) => return,
+        };
+
+        for ev in evs.iter_mut() {
+            if let Event::Ctrl(ev) = ev {
+                if ev.ctrl != self.ctrl { continue; }
+                let value = values.entry((ev.port, ev.channel)).or_insert(self.initial);
+                *value = value.saturating_add(ev.value).clamp(0, 127);
+                ev.value = *value;
+            }
+        }
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut values) = self.values.lock() {
+            values.clear();
+        }
+    }
+}
+
+/// Turns a controller into notes, e.g. to play drums from buttons sending Ctrl events.
+///
+/// The argument is: _ctrl_.
+///
+/// Each Ctrl event for _ctrl_ becomes a NoteOn with its value as velocity, or a NoteOff when
+/// the value is 0. The note is the controller number, use `with_note` for another one, or
+/// `with_note_from_value` to play the note given by the value instead. Other events are
+/// passed unchanged. See [NoteToCtrl] for the other direction.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = CtrlToNote(36);
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,9,36,100), CtrlEvent(0,9,36,0), CtrlEvent(0,9,37,100)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,9,36,100), NoteOffEvent(0,9,36), CtrlEvent(0,9,37,100)]);
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,64,127), CtrlEvent(0,0,64,200)]);
+/// CtrlToNote(64).with_note(60).run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,127), NoteOnEvent(0,0,60,127)]);
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,16,62));
+/// CtrlToNote(16).with_note_from_value(90).run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,62,90));
+/// ```
+pub struct CtrlToNote {
+    ctrl: u32,
+    note: CtrlToNoteNote,
+}
+
+enum CtrlToNoteNote {
+    Fixed(u8),
+    FromValue(u8),
+}
+
+pub fn CtrlToNote(ctrl: u32) -> CtrlToNote {
+    CtrlToNote { ctrl, note: CtrlToNoteNote::Fixed(ctrl.min(127) as u8) }
+}
+
+impl CtrlToNote {
+    /// Plays this note, instead of the controller number.
+    pub fn with_note(mut self, note: u8) -> Self {
+        self.note = CtrlToNoteNote::Fixed(note.min(127));
+        self
+    }
+
+    /// Plays the note given by the value, with this velocity.
+    pub fn with_note_from_value(mut self, velocity: u8) -> Self {
+        self.note = CtrlToNoteNote::FromValue(velocity.min(127));
+        self
+    }
+}
+
+impl FilterTrait for CtrlToNote {
+    fn run(&self, evs: &mut EventStream) {
+        for ev in evs.iter_mut() {
+            let Event::Ctrl(ctrl) = ev else { continue };
+            if ctrl.ctrl != self.ctrl { continue; }
+            let value = ctrl.value.clamp(0, 127) as u8;
+            *ev = match self.note {
+                CtrlToNoteNote::Fixed(note) if value == 0 => NoteOffEvent(ctrl.port, ctrl.channel, note),
+                CtrlToNoteNote::Fixed(note) => NoteOnEvent(ctrl.port, ctrl.channel, note, value),
+                CtrlToNoteNote::FromValue(velocity) => NoteOnEvent(ctrl.port, ctrl.channel, value, velocity),
+            };
+        }
+    }
+}
+
+define_modifier!(
+    /// Turns notes into a controller, e.g. to drive a parameter from the keyboard's velocity.
+    ///
+    /// The argument is: _ctrl_.
+    ///
+    /// Each NoteOn becomes a Ctrl event for _ctrl_ with its velocity as value, and each NoteOff
+    /// one with value 0. Other events are passed unchanged. Use e.g. [KeyFilter] before it to
+    /// convert only some notes. See [CtrlToNote] for the other direction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rmididings;
+    /// # use rmididings::proc::*;
+    /// # fn main() {
+    /// let filter = NoteToCtrl(74);
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,2,60,100), NoteOffEvent(0,2,60), ProgramEvent(0,2,5)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,2,74,100), CtrlEvent(0,2,74,0), ProgramEvent(0,2,5)]);
+    ///
+    /// // Only for one key, other notes are played.
+    /// let filter = Fork!(Chain!(KeyFilter(36), NoteToCtrl(64)), Not!(KeyFilter(36)));
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,36,127), NoteOnEvent(0,0,60,80)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,0,64,127), NoteOnEvent(0,0,60,80)]);
+    /// # }
+    /// ```
+    NoteToCtrl(u32)
+    fn modify_single(&self, ev: &mut Event) {
+        match ev {
+            Event::NoteOn(note) => *ev = CtrlEvent(note.port, note.channel, self.0, note.velocity as i32),
+            Event::NoteOff(note) => *ev = CtrlEvent(note.port, note.channel, self.0, 0),
+            _ => {},
+        }
+    }
+);
+
+define_modifier!(
+    /// Turns pitch bend into a controller, for synths that can't be bent but have a parameter to map.
+    ///
+    /// The argument is: _ctrl_.
+    ///
+    /// Pitch bend from -8192 to 8191 becomes a Ctrl event for _ctrl_ with a value from 0 to 127,
+    /// where the center is 64. Other events are passed unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let filter = PitchBendToCtrl(1);
+    ///
+    /// let mut evs = EventStream::from(vec![PitchBendEvent(0,3,0), PitchBendEvent(0,3,-8192), PitchBendEvent(0,3,8191)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,3,1,64), CtrlEvent(0,3,1,0), CtrlEvent(0,3,1,127)]);
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,3,60,100));
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,3,60,100));
+    /// ```
+    PitchBendToCtrl(u32)
+    fn modify_single(&self, ev: &mut Event) {
+        if let Event::PitchBend(bend) = ev {
+            let value = (bend.value.clamp(-8192, 8191) as i32 + 8192) >> 7;
+            *ev = CtrlEvent(bend.port, bend.channel, self.0, value);
+        }
+    }
+);
+
+/// Clamps event values to their valid MIDI ranges.
+///
+/// Channels are clamped to 16 channels, notes, velocities, controller numbers and values and
+/// programs to 0..127, and NRPN parameters and values and song positions to 0..16383.
+/// Put it at the end of a patch to catch values that aggressive modifiers pushed out of range.
+///
+/// Patches count channels from the `data_offset` set in [ConfigArguments](crate::ConfigArguments),
+/// use [Sanitize::with_data_offset] to match it; by default channels are 0..15.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = Sanitize();
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,16,200,130), CtrlEvent(0,3,7,-5), CtrlEvent(0,3,300,200)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,15,127,127), CtrlEvent(0,3,7,0), CtrlEvent(0,3,127,127)]);
+///
+/// // With channels counted from 1.
+/// let modifier = Sanitize().with_data_offset(1);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,16,60,20), NoteOnEvent(0,17,60,20)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,1,60,20), NoteOnEvent(0,16,60,20), NoteOnEvent(0,16,60,20)]);
+/// ```
+pub struct Sanitize {
+    data_offset: u8,
+}
+
+pub fn Sanitize() -> Sanitize {
+    Sanitize { data_offset: 0 }
+}
+
+impl Sanitize {
+    /// Count channels from this number, like the `data_offset` of the configuration.
+    pub fn with_data_offset(mut self, data_offset: u8) -> Self {
+        self.data_offset = data_offset;
+        self
+    }
+
+    fn modify_single(&self, ev: &mut Event) {
+        if let Some(channel) = ev.channel() {
+            let min = self.data_offset;
+            ev.set_channel(channel.max(min).min(min.saturating_add(15)));
+        }
+        match ev {
+            Event::NoteOn(ev) => {
+                ev.note = ev.note.min(127);
+                ev.velocity = ev.velocity.min(127);
+            },
+            Event::NoteOff(ev) => {
+                ev.note = ev.note.min(127);
+                ev.velocity = ev.velocity.min(127);
+            },
+            Event::Ctrl(ev) => {
+                ev.ctrl = ev.ctrl.min(127);
+                ev.value = ev.value.clamp(0, 127);
+            },
+            Event::Program(ev) => ev.program = ev.program.min(127),
+            Event::PitchBend(ev) => ev.value = ev.value.clamp(-8192, 8191),
+            Event::Nrpn(ev) => {
+                ev.param = ev.param.min(16383);
+                ev.value = ev.value.min(16383);
+            },
+            Event::SongPosition(ev) => ev.position = ev.position.min(16383),
+            Event::SongSelect(ev) => ev.song = ev.song.min(127),
+            Event::MtcQuarterFrame(ev) => {
+                ev.message_type = ev.message_type.min(7);
+                ev.value = ev.value.min(15);
+            },
+            _ => {}
+        }
+    }
+}
+
+impl FilterTrait for Sanitize {
+    fn run(&self, evs: &mut EventStream) {
+        for ev in evs.iter_mut() {
+            self.modify_single(ev);
+        }
+    }
+}
+
+// // Scene switching
+
+/// Switches to a specific scene.
+///
+/// The argument is: _scene_number_.
+///
+/// This event consumes all other events, so after this filter
+/// only the curent scene switch remains.
+///
+/// Note that the scene is only switched when there are events, so
+/// that when an event filter discards all events, the scene switch
+/// is not done. It also means that you need to generate an event
+/// when putting this in a pre, init, exit or post patch.
+///
+/// The scene starts in the subscene it was last in, unless one is given with
+/// [SceneSwitch::with_subscene].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let generator = SceneSwitch(5);
+///
+/// let mut evs = EventStream::none();
+/// generator.run(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(5));
+/// ```
+pub struct SceneSwitch {
+    pub scene: SceneNum,
+    pub subscene: Option<SceneNum>,
+}
+pub fn SceneSwitch(scene: SceneNum) -> SceneSwitch {
+    SceneSwitch { scene, subscene: None }
+}
+impl SceneSwitch {
+    /// Switches to this subscene of the scene, like `SceneSwitch(scene, subscene)` in mididings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = SceneSwitch(2).with_subscene(1);
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, SceneSwitchSubSceneEvent(2, 1));
+    /// ```
+    pub fn with_subscene(mut self, subscene: SceneNum) -> Self {
+        self.subscene = Some(subscene);
+        self
+    }
+}
+impl FilterTrait for SceneSwitch {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        TypeFilter!(SceneSwitch).run(evs);
+        evs.push(match self.subscene {
+            Some(subscene) => SceneSwitchSubSceneEvent(self.scene, subscene),
+            None => SceneSwitchEvent(self.scene),
+        });
+    }
+}
+
+/// Switches to a scene when a controller (CC) has a value, like a foot switch being pressed.
+///
+/// The arguments are: _ctrl_, _value_, _scene_number_.
+///
+/// Events of the controller are consumed, whether they switch the scene or not. Other events
+/// pass unchanged, so this can be forked or chained with other switches in the control patch.
+/// Use [SceneSwitchOnCtrlRange] to switch on a range of values.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = SceneSwitchOnCtrl(64, 127, 2);
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,64,127), CtrlEvent(0,0,7,127)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,127), SceneSwitchEvent(2)]);
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,64,0));
+/// filter.run(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let control = Chain!(ChannelFilter(16), SceneSwitchOnCtrl(80, 127, 1), SceneSwitchOnCtrl(81, 127, 2));
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,16,81,127));
+/// control.run(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(2));
+/// # }
+/// ```
+pub struct SceneSwitchOnCtrl {
+    pub ctrl: u32,
+    pub values: CtrlValueRangeFilter,
+    pub scene: SceneNum,
+}
+pub fn SceneSwitchOnCtrl(ctrl: u32, value: i32, scene: SceneNum) -> SceneSwitchOnCtrl {
+    SceneSwitchOnCtrl { ctrl, values: CtrlValueRangeFilter::between(value, value), scene }
+}
+impl FilterTrait for SceneSwitchOnCtrl {
+    fn run(&self, evs: &mut EventStream) {
+        let mut switch = false;
+        evs.retain(|ev| match ev {
+            Event::Ctrl(ctrl) if ctrl.ctrl == self.ctrl => {
+                switch |= !self.values.quick_reject(ev);
+                false
+            },
+            _ => true,
+        });
+        if switch {
+            evs.push(SceneSwitchEvent(self.scene));
+        }
+    }
+}
+
+/// Switches to a scene when a controller (CC) has a value in a range, see [SceneSwitchOnCtrl].
+///
+/// The arguments are: _ctrl_, _value_range_, _scene_number_. The range can be open-ended, see
+/// [CtrlValueRangeFilter].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = SceneSwitchOnCtrlRange(64, 64.., 3);
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,64,100));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(3));
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,64,63));
+/// filter.run(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+pub fn SceneSwitchOnCtrlRange<R: RangeBounds<i32>>(ctrl: u32, range: R, scene: SceneNum) -> SceneSwitchOnCtrl {
+    SceneSwitchOnCtrl { ctrl, values: CtrlValueRangeFilter(range), scene }
+}
+
+define_generator!(
+    /// Change the current scene by the specified amount.
+    ///
+    /// The argument is: _scene_delta_.
+    ///
+    /// To go to the next scene, use `SceneSwitchOffset(1)`,
+    /// to go to the previous scene, use `SceneSwitchOffset(-1)`.
+    ///
+    /// Note that the scene is only switched when there are events, so
+    /// that when an event filter discards all events, the scene switch
+    /// is not done. It also means that you need to generate an event
+    /// when putting this in a pre, init, exit or post patch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = SceneSwitchOffset(1);
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, SceneSwitchOffsetEvent(1));
+    /// ```
+    SceneSwitchOffset(SceneOffset)
+    fn generate_single(&self) -> Event<'static> {
+        SceneSwitchOffsetEvent(self.0)
+    }
+);
+
+define_generator!(
+    /// Switches to a specific subscene.
+    ///
+    /// The argument is: _subscene_number_.
+    ///
+    /// Note that the subscene is only switched when there are events, so
+    /// that when an event filter discards all events, the subscene switch
+    /// is not done. It also means that you need to generate an event
+    /// when putting this in a pre, init, exit or post patch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let modifier = SubSceneSwitch(5);
+    ///
+    /// let mut evs = EventStream::none();
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, SubSceneSwitchEvent(5));
+    /// ```
+    SubSceneSwitch(SceneNum)
+    fn generate_single(&self) -> Event<'static> {
+        SubSceneSwitchEvent(self.0)
+    }
+);
+
+define_generator!(
+    /// Change the current subscene by the specified amount.
+    ///
+    /// The argument is: _subscene_delta_.
+    ///
+    /// To go to the next scene, use `SubSceneSwitchOffset(1)`,
+    /// to go to the previous scene, use `SubSceneSwitchOffset(-1)`.
+    ///
+    /// Note that the subscene is only switched when there are events, so
+    /// that when an event filter discards all events, the subscene switch
+    /// is not done. It also means that you need to generate an event
+    /// when putting this in a pre, init, exit or post patch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = SubSceneSwitchOffset(1);
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, SubSceneSwitchOffsetEvent(1));
+    /// ```
+    SubSceneSwitchOffset(SceneOffset)
+    fn generate_single(&self) -> Event<'static> {
+        SubSceneSwitchOffsetEvent(self.0)
+    }
+);
+
+define_generator!(
+    /// Switches the current scene to its first subscene.
+    ///
+    /// Note that the subscene is only switched when there are events, see [SubSceneSwitch].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = SubSceneReset();
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, SubSceneResetEvent());
+    /// ```
+    SubSceneReset()
+    fn generate_single(&self) -> Event<'static> {
+        SubSceneResetEvent()
+    }
+);
+
+// Adds an offset to a fixed (sub)scene number, others are left alone.
+fn offset_scene_switch_value(value: &mut SceneSwitchValue, offset: SceneOffset) {
+    if let SceneSwitchValue::Fixed(num) = value {
+        *num = (*num as SceneOffset).saturating_add(offset).clamp(0, SceneNum::MAX as SceneOffset) as SceneNum;
+    }
+}
+
+define_modifier!(
+    #[doc(hidden)]
+    _SceneOffset(SceneOffset)
+    fn modify_single(&self, ev: &mut Event) {
+        if let Event::SceneSwitch(ev) = ev {
+            offset_scene_switch_value(&mut ev.scene, self.0);
+        }
+    }
+);
+
+/// Shifts the scene number of scene switches.
+///
+/// The argument is: _offset_.
+///
+/// Only switches to a specific scene are changed, the number stops at 0. Other events,
+/// including switches relative to the current scene, pass unchanged. The subscene of a
+/// [SceneSwitch] with one is left alone, see [SubSceneOffset] for subscene switches.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let modifier = SceneOffset(-2);
+///
+/// let mut evs = EventStream::from(vec![SceneSwitchEvent(5), SceneSwitchEvent(1), SceneSwitchOffsetEvent(1), NoteOnEvent(0,0,60,20)]);
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![SceneSwitchEvent(3), SceneSwitchEvent(0), SceneSwitchOffsetEvent(1), NoteOnEvent(0,0,60,20)]);
+/// ```
+pub fn SceneOffset(offset: SceneOffset) -> _SceneOffset {
+    _SceneOffset(offset)
+}
+
+define_modifier!(
+    /// Shifts the subscene number of subscene switches.
+    ///
+    /// The argument is: _offset_.
+    ///
+    /// Only switches to a specific subscene are changed, the number stops at 0. Other events
+    /// pass unchanged. This makes it easy to select subscenes from e.g. notes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let modifier = SubSceneOffset(3);
+    ///
+    /// let mut evs = EventStream::from(vec![SubSceneSwitchEvent(1), SubSceneSwitchOffsetEvent(-1), SceneSwitchEvent(1)]);
+    /// modifier.run(&mut evs);
+    /// assert_eq!(evs, vec![SubSceneSwitchEvent(4), SubSceneSwitchOffsetEvent(-1), SceneSwitchEvent(1)]);
+    /// ```
+    ///
+    /// Note 36 and up select the first subscene and up, numbered from 1:
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rmididings;
+    /// # use rmididings::proc::*;
+    /// # fn main() {
+    /// let filter = Chain!(
+    ///     TypeFilter!(NoteOn),
+    ///     ProcessNote!(|_port, _channel, note, _velocity| SubSceneSwitch(note)),
+    ///     SubSceneOffset(1 - 36)
+    /// );
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,37,100));
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, SubSceneSwitchEvent(2));
+    /// # }
+    /// ```
+    SubSceneOffset(SceneOffset)
+    fn modify_single(&self, ev: &mut Event) {
+        if let Event::SubSceneSwitch(ev) = ev {
+            offset_scene_switch_value(&mut ev.subscene, self.0);
+        }
+    }
+);
+
+// Runs the filter on a placeholder event of its own, and adds what it outputs.
+fn run_on_none(f: &dyn FilterTrait, evs: &mut EventStream) {
+    let mut evs_this = EventStream::none();
+    f.run(&mut evs_this);
+    evs_this.retain(|ev| !matches!(ev, Event::None(_)));
+    evs.extend(evs_this);
+}
+
+#[doc(hidden)]
+pub struct _Init<'a>(pub Box<dyn FilterTrait + 'a>);
+#[doc(hidden)]
+impl FilterTrait for _Init<'_> {
+    fn run(&self, _evs: &mut EventStream) {}
+    fn run_init(&self, evs: &mut EventStream) {
+        run_on_none(self.0.as_ref(), evs);
+    }
+    fn run_exit(&self, evs: &mut EventStream) {
+        self.0.run_exit(evs);
+    }
+    fn run_tick(&self, evs: &mut EventStream) {
+        self.0.run_tick(evs);
+    }
+    fn next_tick(&self) -> Option<Instant> {
+        self.0.next_tick()
+    }
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.0.bind(ports)
+    }
+}
+/// Run contained filters on (sub)scene or patch init.
+///
+/// The contained filters get a single [NoneEvent], so that generators output their event once.
+/// What they output is sent as it is, other filters around `Init!` in a [Chain!] or [Fork!]
+/// are not applied to it. Init runs start without events, so each `Init!` adds only its own
+/// events, and a remaining [NoneEvent] is not output. The contents are ticked and exited
+/// like the rest of the patch, so e.g. a [NoteWithDuration] ends its note.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let patch = Fork!(
+///     Chain!(Init!(Program(5)), KeyFilter(60)),
+///     Chain!(ChannelFilter(1), Init!(Fork!(Ctrl(7, 100), Pass()))),
+///     Transpose(12)
+/// );
+///
+/// let mut evs = EventStream::empty();
+/// patch.run_init(&mut evs);
+/// assert_eq!(evs, vec![ProgramEvent(0,0,5), CtrlEvent(0,0,7,100)]);
+///
+/// // Normal runs are not affected.
+/// let mut evs = EventStream::from(NoteOnEvent(0,1,60,20));
+/// patch.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,1,60,20), NoteOnEvent(0,1,72,20)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! Init {
+    ( $f:expr ) => {
+        _Init(Box::new($f))
+    };
+}
+
+#[doc(hidden)]
+pub struct _Exit<'a>(pub Box<dyn FilterTrait + 'a>);
+#[doc(hidden)]
+impl FilterTrait for _Exit<'_> {
+    fn run(&self, _evs: &mut EventStream) {}
+    fn run_exit(&self, evs: &mut EventStream) {
+        run_on_none(self.0.as_ref(), evs);
+        // There are no ticks after exit, so what the contents hold back is output too.
+        self.0.run_exit(evs);
+    }
+    fn run_tick(&self, evs: &mut EventStream) {
+        self.0.run_tick(evs);
+    }
+    fn next_tick(&self) -> Option<Instant> {
+        self.0.next_tick()
+    }
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.0.bind(ports)
+    }
+}
+/// Run contained filters on (sub)scene or patch exit.
+///
+/// Like [Init!], the contained filters get a single [NoneEvent], and what they output is sent
+/// as it is. As nothing is ticked after exit, what the contents hold back for later is output
+/// right after, e.g. the NoteOff of a [NoteWithDuration].
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let patch = Chain!(Exit!(Ctrl(123, 0)), Port(2));
+///
+/// let mut evs = EventStream::empty();
+/// patch.run_exit(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,123,0));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! Exit {
+    ( $f:expr ) => {
+        _Exit(Box::new($f))
+    };
+}
+
+// // Time
+
+/// Condition on a controller (CC) value, used by [ThresholdWatch].
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub enum ThresholdPredicate {
+    /// The value is larger than the argument.
+    Above(i32),
+    /// The value is smaller than the argument.
+    Below(i32),
+}
+pub use ThresholdPredicate::{Above, Below};
+
+impl ThresholdPredicate {
+    pub fn matches(&self, value: i32) -> bool {
+        match self {
+            Above(threshold) => value > *threshold,
+            Below(threshold) => value < *threshold,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+struct ThresholdState {
+    since: Option<Instant>,
+    fired: bool,
+    ev: CtrlEventImpl,
+}
+
+/// Runs a patch when a controller (CC) value keeps meeting a condition for some time.
+///
+/// The arguments are: _ctrl_, _predicate_, _hold_duration_, _patch_.
+///
+/// The condition is tracked separately for each port and channel. Once it has been met
+/// continuously for the hold duration, the patch is run once on the last controller event,
+/// typically to switch scenes. It is run again only after the condition was broken and
+/// then met for the hold duration again.
+///
+/// All incoming events are consumed, so use this inside a [Fork!] if you need them,
+/// or in the control patch. The hold can complete without new events coming in, the
+/// runner then wakes up in time to run the patch.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let watch = ThresholdWatch(2, Above(100), Duration::from_secs(3), SceneSwitch(3)).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,2,110));
+/// watch.run(&mut evs);
+/// assert!(evs.is_empty());
+///
+/// // The hold completes while no events come in.
+/// clock.advance(Duration::from_secs(3));
+/// let mut evs = EventStream::empty();
+/// watch.run_tick(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(3));
+///
+/// // It's only run once.
+/// clock.advance(Duration::from_secs(3));
+/// let mut evs = EventStream::from(CtrlEvent(0,0,2,120));
+/// watch.run(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+///
+/// When the condition is broken before the hold completes, nothing happens.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let watch = ThresholdWatch(2, Below(20), Duration::from_secs(5), SceneSwitch(1)).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,2,10));
+/// watch.run(&mut evs);
+/// assert_eq!(watch.next_tick(), Some(clock.now() + Duration::from_secs(5)));
+///
+/// clock.advance(Duration::from_secs(4));
+/// let mut evs = EventStream::from(CtrlEvent(0,0,2,50));
+/// watch.run(&mut evs);
+/// assert_eq!(watch.next_tick(), None);
+///
+/// clock.advance(Duration::from_secs(4));
+/// let mut evs = EventStream::empty();
+/// watch.run_tick(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+///
+/// After the condition is broken, the watch is armed again.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let watch = ThresholdWatch(2, Above(100), Duration::from_secs(3), SceneSwitch(3)).with_clock(clock.clone());
+///
+/// for value in [110, 50, 110].iter() {
+///     let mut evs = EventStream::from(CtrlEvent(0,0,2,*value));
+///     watch.run(&mut evs);
+///     clock.advance(Duration::from_secs(3));
+///     let mut evs = EventStream::empty();
+///     watch.run_tick(&mut evs);
+///     if *value > 100 {
+///         assert_eq!(evs, SceneSwitchEvent(3));
+///     } else {
+///         assert!(evs.is_empty());
+///     }
+/// }
+/// ```
+pub struct ThresholdWatch<'a> {
+    ctrl: u32,
+    predicate: ThresholdPredicate,
+    hold: Duration,
+    patch: Box<dyn FilterTrait + 'a>,
+    clock: Arc<dyn Clock>,
+    states: Mutex<HashMap<(usize, u8), ThresholdState>>,
+}
+
+pub fn ThresholdWatch<'a, F: FilterTrait + 'a>(ctrl: u32, predicate: ThresholdPredicate, hold: Duration, patch: F) -> ThresholdWatch<'a> {
+    ThresholdWatch {
+        ctrl,
+        predicate,
+        hold,
+        patch: Box::new(patch),
+        clock: system_clock(),
+        states: Mutex::new(HashMap::new()),
+    }
+}
+
+impl ThresholdWatch<'_> {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn run_completed(&self, states: &mut HashMap<(usize, u8), ThresholdState>, now: Instant, evs: &mut EventStream) {
+        for state in states.values_mut() {
+            if let Some(since) = state.since {
+                if !state.fired && now.saturating_duration_since(since) >= self.hold {
+                    state.fired = true;
+                    let mut patch_evs = EventStream::from(Event::Ctrl(state.ev));
+                    self.patch.run(&mut patch_evs);
+                    evs.extend(patch_evs);
+                }
+            }
+        }
+    }
+}
+
+impl FilterTrait for ThresholdWatch<'_> {
+    fn run(&self, evs: &mut EventStream) {
+        let now = self.clock.now();
+        let mut states = match self.states.lock() {
+            Ok(states) => states,
+            Err(_) => return,
+        };
+
+        for ev in evs.iter() {
+            if let Event::Ctrl(ev) = ev {
+                if ev.ctrl != self.ctrl { continue; }
+                let state = states.entry((ev.port, ev.channel)).or_default();
+                state.ev = *ev;
+                if !self.predicate.matches(ev.value) {
+                    state.since = None;
+                    state.fired = false;
+                } else if state.since.is_none() {
+                    state.since = Some(now);
+                }
+            }
+        }
+
+        evs.clear();
+        self.run_completed(&mut states, now, evs);
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        if let Ok(mut states) = self.states.lock() {
+            self.run_completed(&mut states, self.clock.now(), evs);
+        }
+        self.patch.run_tick(evs);
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        let states = self.states.lock().ok()?;
+        states.values()
+            .filter(|state| !state.fired)
+            .filter_map(|state| state.since.map(|since| since + self.hold))
+            .chain(self.patch.next_tick())
+            .min()
+    }
+
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.patch.bind(ports)
+    }
+
+    fn run_exit(&self, _evs: &mut EventStream) {
+        if let Ok(mut states) = self.states.lock() {
+            states.clear();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChordState {
+    // Held notes, with the time they were pressed.
+    held: HashMap<u8, Instant>,
+    fired: bool,
+}
+
+/// Adds a Chord event when all notes of a chord are held.
+///
+/// The arguments are: _notes_, _tolerance_ms_.
+///
+/// The notes have to be pressed within the tolerance of each other, so that playing the
+/// notes one by one over a longer time doesn't count. The Chord event is added right after
+/// the NoteOn that completes the chord, and only once: releasing one of the notes arms the
+/// detector again. Other events are passed unchanged, use [TypeFilter!] to act on the chord.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let detector = ChordDetector(&[60, 64, 67], 50);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,64,20), NoteOnEvent(0,0,67,20)]);
+/// detector.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,64,20), NoteOnEvent(0,0,67,20), ChordEvent(vec![60, 64, 67])]);
+///
+/// // Only once while the chord is held.
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,67,20));
+/// detector.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,67,20));
+///
+/// // Until a note is released.
+/// let mut evs = EventStream::from(vec![NoteOffEvent(0,0,64), NoteOnEvent(0,0,64,20)]);
+/// Chain!(detector, TypeFilter!(Chord), SceneSwitch(2)).run(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(2));
+/// # }
+/// ```
+///
+/// Notes pressed too far apart don't make a chord.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let detector = ChordDetector(vec![60, 64, 67], 50).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,64,20)]);
+/// detector.run(&mut evs);
+/// clock.advance(Duration::from_millis(100));
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,67,20));
+/// detector.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,67,20));
+/// ```
+pub struct ChordDetector {
+    notes: Vec<u8>,
+    tolerance: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<ChordState>,
+}
+
+pub fn ChordDetector<V: Into<Vec<u8>>>(notes: V, tolerance_ms: u64) -> ChordDetector {
+    ChordDetector {
+        notes: notes.into(),
+        tolerance: Duration::from_millis(tolerance_ms),
+        clock: system_clock(),
+        state: Mutex::new(ChordState::default()),
+    }
+}
+
+impl ChordDetector {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    fn is_complete(&self, held: &HashMap<u8, Instant>) -> bool {
+        let times: Option<Vec<&Instant>> = self.notes.iter().map(|note| held.get(note)).collect();
+        match times.as_ref().and_then(|times| Some((times.iter().min()?, times.iter().max()?))) {
+            Some((first, last)) => last.saturating_duration_since(**first) <= self.tolerance,
+            None => false,
+        }
+    }
+}
+
+impl FilterTrait for ChordDetector {
+    fn run(&self, evs: &mut EventStream) {
+        let now = self.clock.now();
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        let mut i = 0;
+        while i < evs.len() {
+            let mut chord = false;
+            match evs.iter().nth(i) {
+                Some(Event::NoteOn(ev)) => {
+                    state.held.insert(ev.note, now);
+                    if !state.fired && self.is_complete(&state.held) {
+                        state.fired = true;
+                        chord = true;
+                    }
+                },
+                Some(Event::NoteOff(ev)) => {
+                    state.held.remove(&ev.note);
+                    if self.notes.contains(&ev.note) { state.fired = false; }
+                },
+                _ => {},
+            }
+            i += 1;
+            if chord {
+                evs.insert(i, ChordEvent(self.notes.clone()));
+                i += 1;
+            }
+        }
+    }
+
+    fn run_exit(&self, _evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = ChordState::default();
+        }
+    }
+}
+
+/// Passes events only while a controller (CC) is at or above a threshold, like a gate.
+///
+/// The arguments are: _ctrl_, _threshold_.
+///
+/// The gate starts closed, as if the controller were at 0. Controller events for _ctrl_ open or
+/// close it, and are consumed; other events are passed while the gate is open, and discarded
+/// while it's closed. Inside [Not!], events are passed only while the gate is closed.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// // Only play while the sustain pedal is down.
+/// let gate = Gate(64, 64);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,64,127), NoteOnEvent(0,0,62,20)]);
+/// gate.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,62,20));
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,100), CtrlEvent(0,0,64,0), NoteOnEvent(0,0,64,20)]);
+/// gate.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,7,100));
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let gate = Not!(Gate(64, 64));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,64,127), NoteOnEvent(0,0,62,20)]);
+/// gate.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// # }
+/// ```
+pub struct Gate {
+    ctrl: u32,
+    threshold: i32,
+    value: Mutex<i32>,
+}
+
+pub fn Gate(ctrl: u32, threshold: i32) -> Gate {
+    Gate { ctrl, threshold, value: Mutex::new(0) }
+}
+
+impl Gate {
+    fn run_gate(&self, evs: &mut EventStream, pass_when_open: bool) {
+        let mut value = match self.value.lock() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        evs.retain(|ev| match ev {
+            Event::Ctrl(ev) if ev.ctrl == self.ctrl => {
+                *value = ev.value;
+                false
+            },
+            _ => (*value >= self.threshold) == pass_when_open,
+        });
+    }
+}
+
+impl FilterTrait for Gate {
+    fn run(&self, evs: &mut EventStream) {
+        self.run_gate(evs, true);
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        self.run_gate(evs, false);
+    }
+}
+
+/// Passes events only while a note is held, e.g. for a foot switch sending notes.
+///
+/// The argument is: _trigger_note_.
+///
+/// Like [Gate], but a NoteOn of _trigger_note_ opens it, and its NoteOff closes it. These notes
+/// are consumed, and the gate starts closed.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let gate = GateNote(36);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,9,36,100), NoteOnEvent(0,0,62,20)]);
+/// gate.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,62,20));
+///
+/// let mut evs = EventStream::from(vec![NoteOffEvent(0,0,62), NoteOffEvent(0,9,36), NoteOnEvent(0,0,64,20)]);
+/// gate.run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,62));
+/// ```
+pub struct GateNote {
+    note: u8,
+    open: Mutex<bool>,
+}
+
+pub fn GateNote(trigger_note: u8) -> GateNote {
+    GateNote { note: trigger_note, open: Mutex::new(false) }
+}
+
+impl GateNote {
+    fn run_gate(&self, evs: &mut EventStream, pass_when_open: bool) {
+        let mut open = match self.open.lock() {
+            Ok(open) => open,
+            Err(_) => return,
+        };
+        evs.retain(|ev| match ev {
+            Event::NoteOn(ev) if ev.note == self.note => {
+                *open = true;
+                false
+            },
+            Event::NoteOff(ev) if ev.note == self.note => {
+                *open = false;
+                false
+            },
+            _ => *open == pass_when_open,
+        });
+    }
+}
+
+impl FilterTrait for GateNote {
+    fn run(&self, evs: &mut EventStream) {
+        self.run_gate(evs, true);
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        self.run_gate(evs, false);
+    }
+}
+
+/// Passes only every n-th MIDI clock event, on each port.
+///
+/// The argument is: _divisor_.
+///
+/// The first clock event is passed, and a Start event restarts counting so that the divided
+/// clock stays aligned with the beat. Other events are passed unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// // Half tempo.
+/// let filter = ClockDivider(2);
+///
+/// let mut evs = EventStream::from(vec![StartEvent(0), ClockEvent(0), ClockEvent(0), ClockEvent(0)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![StartEvent(0), ClockEvent(0), ClockEvent(0)]);
+///
+/// let mut evs = EventStream::from(vec![ClockEvent(0), ClockEvent(1)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, ClockEvent(1));
+/// ```
+pub struct ClockDivider {
+    divisor: u32,
+    // Number of clock events seen since start, for each port.
+    counts: Mutex<HashMap<usize, u32>>,
+}
+
+pub fn ClockDivider(divisor: u32) -> ClockDivider {
+    ClockDivider { divisor: divisor.max(1), counts: Mutex::new(HashMap::new()) }
+}
+
+impl FilterTrait for ClockDivider {
+    fn run(&self, evs: &mut EventStream) {
+        let mut counts = match self.counts.lock() {
+            Ok(counts) => counts,
+            Err(_) => return,
+        };
+
+        evs.retain(|ev| match ev {
+            Event::Clock(ev) => {
+                let count = counts.entry(ev.port).or_insert(0);
+                let pass = *count == 0;
+                *count = (*count + 1) % self.divisor;
+                pass
+            },
+            Event::Start(ev) => {
+                counts.remove(&ev.port);
+                true
+            },
+            _ => true,
+        });
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut counts) = self.counts.lock() {
+            counts.clear();
+        }
+    }
+}
+
+define_filter!(
+    /// Filter on song position, in sixteenth notes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let filter = SongPositionFilter(0);
+    ///
+    /// let mut evs = EventStream::from(vec![SongPositionEvent(0,0), SongPositionEvent(0,16), ClockEvent(0)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![SongPositionEvent(0,0), ClockEvent(0)]);
+    /// ```
+    SongPositionFilter(u16)
+    fn filter_single(&self, ev: &Event) -> bool {
+        match ev {
+            Event::SongPosition(ev) => ev.position == self.0,
+            _ => true,
+        }
+    }
+);
+
+define_filter!(
+    /// Filter on song select number
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let filter = SongSelectFilter(2);
+    ///
+    /// let mut evs = EventStream::from(vec![SongSelectEvent(0,1), SongSelectEvent(0,2)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, SongSelectEvent(0,2));
+    /// ```
+    SongSelectFilter(u8)
+    fn filter_single(&self, ev: &Event) -> bool {
+        match ev {
+            Event::SongSelect(ev) => ev.song == self.0,
+            _ => true,
+        }
+    }
+);
+
+define_filter!(
+    /// Filter on the type of MIDI Timecode quarter frames (0-7)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let filter = MtcQuarterFrameFilter(7);
+    ///
+    /// let mut evs = EventStream::from(vec![MtcQuarterFrameEvent(0,0,4), MtcQuarterFrameEvent(0,7,2)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, MtcQuarterFrameEvent(0,7,2));
+    /// ```
+    MtcQuarterFrameFilter(u8)
+    fn filter_single(&self, ev: &Event) -> bool {
+        match ev {
+            Event::MtcQuarterFrame(ev) => ev.message_type == self.0,
+            _ => true,
+        }
+    }
+);
+
+/// Replaces MIDI Timecode quarter frames by the timecode they make up, on each port.
+///
+/// After the eight quarter frames of a timecode, from type 0 to 7, an [MtcFullFrameEvent] is
+/// output with that time. Quarter frames are discarded, other events are passed unchanged.
+/// As the quarter frames take two frames to send, the time is two frames behind by then.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = MtcAssembler();
+///
+/// // 01:02:03:04 at 25 fps.
+/// let pieces = [4, 0, 3, 0, 2, 0, 1, 2];
+/// let mut evs: EventStream = pieces.iter().enumerate().map(|(i, v)| MtcQuarterFrameEvent(0, i as u8, *v)).collect();
+/// evs.push(ClockEvent(0));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![MtcFullFrameEvent(0,1,2,3,4,MtcFrameRate::Fps25), ClockEvent(0)]);
+///
+/// // An incomplete timecode gives nothing.
+/// let mut evs = EventStream::from(vec![MtcQuarterFrameEvent(0,6,1), MtcQuarterFrameEvent(0,7,2)]);
+/// filter.run(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+pub struct MtcAssembler {
+    // Values of the quarter frames received since the last one of type 0, for each port.
+    pieces: Mutex<HashMap<usize, [Option<u8>; 8]>>,
+}
+
+pub fn MtcAssembler() -> MtcAssembler {
+    MtcAssembler { pieces: Mutex::new(HashMap::new()) }
+}
+
+impl FilterTrait for MtcAssembler {
+    fn run(&self, evs: &mut EventStream) {
+        let mut pieces = match self.pieces.lock() {
+            Ok(pieces) => pieces,
+            Err(_) => return,
+        };
+
+        let mut evs_out = EventStream::empty();
+        for ev in evs.drain(..) {
+            let Event::MtcQuarterFrame(qf) = ev else {
+                evs_out.push(ev);
+                continue;
+            };
+            let message_type = (qf.message_type & 0x07) as usize;
+            let port_pieces = pieces.entry(qf.port).or_insert([None; 8]);
+            if message_type == 0 { *port_pieces = [None; 8]; }
+            port_pieces[message_type] = Some(qf.value & 0x0f);
+            if message_type != 7 || port_pieces.iter().any(Option::is_none) { continue; }
+
+            let p = port_pieces.map(Option::unwrap_or_default);
+            *port_pieces = [None; 8];
+            evs_out.push(MtcFullFrameEvent(
+                qf.port,
+                p[6] | (p[7] & 0x01) << 4,
+                p[4] | (p[5] & 0x03) << 4,
+                p[2] | (p[3] & 0x03) << 4,
+                p[0] | (p[1] & 0x01) << 4,
+                MtcFrameRate::from_code(p[7] >> 1),
+            ));
+        }
+        evs.replace(evs_out);
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut pieces) = self.pieces.lock() {
+            pieces.clear();
+        }
+    }
+}
+
+/// Passes only every n-th event.
+///
+/// The arguments are: _n_, _initial_.
+///
+/// The counter starts at _initial_ and is incremented for each event, the event is passed
+/// when the counter is a multiple of _n_. All events are counted, so put a filter in front
+/// of it to count e.g. only notes. The counter starts again when the patch is initialized.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Counter(4, 0);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,63,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,63,20));
+///
+/// // To pass the first event too, start just before a multiple.
+/// let filter = Counter(2, 1);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20), NoteOnEvent(0,0,62,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20)]);
+/// ```
+pub struct Counter {
+    n: usize,
+    initial: usize,
+    counter: Arc<Mutex<usize>>,
+}
+
+pub fn Counter(n: usize, initial: usize) -> Counter {
+    Counter { n, initial, counter: Arc::new(Mutex::new(initial)) }
+}
+
+impl Counter {
+    /// Returns a filter that restarts this counter on events passing the target filter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use] extern crate rmididings;
+    /// # use rmididings::proc::*;
+    /// # fn main() {
+    /// // Pass the first of every four beats, starting again at each bar.
+    /// let counter = Counter(4, 3);
+    /// let filter = Fork!(
+    ///     Chain!(counter.reset_on(&TypeFilter!(Start)), Discard()),
+    ///     Chain!(TypeFilter!(Note), counter)
+    /// );
+    ///
+    /// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+    ///
+    /// let mut evs = EventStream::from(vec![StartEvent(0), NoteOnEvent(0,0,62,20)]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,62,20));
+    /// # }
+    /// ```
+    pub fn reset_on(&self, target: &'static dyn FilterTrait) -> CounterReset {
+        CounterReset { target, initial: self.initial, counter: self.counter.clone() }
+    }
+}
+
+impl FilterTrait for Counter {
+    fn run(&self, evs: &mut EventStream) {
+        let mut counter = match self.counter.lock() {
+            Ok(counter) => counter,
+            Err(_) => return,
+        };
+
+        evs.retain(|_| {
+            *counter = counter.wrapping_add(1);
+            counter.checked_rem(self.n) == Some(0)
+        });
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut counter) = self.counter.lock() {
+            *counter = self.initial;
+        }
+    }
+}
+
+/// Restarts a [Counter] on events passing the target filter, see [Counter::reset_on].
+///
+/// Events are passed unchanged.
+pub struct CounterReset {
+    target: &'static dyn FilterTrait,
+    initial: usize,
+    counter: Arc<Mutex<usize>>,
+}
+
+impl FilterTrait for CounterReset {
+    fn run(&self, evs: &mut EventStream) {
+        let matches = evs.iter().any(|ev| {
+            let mut target_evs = EventStream::from(ev);
+            self.target.run(&mut target_evs);
+            !target_evs.is_empty()
+        });
+        if matches {
+            if let Ok(mut counter) = self.counter.lock() {
+                *counter = self.initial;
+            }
+        }
+    }
+}
+
+/// MIDI clock ticks per quarter note.
+pub const CLOCK_PPQN: u32 = 24;
+
+/// Generates MIDI clock events at a fixed tempo.
+///
+/// The argument is: _bpm_.
+///
+/// Clock events are sent 24 times per quarter note on the configured ports (see
+/// [ClockGenerator::with_ports]), while running. A Start or Continue event passing through
+/// starts it, a Stop event stops it; see [TransportStart] and [TransportStop]. Use
+/// [ClockGenerator::started] to run from the start of the patch. Tick times are derived from
+/// when it was started, so wake-up delays don't accumulate.
+///
+/// The `midi_clock_bpm` option of `ConfigArguments` runs one for all output ports.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let generator = ClockGenerator(60.0).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(StartEvent(0));
+/// generator.run(&mut evs);
+/// assert_eq!(evs, StartEvent(0));
+///
+/// // Wake up every 7 ms for 10 seconds.
+/// let mut ticks = 0;
+/// for _ in 0..(10_000 / 7) {
+///     clock.advance(Duration::from_millis(7));
+///     let mut evs = EventStream::empty();
+///     generator.run_tick(&mut evs);
+///     ticks += evs.len();
+/// }
+/// // 24 ticks per beat at one beat per second.
+/// assert!((239..=241).contains(&ticks));
+/// ```
+///
+/// The runner wakes up when the next tick is due.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let clock = FakeClock::new();
+/// let start = clock.now();
+/// let generator = ClockGenerator(240.0).with_ports(&[1, 2]).with_clock(clock.clone()).started();
+/// generator.run_init(&mut EventStream::none());
+///
+/// let mut ticks = 0;
+/// while let Some(next_tick) = generator.next_tick() {
+///     if next_tick > start + std::time::Duration::from_secs(10) { break; }
+///     clock.advance(next_tick - clock.now());
+///     let mut evs = EventStream::empty();
+///     generator.run_tick(&mut evs);
+///     assert_eq!(evs, vec![ClockEvent(1), ClockEvent(2)]);
+///     ticks += 1;
+/// }
+/// assert!((959..=961).contains(&ticks));
+///
+/// generator.run(&mut EventStream::from(StopEvent(0)));
+/// assert_eq!(generator.next_tick(), None);
+/// ```
+pub struct ClockGenerator {
+    interval: Duration,
+    ports: Vec<usize>,
+    autostart: bool,
+    clock: Arc<dyn Clock>,
+    state: Mutex<ClockGeneratorState>,
+}
+
+#[derive(Debug, Default)]
+struct ClockGeneratorState {
+    // When the clock was started, if it is running.
+    started: Option<Instant>,
+    // Number of ticks sent since it was started.
+    ticks: u32,
+}
+
+pub fn ClockGenerator(bpm: f32) -> ClockGenerator {
+    ClockGenerator {
+        interval: Duration::from_secs_f64(60.0 / (bpm.max(1.0) as f64 * CLOCK_PPQN as f64)),
+        ports: vec![0],
+        autostart: false,
+        clock: system_clock(),
+        state: Mutex::new(ClockGeneratorState::default()),
+    }
+}
+
+impl ClockGenerator {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Send clock events to these ports, instead of port 0.
+    pub fn with_ports(mut self, ports: &[usize]) -> Self {
+        self.ports = ports.to_vec();
+        self
+    }
+
+    /// Start running when the patch is initialized, without waiting for a Start event.
+    pub fn started(mut self) -> Self {
+        self.autostart = true;
+        self
+    }
+
+    fn start(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = ClockGeneratorState { started: Some(self.clock.now()), ticks: 0 };
+        }
+    }
+
+    fn stop(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.started = None;
+        }
+    }
+}
+
+impl FilterTrait for ClockGenerator {
+    fn run(&self, evs: &mut EventStream) {
+        for ev in evs.iter() {
+            match ev {
+                Event::Start(_) | Event::Continue(_) => self.start(),
+                Event::Stop(_) => self.stop(),
+                _ => {},
+            }
+        }
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if self.autostart {
+            self.start();
+        }
+    }
+
+    fn run_exit(&self, _evs: &mut EventStream) {
+        self.stop();
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let started = match state.started {
+            Some(started) => started,
+            None => return,
+        };
+
+        let now = self.clock.now();
+        while started + self.interval * state.ticks <= now {
+            for port in self.ports.iter() {
+                evs.push(ClockEvent(*port));
+            }
+            state.ticks += 1;
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        let state = self.state.lock().ok()?;
+        state.started.map(|started| started + self.interval * state.ticks)
+    }
+}
+
+define_generator!(
+    /// Generate a Start event, to start sequencers and [ClockGenerator].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = TransportStart();
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(1,0,60,20));
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, StartEvent(1));
+    /// ```
+    TransportStart()
+    fn generate_single(&self) -> Event<'static> {
+        StartEvent(0)
+    }
+);
+
+define_generator!(
+    /// Generate a Stop event, to stop sequencers and [ClockGenerator].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = TransportStop();
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, StopEvent(0));
+    /// ```
+    TransportStop()
+    fn generate_single(&self) -> Event<'static> {
+        StopEvent(0)
+    }
+);
+
+define_generator!(
+    /// Generate a Resync event, to start counting bars from now for quantized scene switches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let generator = Resync();
+    ///
+    /// let mut evs = EventStream::none();
+    /// generator.run(&mut evs);
+    /// assert_eq!(evs, ResyncEvent());
+    /// ```
+    Resync()
+    fn generate_single(&self) -> Event<'static> {
+        ResyncEvent()
+    }
+);
+
+/// Randomizes NoteOn velocity and note timing, so that programmed parts sound less mechanical.
+///
+/// The arguments are: _velocity_spread_, _timing_spread_ms_.
+///
+/// Velocities of NoteOn events are changed by up to _velocity_spread_ either way, staying within
+/// 1..127. Notes are delayed by up to _timing_spread_ms_ milliseconds, and their NoteOff by the
+/// same amount so that note lengths are kept. Other events are passed unchanged.
+///
+/// Use [Humanize::with_seed] to get the same results each time, e.g. for testing.
+/// Delayed notes that are still waiting when the patch exits are output directly.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let filter = Humanize(10, 20).with_seed(1).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,100), CtrlEvent(0,0,7,100)]);
+/// filter.run(&mut evs);
+/// clock.advance(Duration::from_millis(20));
+/// filter.run_tick(&mut evs);
+///
+/// let evs: Vec<Event> = evs.into_iter().collect();
+/// assert_eq!(evs.len(), 2);
+/// assert_eq!(evs[0], CtrlEvent(0,0,7,100));
+/// if let Event::NoteOn(ev) = evs[1] {
+///     assert!(ev.velocity >= 90 && ev.velocity <= 110);
+/// } else {
+///     panic!("expected a NoteOn event");
+/// }
+///
+/// // Without timing spread, notes are output directly.
+/// let filter = Humanize(10, 0).with_seed(1);
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,127), NoteOffEvent(0,0,60)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs.len(), 2);
+/// assert_eq!(evs.into_iter().last(), Some(NoteOffEvent(0,0,60)));
+/// ```
+///
+/// As the `post` patch of the runner, delayed notes are output when they are due.
+///
+/// ```
+/// # use rmididings::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let post = Humanize(0, 20).with_seed(1).with_clock(clock.clone());
+/// let mut md = RMididings::builder().backend(BackendType::Null).out_port("out", "").build().unwrap();
+/// let null = md.null_backend().unwrap();
+/// let mut runner = md.start(RunArguments { patch: &Pass(), post: &post, ..RunArguments::default() }).unwrap();
+///
+/// runner.inject_event(NoteOnEvent(0,0,60,100)).unwrap();
+/// assert_eq!(null.take_output(), vec![]);
+/// clock.advance(Duration::from_millis(20));
+/// runner.step(Duration::ZERO).unwrap();
+/// assert_eq!(null.take_output(), vec![NoteOnEvent(0,0,60,100)]);
+/// ```
+pub struct Humanize {
+    velocity_spread: u8,
+    timing_spread: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<HumanizeState>,
+}
+
+#[derive(Default)]
+struct HumanizeState {
+    rng: Rng,
+    scheduler: Scheduler,
+    // Delay of each sounding note by (port, channel, note), to delay its NoteOff equally.
+    delays: HashMap<(usize, u8, u8), Duration>,
+}
+
+pub fn Humanize(velocity_spread: u8, timing_spread_ms: u32) -> Humanize {
+    Humanize {
+        velocity_spread,
+        timing_spread: Duration::from_millis(timing_spread_ms as u64),
+        clock: system_clock(),
+        state: Mutex::new(HumanizeState { rng: Rng::from_time(), ..HumanizeState::default() }),
+    }
+}
+
+impl Humanize {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Use a fixed seed for the random numbers, so that results are the same each time.
+    pub fn with_seed(self, seed: u64) -> Self {
+        if let Ok(mut state) = self.state.lock() {
+            state.rng = Rng::new(seed);
+        }
+        self
+    }
+}
+
+impl FilterTrait for Humanize {
+    fn run(&self, evs: &mut EventStream) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let now = self.clock.now();
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            let (ev, delay) = match ev {
+                Event::NoteOn(ev) if ev.velocity > 0 => {
+                    let mut ev = *ev;
+                    let spread = self.velocity_spread as i32;
+                    let offset = state.rng.random_up_to(2 * spread as u64) as i32 - spread;
+                    ev.velocity = (ev.velocity as i32 + offset).clamp(1, 127) as u8;
+                    let delay = if self.timing_spread.is_zero() {
+                        Duration::ZERO
+                    } else {
+                        Duration::from_micros(state.rng.random_up_to(self.timing_spread.as_micros() as u64))
+                    };
+                    state.delays.insert((ev.port, ev.channel, ev.note), delay);
+                    (Event::NoteOn(ev), delay)
+                },
+                Event::NoteOn(ev) => {
+                    let delay = state.delays.remove(&(ev.port, ev.channel, ev.note)).unwrap_or_default();
+                    (Event::NoteOn(*ev), delay)
+                },
+                Event::NoteOff(ev) => {
+                    let delay = state.delays.remove(&(ev.port, ev.channel, ev.note)).unwrap_or_default();
+                    (Event::NoteOff(*ev), delay)
+                },
+                _ => {
+                    out.push(ev.clone());
+                    continue;
+                },
+            };
+            if delay.is_zero() {
+                out.push(ev);
+            } else {
+                state.scheduler.schedule(now + delay, ev);
+            }
+        }
+
+        *evs = out;
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.scheduler.run_due(self.clock.now(), evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        self.state.lock().ok()?.scheduler.next_due()
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.scheduler.run_all(evs);
+            state.delays.clear();
+        }
+    }
+}
+
+/// In which order [Strum] plays the notes of a chord.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub enum StrumDirection {
+    /// From the lowest note to the highest.
+    Up,
+    /// From the highest note to the lowest.
+    Down,
+    /// In a random order, different each time.
+    Random,
+}
+
+/// Spreads out the notes of a chord in time, like strumming a guitar.
+///
+/// The arguments are: _delay_per_note_ms_, _direction_.
+///
+/// NoteOn events arriving together are sorted by note in the given direction; the first
+/// one is output directly, each next one _delay_per_note_ms_ milliseconds after the previous.
+/// Their NoteOffs are delayed by the same amount, so that note lengths are kept. Other events
+/// are passed unchanged. Delayed notes that are still waiting when the patch exits are output
+/// directly.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let filter = Strum(30, StrumDirection::Down).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,100), NoteOnEvent(0,0,64,100), NoteOnEvent(0,0,67,100)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,67,100));
+///
+/// let mut evs = EventStream::empty();
+/// clock.advance(Duration::from_millis(30));
+/// filter.run_tick(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,64,100));
+///
+/// let mut evs = EventStream::empty();
+/// clock.advance(Duration::from_millis(30));
+/// filter.run_tick(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,100));
+///
+/// // The NoteOff of the last note played is delayed too.
+/// let mut evs = EventStream::from(vec![NoteOffEvent(0,0,60), NoteOffEvent(0,0,67)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,67));
+///
+/// let mut evs = EventStream::empty();
+/// clock.advance(Duration::from_millis(60));
+/// filter.run_tick(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,60));
+/// ```
+pub struct Strum {
+    delay_per_note: Duration,
+    direction: StrumDirection,
+    clock: Arc<dyn Clock>,
+    state: Mutex<StrumState>,
+}
+
+#[derive(Default)]
+struct StrumState {
+    rng: Rng,
+    scheduler: Scheduler,
+    // Delay of each sounding note by (port, channel, note), to delay its NoteOff equally.
+    delays: HashMap<(usize, u8, u8), Duration>,
+}
+
+pub fn Strum(delay_per_note_ms: u64, direction: StrumDirection) -> Strum {
+    Strum {
+        delay_per_note: Duration::from_millis(delay_per_note_ms),
+        direction,
+        clock: system_clock(),
+        state: Mutex::new(StrumState { rng: Rng::from_time(), ..StrumState::default() }),
+    }
+}
+
+impl Strum {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Use a fixed seed for the random order, so that results are the same each time.
+    pub fn with_seed(self, seed: u64) -> Self {
+        if let Ok(mut state) = self.state.lock() {
+            state.rng = Rng::new(seed);
+        }
+        self
+    }
+
+    // Notes of the NoteOn events in playing order.
+    fn order(&self, state: &mut StrumState, evs: &EventStream) -> Vec<(usize, u8, u8)> {
+        let mut notes: Vec<(usize, u8, u8)> = evs.iter().filter_map(|ev| match ev {
+            Event::NoteOn(ev) if ev.velocity > 0 => Some((ev.port, ev.channel, ev.note)),
+            _ => None,
+        }).collect();
+        match self.direction {
+            StrumDirection::Up => notes.sort_by_key(|(_, _, note)| *note),
+            StrumDirection::Down => notes.sort_by_key(|(_, _, note)| std::cmp::Reverse(*note)),
+            StrumDirection::Random => {
+                // Fisher-Yates shuffle
+                for i in (1..notes.len()).rev() {
+                    let j = state.rng.random_up_to(i as u64) as usize;
+                    notes.swap(i, j);
+                }
+            },
+        }
+        notes
+    }
+}
+
+impl FilterTrait for Strum {
+    fn run(&self, evs: &mut EventStream) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let now = self.clock.now();
+
+        for (i, key) in self.order(&mut state, evs).into_iter().enumerate() {
+            state.delays.insert(key, self.delay_per_note * i as u32);
+        }
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            let (ev, delay) = match ev {
+                Event::NoteOn(ev) if ev.velocity > 0 => (Event::NoteOn(*ev), state.delays.get(&(ev.port, ev.channel, ev.note)).copied()),
+                Event::NoteOn(ev) => (Event::NoteOn(*ev), state.delays.remove(&(ev.port, ev.channel, ev.note))),
+                Event::NoteOff(ev) => (Event::NoteOff(*ev), state.delays.remove(&(ev.port, ev.channel, ev.note))),
+                _ => {
+                    out.push(ev.clone());
+                    continue;
+                },
+            };
+            match delay {
+                Some(delay) if !delay.is_zero() => state.scheduler.schedule(now + delay, ev),
+                _ => out.push(ev),
+            }
+        }
+
+        *evs = out;
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.scheduler.run_due(self.clock.now(), evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        self.state.lock().ok()?.scheduler.next_due()
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.scheduler.run_all(evs);
+            state.delays.clear();
+        }
+    }
+}
+
+/// Generate a NoteOn event, and its NoteOff after a fixed duration.
+///
+/// The arguments are: _note_, _velocity_, _duration_ms_.
+///
+/// Like other generators, port and channel are taken from the incoming event, e.g. to play a
+/// note of fixed length on a single pad press or an OSC message.
+///
+/// The NoteOff is kept in a [Scheduler] and output from [FilterTrait::run_tick], which the
+/// runner calls when [FilterTrait::next_tick] is due; when using it outside of the runner, call
+/// these yourself. When the note is generated again before it ended, it is ended directly and
+/// started anew. Pending NoteOffs are output directly when the patch exits.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let generator = NoteWithDuration(60, 100, 500).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(CtrlEvent(1,2,64,127));
+/// generator.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(1,2,60,100));
+/// assert_eq!(generator.next_tick(), Some(clock.now() + Duration::from_millis(500)));
+///
+/// let mut evs = EventStream::empty();
+/// clock.advance(Duration::from_millis(500));
+/// generator.run_tick(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(1,2,60));
+/// assert_eq!(generator.next_tick(), None);
+/// ```
+///
+/// Retriggering a sounding note.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let generator = NoteWithDuration(60, 100, 500).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::none();
+/// generator.run(&mut evs);
+/// clock.advance(Duration::from_millis(200));
+///
+/// let mut evs = EventStream::none();
+/// generator.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOffEvent(0,0,60), NoteOnEvent(0,0,60,100)]);
+/// assert_eq!(generator.next_tick(), Some(clock.now() + Duration::from_millis(500)));
+/// ```
+///
+/// Playing a note on start, inside [Init!] the runner ticks it too.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::*;
+/// # fn main() {
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let patch = Fork!(Init!(NoteWithDuration(60, 100, 500).with_clock(clock.clone())), Pass());
+/// let mut md = RMididings::builder().backend(BackendType::Null).out_port("out", "").build().unwrap();
+/// let null = md.null_backend().unwrap();
+/// let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+///
+/// runner.step(Duration::ZERO).unwrap();
+/// assert_eq!(null.take_output(), vec![NoteOnEvent(0,0,60,100)]);
+/// clock.advance(Duration::from_millis(500));
+/// runner.step(Duration::ZERO).unwrap();
+/// assert_eq!(null.take_output(), vec![NoteOffEvent(0,0,60)]);
+/// # }
+/// ```
+pub struct NoteWithDuration {
+    note: u8,
+    velocity: u8,
+    duration: Duration,
+    clock: Arc<dyn Clock>,
+    scheduler: Mutex<Scheduler>,
+}
+
+pub fn NoteWithDuration(note: u8, velocity: u8, duration_ms: u64) -> NoteWithDuration {
+    NoteWithDuration {
+        note,
+        velocity,
+        duration: Duration::from_millis(duration_ms),
+        clock: system_clock(),
+        scheduler: Mutex::new(Scheduler::new()),
+    }
+}
+
+impl NoteWithDuration {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl FilterTrait for NoteWithDuration {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        let mut scheduler = match self.scheduler.lock() {
+            Ok(scheduler) => scheduler,
+            Err(_) => return,
+        };
+        let off_at = self.clock.now() + self.duration;
+
+        // One note for each port and channel, like other generators.
+        let mut targets: Vec<(usize, u8)> = vec![];
+        for ev in evs.iter() {
+            let target = (ev.port().unwrap_or_else(first_port), ev.channel().unwrap_or_else(first_channel));
+            if !targets.contains(&target) { targets.push(target); }
+        }
+
+        let mut out = EventStream::empty();
+        for (port, channel) in targets {
+            let note_off = NoteOffEvent(port, channel, self.note);
+            if scheduler.remove(&note_off) {
+                out.push(note_off.clone());
+            }
+            out.push(NoteOnEvent(port, channel, self.note, self.velocity));
+            scheduler.schedule(off_at, note_off);
+        }
+        *evs = out;
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        if let Ok(mut scheduler) = self.scheduler.lock() {
+            scheduler.run_due(self.clock.now(), evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        self.scheduler.lock().ok()?.next_due()
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(mut scheduler) = self.scheduler.lock() {
+            scheduler.run_all(evs);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CtrlSmoothState {
+    // Last value output, for each port and channel.
+    values: HashMap<(usize, u8), i32>,
+    scheduler: Scheduler,
+}
+
+/// Smooths jumps of a controller (CC) by adding values in between, against zipper noise.
+///
+/// The arguments are: _ctrl_, _steps_.
+///
+/// When the controller changes, the change is spread over _steps_ values, each
+/// [CtrlSmooth::with_interval] apart (5 ms by default). The first one replaces the incoming
+/// event, the others are kept in a [Scheduler] and output from [FilterTrait::run_tick], which
+/// the runner calls when [FilterTrait::next_tick] is due; when using it outside of the runner,
+/// call these yourself. A new value while still smoothing continues from the last value output.
+///
+/// This is done for each port and channel. As there is nothing to smooth from, the first value
+/// is passed unchanged, like other events.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let filter = CtrlSmooth(7, 4).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,7,0));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,7,0));
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,100), NoteOnEvent(0,0,60,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,25), NoteOnEvent(0,0,60,20)]);
+/// assert_eq!(filter.next_tick(), Some(clock.now() + Duration::from_millis(5)));
+///
+/// let mut evs = EventStream::empty();
+/// clock.advance(Duration::from_millis(15));
+/// filter.run_tick(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,50), CtrlEvent(0,0,7,75), CtrlEvent(0,0,7,100)]);
+/// assert_eq!(filter.next_tick(), None);
+/// ```
+///
+/// Changing direction halfway.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let filter = CtrlSmooth(7, 2).with_interval(10).with_clock(clock.clone());
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,0), CtrlEvent(0,0,7,100)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,0), CtrlEvent(0,0,7,50)]);
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,7,0));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,7,25));
+///
+/// let mut evs = EventStream::empty();
+/// clock.advance(Duration::from_millis(10));
+/// filter.run_tick(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,7,0));
+/// ```
+pub struct CtrlSmooth {
+    ctrl: u32,
+    steps: u32,
+    interval: Duration,
+    clock: Arc<dyn Clock>,
+    state: Mutex<CtrlSmoothState>,
+}
+
+pub fn CtrlSmooth(ctrl: u32, steps: u32) -> CtrlSmooth {
+    CtrlSmooth {
+        ctrl,
+        steps: steps.max(1),
+        interval: Duration::from_millis(5),
+        clock: system_clock(),
+        state: Mutex::new(CtrlSmoothState::default()),
+    }
+}
+
+impl CtrlSmooth {
+    /// Time between the smoothed values, in milliseconds.
+    pub fn with_interval(mut self, interval_ms: u64) -> Self {
+        self.interval = Duration::from_millis(interval_ms);
+        self
+    }
+
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl FilterTrait for CtrlSmooth {
+    fn run(&self, evs: &mut EventStream) {
+        let now = self.clock.now();
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        let state = &mut *state;
+
+        for ev in evs.iter_mut() {
+            let Event::Ctrl(ctrl) = ev else { continue };
+            if ctrl.ctrl != self.ctrl { continue; }
+            let key = (ctrl.port, ctrl.channel);
+            let Some(&from) = state.values.get(&key) else {
+                state.values.insert(key, ctrl.value);
+                continue;
+            };
+
+            // A new value replaces the rest of the previous change.
+            state.scheduler.retain(|pending| !matches!(pending, Event::Ctrl(p) if p.ctrl == self.ctrl && (p.port, p.channel) == key));
+
+            let target = ctrl.value;
+            let steps = self.steps as i64;
+            let value = |step: i64| (from as i64 + (target as i64 - from as i64) * step / steps) as i32;
+            ctrl.value = value(1);
+            state.values.insert(key, ctrl.value);
+            let mut previous = ctrl.value;
+            for step in 2..=steps {
+                if value(step) == previous { continue; }
+                previous = value(step);
+                let at = now + self.interval * (step - 1) as u32;
+                state.scheduler.schedule(at, CtrlEvent(ctrl.port, ctrl.channel, self.ctrl, previous));
+            }
+        }
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            let mut due = EventStream::empty();
+            state.scheduler.run_due(self.clock.now(), &mut due);
+            for ev in due.iter() {
+                if let Event::Ctrl(ev) = ev {
+                    state.values.insert((ev.port, ev.channel), ev.value);
+                }
+            }
+            evs.extend(due);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        self.state.lock().ok()?.scheduler.next_due()
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.scheduler.run_all(evs);
+            state.values.clear();
+        }
+    }
+}
+
+// // Note handling
+
+/// How [Monophonic] handles a new note while another one is held.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub enum MonoMode {
+    /// Send a NoteOff for the previous note before the new NoteOn.
+    Retrigger,
+    /// Send the NoteOff for the previous note after the new NoteOn, so the notes overlap.
+    Legato,
+}
+
+/// Allows only one note at a time on each port and channel, for a mono synth.
+///
+/// The argument is: _mode_, either [MonoMode::Retrigger] or [MonoMode::Legato].
+///
+/// Held notes are remembered, so when the newest note is released while an older one is
+/// still held, the older note is played again. NoteOffs for notes that were already
+/// turned off are dropped. Other events are passed unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Monophonic(MonoMode::Retrigger);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,62,30));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOffEvent(0,0,60), NoteOnEvent(0,0,62,30)]);
+///
+/// // Releasing the newest note returns to the held one.
+/// let mut evs = EventStream::from(NoteOffEvent(0,0,62));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOffEvent(0,0,62), NoteOnEvent(0,0,60,20)]);
+///
+/// let mut evs = EventStream::from(NoteOffEvent(0,0,60));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,60));
+/// ```
+///
+/// With legato, notes overlap so the synth doesn't retrigger its envelope.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Monophonic(MonoMode::Legato);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,30)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,30), NoteOffEvent(0,0,60)]);
+///
+/// // Releasing an older note that isn't sounding anymore does nothing.
+/// let mut evs = EventStream::from(NoteOffEvent(0,0,60));
+/// filter.run(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+pub struct Monophonic {
+    mode: MonoMode,
+    held: Mutex<HashMap<(usize, u8), HeldNotes>>,
+}
+
+// Held notes with their velocity for a port and channel, the last one is sounding.
+type HeldNotes = Vec<(u8, u8)>;
+
+pub fn Monophonic(mode: MonoMode) -> Monophonic {
+    Monophonic { mode, held: Mutex::new(HashMap::new()) }
+}
+
+impl Monophonic {
+    fn note_on(&self, held: &mut HeldNotes, ev: NoteOnEventImpl, evs: &mut EventStream) {
+        let previous = held.last().copied();
+        // A repeated note replaces the held one.
+        held.retain(|(note, _)| *note != ev.note);
+        held.push((ev.note, ev.velocity));
+
+        match previous {
+            Some((note, _)) if self.mode == MonoMode::Retrigger || note == ev.note => {
+                evs.push(NoteOffEvent(ev.port, ev.channel, note));
+                evs.push(Event::NoteOn(ev));
+            },
+            Some((note, _)) => {
+                evs.push(Event::NoteOn(ev));
+                evs.push(NoteOffEvent(ev.port, ev.channel, note));
+            },
+            None => evs.push(Event::NoteOn(ev)),
+        }
+    }
+
+    fn note_off(&self, held: &mut HeldNotes, ev: NoteOffEventImpl, evs: &mut EventStream) {
+        let sounding = held.last().map(|(note, _)| *note) == Some(ev.note);
+        held.retain(|(note, _)| *note != ev.note);
+        if !sounding { return; }
+
+        match (held.last(), self.mode) {
+            (Some((note, velocity)), MonoMode::Retrigger) => {
+                evs.push(Event::NoteOff(ev));
+                evs.push(NoteOnEvent(ev.port, ev.channel, *note, *velocity));
+            },
+            (Some((note, velocity)), MonoMode::Legato) => {
+                evs.push(NoteOnEvent(ev.port, ev.channel, *note, *velocity));
+                evs.push(Event::NoteOff(ev));
+            },
+            (None, _) => evs.push(Event::NoteOff(ev)),
+        }
+    }
+
+    fn release_all(&self, evs: &mut EventStream) {
+        if let Ok(mut held) = self.held.lock() {
+            for ((port, channel), notes) in held.drain() {
+                if let Some((note, _)) = notes.last() {
+                    evs.push(NoteOffEvent(port, channel, *note));
+                }
+            }
+        }
+    }
+}
+
+impl FilterTrait for Monophonic {
+    fn run(&self, evs: &mut EventStream) {
+        let mut held = match self.held.lock() {
+            Ok(held) => held,
+            Err(_) => return,
+        };
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            match ev {
+                Event::NoteOn(ev) => self.note_on(held.entry((ev.port, ev.channel)).or_default(), *ev, &mut out),
+                Event::NoteOff(ev) => match held.get_mut(&(ev.port, ev.channel)) {
+                    Some(notes) if notes.iter().any(|(note, _)| *note == ev.note) => self.note_off(notes, *ev, &mut out),
+                    _ => out.push(Event::NoteOff(*ev)),
+                },
+                _ => out.push(ev.clone()),
+            }
+        }
+        evs.replace(out);
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut held) = self.held.lock() {
+            held.clear();
+        }
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        // Don't leave notes hanging.
+        self.release_all(evs);
+    }
+}
+
+/// Holds a note, toggled by a trigger note, e.g. a drone started and stopped with a footswitch.
+///
+/// The arguments are: _trigger_note_, _latched_note_.
+///
+/// The first NoteOn of the trigger note starts the latched note with its velocity, the next one
+/// stops it again. When _latched_note_ is 0, the most recently played note is latched instead.
+/// Trigger notes themselves are removed, other events are passed unchanged. A held note is
+/// released on exit, or earlier with [Latch::release].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Latch(36, 48);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,36,90), NoteOffEvent(0,0,36)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,48,90));
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,36,90), NoteOffEvent(0,0,36)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,48));
+/// ```
+///
+/// Latching the last played note.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Latch(36, 0);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,1,62,70), NoteOffEvent(0,1,62), NoteOnEvent(0,0,36,90)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,1,62,70), NoteOffEvent(0,1,62), NoteOnEvent(0,1,62,70)]);
+///
+/// // Don't leave the note hanging.
+/// let mut evs = EventStream::none();
+/// filter.run_exit(&mut evs);
+/// assert_eq!(evs.into_iter().last(), Some(NoteOffEvent(0,1,62)));
+/// ```
+pub struct Latch {
+    trigger_note: u8,
+    latched_note: u8,
+    state: Arc<Mutex<LatchState>>,
+}
+
+#[derive(Default)]
+struct LatchState {
+    // Port, channel, note and velocity of the most recently played note.
+    last: Option<(usize, u8, u8, u8)>,
+    // Port, channel and note of the latched note.
+    held: Option<(usize, u8, u8)>,
+}
+
+impl LatchState {
+    fn release(&mut self, evs: &mut EventStream) {
+        if let Some((port, channel, note)) = self.held.take() {
+            evs.push(NoteOffEvent(port, channel, note));
+        }
+    }
+}
+
+pub fn Latch(trigger_note: u8, latched_note: u8) -> Latch {
+    Latch { trigger_note, latched_note, state: Arc::new(Mutex::new(LatchState::default())) }
+}
+
+impl Latch {
+    /// Returns a filter that releases the latched note, if any, whenever it is run.
+    ///
+    /// Events are passed unchanged, the NoteOff is added after them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// let latch = Latch(36, 48);
+    /// let release = latch.release();
+    ///
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,36,90));
+    /// latch.run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,48,90));
+    ///
+    /// let mut evs = EventStream::from(ProgramEvent(0,0,3));
+    /// release.run(&mut evs);
+    /// assert_eq!(evs, vec![ProgramEvent(0,0,3), NoteOffEvent(0,0,48)]);
+    ///
+    /// // The next trigger latches again.
+    /// let mut evs = EventStream::from(NoteOnEvent(0,0,36,90));
+    /// latch.run(&mut evs);
+    /// assert_eq!(evs, NoteOnEvent(0,0,48,90));
+    /// ```
+    pub fn release(&self) -> LatchRelease {
+        LatchRelease { state: self.state.clone() }
+    }
+
+    fn trigger(&self, state: &mut LatchState, ev: &NoteOnEventImpl, evs: &mut EventStream) {
+        if state.held.is_some() {
+            state.release(evs);
+            return;
+        }
+
+        let latched = if self.latched_note == 0 {
+            state.last
+        } else {
+            Some((ev.port, ev.channel, self.latched_note, ev.velocity))
+        };
+        if let Some((port, channel, note, velocity)) = latched {
+            evs.push(NoteOnEvent(port, channel, note, velocity));
+            state.held = Some((port, channel, note));
+        }
+    }
+}
+
+impl FilterTrait for Latch {
+    fn run(&self, evs: &mut EventStream) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            match ev {
+                Event::NoteOn(ev) if ev.note == self.trigger_note => {
+                    if ev.velocity > 0 { self.trigger(&mut state, ev, &mut out); }
+                },
+                Event::NoteOff(ev) if ev.note == self.trigger_note => {},
+                Event::NoteOn(ev) => {
+                    state.last = Some((ev.port, ev.channel, ev.note, ev.velocity));
+                    out.push(Event::NoteOn(*ev));
+                },
+                _ => out.push(ev.clone()),
+            }
+        }
+        evs.replace(out);
+    }
+
+    fn run_init(&self, _evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = LatchState::default();
+        }
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.release(evs);
+        }
+    }
+}
+
+/// Releases the note held by a [Latch], see [Latch::release].
+pub struct LatchRelease {
+    state: Arc<Mutex<LatchState>>,
+}
+
+impl FilterTrait for LatchRelease {
+    fn run(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.state.lock() {
+            state.release(evs);
+        }
+    }
+}
+
+/// Holds NoteOffs while the sustain pedal (CC 64) is down, like the pedal of a piano.
+///
+/// NoteOffs arriving while the pedal is down are kept, and sent in order after the pedal
+/// event that releases it, which is at a value below 64. When a held note is played again, its
+/// NoteOff is dropped. The pedal events themselves are passed, so the synth gets them too.
+/// On exit, kept NoteOffs are sent so that no notes hang.
+///
+/// Each port and channel has its own pedal. A NoteOn with velocity 0 is a NoteOff too.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = SustainPedalTracker();
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,64,127), NoteOffEvent(0,0,60), NoteOffEvent(0,0,62)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,64,127));
+///
+/// // Playing a note again drops its NoteOff.
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,64,0));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,64,0), NoteOffEvent(0,0,62)]);
+///
+/// let mut evs = EventStream::from(NoteOffEvent(0,0,60));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,60));
+/// ```
+///
+/// The pedal only holds notes on its own channel, also when they end with a NoteOn of velocity 0.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = SustainPedalTracker();
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,64,127), NoteOnEvent(0,0,60,0), NoteOffEvent(0,1,62)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,64,127), NoteOffEvent(0,1,62)]);
+///
+/// let mut evs = EventStream::from(CtrlEvent(0,0,64,0));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,64,0), NoteOnEvent(0,0,60,0)]);
+/// ```
+pub struct SustainPedalTracker {
+    // Pedal for each port and channel.
+    state: Mutex<BTreeMap<(usize, u8), SustainPedal>>,
+}
+
+#[derive(Default)]
+struct SustainPedal {
+    down: bool,
+    // NoteOffs held while the pedal is down, or NoteOns with velocity 0.
+    note_offs: Vec<Event<'static>>,
+}
+
+pub fn SustainPedalTracker() -> SustainPedalTracker {
+    SustainPedalTracker { state: Mutex::new(BTreeMap::new()) }
+}
+
+// Keeps a NoteOff while the pedal of its port and channel is down, or returns it to pass.
+fn hold_note_off(pedals: &mut BTreeMap<(usize, u8), SustainPedal>, key: (usize, u8), ev: Event<'static>) -> Option<Event<'static>> {
+    match pedals.get_mut(&key) {
+        Some(pedal) if pedal.down => {
+            pedal.note_offs.retain(|held| held.note() != ev.note());
+            pedal.note_offs.push(ev);
+            None
+        },
+        _ => Some(ev),
+    }
+}
+
+impl FilterTrait for SustainPedalTracker {
+    fn run(&self, evs: &mut EventStream) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            match ev {
+                Event::Ctrl(ctrl) if ctrl.ctrl == 64 => {
+                    let pedal = state.entry((ctrl.port, ctrl.channel)).or_default();
+                    pedal.down = ctrl.value >= 64;
+                    out.push(ev.clone());
+                    if !pedal.down {
+                        pedal.note_offs.drain(..).for_each(|ev| out.push(ev));
+                    }
+                },
+                Event::NoteOn(note_on) if note_on.velocity == 0 => {
+                    if let Some(ev) = hold_note_off(&mut state, (note_on.port, note_on.channel), Event::NoteOn(*note_on)) {
+                        out.push(ev);
+                    }
+                },
+                Event::NoteOff(note_off) => {
+                    if let Some(ev) = hold_note_off(&mut state, (note_off.port, note_off.channel), Event::NoteOff(*note_off)) {
+                        out.push(ev);
+                    }
+                },
+                Event::NoteOn(note_on) => {
+                    if let Some(pedal) = state.get_mut(&(note_on.port, note_on.channel)) {
+                        pedal.note_offs.retain(|held| held.note() != Some(note_on.note));
+                    }
+                    out.push(ev.clone());
+                },
+                _ => out.push(ev.clone()),
+            }
+        }
+
+        *evs = out;
     }
-}
-/// Run contained filters on (sub)scene or patch init.
-#[macro_export]
-macro_rules! Init {
-    ( $f:expr ) => {
-        _Init(Box::new($f))
-    };
-}
 
-#[doc(hidden)]
-pub struct _Exit<'a>(pub Box<dyn FilterTrait + 'a>);
-#[doc(hidden)]
-impl FilterTrait for _Exit<'_> {
-    fn run(&self, _evs: &mut EventStream) {}
     fn run_exit(&self, evs: &mut EventStream) {
-        self.0.run(evs);
+        if let Ok(mut state) = self.state.lock() {
+            for pedal in state.values_mut() {
+                pedal.down = false;
+                pedal.note_offs.drain(..).for_each(|ev| evs.push(ev));
+            }
+        }
     }
 }
-/// Run contained filters on (sub)scene or patch exit.
-#[macro_export]
-macro_rules! Exit {
-    ( $f:expr ) => {
-        _Exit(Box::new($f))
-    };
+
+// // State
+
+/// Store a value in the [PatchState].
+///
+/// The arguments are: _key_, _value_.
+///
+/// The value is set for each incoming event, and the events are discarded.
+/// The runner keeps the state across scene switches.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::sync::Arc;
+///
+/// let state = Arc::new(PatchState::new());
+/// let generator = SetState("program", StateValue::Int(5));
+///
+/// let mut evs = EventStream::from(NoteOnEvent(1,0,60,20));
+/// state.activate(|| generator.run(&mut evs));
+/// assert!(evs.is_empty());
+/// assert_eq!(state.get("program"), Some(StateValue::Int(5)));
+/// ```
+pub struct SetState(pub &'static str, pub StateValue);
+impl FilterTrait for SetState {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        if let Some(state) = PatchState::active() {
+            state.set(self.0, self.1.clone());
+        }
+        evs.clear();
+    }
+}
+
+/// Replace events with a controller (CC) event having a value from the [PatchState].
+///
+/// The arguments are: _key_, _ctrl_.
+///
+/// Port and channel are taken from the event, when it has them. Events are
+/// discarded when there is no numeric value stored for the key.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::sync::Arc;
+///
+/// let state = Arc::new(PatchState::new());
+/// state.set("volume", StateValue::Int(90));
+/// let modifier = GetStateAsCtrl("volume", 7);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(1,2,60,20));
+/// state.activate(|| modifier.run(&mut evs));
+/// assert_eq!(evs, CtrlEvent(1,2,7,90));
+///
+/// let mut evs = EventStream::from(NoteOnEvent(1,2,60,20));
+/// state.activate(|| GetStateAsCtrl("unknown", 7).run(&mut evs));
+/// assert!(evs.is_empty());
+/// ```
+pub struct GetStateAsCtrl(pub &'static str, pub u32);
+impl FilterTrait for GetStateAsCtrl {
+    fn run(&self, evs: &mut EventStream) {
+        let value = PatchState::active()
+            .and_then(|state| state.get(self.0))
+            .and_then(|value| value.as_ctrl_value());
+
+        match value {
+            Some(value) => {
+                for ev in evs.iter_mut() {
+                    *ev = CtrlEvent(ev.port().unwrap_or_else(first_port), ev.channel().unwrap_or_else(first_channel), self.1, value);
+                }
+            },
+            None => evs.clear(),
+        }
+    }
 }
 
 // // Misc
 
-/// Prints the current events.
-pub struct Print();
+/// Prints the current events, one per line.
+///
+/// Notes are shown with their name, see [notes]. Use `Print::label` to tell the output of
+/// several Prints apart, and `Print::with` to format events yourself. Events are printed
+/// to standard output, `Print::to_log` logs them at info level instead.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let ev = NoteOnEvent(0,1,60,100);
+///
+/// assert_eq!(Print().line(&ev), "NoteOn ch=1 note=C4(60) vel=100 port=0");
+/// assert_eq!(Print::label("before transpose").line(&ev), "before transpose: NoteOn ch=1 note=C4(60) vel=100 port=0");
+/// assert_eq!(Print::with(|ev| format!("port {:?}", ev.port())).line(&ev), "port Some(0)");
+/// assert_eq!(Print().line(&CtrlEvent(1,2,7,64)), "Ctrl ch=2 ctrl=7 value=64 port=1");
+/// assert_eq!(Print().line(&SceneSwitchEvent(2)), "SceneSwitch scene=2");
+/// ```
+///
+/// MIDI clock is sent 24 times per quarter note, which can flood the output. To print
+/// everything except clock events, while still passing them on:
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Fork!(Pass(), Chain!(Not!(TypeFilter!(Clock)), Print(), Discard()));
+///
+/// let mut evs = EventStream::from(ClockEvent(0));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, ClockEvent(0));
+/// # }
+/// ```
+pub struct Print {
+    label: Option<String>,
+    format: Option<EventFormatter>,
+    to_log: bool,
+}
+
+type EventFormatter = Box<dyn Fn(&Event) -> String>;
+
+pub fn Print() -> Print {
+    Print { label: None, format: None, to_log: false }
+}
+
+impl Print {
+    /// Prints events prefixed with a label.
+    pub fn label(label: &str) -> Self {
+        Print { label: Some(label.to_string()), format: None, to_log: false }
+    }
+
+    /// Prints events formatted by a function.
+    pub fn with<F: Fn(&Event) -> String + 'static>(format: F) -> Self {
+        Print { label: None, format: Some(Box::new(format)), to_log: false }
+    }
+
+    /// Logs events with the [log] crate, so they go where other messages go.
+    pub fn to_log() -> Self {
+        Print { label: None, format: None, to_log: true }
+    }
+
+    /// Returns the line printed for an event.
+    pub fn line(&self, ev: &Event) -> String {
+        let text = match &self.format {
+            Some(format) => format(ev),
+            None => format_event(ev),
+        };
+        match &self.label {
+            Some(label) => format!("{}: {}", label, text),
+            None => text,
+        }
+    }
+}
+
 impl FilterTrait for Print {
     fn run(&self, evs: &mut EventStream) {
-        if !evs.is_empty() {
-            println!("{:?}", evs);
+        for ev in evs.iter() {
+            if self.to_log {
+                info!("{}", self.line(ev));
+            } else {
+                println!("{}", self.line(ev));
+            }
         }
     }
 }
 
+/// Concise representation of an event, for [Print].
+fn format_event(ev: &Event) -> String {
+    match ev {
+        Event::NoteOn(ev) => format!("NoteOn ch={} note={}({}) vel={} port={}", ev.channel, note_name(ev.note), ev.note, ev.velocity, ev.port),
+        Event::NoteOff(ev) => format!("NoteOff ch={} note={}({}) vel={} port={}", ev.channel, note_name(ev.note), ev.note, ev.velocity, ev.port),
+        Event::Ctrl(ev) => format!("Ctrl ch={} ctrl={} value={} port={}", ev.channel, ev.ctrl, ev.value, ev.port),
+        Event::Program(ev) => format!("Program ch={} program={} port={}", ev.channel, ev.program, ev.port),
+        Event::PitchBend(ev) => format!("PitchBend ch={} value={} port={}", ev.channel, ev.value, ev.port),
+        Event::Nrpn(ev) => format!("Nrpn ch={} param={} value={} port={}", ev.channel, ev.param, ev.value, ev.port),
+        _ => ev.to_string(),
+    }
+}
+
 /// Quit mididings
 ///
 /// This event consumes all other events, so after this filter
@@ -1164,19 +5409,237 @@ impl FilterTrait for Discard {
         evs.clear();
     }
 
-    fn run_inverse(&self, _evs: &mut EventStream) {
-        // pass, which means: keep event stream as it is
+    fn run_inverse(&self, _evs: &mut EventStream) {
+        // pass, which means: keep event stream as it is
+    }
+
+    fn is_discard(&self) -> bool {
+        true
+    }
+}
+
+/// Passes only the first events.
+///
+/// The argument is: _count_, the number of events to pass each time it is run.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Head(2);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20)]);
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Not!(Head(2));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,64,20));
+/// # }
+/// ```
+pub struct Head(pub usize);
+impl FilterTrait for Head {
+    fn run(&self, evs: &mut EventStream) {
+        evs.truncate(self.0);
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.drain(..self.0.min(evs.len()));
+    }
+}
+
+/// Passes only the last events.
+///
+/// The argument is: _count_, the number of events to pass each time it is run.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = Tail(2);
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Not!(Tail(2));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// # }
+/// ```
+pub struct Tail(pub usize);
+impl FilterTrait for Tail {
+    fn run(&self, evs: &mut EventStream) {
+        evs.drain(..evs.len().saturating_sub(self.0));
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.truncate(evs.len().saturating_sub(self.0));
+    }
+}
+
+/// Sorts events by note, then channel, then port.
+///
+/// See [EventStream::sort] for the order. After a [Fork!], this gives events in the same
+/// order whatever the order of its branches, e.g. the notes of a chord from low to high.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Chain!(Fork!(Transpose(7), Transpose(4), Pass()), Sort());
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,64,20), NoteOnEvent(0,0,67,20)]);
+/// # }
+/// ```
+pub struct Sort();
+impl FilterTrait for Sort {
+    fn run(&self, evs: &mut EventStream) {
+        evs.sort();
+    }
+}
+
+/// Passes or discards all events, switched while running, like a MIDI thru switch.
+///
+/// The argument is: _enabled_, whether events are passed at the start.
+///
+/// To switch it from a control patch, use the filters returned by [Thru::switch] and
+/// [Thru::toggle]. To switch it from elsewhere in the application, also from another thread,
+/// get a [ThruHandle] with [Thru::handle]. All of them switch the same `Thru`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let thru = Thru(true);
+/// let control = Chain!(KeyFilter(36), thru.toggle());
+/// let handle = thru.handle();
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// thru.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,9,36,100));
+/// control.run(&mut evs);
+/// assert!(evs.is_empty());
+/// assert!(!handle.is_enabled());
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// thru.run(&mut evs);
+/// assert!(evs.is_empty());
+///
+/// std::thread::spawn(move || handle.set_enabled(true)).join().unwrap();
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// thru.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// # }
+/// ```
+pub struct Thru {
+    enabled: Arc<AtomicBool>,
+}
+
+pub fn Thru(enabled: bool) -> Thru {
+    Thru { enabled: Arc::new(AtomicBool::new(enabled)) }
+}
+
+impl Thru {
+    /// Returns a handle to switch it, which can be sent to other threads.
+    pub fn handle(&self) -> ThruHandle {
+        ThruHandle { enabled: self.enabled.clone() }
+    }
+
+    /// Returns a filter that enables or disables it when there are events, and removes them.
+    pub fn switch(&self, enabled: bool) -> ThruSwitch {
+        ThruSwitch { enabled: self.enabled.clone(), to: Some(enabled) }
+    }
+
+    /// Returns a filter that toggles it when there are events, and removes them.
+    pub fn toggle(&self) -> ThruSwitch {
+        ThruSwitch { enabled: self.enabled.clone(), to: None }
+    }
+}
+
+impl FilterTrait for Thru {
+    fn run(&self, evs: &mut EventStream) {
+        if !self.enabled.load(Ordering::Relaxed) { evs.clear(); }
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        if self.enabled.load(Ordering::Relaxed) { evs.clear(); }
+    }
+}
+
+/// Switches a [Thru] from anywhere in the application, see [Thru::handle].
+#[derive(Debug, Clone)]
+pub struct ThruHandle {
+    enabled: Arc<AtomicBool>,
+}
+
+impl ThruHandle {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Enables it when disabled and the other way around, returns whether it is now enabled.
+    pub fn toggle(&self) -> bool {
+        !self.enabled.fetch_xor(true, Ordering::Relaxed)
+    }
+}
+
+/// Switches a [Thru] from a patch, see [Thru::switch] and [Thru::toggle].
+pub struct ThruSwitch {
+    enabled: Arc<AtomicBool>,
+    // None to toggle.
+    to: Option<bool>,
+}
+
+impl FilterTrait for ThruSwitch {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        match self.to {
+            Some(enabled) => self.enabled.store(enabled, Ordering::Relaxed),
+            None => { self.enabled.fetch_xor(true, Ordering::Relaxed); },
+        }
+        evs.clear();
     }
 }
 
 /// Send MIDI panic
 ///
-/// Sends all notes off (CC#123) and sustain off (CC#64) on all channels.
+/// Sends all notes off (CC#123) and sustain off (CC#64) on all channels of the first port.
 ///
 /// Note that, in contrast to mididings, the events are subject to port
 /// selection, so if you have multiple ports, send multiple MIDI panic
 /// events (one to each port).
 ///
+/// The runner follows each all notes off controller with NoteOffs for the notes it sent on
+/// that port and channel, for devices that ignore it. Ports marked with
+/// [PortFlags::FEEDBACK](crate::PortFlags::FEEDBACK) are left out of this.
+///
 /// # Examples
 ///
 /// ```
@@ -1191,8 +5654,9 @@ impl FilterTrait for Discard {
 pub struct Panic();
 impl FilterTrait for Panic {
     fn run(&self, evs: &mut EventStream) {
-        evs.extend((0..16).map(|c| CtrlEvent(0, c, 123, 0)));
-        evs.extend((0..16).map(|c| CtrlEvent(0, c,  64, 0)));
+        let (port, channel) = (first_port(), first_channel());
+        evs.extend((0..16).map(|c| CtrlEvent(port, channel.saturating_add(c), 123, 0)));
+        evs.extend((0..16).map(|c| CtrlEvent(port, channel.saturating_add(c),  64, 0)));
     }
 }
 
@@ -1206,6 +5670,15 @@ impl FilterTrait for _Not<'_> {
     fn run_inverse(&self, evs: &mut EventStream) {
         self.0.run(evs);
     }
+    fn run_tick(&self, evs: &mut EventStream) {
+        self.0.run_tick(evs);
+    }
+    fn next_tick(&self) -> Option<Instant> {
+        self.0.next_tick()
+    }
+    fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.0.bind(ports)
+    }
 }
 
 /// Inverses the effect of filters.
@@ -1258,25 +5731,227 @@ macro_rules! Not {
     };
 }
 
-#[doc(hidden)]
-pub struct _Process(pub Box<dyn Fn(&Event) -> Box<dyn FilterTrait>>);
-#[doc(hidden)]
-impl FilterTrait for _Process {
+/// Filter events using a custom function.
+///
+/// The function returns whether to keep the event, like filters such as [KeyFilter].
+/// This makes it easy to write one-off conditions without defining a new filter.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Chain!(FilterFn(|ev| matches!(ev, Event::NoteOn(n) if n.velocity > 100)), Port(2));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,110), NoteOnEvent(0,0,62,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(2,0,60,110));
+/// # }
+/// ```
+///
+/// Inside [Not!], the events that don't match are kept.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Not!(FilterFn(|ev| matches!(ev, Event::NoteOn(n) if n.velocity > 100)));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,110), NoteOnEvent(0,0,62,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,62,20));
+/// # }
+/// ```
+pub struct FilterFn<'a> {
+    f: Box<dyn Fn(&Event) -> bool + 'a>,
+}
+
+pub fn FilterFn<'a, F: Fn(&Event) -> bool + 'a>(f: F) -> FilterFn<'a> {
+    FilterFn { f: Box::new(f) }
+}
+
+impl FilterTrait for FilterFn<'_> {
+    fn run(&self, evs: &mut EventStream) {
+        evs.retain(|ev| (self.f)(ev));
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.retain(|ev| !(self.f)(ev));
+    }
+}
+
+/// Modify events using a custom function.
+///
+/// The function is called for each event, like modifiers such as [Transpose].
+/// Inside [Not!] the behaviour is unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let modifier = Fork!(
+///     ModifyFn(|ev| if let Event::NoteOn(n) = ev { n.velocity /= 2 }),
+///     Not!(ModifyFn(|ev| if let Event::NoteOn(n) = ev { n.note += 12 }))
+/// );
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,100));
+/// modifier.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,50), NoteOnEvent(0,0,72,100)]);
+/// # }
+/// ```
+pub struct ModifyFn<'a> {
+    f: Box<dyn Fn(&mut Event) + 'a>,
+}
+
+pub fn ModifyFn<'a, F: Fn(&mut Event) + 'a>(f: F) -> ModifyFn<'a> {
+    ModifyFn { f: Box::new(f) }
+}
+
+impl FilterTrait for ModifyFn<'_> {
     fn run(&self, evs: &mut EventStream) {
-       let mut results: HashMap<usize, EventStream> = HashMap::new();
+        for ev in evs.iter_mut() {
+            (self.f)(ev);
+        }
+    }
+}
+
+/// Generate events using a custom function.
+///
+/// Each incoming event is replaced by all events returned, in order, keeping
+/// the port and channel like other generators.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// // Bank select MSB and LSB when the scene starts.
+/// let init = Init!(GenerateFn(|| vec![CtrlEvent(0,0,0,1), CtrlEvent(0,0,32,5)]));
+///
+/// let mut evs = EventStream::empty();
+/// init.run_init(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,0,1), CtrlEvent(0,0,32,5)]);
+/// # }
+/// ```
+pub struct GenerateFn<'a> {
+    f: Box<dyn Fn() -> Vec<Event<'static>> + 'a>,
+}
+
+pub fn GenerateFn<'a, F: Fn() -> Vec<Event<'static>> + 'a>(f: F) -> GenerateFn<'a> {
+    GenerateFn { f: Box::new(f) }
+}
 
-        // First gather all resulting EventStreams from the function invocations.
-        for (i, ev) in evs.iter().enumerate() {
-            let mut evs = EventStream::from(ev);
-            self.0(ev).run(&mut evs);
-            results.insert(i, evs);
-       }
+impl FilterTrait for GenerateFn<'_> {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
 
-        // Then replace the events by their results.
-        for (i, r_evs) in results {
-            evs.splice(i..i+1, r_evs);
+        let new_evs = (self.f)();
+        let mut result = EventStream::empty();
+        for ev in evs.iter() {
+            for new_ev in new_evs.iter() {
+                let mut this_new_ev = new_ev.clone();
+                if let Some(port) = ev.port() {
+                    this_new_ev.set_port(port);
+                }
+                if let Some(channel) = ev.channel() {
+                    this_new_ev.set_channel(channel);
+                }
+                result.push(this_new_ev);
+            }
         }
+        evs.replace(result);
+        evs.dedup();
+    }
+}
+
+/// Call a custom function with the events, for its side effects.
+///
+/// The events are passed unchanged. Use it for logging, updating a user interface, or
+/// handing work to another thread. The function is called in the run loop, so it must
+/// not block: events are not processed until it returns. It is not called when there
+/// are no events.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use std::sync::mpsc;
+///
+/// # fn main() {
+/// let (sender, receiver) = mpsc::channel();
+/// let filter = Chain!(TypeFilter!(Note), Call(move |evs| { let _ = sender.send(evs.len()); }));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), CtrlEvent(0,0,7,100)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// assert_eq!(receiver.try_recv(), Ok(1));
+/// # }
+/// ```
+pub struct Call<'a> {
+    f: Box<dyn Fn(&EventStream) + 'a>,
+}
+
+pub fn Call<'a, F: Fn(&EventStream) + 'a>(f: F) -> Call<'a> {
+    Call { f: Box::new(f) }
+}
+
+impl FilterTrait for Call<'_> {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        (self.f)(evs);
+    }
+}
+
+/// Call a custom function with the events, and replace them by the events it returns.
+///
+/// Like [Call], the function must not block the run loop, and it is not called when there
+/// are no events.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// // Keep only the highest note.
+/// let filter = CallReplace(|evs| evs.iter().max_by_key(|ev| ev.note()).into());
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,67,20), NoteOnEvent(0,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,67,20));
+/// # }
+/// ```
+pub struct CallReplace<'a> {
+    f: Box<dyn for<'e> Fn(&EventStream<'e>) -> EventStream<'e> + 'a>,
+}
+
+pub fn CallReplace<'a, F: for<'e> Fn(&EventStream<'e>) -> EventStream<'e> + 'a>(f: F) -> CallReplace<'a> {
+    CallReplace { f: Box::new(f) }
+}
+
+impl FilterTrait for CallReplace<'_> {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        let new_evs = (self.f)(evs);
+        evs.replace(new_evs);
+    }
+}
 
+#[doc(hidden)]
+pub struct _Process(pub Box<dyn Fn(&Event) -> Box<dyn FilterTrait>>);
+#[doc(hidden)]
+impl FilterTrait for _Process {
+    fn run(&self, evs: &mut EventStream) {
+        evs.map_replace(|ev| {
+            let mut r_evs = EventStream::from(ev);
+            self.0(ev).run(&mut r_evs);
+            Some(r_evs)
+        });
         evs.dedup();
     }
 }
@@ -1305,17 +5980,226 @@ impl FilterTrait for _Process {
 /// assert_eq!(evs, NoteOnEvent(0,0,61,40));
 /// # }
 /// ```
+///
+/// Each event is replaced by the result of its patch, which may be any number of events.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = Process!(|ev: &Event| -> Box<dyn FilterTrait> {
+///     match ev {
+///         Event::NoteOn(_) => Box::new(Fork!(Pass(), Transpose(12))),
+///         _ => Box::new(Discard()),
+///     }
+/// });
+///
+/// let mut evs = EventStream::from(vec![CtrlEvent(0,0,7,20), NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,64,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![
+///     NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,72,20),
+///     NoteOnEvent(0,0,64,20), NoteOnEvent(0,0,76,20),
+/// ]);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! Process {
     ( $f:expr ) => { _Process(Box::new($f)) };
 }
 
+#[doc(hidden)]
+pub fn _ProcessNote<F, R>(f: F) -> _Process where F: Fn(usize, u8, u8, u8) -> R + 'static, R: FilterTrait + 'static {
+    _Process(Box::new(move |ev: &Event| -> Box<dyn FilterTrait> {
+        match ev {
+            Event::NoteOn(ev) => Box::new(f(ev.port, ev.channel, ev.note, ev.velocity)),
+            _ => Box::new(Pass()),
+        }
+    }))
+}
+
+/// Process NoteOn events using a function, which gets their fields and returns a patch.
+///
+/// The function's arguments are: _port_, _channel_, _note_, _velocity_. Other events,
+/// including NoteOff, are passed unchanged. See [Process!] for handling any event.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = ProcessNote!(|_port, _channel, note, _velocity| Ctrl(1, note as i32));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,2,60,100), NoteOffEvent(0,2,60), CtrlEvent(0,2,7,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,2,1,60), NoteOffEvent(0,2,60), CtrlEvent(0,2,7,20)]);
+/// # }
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = ProcessNote!(|port, _channel, note, velocity| {
+///     Fork!(NoteOn(note, velocity), Chain!(NoteOn(note + 12, velocity / 2), Port(port + 1)))
+/// });
+///
+/// let mut evs = EventStream::from(NoteOnEvent(1,0,60,100));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(1,0,60,100), NoteOnEvent(2,0,72,50)]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ProcessNote {
+    ( $f:expr ) => { _ProcessNote($f) };
+}
+
 #[macro_export]
 macro_rules! ProcessCtrl {
     ( $f:expr ) => { _Process(Box::new($f)) };
 }
 
+#[cfg(feature = "dbus")]
+pub use dbus::arg::messageitem::MessageItem;
+
+/// Filter on D-Bus interface and method (or signal) name, an empty one matches any.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DbusFilter("org.mpris.MediaPlayer2.Player", "Seeked");
+///
+/// let ev1 = DbusEvent(0, "".to_string(), "/org/mpris/MediaPlayer2".to_string(), "org.mpris.MediaPlayer2.Player".to_string(), "Seeked".to_string(), vec![MessageItem::Int64(0)]);
+/// let ev2 = DbusEvent(0, "".to_string(), "/org/mpris/MediaPlayer2".to_string(), "org.freedesktop.DBus.Properties".to_string(), "PropertiesChanged".to_string(), vec![]);
+/// let mut evs = EventStream::from(vec![ev1.clone(), ev2, NoteOnEvent(0,0,60,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev1, NoteOnEvent(0,0,60,20)]);
+/// ```
+#[cfg(feature = "dbus")]
+pub struct DbusFilter {
+    pub interface: String,
+    pub method: String,
+}
+
+#[cfg(feature = "dbus")]
+pub fn DbusFilter(interface: &str, method: &str) -> DbusFilter {
+    DbusFilter { interface: interface.to_string(), method: method.to_string() }
+}
+
+#[cfg(feature = "dbus")]
+impl DbusFilter {
+    fn filter_single(&self, ev: &Event) -> bool {
+        match ev {
+            Event::Dbus(ev) => {
+                (self.interface.is_empty() || ev.interface == self.interface) &&
+                (self.method.is_empty() || ev.method == self.method)
+            },
+            _ => true,
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl FilterTrait for DbusFilter {
+    fn run(&self, evs: &mut EventStream) {
+        evs.retain(|ev| self.filter_single(ev));
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.retain(|ev| !self.filter_single(ev));
+    }
+
+    fn quick_reject(&self, ev: &Event) -> bool {
+        !self.filter_single(ev)
+    }
+}
+
+/// Generates a D-Bus method call.
+///
+/// The destination and object path are taken from the name of the output port, like
+/// `dbus:org.mpris.MediaPlayer2.vlc/org/mpris/MediaPlayer2`, unless given with
+/// `with_destination`.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DbusCall("org.mpris.MediaPlayer2.Player", "PlayPause");
+///
+/// let mut evs = EventStream::none();
+/// filter.run(&mut evs);
+/// assert_eq!(evs, DbusEvent(0, "".to_string(), "".to_string(), "org.mpris.MediaPlayer2.Player".to_string(), "PlayPause".to_string(), vec![]));
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = DbusCall("org.mpris.MediaPlayer2.Player", "Seek")
+///     .with_destination("org.mpris.MediaPlayer2.vlc", "/org/mpris/MediaPlayer2")
+///     .with_args(vec![MessageItem::Int64(-5_000_000)]);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(1,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, DbusEvent(1, "org.mpris.MediaPlayer2.vlc".to_string(), "/org/mpris/MediaPlayer2".to_string(), "org.mpris.MediaPlayer2.Player".to_string(), "Seek".to_string(), vec![MessageItem::Int64(-5_000_000)]));
+/// ```
+#[cfg(feature = "dbus")]
+pub struct DbusCall {
+    service: String,
+    path: String,
+    interface: String,
+    method: String,
+    args: Vec<MessageItem>,
+}
+
+#[cfg(feature = "dbus")]
+pub fn DbusCall(interface: &str, method: &str) -> DbusCall {
+    DbusCall {
+        service: String::new(),
+        path: String::new(),
+        interface: interface.to_string(),
+        method: method.to_string(),
+        args: vec![],
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl DbusCall {
+    pub fn with_destination(mut self, service: &str, path: &str) -> Self {
+        self.service = service.to_string();
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn with_args(mut self, args: Vec<MessageItem>) -> Self {
+        self.args = args;
+        self
+    }
+}
+
+#[cfg(feature = "dbus")]
+impl FilterTrait for DbusCall {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+
+        // Like define_generator!, keeping the port of the incoming event.
+        let new_ev = DbusEvent(first_port(), self.service.clone(), self.path.clone(), self.interface.clone(), self.method.clone(), self.args.clone());
+        for ev in evs.iter_mut() {
+            let mut this_new_ev = new_ev.clone();
+            if let Some(port) = ev.port() {
+                this_new_ev.set_port(port);
+            }
+            *ev = this_new_ev;
+        }
+        evs.dedup();
+    }
+}
+
 #[cfg(feature = "osc")]
 pub mod osc;
 #[cfg(feature = "osc")]
-pub use osc::*;
\ No newline at end of file
+pub use osc::*;
+
+#[cfg(feature = "hotreload")]
+pub mod hotreload;
+#[cfg(feature = "hotreload")]
+pub use hotreload::DylibPatch;
@@ -0,0 +1,224 @@
+//! Note names, like `C4` for MIDI note 60.
+//!
+//! Octaves go from -1 to 9, so that middle C (60) is `C4`. Sharps are written with `#`,
+//! flats with `b`.
+//!
+//! Names are the note numbers that patches see, they don't follow `octave_offset` of
+//! [ConfigArguments](crate::ConfigArguments): with an offset of 1, `C4` from a backend is
+//! `C5` in patches.
+#![allow(non_snake_case)]
+use std::error::Error;
+
+use super::{KeyFilter, KeyRangeFilter, KeysFilter, NoteOff, NoteOn};
+
+const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Returns the name of a MIDI note number, using sharps.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// assert_eq!(note_name(60), "C4");
+/// assert_eq!(note_name(61), "C#4");
+/// assert_eq!(note_name(0), "C-1");
+/// assert_eq!(note_name(127), "G9");
+/// ```
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NAMES[note as usize % 12], note as i16 / 12 - 1)
+}
+
+/// Returns the MIDI note number of a note name, or `None` when it isn't one.
+///
+/// The letter may be lowercase, and may be followed by a single `#` or `b`.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// assert_eq!(note_number("C4"), Some(60));
+/// assert_eq!(note_number("Db5"), Some(73));
+/// assert_eq!(note_number("f#3"), Some(54));
+/// assert_eq!(note_number("C-1"), Some(0));
+/// assert_eq!(note_number("G9"), Some(127));
+///
+/// assert_eq!(note_number("G#9"), None);
+/// assert_eq!(note_number("Cb-1"), None);
+/// assert_eq!(note_number("H2"), None);
+/// assert_eq!(note_number("C"), None);
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// for note in 0..=127 {
+///     assert_eq!(note_number(&note_name(note)), Some(note));
+/// }
+/// ```
+pub const fn note_number(name: &str) -> Option<u8> {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() { return None; }
+    let semitone: i16 = match bytes[0].to_ascii_uppercase() {
+        b'C' => 0,
+        b'D' => 2,
+        b'E' => 4,
+        b'F' => 5,
+        b'G' => 7,
+        b'A' => 9,
+        b'B' => 11,
+        _ => return None,
+    };
+    let mut i = 1;
+    let mut accidental: i16 = 0;
+    if i < bytes.len() && bytes[i] == b'#' {
+        accidental = 1;
+        i += 1;
+    } else if i < bytes.len() && bytes[i] == b'b' {
+        accidental = -1;
+        i += 1;
+    }
+    let negative = i < bytes.len() && bytes[i] == b'-';
+    if negative { i += 1; }
+    if i == bytes.len() { return None; }
+    let mut octave: i16 = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() || octave > 10 { return None; }
+        octave = octave * 10 + (bytes[i] - b'0') as i16;
+        i += 1;
+    }
+    if negative { octave = -octave; }
+    let note = (octave + 1) * 12 + semitone + accidental;
+    if note >= 0 && note <= 127 { Some(note as u8) } else { None }
+}
+
+/// Returns the MIDI note number of a note name, see [note_number].
+///
+/// This panics when the name is not a valid note. In a constant, like with [note!], that
+/// gives a compile error.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// const MIDDLE_C: u8 = note("C4");
+/// assert_eq!(MIDDLE_C, 60);
+/// assert_eq!(note("Bb3"), 58);
+/// ```
+///
+/// ```compile_fail
+/// # use rmididings::proc::*;
+/// const NOT_A_NOTE: u8 = note("X4");
+/// ```
+pub const fn note(name: &str) -> u8 {
+    match note_number(name) {
+        Some(note) => note,
+        None => panic!("invalid note name"),
+    }
+}
+
+/// Returns the MIDI note number of a note name, resolved at compile time.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = KeyFilter(note!("C4"));
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! note {
+    ( $name:expr ) => {{
+        const NOTE: u8 = $crate::proc::note($name);
+        NOTE
+    }};
+}
+
+/// Filter on a key (note) by its name, see [KeyFilter].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = NoteFilter("C4");
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,61,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// ```
+pub const fn NoteFilter(name: &str) -> KeyFilter {
+    KeyFilter(note(name))
+}
+
+/// Filter on multiple keys (notes) by their names, see [KeysFilter].
+///
+/// Fails when a name is not a valid note. With names known when compiling, use [note!]
+/// instead, like `KeysFilter(&[note!("C4"), note!("Eb4")])`.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = NotesFilter(&["C4", "Eb4"]).unwrap();
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), NoteOnEvent(0,0,62,20), NoteOffEvent(0,0,63)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), NoteOffEvent(0,0,63)]);
+///
+/// assert!(NotesFilter(&["C4", "X4"]).is_err());
+/// ```
+pub fn NotesFilter(names: &[&str]) -> Result<KeysFilter, Box<dyn Error>> {
+    let notes = names
+        .iter()
+        .map(|name| note_number(name).ok_or_else(|| format!("Invalid note name {:?}", name)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(KeysFilter(notes))
+}
+
+/// Filter on a range of keys (notes) by their names, see [KeyRangeFilter].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = NoteRangeFilter("C4", "D4");
+///
+/// let mut evs = EventStream::from(vec![NoteOnEvent(0,0,59,20), NoteOnEvent(0,0,62,20), NoteOnEvent(0,0,63,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,62,20));
+/// ```
+pub const fn NoteRangeFilter(low: &str, high: &str) -> KeyRangeFilter {
+    KeyRangeFilter::between(note(low), note(high))
+}
+
+/// Generate a NoteOn event for a note name, see [NoteOn].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let mut evs = EventStream::none();
+/// NoteOnName("A4", 100).run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,69,100));
+/// ```
+pub const fn NoteOnName(name: &str, velocity: u8) -> NoteOn {
+    NoteOn(note(name), velocity)
+}
+
+/// Generate a NoteOff event for a note name, see [NoteOff].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let mut evs = EventStream::none();
+/// NoteOffName("A4").run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,69));
+/// ```
+pub const fn NoteOffName(name: &str) -> NoteOff {
+    NoteOff(note(name))
+}
@@ -1,9 +1,12 @@
-pub use rosc::OscType;
+pub use rosc::{OscTime, OscType};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime};
 use crate::proc::event::*;
 use crate::proc::filter_trait::*;
 use crate::proc::event_stream::*;
 
-use std::collections::HashMap;
 
 define_generator!(
     #[doc(hidden)]
@@ -42,79 +45,559 @@ define_generator!(
 /// assert_eq!(evs, OscEvent(0, "/bar".to_string(), vec![o::Int(5), o::String("yes".to_string())]));
 /// # }
 /// ```
+///
+/// Arguments can also be plain Rust values, see [IntoOscArg].
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = Osc!("/bar", 5, 0.5f32, "hello", true);
+///
+/// let mut evs = EventStream::none();
+/// filter.run(&mut evs);
+/// assert_eq!(evs, OscEvent(0, "/bar".to_string(), vec![o::Int(5), o::Float(0.5), o::String("hello".to_string()), o::Bool(true)]));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! Osc {
     ( $msg:expr ) => {
         _Osc(String::from($msg), vec![])
     };
     ( $msg:expr, $( $arg:expr ),+ ) => {
-        _Osc(String::from($msg), vec![ $($arg),+ ])
+        _Osc(String::from($msg), vec![ $(IntoOscArg::into_osc_arg($arg)),+ ])
     }
 }
 
-define_filter!(
-    /// Filter on OSC address
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use rmididings::proc::*;
-    /// let filter = OscAddrFilter(&"/foo");
-    ///
-    /// let ev1 = OscEvent(0, "/foo".to_string(), vec![]);
-    /// let ev2 = OscEvent(0, "/bar".to_string(), vec![]);
-    ///
-    /// let mut evs = EventStream::from(vec![&ev1, &ev2]);
-    /// filter.run(&mut evs);
-    /// assert_eq!(evs, ev1);
-    /// ```
-    OscAddrFilter(&'static str)
+define_generator!(
+    #[doc(hidden)]
+    _OscBundle(Vec<_Osc>, Option<Duration>)
+    fn generate_single(&self) -> Event<'static> {
+        let messages = self.0.iter().map(|o| OscEventImpl { port: 0, addr: o.0.clone(), args: o.1.clone(), time: None }).collect();
+        let time = self.1.and_then(|delay| OscTime::try_from(SystemTime::now() + delay).ok());
+        OscBundleEvent(0, messages, time)
+    }
+);
+
+impl _OscBundle {
+    /// Tags the bundle with a time this far from when it is generated, so that receivers
+    /// honoring time tags apply it then.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.1 = Some(delay);
+        self
+    }
+}
+
+/// Generates an OSC bundle, to send several OSC messages in a single packet.
+///
+/// The arguments are [Osc!] messages. By default receivers apply the bundle right away, use
+/// `with_delay` to have them apply it later.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = OscBundle!(Osc!("/mixer/mute", 1), Osc!("/mixer/volume", 0.5f32));
+///
+/// let mut evs = EventStream::none();
+/// filter.run(&mut evs);
+/// let bundle = match evs.into_iter().last() {
+///     Some(Event::OscBundle(bundle)) => bundle,
+///     _ => panic!("expected an OSC bundle"),
+/// };
+/// assert_eq!(bundle.time, None);
+/// assert_eq!(bundle.messages.iter().map(|m| m.addr.as_str()).collect::<Vec<_>>(), vec!["/mixer/mute", "/mixer/volume"]);
+/// # }
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// # fn main() {
+/// let filter = OscBundle!(Osc!("/cue/go")).with_delay(Duration::from_millis(100));
+///
+/// let mut evs = EventStream::none();
+/// filter.run(&mut evs);
+/// assert!(matches!(evs.into_iter().last(), Some(Event::OscBundle(OscBundleEventImpl { time: Some(_), .. }))));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! OscBundle {
+    ( $( $osc:expr ),* ) => {
+        _OscBundle(vec![ $($osc),* ], None)
+    };
+}
+
+/// Groups all OSC messages in the event stream into a bundle per port.
+///
+/// When used as `post` of [RunArguments](crate::RunArguments), the OSC messages output for a
+/// single incoming event are sent in one packet, so that receivers apply them at once. The bundle
+/// takes the place of the first message on its port, other events are kept as they are. By
+/// default receivers apply the bundle right away, use `with_delay` to have them apply it later.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = OscToBundle();
+///
+/// let mut evs = EventStream::from(vec![
+///     OscEvent(0, "/mixer/mute".to_string(), vec![]),
+///     NoteOnEvent(0,0,60,20),
+///     OscEvent(1, "/lights/on".to_string(), vec![]),
+///     OscEvent(0, "/mixer/volume".to_string(), vec![]),
+/// ]);
+/// filter.run(&mut evs);
+/// let addrs: Vec<(usize, Vec<String>)> = evs.iter().filter_map(|ev| match ev {
+///     Event::OscBundle(bundle) => Some((bundle.port, bundle.messages.iter().map(|m| m.addr.clone()).collect())),
+///     _ => None,
+/// }).collect();
+/// assert_eq!(addrs, vec![
+///     (0, vec!["/mixer/mute".to_string(), "/mixer/volume".to_string()]),
+///     (1, vec!["/lights/on".to_string()]),
+/// ]);
+/// assert_eq!(evs.len(), 3);
+/// # }
+/// ```
+pub struct OscToBundle {
+    delay: Option<Duration>,
+}
+
+pub fn OscToBundle() -> OscToBundle {
+    OscToBundle { delay: None }
+}
+
+impl OscToBundle {
+    /// Tags the bundles with a time this far from when they are made, so that receivers
+    /// honoring time tags apply them then.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+impl FilterTrait for OscToBundle {
+    fn run(&self, evs: &mut EventStream) {
+        if !evs.iter().any(|ev| matches!(ev, Event::Osc(_))) { return; }
+
+        let time = self.delay.and_then(|delay| OscTime::try_from(SystemTime::now() + delay).ok());
+        let mut grouped: Vec<Event> = vec![];
+        // Index in grouped of the bundle made for each port.
+        let mut bundle_idxs: HashMap<usize, usize> = HashMap::new();
+        for ev in evs.iter() {
+            let Event::Osc(message) = ev else {
+                grouped.push(ev.clone());
+                continue;
+            };
+            match bundle_idxs.get(&message.port).and_then(|idx| grouped.get_mut(*idx)) {
+                Some(Event::OscBundle(bundle)) => bundle.messages.push(message.clone()),
+                _ => {
+                    bundle_idxs.insert(message.port, grouped.len());
+                    grouped.push(OscBundleEvent(message.port, vec![message.clone()], time));
+                },
+            }
+        }
+        evs.replace(EventStream::from(grouped));
+    }
+}
+
+/// Filter on OSC address
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = OscAddrFilter("/foo");
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![]);
+/// let ev2 = OscEvent(0, "/bar".to_string(), vec![]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, ev1);
+/// ```
+///
+/// The address can also be built at runtime:
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let app = "coolapp";
+/// let filter = OscAddrFilter(format!("/{}/quit", app));
+///
+/// let mut evs = EventStream::from(vec![OscEvent(0, "/coolapp/quit".to_string(), vec![]), OscEvent(0, "/quit".to_string(), vec![])]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, OscEvent(0, "/coolapp/quit".to_string(), vec![]));
+/// ```
+pub struct OscAddrFilter {
+    pub addr: Cow<'static, str>,
+}
+
+pub fn OscAddrFilter<S: Into<Cow<'static, str>>>(addr: S) -> OscAddrFilter {
+    OscAddrFilter { addr: addr.into() }
+}
+
+impl OscAddrFilter {
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::Osc(ev) => ev.addr == self.0,
+            Event::Osc(ev) => ev.addr == self.addr,
             _ => true,
         }
     }
-);
+}
 
-/// Filters OSC messages on an address prefix and strips the prefix from the address.
+impl FilterTrait for OscAddrFilter {
+    fn run(&self, evs: &mut EventStream) {
+        evs.retain(|ev| self.filter_single(ev));
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.retain(|ev| !self.filter_single(ev));
+    }
+
+    fn quick_reject(&self, ev: &Event) -> bool {
+        !self.filter_single(ev)
+    }
+}
+
+/// Sends OSC events to a patch depending on their address.
+///
+/// Each arm is `address => patch`, where the patch gets the OSC events with that address.
+/// A last arm `_ => patch` gets the OSC events that no other arm has. OSC events without an
+/// arm are discarded, other events are passed as they are.
+///
+/// This is a shorthand for a [Fork!] of [Chain!]s with an [OscAddrFilter].
 ///
 /// # Examples
 ///
 /// ```
+/// # #[macro_use] extern crate rmididings;
 /// # use rmididings::proc::*;
-/// let filter = OscStripPrefix("/coolapp");
+/// # fn main() {
+/// let filter = OscRoute! {
+///     "/next_scene" => SceneSwitchOffset(1),
+///     "/prev_scene" => SceneSwitchOffset(-1),
+///     _ => Port(1),
+/// };
 ///
-/// let ev1 = OscEvent(0, "/foo".to_string(), vec![]);
-/// let ev2 = OscEvent(0, "/coolapp/bar".to_string(), vec![]);
+/// let mut evs = EventStream::from(vec![OscEvent(0, "/next_scene".to_string(), vec![]), NoteOnEvent(0,0,60,20)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,0,60,20), SceneSwitchOffsetEvent(1)]);
+///
+/// let mut evs = EventStream::from(OscEvent(0, "/other".to_string(), vec![]));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, OscEvent(1, "/other".to_string(), vec![]));
+/// # }
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let filter = OscRoute! { "/mixer/volume" => Port(2) };
+///
+/// let mut evs = EventStream::from(vec![OscEvent(0, "/other".to_string(), vec![]), OscEvent(0, "/mixer/volume".to_string(), vec![]), CtrlEvent(0,0,7,100)]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,100), OscEvent(2, "/mixer/volume".to_string(), vec![])]);
+/// # }
+/// ```
+///
+/// Addresses can be computed, each is evaluated once.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let mut calls = 0;
+/// let mut addr = |name: &str| { calls += 1; format!("/app/{}", name) };
+/// let filter = OscRoute! { addr("quit") => Quit(), _ => Discard() };
+/// assert_eq!(calls, 1);
+///
+/// let mut evs = EventStream::from(OscEvent(0, "/app/quit".to_string(), vec![]));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, QuitEvent());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! OscRoute {
+    // The arms are collected first, as the last one may be the fallback.
+    (@routes [ $($addr:expr => $patch:expr,)* ] _ => $fallback:expr $(,)?) => (
+        Fork!(
+            Not!(TypeFilter!(Osc)),
+            $( Chain!(TypeFilter!(Osc), OscAddrFilter($addr.clone()), $patch), )*
+            Chain!(TypeFilter!(Osc), $( Not!(OscAddrFilter($addr)), )* $fallback)
+        )
+    );
+    (@routes [ $($addr:expr => $patch:expr,)* ]) => (
+        Fork!(
+            Not!(TypeFilter!(Osc))
+            $( , Chain!(TypeFilter!(Osc), OscAddrFilter($addr), $patch) )*
+        )
+    );
+    // Each address is evaluated once, the fallback uses it again.
+    (@routes [ $($routes:tt)* ] $addr:expr => $patch:expr $(, $($rest:tt)*)?) => ({
+        let addr: ::std::borrow::Cow<'static, str> = ::std::convert::Into::into($addr);
+        OscRoute!(@routes [ $($routes)* addr => $patch, ] $($($rest)*)?)
+    });
+    ( $($arms:tt)+ ) => (
+        OscRoute!(@routes [] $($arms)+)
+    );
+}
+
+/// Filter on OSC address, using OSC pattern matching.
+///
+/// The filter's address can be a pattern, see [osc_pattern_matches]. When an incoming
+/// address is a pattern itself, it is matched against the filter's address instead.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = OscAddrPatternFilter("/synth/*/volume");
+///
+/// let ev1 = OscEvent(0, "/synth/lead/volume".to_string(), vec![]);
+/// let ev2 = OscEvent(0, "/synth/bass/volume".to_string(), vec![]);
+/// let ev3 = OscEvent(0, "/synth/lead/cutoff".to_string(), vec![]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![ev1, ev2]);
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = OscAddrPatternFilter("/mixer/{volume,pan}/*");
+///
+/// let ev1 = OscEvent(0, "/mixer/volume/1".to_string(), vec![]);
+/// let ev2 = OscEvent(0, "/mixer/mute/1".to_string(), vec![]);
+/// let ev3 = OscEvent(0, "/mixer/pan/1/fine".to_string(), vec![]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, ev1);
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = OscAddrPatternFilter("/mixer/volume/1");
+///
+/// let ev1 = OscEvent(0, "/mixer/volume/[0-3]".to_string(), vec![]);
+/// let ev2 = OscEvent(0, "/mixer/volume/[!1]".to_string(), vec![]);
 ///
 /// let mut evs = EventStream::from(vec![&ev1, &ev2]);
 /// filter.run(&mut evs);
-/// assert_eq!(evs, OscEvent(0, "/bar".to_string(), vec![]));
+/// assert_eq!(evs, ev1);
 /// ```
-pub struct OscStripPrefix(pub &'static str);
-impl OscStripPrefix {
+pub struct OscAddrPatternFilter {
+    pub addr: Cow<'static, str>,
+}
+
+pub fn OscAddrPatternFilter<S: Into<Cow<'static, str>>>(addr: S) -> OscAddrPatternFilter {
+    OscAddrPatternFilter { addr: addr.into() }
+}
+
+impl OscAddrPatternFilter {
     fn filter_single(&self, ev: &Event) -> bool {
         match ev {
-            Event::Osc(ev) => ev.addr.starts_with(self.0),
+            Event::Osc(ev) => osc_pattern_matches(&self.addr, &ev.addr) || osc_pattern_matches(&ev.addr, &self.addr),
             _ => true,
         }
     }
+}
 
-    fn modify_single(&self, ev: &mut Event) {
-        match ev {
-            Event::Osc(ev) => ev.addr = ev.addr.strip_prefix(self.0).unwrap().to_string(),
-            _ => {},
+impl FilterTrait for OscAddrPatternFilter {
+    fn run(&self, evs: &mut EventStream) {
+        evs.retain(|ev| self.filter_single(ev));
+    }
+
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.retain(|ev| !self.filter_single(ev));
+    }
+
+    fn quick_reject(&self, ev: &Event) -> bool {
+        !self.filter_single(ev)
+    }
+}
+
+/// Returns whether an OSC address matches a pattern, following the OSC 1.0 specification.
+///
+/// - `?` matches any single character
+/// - `*` matches any sequence of zero or more characters
+/// - `[abc]` matches any of the characters, `[a-z]` a range of them, `[!abc]` any other character
+/// - `{foo,bar}` matches any of the strings
+///
+/// None of these match a `/`, so they don't cross address parts. Everything else matches itself.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let table = [
+///     ("/foo", "/foo", true),
+///     ("/foo", "/foobar", false),
+///     ("/fo?", "/foo", true),
+///     ("/fo?", "/fo", false),
+///     ("/fo?", "/fo/", false),
+///     ("/foo/*", "/foo/bar", true),
+///     ("/foo/*", "/foo/", true),
+///     ("/foo/*", "/foo/bar/baz", false),
+///     ("/*/bar", "/foo/bar", true),
+///     ("/f*o*r", "/foobar", true),
+///     ("/f*z", "/foobar", false),
+///     ("/ch[0-9]", "/ch5", true),
+///     ("/ch[0-9]", "/chx", false),
+///     ("/ch[!0-9]", "/chx", true),
+///     ("/ch[!0-9]", "/ch5", false),
+///     ("/ch[abc]", "/chb", true),
+///     ("/ch[a-]", "/ch-", true),
+///     ("/ch[!a]", "/ch/", false),
+///     ("/{foo,bar}/x", "/bar/x", true),
+///     ("/{foo,bar}/x", "/baz/x", false),
+///     ("/{foo,foobar}", "/foobar", true),
+///     ("/{,a}b", "/b", true),
+///     ("/[unclosed", "/[unclosed", true),
+///     ("/{unclosed", "/{unclosed", true),
+/// ];
+/// for (pattern, addr, expected) in table.iter() {
+///     assert_eq!(osc_pattern_matches(pattern, addr), *expected, "{} against {}", pattern, addr);
+/// }
+/// ```
+pub fn osc_pattern_matches(pattern: &str, addr: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let addr: Vec<char> = addr.chars().collect();
+    pattern_matches(&pattern, &addr)
+}
+
+fn pattern_matches(pattern: &[char], addr: &[char]) -> bool {
+    match pattern.first() {
+        None => addr.is_empty(),
+        Some('*') => {
+            for i in 0..=addr.len() {
+                if pattern_matches(&pattern[1..], &addr[i..]) { return true; }
+                if addr.get(i) == Some(&'/') { break; }
+            }
+            false
+        },
+        Some('?') => match addr.first() {
+            Some(c) if *c != '/' => pattern_matches(&pattern[1..], &addr[1..]),
+            _ => false,
+        },
+        Some('[') if pattern.contains(&']') => {
+            let end = pattern.iter().position(|c| *c == ']').unwrap_or_default();
+            match addr.first() {
+                Some(c) if *c != '/' && char_set_matches(&pattern[1..end], *c) => pattern_matches(&pattern[end+1..], &addr[1..]),
+                _ => false,
+            }
+        },
+        Some('{') if pattern.contains(&'}') => {
+            let end = pattern.iter().position(|c| *c == '}').unwrap_or_default();
+            pattern[1..end].split(|c| *c == ',').any(|alternative| {
+                addr.starts_with(alternative) && pattern_matches(&pattern[end+1..], &addr[alternative.len()..])
+            })
+        },
+        Some(c) => addr.first() == Some(c) && pattern_matches(&pattern[1..], &addr[1..]),
+    }
+}
+
+fn char_set_matches(set: &[char], c: char) -> bool {
+    let (negate, set) = match set.first() {
+        Some('!') => (true, &set[1..]),
+        _ => (false, set),
+    };
+    let mut matches = false;
+    let mut i = 0;
+    while i < set.len() {
+        if i + 2 < set.len() && set[i+1] == '-' {
+            matches |= set[i] <= c && c <= set[i+2];
+            i += 3;
+        } else {
+            matches |= set[i] == c;
+            i += 1;
+        }
+    }
+    matches != negate
+}
+
+/// Filters OSC messages on an address prefix and strips the prefix from the address.
+///
+/// The prefix can be a pattern, see [osc_pattern_matches]. It then needs to match whole address
+/// parts, and the longest matching prefix is stripped.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = OscStripPrefix("/coolapp");
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![]);
+/// let ev2 = OscEvent(0, "/coolapp/bar".to_string(), vec![]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, OscEvent(0, "/bar".to_string(), vec![]));
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let filter = OscStripPrefix("/Carla/*");
+///
+/// let ev1 = OscEvent(0, "/Carla/0/set_volume".to_string(), vec![]);
+/// let ev2 = OscEvent(0, "/Other/0/set_volume".to_string(), vec![]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, OscEvent(0, "/set_volume".to_string(), vec![]));
+/// ```
+pub struct OscStripPrefix {
+    pub prefix: Cow<'static, str>,
+}
+
+pub fn OscStripPrefix<S: Into<Cow<'static, str>>>(prefix: S) -> OscStripPrefix {
+    OscStripPrefix { prefix: prefix.into() }
+}
+
+impl OscStripPrefix {
+    /// Length of the matching prefix in bytes, if it matches.
+    fn prefix_len(&self, addr: &str) -> Option<usize> {
+        if !self.prefix.contains(['?', '*', '[', '{']) {
+            return if addr.starts_with(&*self.prefix) { Some(self.prefix.len()) } else { None };
         }
+        // Try the longest prefix first, ending at a part boundary.
+        let ends = addr.char_indices().filter(|(_, c)| *c == '/').map(|(i, _)| i).chain(Some(addr.len()));
+        let mut ends: Vec<usize> = ends.collect();
+        ends.reverse();
+        ends.into_iter().find(|end| osc_pattern_matches(&self.prefix, &addr[..*end]))
     }
 }
 impl FilterTrait for OscStripPrefix {
     fn run(&self, evs: &mut EventStream) {
-        evs.retain(|ev| self.filter_single(&ev));
+        evs.retain(|ev| match ev {
+            Event::Osc(ev) => self.prefix_len(&ev.addr).is_some(),
+            _ => true,
+        });
         for ev in evs.iter_mut() {
-            self.modify_single(ev);
+            if let Event::Osc(ev) = ev {
+                if let Some(len) = self.prefix_len(&ev.addr) {
+                    ev.addr = ev.addr[len..].to_string();
+                }
+            }
         }
     }
+
+    // Like other filters, only non-matching OSC events are kept, and they aren't modified.
+    fn run_inverse(&self, evs: &mut EventStream) {
+        evs.retain(|ev| match ev {
+            Event::Osc(ev) => self.prefix_len(&ev.addr).is_none(),
+            _ => false,
+        });
+    }
 }
 
 define_modifier!(
@@ -142,30 +625,115 @@ define_modifier!(
     }
 );
 
+define_modifier!(
+    /// Turns OSC messages on an address into NoteOn events.
+    ///
+    /// The first argument is the note, the second one the velocity, or 127 when it is missing.
+    /// Both can be integers or floats; a float velocity is taken as a fraction from 0.0 to 1.0,
+    /// like many OSC controllers send. The address can be a pattern, see [osc_pattern_matches].
+    /// Other OSC messages are passed unchanged, use [ProcessOsc!] for anything more involved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// use rmididings::osc::OscType as o;
+    ///
+    /// let filter = OscToNoteOn("/keys/*");
+    ///
+    /// let ev1 = OscEvent(0, "/keys/press".to_string(), vec![o::Int(60), o::Int(100)]);
+    /// let ev2 = OscEvent(1, "/keys/press".to_string(), vec![o::Float(62.0), o::Float(0.5)]);
+    /// let ev3 = OscEvent(0, "/keys/press".to_string(), vec![o::Int(64)]);
+    /// let ev4 = OscEvent(0, "/keys/press".to_string(), vec![o::String("C".to_string())]);
+    /// let ev5 = OscEvent(0, "/pads/press".to_string(), vec![o::Int(36)]);
+    ///
+    /// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4, &ev5]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,100), NoteOnEvent(1,0,62,64), NoteOnEvent(0,0,64,127), ev4, ev5]);
+    /// ```
+    OscToNoteOn(&'static str)
+    fn modify_single(&self, ev: &mut Event) {
+        let Event::Osc(osc) = ev else { return };
+        if !osc_pattern_matches(self.0, &osc.addr) { return; }
+        let Some(note) = osc.args.first().and_then(osc_arg_number) else { return };
+        let velocity = match osc.args.get(1) {
+            Some(arg) => match osc_arg_fraction(arg) {
+                Some(velocity) => velocity,
+                None => return,
+            },
+            None => 127,
+        };
+        *ev = NoteOnEvent(osc.port, 0, note.clamp(0, 127) as u8, velocity.clamp(0, 127) as u8);
+    }
+);
+
+define_modifier!(
+    /// Turns OSC messages on an address into Ctrl events for a controller.
+    ///
+    /// The first argument is the value. An integer is used as it is, a float is taken as a
+    /// fraction from 0.0 to 1.0 and scaled to 0 to 127, like many OSC controllers send.
+    /// The address can be a pattern, see [osc_pattern_matches]. Other OSC messages are passed
+    /// unchanged, use [ProcessOsc!] for anything more involved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// use rmididings::osc::OscType as o;
+    ///
+    /// let filter = OscToCtrl("/fader1", 7);
+    ///
+    /// let ev1 = OscEvent(0, "/fader1".to_string(), vec![o::Int(100)]);
+    /// let ev2 = OscEvent(0, "/fader1".to_string(), vec![o::Float(1.0)]);
+    /// let ev3 = OscEvent(0, "/fader1".to_string(), vec![]);
+    /// let ev4 = OscEvent(0, "/fader2".to_string(), vec![o::Int(100)]);
+    ///
+    /// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3, &ev4]);
+    /// filter.run(&mut evs);
+    /// assert_eq!(evs, vec![CtrlEvent(0,0,7,100), CtrlEvent(0,0,7,127), ev3, ev4]);
+    /// ```
+    OscToCtrl(&'static str, u32)
+    fn modify_single(&self, ev: &mut Event) {
+        let Event::Osc(osc) = ev else { return };
+        if !osc_pattern_matches(self.0, &osc.addr) { return; }
+        let Some(value) = osc.args.first().and_then(osc_arg_fraction) else { return };
+        *ev = CtrlEvent(osc.port, 0, self.1, value);
+    }
+);
+
+/// Numeric OSC argument, floats rounded.
+fn osc_arg_number(arg: &OscType) -> Option<i32> {
+    match arg {
+        OscType::Int(v) => Some(*v),
+        OscType::Long(v) => Some((*v).clamp(i32::MIN as i64, i32::MAX as i64) as i32),
+        OscType::Float(v) => Some(v.round() as i32),
+        OscType::Double(v) => Some(v.round() as i32),
+        _ => None,
+    }
+}
+
+/// Numeric OSC argument, floats taken as fraction of 127.
+fn osc_arg_fraction(arg: &OscType) -> Option<i32> {
+    match arg {
+        OscType::Float(v) => Some((v.clamp(0.0, 1.0) * 127.0).round() as i32),
+        OscType::Double(v) => Some((v.clamp(0.0, 1.0) * 127.0).round() as i32),
+        _ => osc_arg_number(arg),
+    }
+}
+
 #[doc(hidden)]
 pub struct _ProcessOsc(pub Box<dyn Fn(&Vec<OscType>) -> Box<dyn FilterTrait>>);
 #[doc(hidden)]
 impl FilterTrait for _ProcessOsc {
     fn run(&self, evs: &mut EventStream) {
-        let mut results: HashMap<usize, EventStream> = HashMap::new();
-
-        // First gather all resulting EventStreams from the function invocations.
-        for (i, ev) in evs.iter().enumerate() {
-            match ev {
-                Event::Osc(OscEventImpl { port: _, addr: _, args }) => {
-                    let mut evs = EventStream::from(ev);
-                    self.0(args).run(&mut evs);
-                    results.insert(i, evs);
-                },
-                _ => {},
-            }
-        }
-
-        // Then replace the events by their results.
-        for (i, r_evs) in results {
-            evs.splice(i..i+1, r_evs);
-        }
-
+        evs.map_replace(|ev| match ev {
+            Event::Osc(OscEventImpl { args, .. }) => {
+                let mut r_evs = EventStream::from(ev);
+                self.0(args).run(&mut r_evs);
+                Some(r_evs)
+            },
+            _ => None,
+        });
         evs.dedup();
     }
 
@@ -176,6 +744,9 @@ impl FilterTrait for _ProcessOsc {
 ///
 /// A maximum of eight OSC arguments is currently supported (please open an issue if you need more).
 ///
+/// OSC events with arguments that don't match are passed unchanged. Another patch can be run
+/// on them by giving it as last argument, like `fallback: Discard()`, see also [ProcessOscOrDiscard!].
+///
 /// # Examples
 ///
 /// ```
@@ -211,102 +782,407 @@ impl FilterTrait for _ProcessOsc {
 /// assert_eq!(evs, vec![NoteOnEvent(0,0,60,30), ev2, ev3, ev4, ev5]);
 /// # }
 /// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOsc!(o::Int, o::Int, |a: &i32, b: &i32| Ctrl(*a as u32, *b), fallback: Discard());
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![o::Int(7), o::Int(100)]);
+/// let ev2 = OscEvent(0, "/foo".to_string(), vec![o::Int(60)]);
+/// let ev3 = NoteOnEvent(0,0,62,30);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,100), ev3]);
+/// # }
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOsc!(o::Int, o::Int, o::Float, |channel: &i32, note: &i32, velocity: &f32| {
+///     Chain!(NoteOn(*note as u8, (*velocity * 127.0) as u8), Channel(*channel as u8))
+/// });
+///
+/// let ev1 = OscEvent(0, "/note".to_string(), vec![o::Int(2), o::Int(60), o::Float(1.0)]);
+/// let ev2 = OscEvent(0, "/note".to_string(), vec![o::Int(2), o::Int(60), o::Int(1)]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![NoteOnEvent(0,2,60,127), ev2]);
+/// # }
+/// ```
+///
+/// All argument counts up to eight work the same way.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOsc!(o::Int, o::Int, o::Int, o::Int, o::Int, o::Int, o::Int, o::Int,
+///     |a: &i32, b: &i32, c: &i32, d: &i32, e: &i32, f: &i32, g: &i32, h: &i32| Ctrl(*a as u32, b + c + d + e + f + g + h));
+///
+/// let mut evs = EventStream::from(OscEvent(0, "/sum".to_string(), (1..=8).map(o::Int).collect()));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, CtrlEvent(0,0,1,35));
+/// # }
+/// ```
+///
+/// The patch may result in any number of events, which take the place of the OSC event.
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOsc!(o::Int, |i: &i32| Fork!(NoteOn(*i as u8, 30), NoteOn(*i as u8 + 7, 30)));
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![o::Int(60)]);
+/// let ev2 = OscEvent(0, "/foo".to_string(), vec![o::Int(48)]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![
+///     NoteOnEvent(0,0,60,30), NoteOnEvent(0,0,67,30),
+///     NoteOnEvent(0,0,48,30), NoteOnEvent(0,0,55,30),
+/// ]);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! ProcessOsc {
-    ( $argt0:path, $f:expr ) => {
+    ( $argt0:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0)] => { Box::new($f(arg0)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1)] => { Box::new($f(arg0, arg1)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $argt2:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $argt2:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1), $argt2(arg2)] => { Box::new($f(arg0, arg1, arg2)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1), $argt2(arg2), $argt3(arg3)] => { Box::new($f(arg0, arg1, arg2, arg3)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1), $argt2(arg2), $argt3(arg3), $argt4(arg4)] => { Box::new($f(arg0, arg1, arg2, arg3, arg4)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $argt5:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $argt5:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1), $argt2(arg2), $argt3(arg3), $argt4(arg4), $argt5(arg5)] => { Box::new($f(arg0, arg1, arg2, arg3, arg4, arg5)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $argt5:path, $argt6:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $argt5:path, $argt6:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1), $argt2(arg2), $argt3(arg3), $argt4(arg4), $argt5(arg5), $argt6(arg6)] => { Box::new($f(arg0, arg1, arg2, arg3, arg4, arg5, arg6)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $argt5:path, $argt6:path, $argt7:path, $f:expr ) => {
+    ( $argt0:path, $argt1:path, $argt2:path, $argt3:path, $argt4:path, $argt5:path, $argt6:path, $argt7:path, $f:expr $(, fallback: $fallback:expr)? ) => {
         _ProcessOsc(
             Box::new(
                 |args: &Vec<OscType>| {
                     match &args[..] {
                         [$argt0(arg0), $argt1(arg1), $argt2(arg2), $argt3(arg3), $argt4(arg4), $argt5(arg5), $argt6(arg6), $argt7(arg7)] => { Box::new($f(arg0, arg1, arg2, arg3, arg4, arg5, arg6, arg7)) },
-                        _ => Box::new(Pass()),
+                        _ => Box::new(_ProcessOscFallback!($($fallback)?)),
                     }
                 }
             )
         )
     };
-}
\ No newline at end of file
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _ProcessOscFallback {
+    () => { Pass() };
+    ( $fallback:expr ) => { $fallback };
+}
+
+/// Like [ProcessOsc!], but discards OSC events that don't match the argument types.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOscOrDiscard!(o::Int, |i: &i32| NoteOn(*i as u8, 30));
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![o::Int(60)]);
+/// let ev2 = OscEvent(0, "/foo".to_string(), vec![o::Float(1.0)]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,30));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ProcessOscOrDiscard {
+    ( $($args:tt)* ) => { ProcessOsc!($($args)*, fallback: Discard()) };
+}
+
+/// Converts a Rust value into an OSC argument.
+///
+/// This is used by [Osc!], so plain values can be given there.
+pub trait IntoOscArg {
+    fn into_osc_arg(self) -> OscType;
+}
+
+/// Converts a tuple of Rust values into OSC arguments.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// let args = (7, 0.5f32, "x", vec![1u8, 2]).into_osc_args();
+/// assert_eq!(args, vec![o::Int(7), o::Float(0.5), o::String("x".to_string()), o::Blob(vec![1, 2])]);
+/// ```
+pub trait IntoOscArgs {
+    fn into_osc_args(self) -> Vec<OscType>;
+}
+
+/// Extracts a Rust value from an OSC argument, if it has the matching type.
+pub trait FromOscArg: Sized {
+    fn from_osc_arg(arg: &OscType) -> Option<Self>;
+}
+
+/// Extracts a tuple of Rust values from OSC arguments, if their number and types match.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// let args = vec![o::Int(7), o::String("x".to_string())];
+/// assert_eq!(<(i32, String)>::from_osc_args(&args), Some((7, "x".to_string())));
+/// assert_eq!(<(i32, f32)>::from_osc_args(&args), None);
+/// assert_eq!(<(i32,)>::from_osc_args(&args), None);
+/// ```
+pub trait FromOscArgs: Sized {
+    fn from_osc_args(args: &[OscType]) -> Option<Self>;
+}
+
+/// A function taking Rust values extracted from OSC arguments, returning a patch, see [ProcessOsc2!].
+pub trait OscArgsFn<Args> {
+    fn call_osc_args(&self, args: &[OscType]) -> Option<Box<dyn FilterTrait>>;
+}
+
+impl IntoOscArg for OscType {
+    fn into_osc_arg(self) -> OscType { self }
+}
+
+impl IntoOscArg for &str {
+    fn into_osc_arg(self) -> OscType { OscType::String(self.to_string()) }
+}
+
+impl FromOscArg for OscType {
+    fn from_osc_arg(arg: &OscType) -> Option<Self> { Some(arg.clone()) }
+}
+
+macro_rules! impl_osc_arg {
+    ( $( $variant:ident($t:ty) ),* ) => {
+        $(
+            impl IntoOscArg for $t {
+                fn into_osc_arg(self) -> OscType { OscType::$variant(self) }
+            }
+
+            impl FromOscArg for $t {
+                fn from_osc_arg(arg: &OscType) -> Option<Self> {
+                    match arg {
+                        OscType::$variant(v) => Some(v.clone()),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_osc_arg!(Int(i32), Long(i64), Float(f32), Double(f64), Bool(bool), Char(char), String(String), Blob(Vec<u8>));
+
+impl IntoOscArgs for () {
+    fn into_osc_args(self) -> Vec<OscType> { vec![] }
+}
+
+impl FromOscArgs for () {
+    fn from_osc_args(args: &[OscType]) -> Option<Self> {
+        if args.is_empty() { Some(()) } else { None }
+    }
+}
+
+impl<Func, Ret> OscArgsFn<()> for Func where Func: Fn() -> Ret, Ret: FilterTrait + 'static {
+    fn call_osc_args(&self, args: &[OscType]) -> Option<Box<dyn FilterTrait>> {
+        <()>::from_osc_args(args)?;
+        Some(Box::new(self()))
+    }
+}
+
+macro_rules! impl_osc_args {
+    ( $( $t:ident $v:ident ),+ ) => {
+        impl<$($t: IntoOscArg),+> IntoOscArgs for ($($t,)+) {
+            fn into_osc_args(self) -> Vec<OscType> {
+                let ($($v,)+) = self;
+                vec![$($v.into_osc_arg()),+]
+            }
+        }
+
+        impl<$($t: FromOscArg),+> FromOscArgs for ($($t,)+) {
+            fn from_osc_args(args: &[OscType]) -> Option<Self> {
+                match args {
+                    [$($v),+] => Some(($($t::from_osc_arg($v)?,)+)),
+                    _ => None,
+                }
+            }
+        }
+
+        impl<Func, Ret, $($t: FromOscArg),+> OscArgsFn<($($t,)+)> for Func where Func: Fn($($t),+) -> Ret, Ret: FilterTrait + 'static {
+            fn call_osc_args(&self, args: &[OscType]) -> Option<Box<dyn FilterTrait>> {
+                let ($($v,)+) = <($($t,)+)>::from_osc_args(args)?;
+                Some(Box::new(self($($v),+)))
+            }
+        }
+    }
+}
+
+impl_osc_args!(A a);
+impl_osc_args!(A a, B b);
+impl_osc_args!(A a, B b, C c);
+impl_osc_args!(A a, B b, C c, D d);
+impl_osc_args!(A a, B b, C c, D d, E e);
+impl_osc_args!(A a, B b, C c, D d, E e, F f);
+impl_osc_args!(A a, B b, C c, D d, E e, F f, G g);
+impl_osc_args!(A a, B b, C c, D d, E e, F f, G g, H h);
+
+/// Like [ProcessOsc!], but with argument types taken from the function.
+///
+/// The function's arguments can be any type implementing [FromOscArg], like `i32`, `f32`,
+/// `String`, `bool` or `Vec<u8>`, and need to be annotated. A maximum of eight arguments is
+/// supported. OSC events with arguments that don't match are passed unchanged, unless a
+/// fallback patch is given as last argument, like `fallback: Discard()`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOsc2!(|ctrl: i32, value: f32| Ctrl(ctrl as u32, (value * 127.0) as i32));
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![o::Int(7), o::Float(1.0)]);
+/// let ev2 = OscEvent(0, "/foo".to_string(), vec![o::Int(7), o::Int(127)]);
+/// let ev3 = NoteOnEvent(0,0,62,30);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2, &ev3]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, vec![CtrlEvent(0,0,7,127), ev2, ev3]);
+/// # }
+/// ```
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use rmididings::osc::OscType as o;
+///
+/// # fn main() {
+/// let filter = ProcessOsc2!(|name: String| if name == "on" { NoteOn(60, 100) } else { NoteOn(60, 0) }, fallback: Discard());
+///
+/// let ev1 = OscEvent(0, "/foo".to_string(), vec![o::String("on".to_string())]);
+/// let ev2 = OscEvent(0, "/foo".to_string(), vec![o::Bool(true)]);
+///
+/// let mut evs = EventStream::from(vec![&ev1, &ev2]);
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,100));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ProcessOsc2 {
+    ( $f:expr $(, fallback: $fallback:expr)? ) => {
+        {
+            let f = $f;
+            _ProcessOsc(
+                Box::new(
+                    move |args: &Vec<OscType>| {
+                        match OscArgsFn::call_osc_args(&f, args) {
+                            Some(patch) => patch,
+                            None => Box::new(_ProcessOscFallback!($($fallback)?)),
+                        }
+                    }
+                )
+            )
+        }
+    };
+}
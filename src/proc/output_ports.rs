@@ -0,0 +1,60 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+thread_local! {
+    static ACTIVE_OUTPUT_PORTS: RefCell<Option<Arc<OutputPorts>>> = const { RefCell::new(None) };
+}
+
+/// Which output ports have something receiving their events, as far as the backends know.
+///
+/// The runner asks the backends before processing each incoming event, and makes it available
+/// to filters like [PortFallback](super::PortFallback) while running patches. Ports are numbered
+/// like patches use them.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let mut ports = OutputPorts::new();
+/// ports.set_has_receivers(1, false);
+/// ports.set_has_receivers(2, true);
+/// assert!(!ports.has_receivers(1));
+/// assert!(ports.has_receivers(2));
+///
+/// // When it isn't known, assume there are.
+/// assert!(ports.has_receivers(3));
+/// ```
+#[derive(Debug, Default)]
+pub struct OutputPorts {
+    receivers: HashMap<usize, bool>,
+}
+
+impl OutputPorts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the port has receivers, `true` when this isn't known.
+    pub fn has_receivers(&self, port: usize) -> bool {
+        self.receivers.get(&port).copied().unwrap_or(true)
+    }
+
+    /// Records whether the port has receivers, any backend having them for a port is enough.
+    pub fn set_has_receivers(&mut self, port: usize, has_receivers: bool) {
+        *self.receivers.entry(port).or_insert(false) |= has_receivers;
+    }
+
+    /// Makes these the output ports used by filters on this thread, while running `f`.
+    pub fn activate<R, F: FnOnce() -> R>(self: &Arc<Self>, f: F) -> R {
+        let previous = ACTIVE_OUTPUT_PORTS.with(|active| active.replace(Some(self.clone())));
+        let result = f();
+        ACTIVE_OUTPUT_PORTS.with(|active| active.replace(previous));
+        result
+    }
+
+    /// Returns the output ports activated with [OutputPorts::activate], if any.
+    pub fn active() -> Option<Arc<OutputPorts>> {
+        ACTIVE_OUTPUT_PORTS.with(|active| active.borrow().clone())
+    }
+}
@@ -0,0 +1,401 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+use super::clock::*;
+use super::event::*;
+use super::event_stream::*;
+use super::filter_trait::*;
+use super::scheduler::*;
+
+/// A recorded phrase of notes, shared by [PhraseRecord], [ArmRecord], [PhrasePlay] and [PhraseStop].
+///
+/// Cloning gives another handle to the same phrase.
+#[derive(Clone)]
+pub struct Phrase {
+    clock: Arc<dyn Clock>,
+    state: Arc<Mutex<PhraseState>>,
+}
+
+#[derive(Default)]
+struct PhraseState {
+    armed: bool,
+    // Time of the first recorded event, offsets are relative to this.
+    record_start: Option<Instant>,
+    events: Vec<(Duration, Event<'static>)>,
+    // Length of the phrase, used for looping.
+    length: Duration,
+    playback: Option<Playback>,
+}
+
+struct Playback {
+    scheduler: Scheduler,
+    transpose: i16,
+    looped: bool,
+    cycle_start: Instant,
+    // Notes that were played and not yet released: port, channel, note.
+    sounding: Vec<(usize, u8, u8)>,
+}
+
+impl Phrase {
+    pub fn new() -> Self {
+        Self::with_clock(system_clock())
+    }
+
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self { clock, state: Arc::new(Mutex::new(PhraseState::default())) }
+    }
+
+    /// Whether recording is armed.
+    pub fn is_armed(&self) -> bool {
+        self.state.lock().map(|state| state.armed).unwrap_or(false)
+    }
+
+    /// Whether the phrase is being played.
+    pub fn is_playing(&self) -> bool {
+        self.state.lock().map(|state| state.playback.is_some()).unwrap_or(false)
+    }
+
+    /// The recorded events, with their time relative to the first one.
+    pub fn events(&self) -> Vec<(Duration, Event<'static>)> {
+        self.state.lock().map(|state| state.events.clone()).unwrap_or_default()
+    }
+}
+
+impl Default for Phrase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhraseState {
+    fn toggle_armed(&mut self, now: Instant) {
+        self.armed = !self.armed;
+        if self.armed {
+            // Start a new take.
+            self.record_start = None;
+            self.events.clear();
+            self.length = Duration::ZERO;
+        } else if let Some(record_start) = self.record_start {
+            self.length = now.saturating_duration_since(record_start);
+        }
+    }
+
+    fn record(&mut self, ev: Event<'static>, now: Instant) {
+        let record_start = *self.record_start.get_or_insert(now);
+        let offset = now.saturating_duration_since(record_start);
+        self.length = self.length.max(offset);
+        self.events.push((offset, ev));
+    }
+
+    fn first_note(&self) -> Option<u8> {
+        self.events.iter().find_map(|(_, ev)| match ev {
+            Event::NoteOn(ev) => Some(ev.note),
+            _ => None,
+        })
+    }
+
+    fn start(&mut self, now: Instant, transpose: i16, looped: bool, evs: &mut EventStream) {
+        let mut playback = Playback {
+            scheduler: Scheduler::new(),
+            transpose,
+            looped,
+            cycle_start: now,
+            sounding: vec![],
+        };
+        playback.schedule_cycle(&self.events);
+        playback.run_due(now, self.length, &self.events, evs);
+        self.playback = Some(playback);
+    }
+
+    fn stop(&mut self, evs: &mut EventStream) {
+        if let Some(playback) = self.playback.take() {
+            for (port, channel, note) in playback.sounding {
+                evs.push(NoteOffEvent(port, channel, note));
+            }
+        }
+    }
+
+    fn run_due(&mut self, now: Instant, evs: &mut EventStream) {
+        if let Some(playback) = &mut self.playback {
+            playback.run_due(now, self.length, &self.events, evs);
+            if playback.scheduler.is_empty() && !playback.looped {
+                self.playback = None;
+            }
+        }
+    }
+}
+
+impl Playback {
+    fn schedule_cycle(&mut self, events: &[(Duration, Event<'static>)]) {
+        for (offset, ev) in events.iter() {
+            if let Some(ev) = transpose_note(ev, self.transpose) {
+                self.scheduler.schedule(self.cycle_start + *offset, ev);
+            }
+        }
+    }
+
+    fn run_due(&mut self, now: Instant, length: Duration, events: &[(Duration, Event<'static>)], evs: &mut EventStream) {
+        loop {
+            let mut due = EventStream::empty();
+            self.scheduler.run_due(now, &mut due);
+            for ev in due.iter() {
+                match ev {
+                    Event::NoteOn(ev) => self.sounding.push((ev.port, ev.channel, ev.note)),
+                    Event::NoteOff(ev) => self.sounding.retain(|s| *s != (ev.port, ev.channel, ev.note)),
+                    _ => {},
+                }
+            }
+            evs.extend(due);
+
+            // Schedule the next cycle when looping, it may already be due.
+            if !self.looped || !self.scheduler.is_empty() || length.is_zero() || events.is_empty() { break; }
+            self.cycle_start += length;
+            self.schedule_cycle(events);
+        }
+    }
+}
+
+fn transpose_note(ev: &Event<'static>, transpose: i16) -> Option<Event<'static>> {
+    let transposed = |note: u8| {
+        let note = note as i16 + transpose;
+        if (0..=127).contains(&note) { Some(note as u8) } else { None }
+    };
+    match ev {
+        Event::NoteOn(ev) => Some(Event::NoteOn(NoteOnEventImpl { note: transposed(ev.note)?, ..*ev })),
+        Event::NoteOff(ev) => Some(Event::NoteOff(NoteOffEventImpl { note: transposed(ev.note)?, ..*ev })),
+        _ => Some(ev.clone()),
+    }
+}
+
+/// Records the notes passing through into a [Phrase], while it is armed.
+///
+/// The events themselves are passed unchanged. Use [ArmRecord] to start and stop recording.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// # fn main() {
+/// let clock = FakeClock::new();
+/// let phrase = Phrase::with_clock(clock.clone());
+/// let filter = Fork!(
+///     Chain!(KeyFilter(0), ArmRecord(&phrase)),
+///     Chain!(Not!(KeyFilter(0)), PhraseRecord(&phrase))
+/// );
+///
+/// filter.run(&mut EventStream::from(NoteOnEvent(0,0,0,100)));
+/// assert!(phrase.is_armed());
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+/// filter.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// clock.advance(Duration::from_millis(250));
+/// filter.run(&mut EventStream::from(NoteOffEvent(0,0,60)));
+///
+/// assert_eq!(phrase.events(), vec![
+///     (Duration::ZERO, NoteOnEvent(0,0,60,20)),
+///     (Duration::from_millis(250), NoteOffEvent(0,0,60)),
+/// ]);
+/// # }
+/// ```
+pub struct PhraseRecord {
+    phrase: Phrase,
+}
+
+pub fn PhraseRecord(phrase: &Phrase) -> PhraseRecord {
+    PhraseRecord { phrase: phrase.clone() }
+}
+
+impl FilterTrait for PhraseRecord {
+    fn run(&self, evs: &mut EventStream) {
+        let now = self.phrase.clock.now();
+        if let Ok(mut state) = self.phrase.state.lock() {
+            if !state.armed { return; }
+            for ev in evs.iter() {
+                match ev {
+                    Event::NoteOn(ev) => state.record(Event::NoteOn(*ev), now),
+                    Event::NoteOff(ev) => state.record(Event::NoteOff(*ev), now),
+                    _ => {},
+                }
+            }
+        }
+    }
+}
+
+/// Toggles recording of a [Phrase], consuming the events.
+///
+/// Arming starts a new recording. Note that each run with events toggles, so you'll
+/// probably want to use a filter like `TypeFilter!(NoteOn)` before it.
+pub struct ArmRecord {
+    phrase: Phrase,
+}
+
+pub fn ArmRecord(phrase: &Phrase) -> ArmRecord {
+    ArmRecord { phrase: phrase.clone() }
+}
+
+impl FilterTrait for ArmRecord {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        let now = self.phrase.clock.now();
+        if let Ok(mut state) = self.phrase.state.lock() {
+            state.toggle_armed(now);
+        }
+        evs.clear();
+    }
+}
+
+/// Plays a recorded [Phrase] when an event reaches it.
+///
+/// The events are replaced by the phrase, keeping the recorded timing. When the
+/// triggering event is a NoteOn, the phrase is transposed by the difference between
+/// its note and the first note of the phrase. Triggering again restarts the phrase.
+///
+/// Use `PhrasePlay(&phrase).looped()` to keep repeating the phrase until [PhraseStop].
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let phrase = Phrase::with_clock(clock.clone());
+/// ArmRecord(&phrase).run(&mut EventStream::none());
+/// PhraseRecord(&phrase).run(&mut EventStream::from(NoteOnEvent(0,0,60,20)));
+/// clock.advance(Duration::from_millis(100));
+/// PhraseRecord(&phrase).run(&mut EventStream::from(NoteOffEvent(0,0,60)));
+/// ArmRecord(&phrase).run(&mut EventStream::none());
+///
+/// // The first note is played right away, the rest when it is time.
+/// let play = PhrasePlay(&phrase);
+/// let mut evs = EventStream::from(CtrlEvent(0,0,64,127));
+/// play.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,20));
+/// assert_eq!(play.next_tick(), Some(clock.now() + Duration::from_millis(100)));
+///
+/// clock.advance(Duration::from_millis(50));
+/// let mut evs = EventStream::empty();
+/// play.run_tick(&mut evs);
+/// assert!(evs.is_empty());
+///
+/// clock.advance(Duration::from_millis(50));
+/// play.run_tick(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,60));
+/// assert!(!phrase.is_playing());
+///
+/// // Triggering with a NoteOn transposes the phrase.
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,64,100));
+/// play.run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,64,20));
+/// ```
+///
+/// A looped phrase is repeated until stopped, which releases sounding notes.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let phrase = Phrase::with_clock(clock.clone());
+/// ArmRecord(&phrase).run(&mut EventStream::none());
+/// PhraseRecord(&phrase).run(&mut EventStream::from(NoteOnEvent(0,0,60,20)));
+/// clock.advance(Duration::from_millis(100));
+/// PhraseRecord(&phrase).run(&mut EventStream::from(NoteOffEvent(0,0,60)));
+/// clock.advance(Duration::from_millis(100));
+/// ArmRecord(&phrase).run(&mut EventStream::none());
+///
+/// let play = PhrasePlay(&phrase).looped();
+/// play.run(&mut EventStream::none());
+/// clock.advance(Duration::from_millis(200));
+/// let mut evs = EventStream::empty();
+/// play.run_tick(&mut evs);
+/// assert_eq!(evs, vec![NoteOffEvent(0,0,60), NoteOnEvent(0,0,60,20)]);
+///
+/// let mut evs = EventStream::from(NoteOnEvent(0,0,0,100));
+/// PhraseStop(&phrase).run(&mut evs);
+/// assert_eq!(evs, NoteOffEvent(0,0,60));
+/// assert!(!phrase.is_playing());
+/// assert_eq!(play.next_tick(), None);
+/// ```
+pub struct PhrasePlay {
+    phrase: Phrase,
+    looped: bool,
+}
+
+pub fn PhrasePlay(phrase: &Phrase) -> PhrasePlay {
+    PhrasePlay { phrase: phrase.clone(), looped: false }
+}
+
+impl PhrasePlay {
+    /// Repeat the phrase until stopped.
+    pub fn looped(mut self) -> Self {
+        self.looped = true;
+        self
+    }
+}
+
+impl FilterTrait for PhrasePlay {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        let now = self.phrase.clock.now();
+        let trigger_note = evs.iter().find_map(|ev| match ev {
+            Event::NoteOn(ev) => Some(ev.note),
+            _ => None,
+        });
+
+        let mut out = EventStream::empty();
+        if let Ok(mut state) = self.phrase.state.lock() {
+            let transpose = match (trigger_note, state.first_note()) {
+                (Some(trigger_note), Some(first_note)) => trigger_note as i16 - first_note as i16,
+                _ => 0,
+            };
+            state.stop(&mut out);
+            state.start(now, transpose, self.looped, &mut out);
+        }
+        evs.replace(out);
+    }
+
+    fn run_tick(&self, evs: &mut EventStream) {
+        let now = self.phrase.clock.now();
+        if let Ok(mut state) = self.phrase.state.lock() {
+            state.run_due(now, evs);
+        }
+    }
+
+    fn next_tick(&self) -> Option<Instant> {
+        let state = self.phrase.state.lock().ok()?;
+        state.playback.as_ref()?.scheduler.next_due()
+    }
+
+    fn run_exit(&self, evs: &mut EventStream) {
+        if let Ok(mut state) = self.phrase.state.lock() {
+            state.stop(evs);
+        }
+    }
+}
+
+/// Stops playing a [Phrase], replacing the events by NoteOffs for notes still sounding.
+pub struct PhraseStop {
+    phrase: Phrase,
+}
+
+pub fn PhraseStop(phrase: &Phrase) -> PhraseStop {
+    PhraseStop { phrase: phrase.clone() }
+}
+
+impl FilterTrait for PhraseStop {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        let mut out = EventStream::empty();
+        if let Ok(mut state) = self.phrase.state.lock() {
+            state.stop(&mut out);
+        }
+        evs.replace(out);
+    }
+}
@@ -0,0 +1,97 @@
+use std::cell::Cell;
+use std::error::Error;
+
+thread_local! {
+    static ACTIVE_DATA_OFFSET: Cell<(usize, u8)> = const { Cell::new((0, 0)) };
+}
+
+/// Names of the configured ports, to refer to ports by name in filters.
+///
+/// Ports are numbered like patches use them, i.e. starting at the data offset. The runner
+/// passes it to [FilterTrait::bind](super::FilterTrait::bind) when it is started, so that
+/// filters like [PortNameFilter](super::PortNameFilter) can look up their port.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let ports = PortRegistry::new(&["keys", "pads"], &["synth"], 1);
+/// assert_eq!(ports.in_port("pads").unwrap(), 2);
+/// assert_eq!(ports.out_port("synth").unwrap(), 1);
+///
+/// let error = ports.in_port("drums").unwrap_err().to_string();
+/// assert_eq!(error, "Unknown input port \"drums\", available are: \"keys\", \"pads\"");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PortRegistry {
+    in_ports: Vec<String>,
+    out_ports: Vec<String>,
+    offset: usize,
+}
+
+impl PortRegistry {
+    pub fn new(in_ports: &[&str], out_ports: &[&str], offset: usize) -> Self {
+        Self {
+            in_ports: in_ports.iter().map(|name| name.to_string()).collect(),
+            out_ports: out_ports.iter().map(|name| name.to_string()).collect(),
+            offset,
+        }
+    }
+
+    /// Number of the input port with the name.
+    pub fn in_port(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+        self.find("input", &self.in_ports, name)
+    }
+
+    /// Number of the output port with the name.
+    pub fn out_port(&self, name: &str) -> Result<usize, Box<dyn Error>> {
+        self.find("output", &self.out_ports, name)
+    }
+
+    fn find(&self, kind: &str, names: &[String], name: &str) -> Result<usize, Box<dyn Error>> {
+        match names.iter().position(|n| n == name) {
+            Some(i) => Ok(i + self.offset),
+            None if names.is_empty() => Err(format!("Unknown {} port {:?}, there are none", kind, name).into()),
+            None => {
+                let available: Vec<String> = names.iter().map(|n| format!("{:?}", n)).collect();
+                Err(format!("Unknown {} port {:?}, available are: {}", kind, name, available.join(", ")).into())
+            },
+        }
+    }
+}
+
+/// Makes these the first port and channel for filters on this thread, while running `f`.
+///
+/// The runner does this with the `data_offset` of the configuration, so that generators like
+/// [NoteOn](super::NoteOn) output to the first port and channel as patches number them, when
+/// the incoming event has no port or channel of its own.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let mut evs = EventStream::none();
+/// with_data_offset(1, 1, || NoteOn(60, 100).run(&mut evs));
+/// assert_eq!(evs, NoteOnEvent(1,1,60,100));
+///
+/// // Outside of it, ports and channels start at 0.
+/// let mut evs = EventStream::none();
+/// NoteOn(60, 100).run(&mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,60,100));
+/// ```
+pub fn with_data_offset<R, F: FnOnce() -> R>(port_offset: usize, channel_offset: u8, f: F) -> R {
+    let previous = ACTIVE_DATA_OFFSET.with(|active| active.replace((port_offset, channel_offset)));
+    let result = f();
+    ACTIVE_DATA_OFFSET.with(|active| active.set(previous));
+    result
+}
+
+/// First port as patches number it, see [with_data_offset].
+pub fn first_port() -> usize {
+    ACTIVE_DATA_OFFSET.with(|active| active.get().0)
+}
+
+/// First channel as patches number it, see [with_data_offset].
+pub fn first_channel() -> u8 {
+    ACTIVE_DATA_OFFSET.with(|active| active.get().1)
+}
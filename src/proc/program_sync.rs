@@ -0,0 +1,333 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+use super::clock::*;
+use super::event::*;
+use super::event_stream::*;
+use super::filter_trait::*;
+
+/// Default time in which a program change coming back is considered an echo.
+const DEFAULT_WINDOW: Duration = Duration::from_millis(500);
+
+thread_local! {
+    // Scene offset, and the index of the current scene.
+    static ACTIVE_SCENE: Cell<(SceneNum, Option<SceneNum>)> = const { Cell::new((0, None)) };
+}
+
+/// Makes these the scene offset and current scene for filters on this thread, while running `f`.
+///
+/// The runner does this with the `scene_offset` of the configuration, so that filters like
+/// [ProgramSwitchScenes] and [SceneToProgram] number scenes like patches do. The current scene
+/// is given as index, starting at 0.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// assert_eq!(with_scene(1, Some(0), || (first_scene(), current_scene())), (1, Some(1)));
+///
+/// // Outside of it, scenes start at 0 and there is no current scene.
+/// assert_eq!((first_scene(), current_scene()), (0, None));
+/// ```
+pub fn with_scene<R, F: FnOnce() -> R>(scene_offset: SceneNum, current_scene: Option<SceneNum>, f: F) -> R {
+    let previous = ACTIVE_SCENE.with(|active| active.replace((scene_offset, current_scene)));
+    let result = f();
+    ACTIVE_SCENE.with(|active| active.set(previous));
+    result
+}
+
+/// First scene as patches number it, see [with_scene].
+pub fn first_scene() -> SceneNum {
+    ACTIVE_SCENE.with(|active| active.get().0)
+}
+
+/// Current scene as patches number it, see [with_scene].
+pub fn current_scene() -> Option<SceneNum> {
+    ACTIVE_SCENE.with(|active| {
+        let (scene_offset, current_scene) = active.get();
+        current_scene.map(|s| s.saturating_add(scene_offset))
+    })
+}
+
+/// Keeps scenes in sync with the program of an external device, in both directions.
+///
+/// The arguments are: _port_, _channel_, _programs_, where the programs are pairs of a
+/// scene number (as used by [SceneSwitch](super::SceneSwitch)) and the device's program.
+///
+/// Use [BidirectionalProgramSync::enter] as the init patch of each scene, to send its program
+/// to the device. Use the sync itself as control patch, to switch scenes when the device sends
+/// a program change. Program changes that match one sent within a short window (see
+/// [BidirectionalProgramSync::with_window]) are taken to be the device's echo, and ignored,
+/// so scene switches don't ping-pong between rmididings and the device.
+///
+/// As control patch, it outputs only scene switches, other events are discarded.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let sync = BidirectionalProgramSync(0, 0, &[(1, 10), (2, 20)]).with_clock(clock.clone());
+/// let enter_scene_2 = sync.enter(2);
+///
+/// // Entering the scene sends its program.
+/// let mut evs = EventStream::none();
+/// enter_scene_2.run(&mut evs);
+/// assert_eq!(evs, ProgramEvent(0,0,20));
+///
+/// // The device echoes it back, which doesn't switch scenes.
+/// let mut evs = EventStream::from(ProgramEvent(0,0,20));
+/// sync.run(&mut evs);
+/// assert!(evs.is_empty());
+///
+/// // Selecting a program on the device switches scenes.
+/// clock.advance(Duration::from_secs(2));
+/// let mut evs = EventStream::from(vec![ProgramEvent(0,0,10), NoteOnEvent(0,0,60,20)]);
+/// sync.run(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(1));
+/// ```
+///
+/// After the window, the same program is not an echo anymore.
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let sync = BidirectionalProgramSync(1, 2, &[(1, 10), (2, 20)])
+///     .with_window(Duration::from_millis(200))
+///     .with_clock(clock.clone());
+///
+/// sync.enter(2).run(&mut EventStream::none());
+/// clock.advance(Duration::from_millis(300));
+///
+/// let mut evs = EventStream::from(ProgramEvent(1,2,20));
+/// sync.run(&mut evs);
+/// assert_eq!(evs, SceneSwitchEvent(2));
+///
+/// // Other ports and channels, and programs without a scene, are ignored.
+/// let mut evs = EventStream::from(vec![ProgramEvent(0,2,10), ProgramEvent(1,0,10), ProgramEvent(1,2,30)]);
+/// sync.run(&mut evs);
+/// assert!(evs.is_empty());
+/// ```
+pub struct BidirectionalProgramSync {
+    port: usize,
+    channel: u8,
+    programs: Vec<(SceneNum, u8)>,
+    window: Duration,
+    clock: Arc<dyn Clock>,
+    // Programs sent to the device, and when.
+    sent: Arc<Mutex<VecDeque<(Instant, u8)>>>,
+}
+
+pub fn BidirectionalProgramSync(port: usize, channel: u8, programs: &[(SceneNum, u8)]) -> BidirectionalProgramSync {
+    BidirectionalProgramSync {
+        port,
+        channel,
+        programs: programs.to_vec(),
+        window: DEFAULT_WINDOW,
+        clock: system_clock(),
+        sent: Arc::new(Mutex::new(VecDeque::new())),
+    }
+}
+
+impl BidirectionalProgramSync {
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    ///
+    /// Call this before [BidirectionalProgramSync::enter], which shares the clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Time in which a program change from the device is considered an echo.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Returns the init patch for a scene, which sends its program.
+    ///
+    /// When the scene has no program, the returned patch does nothing.
+    pub fn enter(&self, scene: SceneNum) -> ProgramSyncEnter {
+        ProgramSyncEnter {
+            port: self.port,
+            channel: self.channel,
+            program: self.programs.iter().find(|(s, _)| *s == scene).map(|(_, p)| *p),
+            clock: self.clock.clone(),
+            sent: self.sent.clone(),
+        }
+    }
+
+    /// Returns whether the program was sent recently, forgetting it when it was.
+    fn is_echo(&self, program: u8) -> bool {
+        let now = self.clock.now();
+        let mut sent = match self.sent.lock() {
+            Ok(sent) => sent,
+            Err(_) => return false,
+        };
+        sent.retain(|(t, _)| now.saturating_duration_since(*t) <= self.window);
+        if let Some(i) = sent.iter().position(|(_, p)| *p == program) {
+            sent.remove(i);
+            return true;
+        }
+        false
+    }
+}
+
+impl FilterTrait for BidirectionalProgramSync {
+    fn run(&self, evs: &mut EventStream) {
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            if let Event::Program(ev) = ev {
+                if ev.port != self.port || ev.channel != self.channel { continue; }
+                if self.is_echo(ev.program) { continue; }
+                if let Some((scene, _)) = self.programs.iter().find(|(_, p)| *p == ev.program) {
+                    out.push(SceneSwitchEvent(*scene));
+                }
+            }
+        }
+        evs.replace(out);
+    }
+}
+
+/// Init patch that sends a scene's program, see [BidirectionalProgramSync::enter].
+pub struct ProgramSyncEnter {
+    port: usize,
+    channel: u8,
+    program: Option<u8>,
+    clock: Arc<dyn Clock>,
+    sent: Arc<Mutex<VecDeque<(Instant, u8)>>>,
+}
+
+impl FilterTrait for ProgramSyncEnter {
+    fn run(&self, evs: &mut EventStream) {
+        if evs.is_empty() { return; }
+        evs.clear();
+
+        if let Some(program) = self.program {
+            if let Ok(mut sent) = self.sent.lock() {
+                sent.push_back((self.clock.now(), program));
+            }
+            evs.push(ProgramEvent(self.port, self.channel, program));
+        }
+    }
+}
+
+/// Switches scenes with program changes, program 0 selects the first scene.
+///
+/// This is the common setup with a foot controller sending program changes. Use it in the
+/// control patch, after filtering the controller's port and channel. Program events are
+/// replaced by scene switches, other events are discarded.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let control = Chain!(PortFilter(1), ChannelFilter(16), ProgramSwitchScenes());
+///
+/// let mut evs = EventStream::from(vec![ProgramEvent(1,16,2), NoteOnEvent(1,16,60,20), ProgramEvent(1,1,3)]);
+/// with_scene(1, Some(0), || control.run(&mut evs));
+/// assert_eq!(evs, SceneSwitchEvent(3));
+/// # }
+/// ```
+pub struct ProgramSwitchScenes();
+
+impl FilterTrait for ProgramSwitchScenes {
+    fn run(&self, evs: &mut EventStream) {
+        let mut out = EventStream::empty();
+        for ev in evs.iter() {
+            if let Event::Program(ev) = ev {
+                out.push(SceneSwitchEvent(ev.program.saturating_add(first_scene())));
+            }
+        }
+        out.dedup();
+        evs.replace(out);
+    }
+}
+
+/// Sends a program change when another scene is entered, the first scene is program 0.
+///
+/// The arguments are: _port_, _channel_.
+///
+/// This lets a controller that switches scenes with [ProgramSwitchScenes] show the current
+/// scene, also when it was switched in another way. Use it in the post patch, which is also
+/// run when a scene is entered. Events are passed unchanged, the program change is added after
+/// them. Scenes beyond program 127 don't send a program change.
+///
+/// It needs to run without events too, so chain it rather than putting it in a [Fork!],
+/// which skips its branches when there are no events. Keep scene switches from the control
+/// patch, they also pass the post patch.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::proc::*;
+/// # fn main() {
+/// let post = SceneToProgram(1, 16);
+///
+/// let mut evs = EventStream::empty();
+/// with_scene(1, Some(2), || post.run(&mut evs));
+/// assert_eq!(evs, ProgramEvent(1,16,2));
+///
+/// // Only once for each scene change.
+/// let mut evs = EventStream::from(NoteOnEvent(0,1,60,20));
+/// with_scene(1, Some(2), || post.run(&mut evs));
+/// assert_eq!(evs, NoteOnEvent(0,1,60,20));
+/// # }
+/// ```
+///
+/// Together with the runner:
+///
+/// ```no_run
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::*;
+/// # fn main() {
+/// let mut md = RMididings::new().unwrap();
+/// md.run(RunArguments {
+///     scenes: &[
+///         &Scene { name: "Piano", patch: &Pass(), ..Scene::default() },
+///         &Scene { name: "Strings", patch: &Transpose(12), ..Scene::default() },
+///     ],
+///     control: &Chain!(ChannelFilter(16), ProgramSwitchScenes()),
+///     post: &SceneToProgram(1, 16),
+///     ..RunArguments::default()
+/// }).unwrap();
+/// # }
+/// ```
+pub struct SceneToProgram {
+    port: usize,
+    channel: u8,
+    // Index of the scene the program was last sent for.
+    sent: Mutex<Option<SceneNum>>,
+}
+
+pub fn SceneToProgram(port: usize, channel: u8) -> SceneToProgram {
+    SceneToProgram { port, channel, sent: Mutex::new(None) }
+}
+
+impl FilterTrait for SceneToProgram {
+    fn run(&self, evs: &mut EventStream) {
+        let scene = match current_scene() {
+            Some(scene) => scene.saturating_sub(first_scene()),
+            None => return,
+        };
+        let mut sent = match self.sent.lock() {
+            Ok(sent) => sent,
+            Err(_) => return,
+        };
+        if *sent == Some(scene) { return; }
+        *sent = Some(scene);
+        if scene <= 127 {
+            evs.push(ProgramEvent(self.port, self.channel, scene));
+        }
+    }
+}
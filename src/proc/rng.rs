@@ -0,0 +1,33 @@
+use web_time::{SystemTime, UNIX_EPOCH};
+
+// Random numbers for filters that vary their output, like Humanize and Random!.
+//
+// SplitMix64, which is plenty random for this and easy to seed.
+#[derive(Debug, Default)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    // Seeded from the current time, so that it differs each run.
+    pub(crate) fn from_time() -> Self {
+        Self(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64))
+    }
+
+    pub(crate) fn next_random(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Random number in `0..=max`.
+    pub(crate) fn random_up_to(&mut self, max: u64) -> u64 {
+        self.next_random() % max.saturating_add(1)
+    }
+}
@@ -0,0 +1,91 @@
+use web_time::Instant;
+
+use super::event::*;
+use super::event_stream::*;
+
+/// Queue of events to be output at a later time.
+///
+/// Filters that output events later keep one of these, and use it from
+/// [FilterTrait::run_tick](super::FilterTrait::run_tick) and
+/// [FilterTrait::next_tick](super::FilterTrait::next_tick), so that the runner
+/// wakes up in time to output them.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::{Duration, Instant};
+///
+/// let now = Instant::now();
+/// let mut scheduler = Scheduler::new();
+/// scheduler.schedule(now + Duration::from_millis(20), NoteOffEvent(0,0,60));
+/// scheduler.schedule(now + Duration::from_millis(10), NoteOnEvent(0,0,62,20));
+/// assert_eq!(scheduler.next_due(), Some(now + Duration::from_millis(10)));
+///
+/// let mut evs = EventStream::empty();
+/// scheduler.run_due(now + Duration::from_millis(15), &mut evs);
+/// assert_eq!(evs, NoteOnEvent(0,0,62,20));
+/// assert_eq!(scheduler.len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    // Sorted by time, events scheduled at the same time keep their order.
+    queue: Vec<(Instant, Event<'static>)>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an event to be output at the given time.
+    pub fn schedule(&mut self, at: Instant, ev: Event<'static>) {
+        let i = self.queue.partition_point(|(t, _)| *t <= at);
+        self.queue.insert(i, (at, ev));
+    }
+
+    /// Move all events that are due at the given time to the event stream.
+    pub fn run_due(&mut self, now: Instant, evs: &mut EventStream) {
+        let n = self.queue.partition_point(|(t, _)| *t <= now);
+        for (_, ev) in self.queue.drain(..n) {
+            evs.push(ev);
+        }
+    }
+
+    /// Move all events to the event stream, whether they are due or not.
+    pub fn run_all(&mut self, evs: &mut EventStream) {
+        for (_, ev) in self.queue.drain(..) {
+            evs.push(ev);
+        }
+    }
+
+    /// Remove a scheduled event, returns whether it was there.
+    pub fn remove(&mut self, ev: &Event) -> bool {
+        match self.queue.iter().position(|(_, e)| e == ev) {
+            Some(i) => { self.queue.remove(i); true },
+            None => false,
+        }
+    }
+
+    /// Keep only the scheduled events for which the predicate returns `true`.
+    pub fn retain<F: FnMut(&Event) -> bool>(&mut self, mut f: F) {
+        self.queue.retain(|(_, ev)| f(ev));
+    }
+
+    /// When the next event is due, if any.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue.first().map(|(t, _)| *t)
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
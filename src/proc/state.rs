@@ -0,0 +1,105 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A value in the [PatchState].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateValue {
+    Int(i32),
+    Float(f32),
+    Str(String),
+}
+
+impl StateValue {
+    /// Returns the value as a controller value, rounding floats; strings have none.
+    pub fn as_ctrl_value(&self) -> Option<i32> {
+        match self {
+            StateValue::Int(v) => Some(*v),
+            StateValue::Float(v) => Some(v.round() as i32),
+            StateValue::Str(_) => None,
+        }
+    }
+}
+
+impl From<i32> for StateValue {
+    fn from(v: i32) -> Self { StateValue::Int(v) }
+}
+
+impl From<f32> for StateValue {
+    fn from(v: f32) -> Self { StateValue::Float(v) }
+}
+
+impl From<&str> for StateValue {
+    fn from(v: &str) -> Self { StateValue::Str(v.to_string()) }
+}
+
+impl From<String> for StateValue {
+    fn from(v: String) -> Self { StateValue::Str(v) }
+}
+
+thread_local! {
+    static ACTIVE_STATE: RefCell<Option<Arc<PatchState>>> = const { RefCell::new(None) };
+}
+
+/// Key-value store that persists across scene switches.
+///
+/// The runner holds the state as `Arc<PatchState>`, and makes it available to the
+/// [SetState] and [GetStateAsCtrl] filters while running patches. Pass your own state
+/// in `RunArguments` to give it initial values, or to access it from [Process!] closures.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::sync::Arc;
+///
+/// let state = Arc::new(PatchState::new());
+/// state.set("program", StateValue::Int(5));
+///
+/// let closure_state = state.clone();
+/// let f = move || closure_state.get("program");
+/// assert_eq!(f(), Some(StateValue::Int(5)));
+/// ```
+#[derive(Debug, Default)]
+pub struct PatchState {
+    values: Arc<Mutex<HashMap<String, StateValue>>>,
+}
+
+impl PatchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<StateValue> {
+        self.values.lock().ok()?.get(key).cloned()
+    }
+
+    pub fn set<K: Into<String>>(&self, key: K, value: StateValue) {
+        if let Ok(mut values) = self.values.lock() {
+            values.insert(key.into(), value);
+        }
+    }
+
+    pub fn remove(&self, key: &str) -> Option<StateValue> {
+        self.values.lock().ok()?.remove(key)
+    }
+
+    /// Makes this the state used by state filters on this thread, while running `f`.
+    pub fn activate<R, F: FnOnce() -> R>(self: &Arc<Self>, f: F) -> R {
+        let previous = ACTIVE_STATE.with(|active| active.replace(Some(self.clone())));
+        let result = f();
+        ACTIVE_STATE.with(|active| active.replace(previous));
+        result
+    }
+
+    /// Returns the state activated with [PatchState::activate], if any.
+    pub fn active() -> Option<Arc<PatchState>> {
+        ACTIVE_STATE.with(|active| active.borrow().clone())
+    }
+}
+
+impl From<HashMap<String, StateValue>> for PatchState {
+    fn from(values: HashMap<String, StateValue>) -> Self {
+        Self { values: Arc::new(Mutex::new(values)) }
+    }
+}
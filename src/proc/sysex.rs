@@ -0,0 +1,113 @@
+//! Building system exclusive (SysEx) messages from runtime data.
+
+/// Start of a system exclusive message.
+const SYSEX_START: u8 = 0xf0;
+/// End of a system exclusive message.
+const SYSEX_END: u8 = 0xf7;
+
+/// Builds a system exclusive message, for when it can't be written as a literal.
+///
+/// The start (`0xf0`) and end (`0xf7`) bytes are added by [SysExBuilder::build] when they are
+/// missing. The message can be output with [SysExEvent](super::SysExEvent), or generated by a
+/// patch with [SysEx](super::SysEx), which needs the message to live as long as the program.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let part = 2;
+/// let data = SysExBuilder::new()
+///     .push_bytes(&[0x41, 0x10, 0x42, 0x12])
+///     .push_byte(0x10 | part)
+///     .push_msb_lsb(300)
+///     .build();
+/// assert_eq!(data, vec![0xf0, 0x41, 0x10, 0x42, 0x12, 0x12, 0x02, 0x2c, 0xf7]);
+///
+/// let mut evs = EventStream::from(SysExEvent(0, &data));
+/// Port(1).run(&mut evs);
+/// assert_eq!(evs, SysExEvent(1, &data));
+/// ```
+///
+/// ```
+/// # use rmididings::proc::*;
+/// let volume = 100;
+/// let generator = SysEx(SysExBuilder::gm_master_volume(volume).build().leak());
+///
+/// let mut evs = EventStream::none();
+/// generator.run(&mut evs);
+/// assert_eq!(evs, SysExEvent(0, &[0xf0, 0x7f, 0x7f, 0x04, 0x01, 0x00, 100, 0xf7]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SysExBuilder {
+    data: Vec<u8>,
+}
+
+impl SysExBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_byte(mut self, byte: u8) -> Self {
+        self.data.push(byte);
+        self
+    }
+
+    pub fn push_bytes(mut self, bytes: &[u8]) -> Self {
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    /// Adds a 14-bit value as two data bytes, the most significant 7 bits first.
+    ///
+    /// Higher bits of the value are dropped.
+    pub fn push_msb_lsb(self, value: u16) -> Self {
+        self.push_bytes(&[(value >> 7) as u8 & 0x7f, value as u8 & 0x7f])
+    }
+
+    /// Returns the message, with start and end bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// assert_eq!(SysExBuilder::new().push_bytes(&[0xf0, 0x7d, 0xf7]).build(), vec![0xf0, 0x7d, 0xf7]);
+    /// assert_eq!(SysExBuilder::new().push_byte(0x7d).build(), vec![0xf0, 0x7d, 0xf7]);
+    /// assert_eq!(SysExBuilder::new().build(), vec![0xf0, 0xf7]);
+    /// ```
+    pub fn build(self) -> Vec<u8> {
+        let mut data = self.data;
+        if data.first() != Some(&SYSEX_START) {
+            data.insert(0, SYSEX_START);
+        }
+        if data.len() < 2 || data.last() != Some(&SYSEX_END) {
+            data.push(SYSEX_END);
+        }
+        data
+    }
+
+    /// General MIDI master volume, for all devices, with a _level_ from 0 to 127.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// assert_eq!(SysExBuilder::gm_master_volume(127).build(), vec![0xf0, 0x7f, 0x7f, 0x04, 0x01, 0x00, 0x7f, 0xf7]);
+    /// ```
+    pub fn gm_master_volume(level: u8) -> Self {
+        // Universal realtime, all devices, device control, master volume, then LSB and MSB.
+        Self::new().push_bytes(&[0x7f, 0x7f, 0x04, 0x01, 0x00, level.min(127)])
+    }
+
+    /// Identity request, for all devices, which answer with their manufacturer and model.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rmididings::proc::*;
+    /// assert_eq!(SysExBuilder::identity_request().build(), vec![0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7]);
+    /// ```
+    pub fn identity_request() -> Self {
+        // Universal non-realtime, all devices, general information, identity request.
+        Self::new().push_bytes(&[0x7e, 0x7f, 0x06, 0x01])
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+use super::clock::*;
+
+/// A musical duration, in quarter notes.
+pub type Beats = f32;
+
+/// Shared tempo and bar position, e.g. to quantize scene switches.
+///
+/// The bar reference is the time of the last transport Start or [ResyncEvent](super::ResyncEvent),
+/// bar boundaries are whole bars after it.
+///
+/// # Examples
+///
+/// ```
+/// # use rmididings::proc::*;
+/// use std::time::Duration;
+///
+/// let clock = FakeClock::new();
+/// let tempo = TempoHandle::new(120.0).with_clock(clock.clone());
+/// tempo.resync();
+///
+/// // A bar of 4 beats at 120 bpm takes two seconds.
+/// clock.advance(Duration::from_millis(500));
+/// assert_eq!(tempo.next_boundary(4.0) - clock.now(), Duration::from_millis(1500));
+///
+/// // Right on the boundary, it's now.
+/// clock.advance(Duration::from_millis(1500));
+/// assert_eq!(tempo.next_boundary(4.0), clock.now());
+/// ```
+#[derive(Clone)]
+pub struct TempoHandle {
+    state: Arc<Mutex<TempoState>>,
+    clock: Arc<dyn Clock>,
+}
+
+struct TempoState {
+    bpm: f32,
+    reference: Option<Instant>,
+}
+
+impl TempoHandle {
+    pub fn new(bpm: f32) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TempoState { bpm, reference: None })),
+            clock: system_clock(),
+        }
+    }
+
+    /// Use another clock, e.g. a [FakeClock] for testing.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub fn bpm(&self) -> f32 {
+        self.state.lock().map_or(0.0, |state| state.bpm)
+    }
+
+    pub fn set_bpm(&self, bpm: f32) {
+        if let Ok(mut state) = self.state.lock() {
+            state.bpm = bpm;
+        }
+    }
+
+    /// Starts counting bars from now.
+    pub fn resync(&self) {
+        let now = self.clock.now();
+        if let Ok(mut state) = self.state.lock() {
+            state.reference = Some(now);
+        }
+    }
+
+    /// When counting of bars started, if it did.
+    pub fn reference(&self) -> Option<Instant> {
+        self.state.lock().ok()?.reference
+    }
+
+    /// The first boundary of `beats` long bars from now on, now when there is no reference yet.
+    pub fn next_boundary(&self, beats: Beats) -> Instant {
+        let now = self.clock.now();
+        let (bpm, reference) = match self.state.lock() {
+            Ok(state) => (state.bpm, state.reference),
+            Err(_) => return now,
+        };
+        let bar = Duration::from_secs_f64(60.0 * beats as f64 / bpm as f64);
+        match reference {
+            Some(reference) if !bar.is_zero() => {
+                let bars = now.saturating_duration_since(reference).as_secs_f64() / bar.as_secs_f64();
+                reference + bar.mul_f64(bars.ceil())
+            },
+            _ => now,
+        }
+    }
+}
@@ -1,11 +1,68 @@
-use super::proc::{Discard, FilterTrait, SceneNum};
+use std::error::Error;
 
+use super::proc::{Discard, FilterTrait, PortRegistry, SceneNum};
+
+/// A scene, which runs its patch while it is active.
+///
+/// # Parameters
+///
+/// A scene can declare controller values in `params`, which are sent as Ctrl events on the
+/// first port and channel when the scene is entered (before its `init` patch, and through the
+/// `post` patch of [RunArguments](crate::RunArguments), so they can be routed there). When
+/// `remember_edits` is set, incoming Ctrl events for these controllers while the scene is active
+/// are remembered, and entering the scene again sends the edited values instead of the declared ones.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate rmididings;
+/// # use rmididings::*;
+/// # fn main() {
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+///
+/// let sent = Arc::new(Mutex::new(vec![]));
+/// let sent_clone = sent.clone();
+/// let post = Process!(move |ev: &Event| {
+///     if let Event::Ctrl(ev) = ev { sent_clone.lock().unwrap().push((ev.ctrl, ev.value)); }
+///     Box::new(Pass())
+/// });
+///
+/// let mut md = RMididings::new().unwrap();
+/// let mut runner = md.start(RunArguments {
+///     scenes: &[
+///         &Scene { name: "Remembers", params: &[(7, 100)], remember_edits: true, ..Scene::DEFAULT },
+///         &Scene { name: "Declared", params: &[(7, 50)], ..Scene::DEFAULT },
+///     ],
+///     control: &TypeFilter!(SceneSwitch),
+///     post: &post,
+///     ..RunArguments::default()
+/// }).unwrap();
+/// runner.step(Duration::ZERO).unwrap();
+/// assert_eq!(sent.lock().unwrap().drain(..).collect::<Vec<_>>(), vec![(7, 100)]);
+///
+/// // Edit the volume in both scenes.
+/// runner.inject_event(CtrlEvent(1,1,7,80)).unwrap();
+/// runner.inject_event(SceneSwitchEvent(2)).unwrap();
+/// runner.inject_event(CtrlEvent(1,1,7,20)).unwrap();
+/// runner.inject_event(SceneSwitchEvent(1)).unwrap();
+/// runner.inject_event(SceneSwitchEvent(2)).unwrap();
+///
+/// // Scene 1 restores its edited value, scene 2 its declared one.
+/// assert_eq!(*sent.lock().unwrap(), vec![(7, 50), (7, 80), (7, 50)]);
+/// # }
+/// ```
+#[derive(Clone, Copy)]
 pub struct Scene<'a> {
     pub name: &'a str,
     pub patch: &'a dyn FilterTrait,
     pub init: &'a dyn FilterTrait,
     pub exit: &'a dyn FilterTrait,
     pub subscenes: &'a [&'a Scene<'a>],
+    /// Controller values (controller, value) to send when entering the scene.
+    pub params: &'a [(u32, i32)],
+    /// Whether edits of `params` while the scene is active are sent on re-entry.
+    pub remember_edits: bool,
 }
 
 impl Scene<'_> {
@@ -15,6 +72,8 @@ impl Scene<'_> {
         init: &Discard(),
         exit: &Discard(),
         subscenes: &[],
+        params: &[],
+        remember_edits: false,
     };
 
     pub fn default() -> Self {
@@ -22,6 +81,14 @@ impl Scene<'_> {
         Self::DEFAULT
     }
 
+    /// Resolves ports referred to by name in its filters and those of its subscenes.
+    pub(crate) fn bind(&self, ports: &PortRegistry) -> Result<(), Box<dyn Error>> {
+        self.patch.bind(ports)?;
+        self.init.bind(ports)?;
+        self.exit.bind(ports)?;
+        self.subscenes.iter().try_for_each(|subscene| subscene.bind(ports))
+    }
+
     pub fn get_subscene(&self, subscene_num: SceneNum) -> Option<&Scene> {
         if self.subscenes.len() > subscene_num as usize {
             Some(self.subscenes[subscene_num as usize])
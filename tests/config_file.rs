@@ -0,0 +1,110 @@
+//! Reading port and scene configuration files.
+#![cfg(feature = "config-file")]
+use std::fs;
+use std::time::Duration;
+
+use rmididings::config::{self, Config, PortConfig, SceneConfig};
+use rmididings::*;
+
+fn sample_config() -> Config {
+    Config {
+        client_name: "Gig".to_string(),
+        backend: Some(BackendType::Null),
+        data_offset: 0,
+        port_offset: Some(1),
+        channel_offset: None,
+        initial_scene: 1,
+        in_ports: vec![
+            PortConfig { name: "keys".to_string(), connect: "Virtual Keyboard:Virtual Keyboard".to_string() },
+            PortConfig { name: "osc.udp://localhost:56418".to_string(), connect: "".to_string() },
+        ],
+        out_ports: vec![
+            PortConfig { name: "synth".to_string(), connect: "midisnoop:MIDI Input".to_string() },
+        ],
+        scenes: vec![
+            SceneConfig { name: "Run".to_string() },
+            SceneConfig { name: "Pause".to_string() },
+        ],
+    }
+}
+
+#[test]
+fn round_trips() {
+    let cfg = sample_config();
+    let toml = config::to_string(&cfg).unwrap();
+    assert_eq!(config::from_str(&toml).unwrap(), cfg);
+}
+
+#[test]
+fn round_trips_defaults() {
+    let cfg = Config::default();
+    let toml = config::to_string(&cfg).unwrap();
+    assert_eq!(config::from_str(&toml).unwrap(), cfg);
+    assert_eq!(config::from_str("").unwrap(), cfg);
+}
+
+#[test]
+fn reads_from_path() {
+    let path = std::env::temp_dir().join(format!("rmididings-config-{}.toml", std::process::id()));
+    fs::write(&path, config::to_string(&sample_config()).unwrap()).unwrap();
+    let result = config::from_path(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(result.unwrap(), sample_config());
+}
+
+#[test]
+fn errors_name_the_key() {
+    let cases = [
+        ("[[in_ports]]\nname = \"keys\"\n[[in_ports]]\nconnect = \"x\"\n", "missing field `name`"),
+        ("[[in_ports]]\nname = \"\"\n", "in_ports[0].name: must not be empty"),
+        ("[[out_ports]]\nname = \"a\"\nconnect = \"b\\u0000\"\n", "out_ports[0].connect: "),
+        ("[[scenes]]\nname = \"Run\"\nport = 1\n", "unknown field `port`"),
+        ("initial_scene = 2\n[[scenes]]\nname = \"Run\"\n", "initial_scene: there is no scene 2"),
+        ("data_offset = \"one\"\n", "data_offset"),
+        ("backend = \"midi\"\n", "unknown variant `midi`"),
+    ];
+    for (toml, expected) in cases.iter() {
+        let err = config::from_str(toml).unwrap_err().to_string();
+        assert!(err.contains(expected), "{:?} gave {:?}, expected {:?}", toml, err, expected);
+    }
+}
+
+#[test]
+fn path_in_errors() {
+    let err = config::from_path("/nonexistent/setup.toml").unwrap_err().to_string();
+    assert!(err.starts_with("/nonexistent/setup.toml: "), "{}", err);
+}
+
+#[test]
+fn config_from_validates() {
+    let mut cfg = sample_config();
+    cfg.out_ports[0].name = "".to_string();
+    let mut md = RMididings::new().unwrap();
+    let err = md.config_from(&cfg).unwrap_err().to_string();
+    assert_eq!(err, "out_ports[0].name: must not be empty");
+}
+
+#[test]
+fn config_from_sets_backend_and_offsets() {
+    let cfg = config::from_str(r#"
+        backend = "null"
+        data_offset = 0
+        channel_offset = 1
+
+        [[in_ports]]
+        name = "input"
+
+        [[out_ports]]
+        name = "output"
+    "#).unwrap();
+    let mut md = RMididings::new().unwrap();
+    md.config_from(&cfg).unwrap();
+    let null = md.null_backend().unwrap();
+
+    // The backend's first port and channel are port 0 and channel 1 in patches.
+    let patch = Chain!(PortFilter(0), ChannelFilter(1));
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+    null.push_input(NoteOnEvent(0,0,60,100));
+    let _ = runner.step(Duration::ZERO).unwrap();
+    assert_events!(null.take_output(), [NoteOnEvent(0,0,60,100)]);
+}
@@ -0,0 +1,112 @@
+//! Patches number ports and channels from the data offset, or the port and channel offsets,
+//! also for generated events, and see notes shifted by the octave offset.
+#![cfg(feature = "midifile")]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rmididings::*;
+
+/// Runs the patch for a single event, and returns the MIDI file recorded from the output.
+fn record(name: &str, data_offset: u8, octave_offset: u8, patch: impl FilterTrait + 'static, ev: Event<'static>) -> Vec<u8> {
+    record_with(name, |b| b.data_offset(data_offset).octave_offset(octave_offset), patch, ev)
+}
+
+/// Like [record], configuring the offsets with `configure`.
+fn record_with(name: &str, configure: impl FnOnce(ConfigBuilder) -> ConfigBuilder, patch: impl FilterTrait + 'static, ev: Event<'static>) -> Vec<u8> {
+    let path: PathBuf = std::env::temp_dir().join(format!("rmididings-test-data-offset-{}.mid", name));
+    {
+        let mut md = configure(RMididings::builder().backend(BackendType::Null))
+            .out_port("output", "")
+            .record_midi_file(&path)
+            .build()
+            .unwrap();
+        md.run_builder().patch(patch).run_until_quit_after(ev).unwrap();
+    }
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    data
+}
+
+fn contains(data: &[u8], message: &[u8]) -> bool {
+    data.windows(message.len()).any(|w| w == message)
+}
+
+#[test]
+fn generated_note_is_on_first_port_and_channel() {
+    let patch = Chain!(NoteOn(60, 100), PortFilter(1), ChannelFilter(1));
+    let data = record("offset-1", 1, 0, patch, NoneEvent());
+    assert!(contains(&data, &[0x90, 60, 100]));
+}
+
+#[test]
+fn generated_note_without_offset() {
+    let patch = Chain!(NoteOn(60, 100), PortFilter(0), ChannelFilter(0));
+    let data = record("offset-0", 0, 0, patch, NoneEvent());
+    assert!(contains(&data, &[0x90, 60, 100]));
+}
+
+#[test]
+fn generated_event_keeps_incoming_channel() {
+    let data = record("incoming", 1, 0, Ctrl(7, 90), NoteOnEvent(1,3,60,100));
+    assert!(contains(&data, &[0xb2, 7, 90]));
+}
+
+#[test]
+fn port_and_channel_offsets_override_data_offset() {
+    let patch = Chain!(NoteOn(60, 100), PortFilter(0), ChannelFilter(1));
+    let data = record_with("port-channel", |b| b.data_offset(5).port_offset(0).channel_offset(1), patch, NoneEvent());
+    assert!(contains(&data, &[0x90, 60, 100]));
+}
+
+#[test]
+fn port_offset_names_ports() {
+    let patch = Chain!(Ctrl(7, 90), PortName("output"), PortFilter(0), ChannelFilter(1));
+    let data = record_with("port-name", |b| b.port_offset(0), patch, NoneEvent());
+    assert!(contains(&data, &[0xb0, 7, 90]));
+}
+
+#[test]
+fn octave_offset_shifts_notes_from_patches() {
+    let data = record("octave", 1, 1, Transpose(12), NoteOnEvent(1,1,60,100));
+    assert!(contains(&data, &[0x90, 60, 100]));
+}
+
+/// Runs the patch with an octave offset of 1 on notes from a backend, and returns what was output.
+fn run_octave_offset(patch: impl FilterTrait + 'static, notes: &[u8]) -> Vec<Event<'static>> {
+    let mut md = RMididings::builder()
+        .backend(BackendType::Null)
+        .octave_offset(1)
+        .in_port("input", "")
+        .out_port("output", "")
+        .build()
+        .unwrap();
+    let null = md.null_backend().unwrap();
+    for note in notes {
+        null.push_input(NoteOnEvent(0,0,*note,100));
+    }
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+    let _ = runner.step(Duration::ZERO).unwrap();
+    null.take_output()
+}
+
+#[test]
+fn octave_offset_round_trips_notes() {
+    let out = run_octave_offset(Pass(), &[0, 60, 115]);
+    assert_eq!(out, vec![NoteOnEvent(0,0,0,100), NoteOnEvent(0,0,60,100), NoteOnEvent(0,0,115,100)]);
+}
+
+#[test]
+fn octave_offset_drops_notes_shifted_above_range() {
+    let out = run_octave_offset(Pass(), &[116, 120, 127]);
+    assert_eq!(out, vec![]);
+}
+
+#[test]
+fn octave_offset_drops_notes_shifted_below_range() {
+    // Patches see notes from 12, anything lower can't be output.
+    let out = run_octave_offset(Transpose(-12), &[11, 12]);
+    assert_eq!(out, vec![NoteOnEvent(0,0,0,100)]);
+    let data = record("octave-range", 1, 2, Pass(), NoteOnEvent(1,1,20,100));
+    assert!(!contains(&data, &[0x90, 0, 100]));
+}
@@ -0,0 +1,26 @@
+//! Without a logger set up by the application, configuring sets up one that prints.
+
+use rmididings::*;
+
+struct NoLogger;
+
+impl log::Log for NoLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool { false }
+    fn log(&self, _record: &log::Record) {}
+    fn flush(&self) {}
+}
+
+// A single test, as the logger is shared by the whole process.
+#[test]
+fn fallback_logger_is_installed_on_config() {
+    let mut md = RMididings::new().unwrap();
+    md.config(ConfigArguments { backend: BackendType::Null, verbosity: log::LevelFilter::Warn, ..ConfigArguments::default() }).unwrap();
+    assert_eq!(log::max_level(), log::LevelFilter::Warn);
+
+    // Configuring again changes what is printed.
+    md.config(ConfigArguments { backend: BackendType::Null, verbosity: log::LevelFilter::Off, ..ConfigArguments::default() }).unwrap();
+    assert_eq!(log::max_level(), log::LevelFilter::Off);
+
+    // A logger set up afterwards is too late.
+    assert!(log::set_logger(&NoLogger).is_err());
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "hotreload")]
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{thread, time};
+
+use rmididings::proc::*;
+
+/// Builds the example patch library with the given transposition, returns the library path.
+fn build_patch_library(transpose: i16) -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = manifest_dir.join("target").join(format!("hotreload-test-{}", transpose));
+    let status = Command::new(env!("CARGO"))
+        .arg("build")
+        .arg("--manifest-path").arg(manifest_dir.join("examples/hotreload_patch/Cargo.toml"))
+        .arg("--target-dir").arg(&target_dir)
+        .env("HOTRELOAD_PATCH_TRANSPOSE", transpose.to_string())
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success());
+    target_dir.join("debug").join(format!("{}hotreload_patch{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX))
+}
+
+/// Replaces a file by another one, without touching the existing file's contents.
+fn replace_file(from: &Path, to: &Path) {
+    let tmp = to.with_extension("tmp");
+    fs::copy(from, &tmp).unwrap();
+    fs::rename(&tmp, to).unwrap();
+}
+
+#[test]
+fn reloads_changed_library() {
+    let lib_octave = build_patch_library(12);
+    let lib_fifth = build_patch_library(7);
+
+    let dir = std::env::temp_dir().join(format!("rmididings-hotreload-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join(lib_octave.file_name().unwrap());
+    replace_file(&lib_octave, &lib);
+
+    let patch = DylibPatch::new(&lib).unwrap();
+
+    let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+    patch.run(&mut evs);
+    assert_eq!(evs, NoteOnEvent(0,0,72,20));
+
+    // Make sure the modification time differs, also on filesystems with a coarse resolution.
+    thread::sleep(time::Duration::from_millis(1100));
+    replace_file(&lib_fifth, &lib);
+
+    // The library is only checked for changes on a tick.
+    let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+    patch.run(&mut evs);
+    assert_eq!(evs, NoteOnEvent(0,0,72,20));
+
+    let mut tick_evs = EventStream::empty();
+    patch.run_tick(&mut tick_evs);
+    assert!(tick_evs.is_empty());
+
+    let mut evs = EventStream::from(NoteOnEvent(0,0,60,20));
+    patch.run(&mut evs);
+    assert_eq!(evs, NoteOnEvent(0,0,67,20));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keeps_unsupported_events() {
+    let lib = build_patch_library(12);
+    let patch = DylibPatch::new(&lib).unwrap();
+
+    let mut evs = EventStream::from(vec![SysExEvent(0, &[0xf0, 0xf7]), NoteOnEvent(0,0,60,20)]);
+    patch.run(&mut evs);
+    assert_eq!(evs, vec![SysExEvent(0, &[0xf0, 0xf7]), NoteOnEvent(0,0,72,20)]);
+
+    let mut evs = EventStream::from(vec![NoteOnEvent(0,0,60,20), SysExEvent(0, &[0xf0, 0xf7]), NoteOnEvent(0,0,62,20)]);
+    patch.run(&mut evs);
+    assert_eq!(evs, vec![NoteOnEvent(0,0,72,20), SysExEvent(0, &[0xf0, 0xf7]), NoteOnEvent(0,0,74,20)]);
+}
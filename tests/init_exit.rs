@@ -0,0 +1,50 @@
+//! Init! and Exit! output only what their contents generate, wherever they are in a patch.
+#![cfg(feature = "midifile")]
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rmididings::*;
+
+/// Runs the patch until quit, so both init and exit are run, and returns the MIDI file recorded from the output.
+fn record(name: &str, patch: impl FilterTrait + 'static) -> Vec<u8> {
+    let path: PathBuf = std::env::temp_dir().join(format!("rmididings-test-init-exit-{}.mid", name));
+    {
+        let mut md = RMididings::builder()
+            .backend(BackendType::Null)
+            .out_port("output", "")
+            .record_midi_file(&path)
+            .build()
+            .unwrap();
+        let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+        runner.inject_event(QuitEvent()).unwrap();
+        assert!(runner.step(Duration::ZERO).unwrap().is_break());
+    }
+    let data = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    data
+}
+
+fn count(data: &[u8], message: &[u8]) -> usize {
+    data.windows(message.len()).filter(|w| *w == message).count()
+}
+
+#[test]
+fn init_in_fork_and_chain_outputs_each_once() {
+    let patch = Fork!(
+        Init!(Ctrl(7, 100)),
+        Chain!(Init!(Program(5)), Pass()),
+        Pass()
+    );
+    let data = record("fork", patch);
+    assert_eq!(count(&data, &[0xb0, 7, 100]), 1);
+    assert_eq!(count(&data, &[0xc0, 5]), 1);
+}
+
+#[test]
+fn exit_in_chain_outputs_once() {
+    let patch = Chain!(Exit!(Program(9)), Init!(Ctrl(7, 100)), Pass());
+    let data = record("chain", patch);
+    assert_eq!(count(&data, &[0xb0, 7, 100]), 1);
+    assert_eq!(count(&data, &[0xc0, 9]), 1);
+}
@@ -0,0 +1,62 @@
+//! Messages go through the log crate when the application set up a logger.
+
+use std::sync::Mutex;
+
+use rmididings::*;
+
+/// Keeps all log messages.
+struct CapturingLogger {
+    messages: Mutex<Vec<(log::Level, String)>>,
+}
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool { true }
+
+    fn log(&self, record: &log::Record) {
+        self.messages.lock().unwrap().push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger { messages: Mutex::new(vec![]) };
+
+fn take_messages() -> Vec<(log::Level, String)> {
+    std::mem::take(&mut *LOGGER.messages.lock().unwrap())
+}
+
+// A single test, as the logger is shared by the whole process.
+#[test]
+fn messages_are_logged() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let mut md = RMididings::new().unwrap();
+    md.config(ConfigArguments { backend: BackendType::Null, ..ConfigArguments::default() }).unwrap();
+    let control = Fork!(
+        Chain!(KeyFilter(60), SceneSwitch(1)),
+        Chain!(KeyFilter(62), SceneSwitch(2))
+    );
+    let print = Print::to_log();
+    let run = Scene { name: "Run", patch: &Pass(), ..Scene::default() };
+    let pause = Scene { name: "Pause", patch: &print, ..Scene::default() };
+    let scenes = [&run, &pause];
+    let mut runner = md.start(RunArguments {
+        scenes: &scenes,
+        control: &control,
+        ..RunArguments::default()
+    }).unwrap();
+
+    runner.inject_event(NoteOnEvent(1,1,62,100)).unwrap();
+    runner.inject_event(NoteOnEvent(1,1,64,100)).unwrap();
+    runner.inject_event(NoteOnEvent(1,1,60,100)).unwrap();
+
+    assert_eq!(take_messages(), vec![
+        (log::Level::Info, "Scene 1: Run".to_string()),
+        (log::Level::Info, "Scene 2: Pause".to_string()),
+        // The event switching scenes is processed by the new scene.
+        (log::Level::Info, "NoteOn ch=1 note=D4(62) vel=100 port=1".to_string()),
+        (log::Level::Info, "NoteOn ch=1 note=E4(64) vel=100 port=1".to_string()),
+        (log::Level::Info, "Scene 1: Run".to_string()),
+    ]);
+}
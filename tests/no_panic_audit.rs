@@ -0,0 +1,77 @@
+//! Inputs that used to panic at runtime, and should now fail soft.
+use rmididings::*;
+
+#[test]
+fn nul_in_client_name_is_an_error() {
+    let mut md = RMididings::new().unwrap();
+    let result = md.config(ConfigArguments {
+        backend: BackendType::Null,
+        client_name: "bad\0name",
+        ..ConfigArguments::default()
+    });
+    assert!(result.unwrap_err().to_string().contains("NUL"));
+}
+
+#[test]
+fn nul_in_port_names_is_an_error() {
+    let mut md = RMididings::new().unwrap();
+    let result = md.config(ConfigArguments {
+        backend: BackendType::Null,
+        in_ports: &[["in\0put", ""]],
+        ..ConfigArguments::default()
+    });
+    assert!(result.is_err());
+
+    let result = md.config(ConfigArguments {
+        backend: BackendType::Null,
+        out_ports: &[["output", "synth\0"]],
+        ..ConfigArguments::default()
+    });
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "osc")]
+#[test]
+fn osc_strip_prefix_inverse_passes_non_matching() {
+    let filter = OscStripPrefix("/coolapp");
+    let mut evs = EventStream::from(vec![
+        OscEvent(0, "/other".to_string(), vec![]),
+        OscEvent(0, "/coolapp/bar".to_string(), vec![]),
+        NoteOnEvent(0,0,60,20),
+    ]);
+    filter.run_inverse(&mut evs);
+    assert_eq!(evs, OscEvent(0, "/other".to_string(), vec![]));
+
+    let patch = Not!(OscStripPrefix("/coolapp"));
+    let mut evs = EventStream::from(OscEvent(0, "/other".to_string(), vec![]));
+    patch.run(&mut evs);
+    assert_eq!(evs, OscEvent(0, "/other".to_string(), vec![]));
+}
+
+fn output_empty_sysex(backend: BackendType) -> Result<(), Box<dyn std::error::Error>> {
+    let mut md = RMididings::new()?;
+    md.config(ConfigArguments {
+        backend,
+        out_ports: &[["output", ""]],
+        ..ConfigArguments::default()
+    })?;
+    let patch = Pass();
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() })?;
+    runner.inject_event(SysExEvent(1, &[]))
+}
+
+#[test]
+fn empty_sysex_is_not_output() {
+    output_empty_sysex(BackendType::Null).unwrap();
+}
+
+#[cfg(feature = "alsa")]
+#[test]
+fn empty_sysex_is_not_output_by_alsa() {
+    // Not every machine running the tests has an ALSA sequencer.
+    if !std::path::Path::new("/dev/snd/seq").exists() {
+        eprintln!("No ALSA sequencer, skipping");
+        return;
+    }
+    output_empty_sysex(BackendType::Alsa).unwrap();
+}
@@ -0,0 +1,101 @@
+//! OSC over TCP, with connections coming and going while running.
+#![cfg(feature = "osc")]
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::ops::ControlFlow;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use rmididings::*;
+
+/// Gives up waiting after this, so a failing test doesn't hang.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn leak_ports(name: String, connect: String) -> &'static [[&'static str; 2]] {
+    Box::leak(Box::new([[Box::leak(name.into_boxed_str()) as &str, Box::leak(connect.into_boxed_str())]]))
+}
+
+fn write_osc(stream: &mut TcpStream, addr: &str) {
+    let packet = rosc::OscPacket::Message(rosc::OscMessage { addr: addr.to_string(), args: vec![] });
+    let data = rosc::encoder::encode(&packet).unwrap();
+    stream.write_all(&(data.len() as i32).to_be_bytes()).unwrap();
+    stream.write_all(&data).unwrap();
+}
+
+fn read_osc_addr(stream: &mut TcpStream) -> String {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length).unwrap();
+    let mut data = vec![0u8; i32::from_be_bytes(length) as usize];
+    stream.read_exact(&mut data).unwrap();
+    match rosc::decoder::decode(&data).unwrap() {
+        rosc::OscPacket::Message(message) => message.addr,
+        packet => panic!("expected an OSC message, got {:?}", packet),
+    }
+}
+
+#[test]
+fn osc_tcp_input_from_client_connecting_later() {
+    // Find a free port to listen on.
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let in_ports = leak_ports(format!("osc.tcp:{}", addr), String::new());
+
+    let mut md = RMididings::new().unwrap();
+    md.config(ConfigArguments {
+        backend: BackendType::Null,
+        in_ports,
+        ..ConfigArguments::default()
+    }).unwrap();
+    let patch = Chain!(OscAddrFilter("/quit"), Quit());
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+    assert_eq!(runner.step(Duration::ZERO).unwrap(), ControlFlow::Continue(()));
+
+    // Connect after starting, and only send once the connection was accepted, so that the
+    // message is only seen when the new stream is polled too.
+    let mut client = TcpStream::connect(&addr).unwrap();
+    for _ in 0..5 {
+        assert_eq!(runner.step(Duration::ZERO).unwrap(), ControlFlow::Continue(()));
+        sleep(Duration::from_millis(10));
+    }
+    write_osc(&mut client, "/quit");
+
+    let deadline = Instant::now() + TIMEOUT;
+    while !runner.is_finished() {
+        assert!(Instant::now() < deadline, "OSC message from the new client was not received");
+        let _ = runner.step(Duration::from_millis(100)).unwrap();
+    }
+}
+
+#[test]
+fn osc_tcp_output_reconnects_after_receiver_restart() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let out_ports = leak_ports("osc.tcp:out".to_string(), listener.local_addr().unwrap().to_string());
+
+    let mut md = RMididings::new().unwrap();
+    md.config(ConfigArguments {
+        backend: BackendType::Null,
+        out_ports,
+        ..ConfigArguments::default()
+    }).unwrap();
+    let patch = Pass();
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+
+    let (mut receiver, _) = listener.accept().unwrap();
+    runner.inject_event(OscEvent(1, "/first".to_string(), vec![])).unwrap();
+    assert_eq!(read_osc_addr(&mut receiver), "/first");
+
+    // The receiver restarts: the connection is closed, and it listens again.
+    drop(receiver);
+    sleep(Duration::from_millis(50));
+    runner.inject_event(OscEvent(1, "/second".to_string(), vec![])).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let deadline = Instant::now() + TIMEOUT;
+    let mut receiver = loop {
+        match listener.accept() {
+            Ok((stream, _)) => break stream,
+            Err(_) if Instant::now() < deadline => sleep(Duration::from_millis(10)),
+            Err(e) => panic!("OSC output did not reconnect: {}", e),
+        }
+    };
+    receiver.set_nonblocking(false).unwrap();
+    assert_eq!(read_osc_addr(&mut receiver), "/second");
+}
@@ -0,0 +1,89 @@
+//! Filters combined in patches, and stateful filters over several runs.
+use rmididings::proc::*;
+use rmididings::{assert_events, Chain, Fork, Not, Random, RoundRobin, TypeFilter};
+
+fn run(filter: &dyn FilterTrait, evs: Vec<Event<'static>>) -> EventStream<'static> {
+    let mut evs = EventStream::from(evs);
+    filter.run(&mut evs);
+    evs
+}
+
+#[test]
+fn fork_of_chains_routes_by_channel() {
+    let patch = Fork!(
+        Chain!(ChannelFilter(1), Port(2)),
+        Chain!(ChannelFilter(2), Transpose(-12), Port(3))
+    );
+    let evs = run(&patch, vec![NoteOnEvent(1,1,60,100), NoteOnEvent(1,2,60,100), NoteOnEvent(1,3,60,100)]);
+    assert_events!(evs, [NoteOnEvent(2,1,60,100), NoteOnEvent(3,2,48,100)]);
+}
+
+#[test]
+fn not_of_chain_passes_what_the_chain_rejects() {
+    let patch = Not!(Chain!(ChannelFilter(1), KeyFilter(60)));
+    let evs = run(&patch, vec![NoteOnEvent(0,1,60,100), NoteOnEvent(0,1,61,100), NoteOnEvent(0,2,60,100)]);
+    // It is a fork of the inverted filters, so events come in the order of those.
+    assert_events!(evs, [NoteOnEvent(0,2,60,100), NoteOnEvent(0,1,61,100)]);
+}
+
+#[test]
+fn sustain_pedal_tracker_holds_note_offs_over_runs() {
+    let tracker = SustainPedalTracker();
+    assert_events!(run(&tracker, vec![CtrlEvent(0,0,64,127)]), [CtrlEvent(0,0,64,127)]);
+    assert_events!(run(&tracker, vec![NoteOffEvent(0,0,60), NoteOffEvent(0,0,62)]), []);
+    // Playing the note again drops its held NoteOff.
+    assert_events!(run(&tracker, vec![NoteOnEvent(0,0,60,100)]), [NoteOnEvent(0,0,60,100)]);
+    assert_events!(run(&tracker, vec![CtrlEvent(0,0,64,0)]), [CtrlEvent(0,0,64,0), NoteOffEvent(0,0,62)]);
+}
+
+#[test]
+fn sustain_pedal_tracker_holds_only_its_own_channel() {
+    let tracker = SustainPedalTracker();
+    assert_events!(run(&tracker, vec![CtrlEvent(0,1,64,127)]), [CtrlEvent(0,1,64,127)]);
+    assert_events!(run(&tracker, vec![NoteOffEvent(0,2,60), NoteOffEvent(1,1,60)]), [NoteOffEvent(0,2,60), NoteOffEvent(1,1,60)]);
+    assert_events!(run(&tracker, vec![NoteOffEvent(0,1,60)]), []);
+    assert_events!(run(&tracker, vec![CtrlEvent(0,1,64,0)]), [CtrlEvent(0,1,64,0), NoteOffEvent(0,1,60)]);
+}
+
+#[test]
+fn sustain_pedal_tracker_holds_note_on_with_velocity_zero() {
+    let tracker = SustainPedalTracker();
+    assert_events!(run(&tracker, vec![CtrlEvent(0,0,64,127), NoteOnEvent(0,0,60,100), NoteOnEvent(0,0,60,0)]), [CtrlEvent(0,0,64,127), NoteOnEvent(0,0,60,100)]);
+    assert_events!(run(&tracker, vec![CtrlEvent(0,0,64,0)]), [CtrlEvent(0,0,64,0), NoteOnEvent(0,0,60,0)]);
+}
+
+#[test]
+fn thru_switch_toggles_another_patch() {
+    let thru = Thru(true);
+    let toggle = thru.toggle();
+    let patch = Fork!(Chain!(TypeFilter!(Ctrl), CtrlFilter(80), toggle), Chain!(TypeFilter!(Note), thru));
+    assert_events!(run(&patch, vec![NoteOnEvent(0,0,60,100)]), [NoteOnEvent(0,0,60,100)]);
+    assert_events!(run(&patch, vec![CtrlEvent(0,0,80,127)]), []);
+    assert_events!(run(&patch, vec![NoteOnEvent(0,0,60,100)]), []);
+}
+
+#[test]
+fn scene_switch_on_ctrl_in_a_chain_of_switches() {
+    let control = Chain!(SceneSwitchOnCtrl(80, 127, 1), SceneSwitchOnCtrlRange(81, 64.., 2));
+    assert_events!(run(&control, vec![CtrlEvent(0,0,81,100)]), [SceneSwitchEvent(2)]);
+    assert_events!(run(&control, vec![CtrlEvent(0,0,81,10), CtrlEvent(0,0,80,0)]), []);
+}
+
+#[test]
+fn round_robin_ends_notes_on_velocity_zero() {
+    let filter = RoundRobin!(Port(0), Port(1));
+    let evs = run(&filter, vec![NoteOnEvent(5,0,60,100), NoteOnEvent(5,0,60,0), NoteOnEvent(5,0,62,100)]);
+    // The NoteOn with velocity 0 goes where its note went, and doesn't take a turn.
+    assert_events!(evs, [NoteOnEvent(0,0,60,100), NoteOnEvent(0,0,60,0), NoteOnEvent(1,0,62,100)]);
+}
+
+#[test]
+fn random_ends_notes_on_velocity_zero() {
+    let filter = Random!(Port(0), Port(1), Port(2));
+    for note in 60..70 {
+        let evs = run(&filter, vec![NoteOnEvent(5,0,note,100)]);
+        let port = evs.iter().next().unwrap().port().unwrap();
+        let evs = run(&filter, vec![NoteOnEvent(5,0,note,0)]);
+        assert_events!(evs, [NoteOnEvent(port,0,note,0)]);
+    }
+}
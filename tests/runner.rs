@@ -0,0 +1,215 @@
+//! The runner with scripted input from the Null backend, checking what is output.
+use std::time::Duration;
+
+use rmididings::*;
+
+/// A Null backend with one input and one output port.
+fn null_rmididings() -> (RMididings<'static>, NullBackend) {
+    let md = RMididings::builder()
+        .backend(BackendType::Null)
+        .in_port("input", "")
+        .out_port("output", "")
+        .build()
+        .unwrap();
+    let null = md.null_backend().unwrap();
+    (md, null)
+}
+
+/// Receives the events, and returns what was output.
+fn step(runner: &mut Runner, null: &NullBackend, evs: Vec<Event<'static>>) -> Vec<Event<'static>> {
+    for ev in evs {
+        null.push_input(ev);
+    }
+    let _ = runner.step(Duration::ZERO).unwrap();
+    null.take_output()
+}
+
+#[test]
+fn patch_processes_input() {
+    let (mut md, null) = null_rmididings();
+    let patch = Fork!(Pass(), Transpose(12));
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100), NoteOffEvent(0,0,60)]);
+    assert_events!(out, [NoteOnEvent(0,0,60,100), NoteOnEvent(0,0,72,100), NoteOffEvent(0,0,60), NoteOffEvent(0,0,72)]);
+}
+
+#[test]
+fn control_patch_switches_scenes() {
+    let (mut md, null) = null_rmididings();
+    let one = Scene { name: "One", patch: &Pass(), ..Scene::default() };
+    let two = Scene { name: "Two", patch: &Transpose(12), init: &Ctrl(7, 100), ..Scene::default() };
+    let control = Chain!(TypeFilter!(Ctrl), SceneSwitchOnCtrl(80, 127, 2));
+    let scenes = [&one, &two];
+    let mut runner = md.start(RunArguments { scenes: &scenes, control: &control, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100)]);
+    assert_events!(out, [NoteOnEvent(0,0,60,100)]);
+
+    let out = step(&mut runner, &null, vec![CtrlEvent(0,0,80,127)]);
+    assert_eq!(runner.current_scene(), Some(2));
+    assert_events!(out, [CtrlEvent(0,0,7,100), CtrlEvent(0,0,80,127)]);
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100)]);
+    assert_events!(out, [NoteOnEvent(0,0,72,100)]);
+}
+
+#[test]
+fn initial_scene_cant_be_removed_before_start() {
+    let md = RMididings::builder().backend(BackendType::Null).in_port("input", "").out_port("output", "").initial_scene(1);
+    let mut md = md.build().unwrap();
+    let null = md.null_backend().unwrap();
+    let one = Scene { name: "One", patch: &Pass(), ..Scene::default() };
+    let two = Scene { name: "Two", patch: &Transpose(12), ..Scene::default() };
+    let scenes = [&one, &two];
+    let mut runner = md.start(RunArguments { scenes: &scenes, ..RunArguments::default() }).unwrap();
+
+    // The last scene is the initial one, removing it would leave nothing to start with.
+    assert!(runner.remove_scene(2).is_err());
+    runner.remove_scene(1).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100)]);
+    assert_eq!(runner.current_scene(), Some(1));
+    assert_events!(out, [NoteOnEvent(0,0,72,100)]);
+}
+
+#[test]
+fn stateful_filter_keeps_state_between_steps() {
+    let (mut md, null) = null_rmididings();
+    let patch = SustainPedalTracker();
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![CtrlEvent(0,0,64,127), NoteOnEvent(0,0,60,100)]);
+    assert_events!(out, [CtrlEvent(0,0,64,127), NoteOnEvent(0,0,60,100)]);
+
+    let out = step(&mut runner, &null, vec![NoteOffEvent(0,0,60)]);
+    assert_events!(out, []);
+
+    let out = step(&mut runner, &null, vec![CtrlEvent(0,0,64,0)]);
+    assert_events!(out, [CtrlEvent(0,0,64,0), NoteOffEvent(0,0,60)]);
+}
+
+#[test]
+fn scene_change_is_sent_as_program() {
+    let (mut md, null) = null_rmididings();
+    let one = Scene { name: "One", patch: &ChannelFilter(1), ..Scene::default() };
+    let two = Scene { name: "Two", patch: &ChannelFilter(1), ..Scene::default() };
+    let control = Chain!(ChannelFilter(16), ProgramSwitchScenes());
+    let post = SceneToProgram(1, 16);
+    let scenes = [&one, &two];
+    let mut runner = md.start(RunArguments { scenes: &scenes, control: &control, post: &post, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![]);
+    assert_events!(out, [ProgramEvent(0,15,0)]);
+
+    let out = step(&mut runner, &null, vec![ProgramEvent(0,15,1)]);
+    assert_eq!(runner.current_scene(), Some(2));
+    assert_events!(out, [ProgramEvent(0,15,1)]);
+}
+
+#[test]
+fn exit_is_output_on_quit() {
+    let (mut md, null) = null_rmididings();
+    let patch = Fork!(Exit!(Ctrl(123, 0)), Chain!(KeyFilter(60), Quit()));
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100)]);
+    assert!(runner.is_finished());
+    // All notes off is followed by a NoteOff for the note still sounding.
+    assert_events!(out, [NoteOnEvent(0,0,60,100), CtrlEvent(0,0,123,0), NoteOffEvent(0,0,60)]);
+}
+
+#[test]
+fn init_contents_are_ticked() {
+    let (mut md, null) = null_rmididings();
+    let clock = FakeClock::new();
+    let patch = Fork!(Init!(NoteWithDuration(60, 100, 500).with_clock(clock.clone())), Pass());
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![]);
+    assert_events!(out, [NoteOnEvent(0,0,60,100)]);
+
+    clock.advance(Duration::from_millis(500));
+    let out = step(&mut runner, &null, vec![]);
+    assert_events!(out, [NoteOffEvent(0,0,60)]);
+}
+
+#[test]
+fn scene_init_is_ticked_and_ended_on_leaving() {
+    let (mut md, null) = null_rmididings();
+    let clock = FakeClock::new();
+    let init = NoteWithDuration(60, 100, 500).with_clock(clock.clone());
+    let one = Scene { name: "One", init: &init, ..Scene::default() };
+    let two = Scene { name: "Two", ..Scene::default() };
+    let scenes = [&one, &two];
+    let mut runner = md.start(RunArguments { scenes: &scenes, control: &TypeFilter!(SceneSwitch), ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![]);
+    assert_events!(out, [NoteOnEvent(0,0,60,100)]);
+    clock.advance(Duration::from_millis(500));
+    let out = step(&mut runner, &null, vec![]);
+    assert_events!(out, [NoteOffEvent(0,0,60)]);
+
+    // Leaving the scene before the note ended ends it right away.
+    runner.inject_event(SceneSwitchEvent(2)).unwrap();
+    runner.inject_event(SceneSwitchEvent(1)).unwrap();
+    assert_events!(null.take_output(), [NoteOnEvent(0,0,60,100)]);
+    runner.inject_event(SceneSwitchEvent(2)).unwrap();
+    assert_events!(null.take_output(), [NoteOffEvent(0,0,60)]);
+}
+
+#[test]
+fn pre_and_post_are_ticked() {
+    let (mut md, null) = null_rmididings();
+    let clock = FakeClock::new();
+    let pre = Humanize(0, 20).with_seed(1).with_clock(clock.clone());
+    let post = Humanize(0, 20).with_seed(2).with_clock(clock.clone());
+    let patch = Transpose(12);
+    let mut runner = md.start(RunArguments { patch: &patch, pre: &pre, post: &post, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100)]);
+    assert_events!(out, []);
+
+    // Held back by pre, transposed by the patch and held back by post once more.
+    clock.advance(Duration::from_millis(20));
+    let _ = step(&mut runner, &null, vec![]);
+    clock.advance(Duration::from_millis(20));
+    let out = step(&mut runner, &null, vec![]);
+    assert_events!(out, [NoteOnEvent(0,0,72,100)]);
+}
+
+#[test]
+fn post_is_flushed_on_quit() {
+    let (mut md, null) = null_rmididings();
+    let post = Humanize(0, 1000).with_seed(1);
+    let patch = Fork!(TypeFilter!(Note), Chain!(TypeFilter!(Ctrl), Quit()));
+    let mut runner = md.start(RunArguments { patch: &patch, post: &post, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100), CtrlEvent(0,0,7,0)]);
+    assert!(runner.is_finished());
+    assert_events!(out, [NoteOnEvent(0,0,60,100)]);
+}
+
+#[test]
+fn feedback_port_notes_are_not_panicked() {
+    let mut md = RMididings::builder()
+        .backend(BackendType::Null)
+        .in_port("keys", "")
+        .in_port("pads", "")
+        .out_port("synth", "")
+        .out_port("leds", "")
+        .out_port_flags(vec![PortFlags::NONE, PortFlags::FEEDBACK])
+        .build()
+        .unwrap();
+    let null = md.null_backend().unwrap();
+    let patch = Fork!(TypeFilter!(Note), Chain!(TypeFilter!(Ctrl), Panic(), Port(1)), Chain!(TypeFilter!(Ctrl), Panic(), Port(2)));
+    let mut runner = md.start(RunArguments { patch: &patch, ..RunArguments::default() }).unwrap();
+
+    let out = step(&mut runner, &null, vec![NoteOnEvent(0,0,60,100), NoteOnEvent(1,0,36,127), NoteOnEvent(0,1,62,100), NoteOffEvent(0,1,62)]);
+    assert_events!(out, [NoteOnEvent(0,0,60,100), NoteOnEvent(1,0,36,127), NoteOnEvent(0,1,62,100), NoteOffEvent(0,1,62)]);
+
+    // Only the note still sounding on the synth port is ended, the LED isn't.
+    let out = step(&mut runner, &null, vec![CtrlEvent(0,0,1,0)]);
+    let note_offs: Vec<_> = out.into_iter().filter(|ev| matches!(ev, Event::NoteOff(_))).collect();
+    assert_events!(note_offs, [NoteOffEvent(0,0,60)]);
+}
@@ -0,0 +1,41 @@
+//! Runs each filter category once within a generous time budget, so that grossly
+//! pathological performance regressions (like quadratic behaviour) are noticed.
+#[macro_use]
+extern crate rmididings;
+
+use std::time::{Duration, Instant};
+
+use rmididings::proc::*;
+
+mod testutil;
+use testutil::*;
+
+/// Generous, as tests may run unoptimized and in parallel.
+const BUDGET: Duration = Duration::from_secs(2);
+
+#[test]
+fn categories_run_within_budget() {
+    for category in categories(CORPUS_SIZE * 10) {
+        let start = Instant::now();
+        run_category(&category);
+        let elapsed = start.elapsed();
+        assert!(elapsed < BUDGET, "{} took {:?}, budget is {:?}", category.name, elapsed, BUDGET);
+    }
+}
+
+#[test]
+fn categories_produce_events() {
+    for category in categories(CORPUS_SIZE) {
+        assert!(run_category(&category) > 0, "{} produced no events", category.name);
+    }
+}
+
+#[test]
+fn fork_quick_reject_keeps_results() {
+    let events = mixed_corpus(CORPUS_SIZE);
+    let mut evs_filter_first = EventStream::from(events.clone());
+    let mut evs_modifier_first = EventStream::from(events);
+    routing_fork(true).run(&mut evs_filter_first);
+    routing_fork(false).run(&mut evs_modifier_first);
+    assert_eq!(evs_filter_first, evs_modifier_first);
+}
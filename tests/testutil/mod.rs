@@ -0,0 +1,90 @@
+//! Event corpora and filter categories, shared by the benchmarks and smoke tests.
+#![allow(dead_code)]
+use rmididings::proc::*;
+#[cfg(feature = "osc")]
+use rmididings::osc::OscType as o;
+
+/// Number of events in the standard corpora.
+pub const CORPUS_SIZE: usize = 1000;
+
+/// Alternating NoteOn and NoteOff events, spread over channels and notes.
+pub fn note_corpus(n: usize) -> Vec<Event<'static>> {
+    (0..n).map(|i| {
+        let channel = (i / 2 % 16) as u8;
+        let note = (i / 32 % 128) as u8;
+        if i % 2 == 0 {
+            NoteOnEvent(0, channel, note, (i % 127 + 1) as u8)
+        } else {
+            NoteOffEvent(0, channel, note)
+        }
+    }).collect()
+}
+
+/// Controller events, spread over channels and controllers.
+pub fn ctrl_corpus(n: usize) -> Vec<Event<'static>> {
+    (0..n).map(|i| CtrlEvent(0, (i % 16) as u8, (i / 16 % 128) as u32, (i % 128) as i32)).collect()
+}
+
+/// Notes and controllers, interleaved.
+pub fn mixed_corpus(n: usize) -> Vec<Event<'static>> {
+    note_corpus(n / 2).into_iter().zip(ctrl_corpus(n - n / 2)).flat_map(|(a, b)| vec![a, b]).collect()
+}
+
+/// OSC events with a single integer argument.
+#[cfg(feature = "osc")]
+pub fn osc_corpus(n: usize) -> Vec<Event<'static>> {
+    (0..n).map(|i| OscEvent(0, "/note".to_string(), vec![o::Int((i % 128) as i32)])).collect()
+}
+
+/// A filter category to measure, with the events to run it on.
+pub struct Category {
+    pub name: &'static str,
+    pub filter: Box<dyn FilterTrait>,
+    pub events: Vec<Event<'static>>,
+}
+
+/// Representative filters of each kind, with corpora of the given size.
+pub fn categories(n: usize) -> Vec<Category> {
+    vec![
+        Category { name: "filter", filter: Box::new(KeyRangeFilter(40..=80)), events: mixed_corpus(n) },
+        Category { name: "modifier", filter: Box::new(Transpose(12)), events: mixed_corpus(n) },
+        Category { name: "generator", filter: Box::new(Ctrl(7, 100)), events: note_corpus(n) },
+        Category {
+            name: "fork4",
+            filter: Box::new(Fork!(Channel(0), Channel(1), Transpose(12), KeyRangeFilter(40..=80))),
+            events: mixed_corpus(n),
+        },
+        Category { name: "fork_routing", filter: Box::new(routing_fork(true)), events: mixed_corpus(n) },
+        Category { name: "fork_routing_modifier_first", filter: Box::new(routing_fork(false)), events: mixed_corpus(n) },
+        Category { name: "stateful", filter: Box::new(RoundRobin!(Port(0), Port(1), Port(2))), events: note_corpus(n) },
+        #[cfg(feature = "osc")]
+        Category {
+            name: "process_osc",
+            filter: Box::new(ProcessOsc!(o::Int, |i: &i32| NoteOn(*i as u8, 100))),
+            events: osc_corpus(n),
+        },
+    ]
+}
+
+/// Fork that routes each channel to its own port, like a split over multiple synths.
+///
+/// With `filter_first`, each branch starts with its channel filter, so events for other
+/// channels can be rejected before copying them to the branch. Otherwise, the branch starts
+/// with a modifier, giving the same result while copying all events.
+pub fn routing_fork(filter_first: bool) -> FilterChain<'static> {
+    let branches: Vec<Box<dyn FilterTrait>> = (0..8u8).map(|channel| -> Box<dyn FilterTrait> {
+        if filter_first {
+            Box::new(Chain!(ChannelFilter(channel), Port(channel as usize + 1), Transpose(12)))
+        } else {
+            Box::new(Chain!(Port(channel as usize + 1), ChannelFilter(channel), Transpose(12)))
+        }
+    }).collect();
+    FilterChain::new(ConnectionType::Fork, branches)
+}
+
+/// Runs the category's filter once over its events.
+pub fn run_category(category: &Category) -> usize {
+    let mut evs = EventStream::from(category.events.clone());
+    category.filter.run(&mut evs);
+    evs.len()
+}